@@ -103,3 +103,111 @@ pub fn derive_from_enum(item: proc_macro::TokenStream) -> proc_macro::TokenStrea
 pub fn from(attr: proc_macro::TokenStream, _: proc_macro::TokenStream) -> proc_macro::TokenStream {
     attr
 }
+
+// Writes the given fields as a JSON object (named fields) or array (tuple
+// fields), where access(i, field) is an expression referencing each field
+fn fields_to_json(fields: &syn::Fields, access: impl Fn(usize, &syn::Field) -> proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    let mut writes = proc_macro2::TokenStream::new();
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            writes.extend(quote!{ out.push(','); });
+        }
+        if let Some(identifier) = &field.ident {
+            let key = format!("\"{}\":", identifier.to_string().trim_start_matches("r#"));
+            writes.extend(quote!{ out.push_str(#key); });
+        }
+        let value = access(i, field);
+        writes.extend(quote!{ ToJson::write_json(#value, out); });
+    }
+    match fields {
+        syn::Fields::Named(_) => quote!{ out.push('{'); #writes out.push('}'); },
+        syn::Fields::Unnamed(_) => quote!{ out.push('['); #writes out.push(']'); },
+        syn::Fields::Unit => quote!{ out.push_str("null"); },
+    }
+}
+
+// Serializes structs as JSON objects (or arrays, for tuple structs), fieldless
+// enum variants as their name, and other variants as {"Variant": fields}. The
+// ToJson trait must be in scope wherever this is derived.
+#[proc_macro_derive(ToJson)]
+pub fn derive_to_json(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(item as syn::DeriveInput);
+    let identifier = &input.ident;
+    let mut generics = input.generics.clone();
+    for param in generics.type_params_mut() {
+        param.bounds.push(syn::parse_quote!(ToJson));
+    }
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+
+    let body = match &input.data {
+        Data::Struct(syn::DataStruct { fields, .. }) => {
+            fields_to_json(fields, |i, field| match &field.ident {
+                Some(field_identifier) => quote!{ &self.#field_identifier },
+                None => {
+                    let index = syn::Index::from(i);
+                    quote!{ &self.#index }
+                },
+            })
+        },
+        Data::Enum(syn::DataEnum { variants, .. }) => {
+            let mut arms = proc_macro2::TokenStream::new();
+            for variant in variants {
+                let variant_identifier = &variant.ident;
+                let name = format!("\"{}\"", variant_identifier);
+                let bindings: Vec<syn::Ident> = (0..variant.fields.len())
+                    .map(|i| quote::format_ident!("field{}", i))
+                    .collect();
+                // single-field variants (e.g. newtypes) aren't wrapped in an array
+                let fields = match &variant.fields {
+                    syn::Fields::Unnamed(unnamed) if unnamed.unnamed.len() == 1 => quote!{ ToJson::write_json(field0, out); },
+                    fields => fields_to_json(fields, |i, _| {
+                        let binding = &bindings[i];
+                        quote!{ #binding }
+                    }),
+                };
+                let field_identifiers = variant.fields.iter().map(|field| &field.ident);
+                arms.extend(match &variant.fields {
+                    syn::Fields::Unit => quote!{
+                        Self::#variant_identifier => out.push_str(#name),
+                    },
+                    syn::Fields::Unnamed(_) => quote!{
+                        Self::#variant_identifier(#(#bindings),*) => {
+                            out.push('{');
+                            out.push_str(#name);
+                            out.push(':');
+                            #fields
+                            out.push('}');
+                        },
+                    },
+                    syn::Fields::Named(_) => quote!{
+                        Self::#variant_identifier { #(#field_identifiers: #bindings),* } => {
+                            out.push('{');
+                            out.push_str(#name);
+                            out.push(':');
+                            #fields
+                            out.push('}');
+                        },
+                    },
+                });
+            }
+            quote!{
+                match self {
+                    #arms
+                }
+            }
+        },
+        Data::Union(_) => {
+            return syn::Error::new_spanned(&input.ident, "unions are not supported")
+                .to_compile_error()
+                .into();
+        },
+    };
+
+    quote!{
+        impl #impl_generics ToJson for #identifier #type_generics #where_clause {
+            fn write_json(&self, out: &mut String) {
+                #body
+            }
+        }
+    }.into()
+}
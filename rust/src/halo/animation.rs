@@ -0,0 +1,594 @@
+use std::io::Cursor;
+use byteorder::{LittleEndian, ReadBytesExt};
+use deku::prelude::*;
+use js_sys::Float32Array;
+use nalgebra_glm::{identity, inverse, quat_conjugate, quat_normalize, quat_slerp, quat_to_mat4, scale, translate, vec3, Mat4, Quat, Vec3};
+use wasm_bindgen::prelude::*;
+
+use crate::halo::common::*;
+use crate::halo::model::*;
+use crate::halo::json::*;
+
+const FRAMES_PER_SECOND: f64 = 30.0;
+const ANIMATION_FLAG_COMPRESSED_DATA: u16 = 0x1;
+
+#[wasm_bindgen(js_name = "HaloModelAnimations")]
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct ModelAnimations {
+    #[deku(pad_bytes_before = "96")]
+    pub limp_body_node_radius: f32,
+    #[deku(pad_bytes_after = "2")]
+    pub flags: u16,
+    pub(crate) nodes: Block<AnimationGraphNode>,
+    pub(crate) animations: Block<Animation>,
+}
+
+#[wasm_bindgen(js_class = "HaloModelAnimations")]
+impl ModelAnimations {
+    pub fn get_animation_names(&self) -> Vec<String> {
+        self.animations.items.as_ref().unwrap().iter()
+            .map(|animation| animation.name.clone())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct AnimationGraphNode {
+    #[deku(reader = "read_tag_string(deku::reader)")]
+    pub name: String,
+    pub next_sibling_node_index: i16,
+    pub first_child_node_index: i16,
+    #[deku(pad_bytes_after = "2")]
+    pub parent_node_index: i16,
+    pub node_joint_flags: u32,
+    pub base_vector: Vector3D,
+    #[deku(pad_bytes_after = "4")]
+    pub vector_range: f32,
+}
+
+#[wasm_bindgen(js_name = "HaloAnimationType")]
+#[derive(Debug, Copy, Clone, PartialEq, DekuRead, ToJson)]
+#[deku(id_type = "u16")]
+#[repr(u16)]
+pub enum AnimationType {
+    Base = 0,
+    Overlay = 1,
+    Replacement = 2,
+}
+
+// Each frame stores, for every node, a rotation (4 normalized i16s), a
+// translation (3 f32s) and a scale (f32), but only for the components whose
+// node bit is set in the corresponding flags. The rest come from the default
+// data, which is laid out the same way for the unset components.
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct Animation {
+    #[deku(reader = "read_tag_string(deku::reader)")]
+    pub name: String,
+    pub animation_type: AnimationType,
+    pub frame_count: u16,
+    pub frame_size: u16,
+    pub frame_info_type: u16,
+    pub node_list_checksum: i32,
+    pub node_count: u16,
+    pub loop_frame_index: u16,
+    pub weight: f32,
+    pub key_frame_index: u16,
+    pub second_key_frame_index: u16,
+    pub next_animation: i16,
+    pub flags: u16,
+    pub sound: i16,
+    pub sound_frame_index: u16,
+    pub left_foot_frame_index: i8,
+    #[deku(pad_bytes_after = "6")]
+    pub right_foot_frame_index: i8,
+    pub frame_info_offset: TagDataOffset,
+    #[deku(pad_bytes_after = "8")]
+    pub node_translation_flags: [u32; 2],
+    #[deku(pad_bytes_after = "8")]
+    pub node_rotation_flags: [u32; 2],
+    #[deku(pad_bytes_after = "4")]
+    pub node_scale_flags: [u32; 2],
+    pub offset_to_compressed_data: u32,
+    pub(crate) default_data_offset: TagDataOffset,
+    pub(crate) frame_data_offset: TagDataOffset,
+    #[deku(skip)]
+    pub(crate) default_data: Vec<u8>,
+    #[deku(skip)]
+    pub(crate) frame_data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct NodeTransform {
+    pub rotation: Quat,
+    pub translation: Vec3,
+    pub scale: f32,
+}
+
+impl NodeTransform {
+    fn lerp(&self, other: &NodeTransform, t: f32) -> NodeTransform {
+        NodeTransform {
+            rotation: quat_slerp(&self.rotation, &other.rotation, t),
+            translation: self.translation.lerp(&other.translation, t),
+            scale: self.scale + (other.scale - self.scale) * t,
+        }
+    }
+
+    // Halo stores node rotations inverted
+    fn get_matrix(&self) -> Mat4 {
+        let m = translate(&identity(), &self.translation);
+        let m = m * quat_to_mat4(&quat_conjugate(&self.rotation));
+        scale(&m, &vec3(self.scale, self.scale, self.scale))
+    }
+}
+
+fn is_node_flagged(flags: &[u32; 2], node: usize) -> bool {
+    node < 64 && flags[node / 32] & (1 << (node % 32)) != 0
+}
+
+fn read_rotation(cursor: &mut Cursor<&[u8]>) -> std::io::Result<Quat> {
+    let mut v = [0.0; 4];
+    for x in v.iter_mut() {
+        *x = cursor.read_i16::<LittleEndian>()? as f32 / i16::MAX as f32;
+    }
+    Ok(quat_normalize(&Quat::new(v[3], v[0], v[1], v[2])))
+}
+
+fn read_translation(cursor: &mut Cursor<&[u8]>) -> std::io::Result<Vec3> {
+    let x = cursor.read_f32::<LittleEndian>()?;
+    let y = cursor.read_f32::<LittleEndian>()?;
+    let z = cursor.read_f32::<LittleEndian>()?;
+    Ok(vec3(x, y, z))
+}
+
+// Compressed rotations pack i, j, k and w into 48 bits as 12 bit ones'
+// complement values, most significant first
+fn read_compressed_rotation(cursor: &mut Cursor<&[u8]>) -> std::io::Result<Quat> {
+    let mut packed = 0u64;
+    for _ in 0..3 {
+        packed = packed << 16 | cursor.read_u16::<LittleEndian>()? as u64;
+    }
+    let component = |shift: u32| {
+        let x = ((packed >> shift) & 0xFFF) as i32;
+        if x & 0x800 != 0 { (x - 0xFFF) as f32 } else { x as f32 }
+    };
+    let rotation = Quat::new(component(0), component(36), component(24), component(12));
+    if rotation.coords.norm() == 0.0 {
+        return Ok(Quat::identity());
+    }
+    Ok(quat_normalize(&rotation))
+}
+
+fn read_scale(cursor: &mut Cursor<&[u8]>) -> std::io::Result<f32> {
+    cursor.read_f32::<LittleEndian>()
+}
+
+const COMPRESSED_HEADER_SIZE: usize = 44;
+
+// Compressed frame data starts with 11 u32 offsets to the sections following
+// the header. Rotations, translations and scales each get four sections: a
+// u32 per node holding a 12 bit keyframe count and a 20 bit index into the
+// keyframe lists, the keyframe times (u16 frame indices), a default value per
+// node, and the keyframe values.
+struct CompressedData<'a> {
+    data: &'a [u8],
+    sections: [usize; 12],
+}
+
+impl<'a> CompressedData<'a> {
+    fn new(data: &'a [u8]) -> Option<Self> {
+        let mut cursor = Cursor::new(data);
+        let mut sections = [COMPRESSED_HEADER_SIZE; 12];
+        for section in sections.iter_mut().skip(1) {
+            *section = cursor.read_u32::<LittleEndian>().ok()? as usize;
+        }
+        Some(CompressedData { data, sections })
+    }
+
+    fn cursor_at(&self, offset: usize) -> Option<Cursor<&'a [u8]>> {
+        self.data.get(offset..).map(Cursor::new)
+    }
+
+    // Returns the node's default value, which applies at frame 0, along with
+    // its keyframes
+    fn read_track<T>(&self, channel: usize, node: usize, value_size: usize, read: fn(&mut Cursor<&[u8]>) -> std::io::Result<T>) -> Option<(T, Vec<(usize, T)>)> {
+        let [headers, times, defaults, values] = [0, 1, 2, 3].map(|i| self.sections[channel * 4 + i]);
+        let header = self.cursor_at(headers + node * 4)?.read_u32::<LittleEndian>().ok()?;
+        let default = read(&mut self.cursor_at(defaults + node * value_size)?).ok()?;
+        let count = (header & 0xFFF) as usize;
+        let first = (header >> 12) as usize;
+        let mut time_cursor = self.cursor_at(times + first * 2)?;
+        let mut value_cursor = self.cursor_at(values + first * value_size)?;
+        let mut keyframes = Vec::with_capacity(count);
+        for _ in 0..count {
+            let time = time_cursor.read_u16::<LittleEndian>().ok()? as usize;
+            keyframes.push((time, read(&mut value_cursor).ok()?));
+        }
+        Some((default, keyframes))
+    }
+}
+
+// Samples a compressed track, interpolating between the surrounding keyframes
+fn sample_track<T: Copy>(default: T, keyframes: &[(usize, T)], frame: usize, lerp: fn(&T, &T, f32) -> T) -> T {
+    let mut previous = (0, default);
+    for &(time, value) in keyframes {
+        if time >= frame {
+            if time == previous.0 {
+                return value;
+            }
+            let t = (frame - previous.0) as f32 / (time - previous.0) as f32;
+            return lerp(&previous.1, &value, t);
+        }
+        previous = (time, value);
+    }
+    previous.1
+}
+
+impl Animation {
+    pub fn is_compressed(&self) -> bool {
+        self.flags & ANIMATION_FLAG_COMPRESSED_DATA != 0
+    }
+
+    // Decodes every frame into per-node transforms, starting from the given
+    // rest pose. Compressed animations keep an uncompressed copy of their
+    // frames ahead of the compressed data when offset_to_compressed_data is
+    // set, which we prefer since it's cheaper to read.
+    pub fn decode_frames(&self, rest_pose: &[NodeTransform]) -> Option<Vec<Vec<NodeTransform>>> {
+        let frame_count = self.frame_count as usize;
+        let frame_size = self.frame_size as usize;
+        if self.is_compressed() && self.offset_to_compressed_data == 0 {
+            return self.decode_compressed_frames(rest_pose);
+        }
+        if self.frame_data.len() < frame_count * frame_size {
+            return None;
+        }
+        let node_count = (self.node_count as usize).min(rest_pose.len());
+
+        let mut defaults = rest_pose.to_vec();
+        let mut cursor = Cursor::new(self.default_data.as_slice());
+        for (node, transform) in defaults.iter_mut().enumerate().take(node_count) {
+            if !is_node_flagged(&self.node_rotation_flags, node) {
+                transform.rotation = read_rotation(&mut cursor).ok()?;
+            }
+            if !is_node_flagged(&self.node_translation_flags, node) {
+                transform.translation = read_translation(&mut cursor).ok()?;
+            }
+            if !is_node_flagged(&self.node_scale_flags, node) {
+                transform.scale = cursor.read_f32::<LittleEndian>().ok()?;
+            }
+        }
+
+        let mut frames = Vec::with_capacity(frame_count);
+        for frame_index in 0..frame_count {
+            let start = frame_index * frame_size;
+            let mut cursor = Cursor::new(&self.frame_data[start..start + frame_size]);
+            let mut frame = defaults.clone();
+            for (node, transform) in frame.iter_mut().enumerate().take(node_count) {
+                if is_node_flagged(&self.node_rotation_flags, node) {
+                    transform.rotation = read_rotation(&mut cursor).ok()?;
+                }
+                if is_node_flagged(&self.node_translation_flags, node) {
+                    transform.translation = read_translation(&mut cursor).ok()?;
+                }
+                if is_node_flagged(&self.node_scale_flags, node) {
+                    transform.scale = cursor.read_f32::<LittleEndian>().ok()?;
+                }
+            }
+            frames.push(frame);
+        }
+        Some(frames)
+    }
+
+    fn decode_compressed_frames(&self, rest_pose: &[NodeTransform]) -> Option<Vec<Vec<NodeTransform>>> {
+        let data = CompressedData::new(&self.frame_data)?;
+        let node_count = (self.node_count as usize).min(rest_pose.len());
+        let mut frames = vec![rest_pose.to_vec(); self.frame_count as usize];
+        for node in 0..node_count {
+            let (rotation, rotations) = data.read_track(0, node, 6, read_compressed_rotation)?;
+            let (translation, translations) = data.read_track(1, node, 12, read_translation)?;
+            let (scale, scales) = data.read_track(2, node, 4, read_scale)?;
+            for (frame_index, frame) in frames.iter_mut().enumerate() {
+                frame[node] = NodeTransform {
+                    rotation: sample_track(rotation, &rotations, frame_index, quat_slerp),
+                    translation: sample_track(translation, &translations, frame_index, |a, b, t| a.lerp(b, t)),
+                    scale: sample_track(scale, &scales, frame_index, |a, b, t| a + (b - a) * t),
+                };
+            }
+        }
+        Some(frames)
+    }
+}
+
+#[wasm_bindgen(js_name = "HaloAnimationManager")]
+#[derive(Debug, Clone)]
+pub struct AnimationManager {
+    animations: Vec<Animation>,
+    node_parents: Vec<i16>,
+    rest_pose: Vec<NodeTransform>,
+    inverse_rest_matrices: Vec<Mat4>,
+    current_animation: Option<usize>,
+    current_frames: Vec<Vec<NodeTransform>>,
+    animation_time: f64,
+    playback_rate: f64,
+}
+
+#[wasm_bindgen(js_class = "HaloAnimationManager")]
+impl AnimationManager {
+    pub fn new(model: &GbxModel, animations: &ModelAnimations) -> AnimationManager {
+        let nodes = model.nodes.items.as_ref().unwrap();
+        let rest_pose: Vec<NodeTransform> = nodes.iter()
+            .map(|node| NodeTransform {
+                rotation: Quat::new(node.default_rotation.w, node.default_rotation.i, node.default_rotation.j, node.default_rotation.k),
+                translation: vec3(node.default_translation.x, node.default_translation.y, node.default_translation.z),
+                scale: 1.0,
+            })
+            .collect();
+        let node_parents: Vec<i16> = nodes.iter().map(|node| node.parent_node_index).collect();
+        let inverse_rest_matrices = compute_world_matrices(&node_parents, &rest_pose).iter()
+            .map(inverse)
+            .collect();
+        let mut manager = AnimationManager {
+            animations: animations.animations.items.as_ref().cloned().unwrap(),
+            node_parents,
+            rest_pose,
+            inverse_rest_matrices,
+            current_animation: None,
+            current_frames: Vec::new(),
+            animation_time: 0.0,
+            playback_rate: 1.0,
+        };
+        if !manager.animations.is_empty() {
+            manager.set_animation_index(0);
+        }
+        manager
+    }
+
+    // delta_time is in milliseconds
+    pub fn update(&mut self, delta_time: f64) {
+        let Some(index) = self.current_animation else {
+            return;
+        };
+        self.animation_time += delta_time * self.playback_rate;
+        let frame_count = self.current_frames.len() as f64;
+        if frame_count == 0.0 || self.get_current_frame() < frame_count {
+            return;
+        }
+
+        let animation = &self.animations[index];
+        let next = animation.next_animation;
+        if next >= 0 && next as usize != index && (next as usize) < self.animations.len() {
+            let overflow = self.animation_time - self.get_duration();
+            self.set_animation_index(next as usize);
+            self.animation_time = overflow.max(0.0);
+        } else {
+            let loop_frame = (animation.loop_frame_index as f64).min(frame_count - 1.0);
+            let loop_time = loop_frame * 1000.0 / FRAMES_PER_SECOND;
+            let loop_duration = self.get_duration() - loop_time;
+            if loop_duration > 0.0 {
+                self.animation_time = loop_time + (self.animation_time - loop_time) % loop_duration;
+            } else {
+                self.animation_time = loop_time;
+            }
+        }
+    }
+
+    pub fn get_animation_names(&self) -> Vec<String> {
+        self.animations.iter().map(|animation| animation.name.clone()).collect()
+    }
+
+    pub fn find_animation(&self, name: &str) -> Option<usize> {
+        self.animations.iter().position(|animation| animation.name == name)
+    }
+
+    pub fn get_animation_index(&self) -> Option<usize> {
+        self.current_animation
+    }
+
+    // Animations whose frames can't be decoded leave the model in its rest pose
+    pub fn set_animation_index(&mut self, index: usize) {
+        if index >= self.animations.len() {
+            return;
+        }
+        self.current_animation = Some(index);
+        self.current_frames = self.animations[index].decode_frames(&self.rest_pose).unwrap_or_default();
+        self.animation_time = 0.0;
+    }
+
+    // e.g. for sky animations, which specify how many seconds a cycle lasts
+    pub fn set_playback_rate(&mut self, rate: f64) {
+        self.playback_rate = rate;
+    }
+
+    pub fn get_duration(&self) -> f64 {
+        self.current_frames.len() as f64 * 1000.0 / FRAMES_PER_SECOND
+    }
+
+    pub fn get_num_nodes(&self) -> usize {
+        self.node_parents.len()
+    }
+
+    pub fn get_node_parents(&self) -> Vec<i16> {
+        self.node_parents.clone()
+    }
+
+    // Fills in a 4x4 skinning matrix per node, which transforms rest pose
+    // model space positions into the current pose
+    pub fn update_node_matrices(&self, node_matrices: &Float32Array) {
+        for (i, matrix) in self.compute_skinning_matrices().iter().enumerate() {
+            for (j, value) in matrix.as_slice().iter().enumerate() {
+                node_matrices.set_index((i * 16 + j) as u32, *value);
+            }
+        }
+    }
+}
+
+// rust-only interface
+impl AnimationManager {
+    fn get_current_frame(&self) -> f64 {
+        self.animation_time * FRAMES_PER_SECOND / 1000.0
+    }
+
+    pub fn get_current_pose(&self) -> Vec<NodeTransform> {
+        let frame_count = self.current_frames.len();
+        if frame_count == 0 {
+            return self.rest_pose.clone();
+        }
+        let frame = self.get_current_frame().max(0.0);
+        let frame_index = (frame.floor() as usize).min(frame_count - 1);
+        let t = (frame - frame_index as f64).min(1.0) as f32;
+        let next_index = if frame_index + 1 < frame_count {
+            frame_index + 1
+        } else {
+            let loop_frame = self.current_animation
+                .map(|index| self.animations[index].loop_frame_index as usize)
+                .unwrap_or(0);
+            loop_frame.min(frame_count - 1)
+        };
+        self.current_frames[frame_index].iter()
+            .zip(self.current_frames[next_index].iter())
+            .map(|(a, b)| a.lerp(b, t))
+            .collect()
+    }
+
+    pub fn compute_skinning_matrices(&self) -> Vec<Mat4> {
+        compute_world_matrices(&self.node_parents, &self.get_current_pose()).iter()
+            .zip(self.inverse_rest_matrices.iter())
+            .map(|(world, inverse_rest)| world * inverse_rest)
+            .collect()
+    }
+}
+
+// Node lists are ordered such that parents always come before their children
+fn compute_world_matrices(node_parents: &[i16], pose: &[NodeTransform]) -> Vec<Mat4> {
+    let mut result: Vec<Mat4> = Vec::with_capacity(pose.len());
+    for (i, transform) in pose.iter().enumerate() {
+        let local = transform.get_matrix();
+        let parent = node_parents.get(i).cloned().unwrap_or(-1);
+        if parent >= 0 && (parent as usize) < i {
+            result.push(result[parent as usize] * local);
+        } else {
+            result.push(local);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_animation(default_data: Vec<u8>, frame_data: Vec<u8>, frame_size: u16, frame_count: u16) -> Animation {
+        let empty = TagDataOffset { size: 0, external: 0, file_offset: 0, pointer: 0 };
+        Animation {
+            name: "test".to_string(),
+            animation_type: AnimationType::Base,
+            frame_count,
+            frame_size,
+            frame_info_type: 0,
+            node_list_checksum: 0,
+            node_count: 2,
+            loop_frame_index: 0,
+            weight: 1.0,
+            key_frame_index: 0,
+            second_key_frame_index: 0,
+            next_animation: -1,
+            flags: 0,
+            sound: -1,
+            sound_frame_index: 0,
+            left_foot_frame_index: -1,
+            right_foot_frame_index: -1,
+            frame_info_offset: empty.clone(),
+            node_translation_flags: [0b10, 0],
+            node_rotation_flags: [0, 0],
+            node_scale_flags: [0, 0],
+            offset_to_compressed_data: 0,
+            default_data_offset: empty.clone(),
+            frame_data_offset: empty,
+            default_data,
+            frame_data,
+        }
+    }
+
+    fn identity_rotation() -> Vec<u8> {
+        [0i16, 0, 0, i16::MAX].iter().flat_map(|x| x.to_le_bytes()).collect()
+    }
+
+    fn floats(values: &[f32]) -> Vec<u8> {
+        values.iter().flat_map(|x| x.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn test_decode_frames() {
+        // node 0 is entirely static, node 1 has an animated translation
+        let mut default_data = identity_rotation();
+        default_data.extend(floats(&[1.0, 2.0, 3.0, 1.0]));
+        default_data.extend(identity_rotation());
+        default_data.extend(floats(&[1.0]));
+        let mut frame_data = floats(&[0.0, 0.0, 0.0]);
+        frame_data.extend(floats(&[0.0, 0.0, 4.0]));
+        let animation = make_animation(default_data, frame_data, 12, 2);
+
+        let rest = NodeTransform { rotation: Quat::identity(), translation: vec3(0.0, 0.0, 0.0), scale: 1.0 };
+        let frames = animation.decode_frames(&[rest, rest]).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0][0].translation, vec3(1.0, 2.0, 3.0));
+        assert_eq!(frames[1][1].translation, vec3(0.0, 0.0, 4.0));
+        assert_eq!(frames[1][1].lerp(&frames[0][1], 0.5).translation, vec3(0.0, 0.0, 2.0));
+
+        let world = compute_world_matrices(&[-1, 0], &frames[1]);
+        assert_eq!(world[1].column(3).xyz(), vec3(1.0, 2.0, 7.0));
+
+        let truncated = make_animation(Vec::new(), Vec::new(), 12, 2);
+        assert!(truncated.decode_frames(&[rest, rest]).is_none());
+    }
+
+    fn packed_rotation(i: u64, j: u64, k: u64, w: u64) -> Vec<u8> {
+        let packed = i << 36 | j << 24 | k << 12 | w;
+        [packed >> 32, packed >> 16, packed].iter().flat_map(|x| (*x as u16).to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn test_decode_compressed_frames() {
+        // node 0 is static, node 1 rotates to -k on frame 2 and translates to
+        // (0, 0, 4) on frame 3
+        let identity = packed_rotation(0, 0, 0, 0x7FF);
+        let mut sections = vec![
+            [1u32 << 12, 1].iter().flat_map(|x| x.to_le_bytes()).collect::<Vec<u8>>(),
+            2u16.to_le_bytes().to_vec(),
+            [identity.clone(), identity].concat(),
+            packed_rotation(0, 0, 0x800, 0),
+            [1u32 << 12, 1].iter().flat_map(|x| x.to_le_bytes()).collect(),
+            3u16.to_le_bytes().to_vec(),
+            floats(&[1.0, 2.0, 3.0, 0.0, 0.0, 0.0]),
+            floats(&[0.0, 0.0, 4.0]),
+            vec![0; 8],
+            Vec::new(),
+            floats(&[1.0, 1.0]),
+            Vec::new(),
+        ];
+        let mut data = Vec::new();
+        let mut offset = COMPRESSED_HEADER_SIZE;
+        for section in &sections[..11] {
+            offset += section.len();
+            data.extend((offset as u32).to_le_bytes());
+        }
+        for section in sections.iter_mut() {
+            data.append(section);
+        }
+        let mut animation = make_animation(Vec::new(), data, 0, 4);
+        animation.flags = ANIMATION_FLAG_COMPRESSED_DATA;
+
+        let rest = NodeTransform { rotation: Quat::identity(), translation: vec3(0.0, 0.0, 0.0), scale: 1.0 };
+        let frames = animation.decode_frames(&[rest, rest]).unwrap();
+        assert_eq!(frames.len(), 4);
+        assert_eq!(frames[3][0].translation, vec3(1.0, 2.0, 3.0));
+        assert_eq!(frames[0][1].rotation, Quat::identity());
+        assert_eq!(frames[2][1].rotation, Quat::new(0.0, 0.0, 0.0, -1.0));
+        assert_eq!(frames[3][1].rotation, Quat::new(0.0, 0.0, 0.0, -1.0));
+        assert_eq!(frames[1][1].translation, vec3(0.0, 0.0, 4.0 / 3.0));
+        assert_eq!(frames[3][1].translation, vec3(0.0, 0.0, 4.0));
+        assert_eq!(frames[2][1].scale, 1.0);
+
+        animation.frame_data.truncate(COMPRESSED_HEADER_SIZE + 4);
+        assert!(animation.decode_frames(&[rest, rest]).is_none());
+    }
+}
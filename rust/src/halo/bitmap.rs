@@ -4,9 +4,10 @@ use deku::prelude::*;
 use wasm_bindgen::prelude::*;
 use crate::{halo::common::*, unity::types::common::NullTerminatedAsciiString};
 use crate::halo::bitmap_utils;
+use crate::halo::json::*;
 
 #[wasm_bindgen(js_name = "HaloBitmapType")]
-#[derive(Debug, Clone, Copy, DekuRead)]
+#[derive(Debug, Clone, Copy, DekuRead, ToJson)]
 #[deku(id_type = "u16")]
 #[repr(u16)]
 pub enum BitmapType {
@@ -18,7 +19,7 @@ pub enum BitmapType {
 }
 
 #[wasm_bindgen(js_name = "HaloBitmapEncodingFormat")]
-#[derive(Debug, Clone, Copy, DekuRead)]
+#[derive(Debug, Clone, Copy, DekuRead, ToJson)]
 #[deku(id_type = "u16")]
 #[repr(u16)]
 pub enum BitmapEncodingFormat {
@@ -31,7 +32,7 @@ pub enum BitmapEncodingFormat {
 }
 
 #[wasm_bindgen(js_name = "HaloBitmapUsage")]
-#[derive(Debug, Copy, Clone, DekuRead)]
+#[derive(Debug, Copy, Clone, DekuRead, ToJson)]
 #[deku(id_type = "u16")]
 #[repr(u16)]
 pub enum BitmapUsage {
@@ -43,7 +44,7 @@ pub enum BitmapUsage {
     Vectormap = 0x5,
 }
 
-#[derive(Debug, Copy, Clone, DekuRead)]
+#[derive(Debug, Copy, Clone, DekuRead, ToJson)]
 #[deku(id_type = "u16")]
 #[repr(u16)]
 pub enum BitmapSpriteBudgetSize {
@@ -57,7 +58,7 @@ pub enum BitmapSpriteBudgetSize {
 }
 
 #[wasm_bindgen(js_name = "HaloBitmapSpriteUsage")]
-#[derive(Debug, Copy, Clone, DekuRead)]
+#[derive(Debug, Copy, Clone, DekuRead, ToJson)]
 #[deku(id_type = "u16")]
 #[repr(u16)]
 pub enum BitmapSpriteUsage {
@@ -66,7 +67,7 @@ pub enum BitmapSpriteUsage {
     DoubleMultiply = 0x2,
 }
 
-#[derive(Debug, Clone, DekuRead)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
 pub struct Sprite {
     pub bitmap_index: u16,
     pub left: f32,
@@ -76,7 +77,7 @@ pub struct Sprite {
     pub registration_point: Point2D,
 }
 
-#[derive(Debug, Clone, DekuRead)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
 pub struct BitmapGroup {
     pub name: NullTerminatedAsciiString,
     pub first_bitmap_index: u16,
@@ -85,7 +86,7 @@ pub struct BitmapGroup {
 }
 
 #[wasm_bindgen(js_name = "HaloBitmapClass")]
-#[derive(Debug, Copy, Clone, DekuRead)]
+#[derive(Debug, Copy, Clone, DekuRead, ToJson)]
 #[deku(id_type = "u32")]
 #[repr(u32)]
 pub enum BitmapClass {
@@ -177,7 +178,7 @@ pub enum BitmapClass {
 }
 
 #[wasm_bindgen]
-#[derive(Debug, Copy, Clone, DekuRead)]
+#[derive(Debug, Copy, Clone, DekuRead, ToJson)]
 #[deku(id_type = "u16")]
 #[repr(u16)]
 pub enum BitmapDataType {
@@ -188,7 +189,7 @@ pub enum BitmapDataType {
 }
 
 #[wasm_bindgen]
-#[derive(Debug, Copy, Clone, DekuRead)]
+#[derive(Debug, Copy, Clone, DekuRead, ToJson)]
 #[deku(id_type = "u16")]
 #[repr(u16)]
 pub enum BitmapFormat {
@@ -230,7 +231,7 @@ impl BitmapFormat {
 }
 
 #[wasm_bindgen(js_name = "HaloBitmapMetadata")]
-#[derive(Debug, Clone, DekuRead)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
 pub struct BitmapData {
     pub bitmap_class: BitmapClass,
     pub width: u16,
@@ -257,7 +258,7 @@ impl BitmapData {
 }
 
 #[wasm_bindgen(js_name = "HaloBitmap")]
-#[derive(Debug, Clone, DekuRead)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
 pub struct Bitmap {
     pub bitmap_type: BitmapType,
     pub encoding_format: BitmapEncodingFormat,
@@ -291,22 +292,205 @@ impl Bitmap {
     pub fn get_tag_id(&self) -> u32 {
         self.data.items.as_ref().unwrap()[0].bitmap_tag_id
     }
+
+    pub fn get_bitmap_count(&self) -> usize {
+        self.data.items.as_ref().map_or(0, |items| items.len())
+    }
+}
+
+// Halo stores cube map faces as +X, +Y, -X, -Y, +Z, -Z; this is the Halo face
+// for each face in the usual +X, -X, +Y, -Y, +Z, -Z order
+const CUBE_MAP_FACE_ORDER: [usize; 6] = [0, 2, 1, 3, 4, 5];
+// on Xbox, each cube map face (and all its mips) starts on a 128 byte boundary
+const XBOX_CUBE_MAP_FACE_ALIGNMENT: usize = 0x80;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BitmapConversionOptions {
+    // decode DXT1/3/5 to RGBA8, rather than leaving them compressed
+    pub decode_dxt: bool,
+    // Xbox maps store cube maps face by face rather than mip by mip
+    pub xbox_layout: bool,
 }
 
-pub fn get_and_convert_bitmap_data(reader: &mut deku::reader::Reader<Cursor<Vec<u8>>>, bitmap_data: &BitmapData) -> Vec<u8> {
+impl BitmapData {
+    pub fn is_swizzled(&self) -> bool {
+        (self.flags & 0x8) > 0
+    }
+
+    fn get_face_count(&self) -> usize {
+        match self.bitmap_type {
+            BitmapDataType::CubeMap => 6,
+            _ => 1,
+        }
+    }
+
+    fn get_level_dimensions(&self, level: usize) -> (usize, usize, usize) {
+        let depth = match self.bitmap_type {
+            BitmapDataType::Tex3D => (self.depth as usize >> level).max(1),
+            _ => 1,
+        };
+        ((self.width as usize >> level).max(1), (self.height as usize >> level).max(1), depth)
+    }
+
+    // size of a single face (or the entire volume of a 3D texture) at a mip level
+    fn get_level_size(&self, level: usize) -> usize {
+        let (width, height, depth) = self.get_level_dimensions(level);
+        match self.format {
+            BitmapFormat::Dxt1 => width.div_ceil(4) * height.div_ceil(4) * 8 * depth,
+            BitmapFormat::Dxt3 | BitmapFormat::Dxt5 => width.div_ceil(4) * height.div_ceil(4) * 16 * depth,
+            format => width * height * depth * format.pitch() as usize,
+        }
+    }
+}
+
+// Returns every mip level, largest first. Within a level, cube maps have six
+// faces ordered +X, -X, +Y, -Y, +Z, -Z, and 3D textures have all of that
+// level's slices, front to back. Everything is converted to RGBA8, except for
+// DXT formats when not decoding them and formats we can't convert, which are
+// left as is.
+pub fn get_and_convert_bitmap_data(reader: &mut deku::reader::Reader<Cursor<Vec<u8>>>, bitmap_data: &BitmapData, options: &BitmapConversionOptions) -> Vec<u8> {
     let offset = bitmap_data.pixel_data_offset as u64;
     let length = bitmap_data.pixel_data_size as usize;
     let mut bytes = vec![0; length];
     reader.seek(std::io::SeekFrom::Start(offset)).unwrap();
     reader.read_bytes(length, &mut bytes, deku::ctx::Order::Msb0).unwrap();
+
+    // find where each face of each level lives in the source data
+    let level_count = bitmap_data.mipmap_count as usize + 1;
+    let face_count = bitmap_data.get_face_count();
+    let mut surfaces: Vec<Vec<(usize, usize)>> = vec![Vec::new(); level_count];
+    let mut position = 0;
+    if options.xbox_layout && face_count > 1 {
+        for face in 0..face_count {
+            for (level, level_surfaces) in surfaces.iter_mut().enumerate() {
+                let size = bitmap_data.get_level_size(level);
+                level_surfaces.push((position, size));
+                position += size;
+            }
+            if face < face_count - 1 {
+                position = position.next_multiple_of(XBOX_CUBE_MAP_FACE_ALIGNMENT);
+            }
+        }
+    } else {
+        for (level, level_surfaces) in surfaces.iter_mut().enumerate() {
+            let size = bitmap_data.get_level_size(level);
+            for _ in 0..face_count {
+                level_surfaces.push((position, size));
+                position += size;
+            }
+        }
+    }
+
+    let mut result = Vec::with_capacity(length * 4);
+    for (level, level_surfaces) in surfaces.iter().enumerate() {
+        // some bitmaps don't actually include their smallest mips
+        if level_surfaces.iter().any(|&(start, size)| start + size > bytes.len()) {
+            break;
+        }
+        let face_order: &[usize] = if face_count == 6 { &CUBE_MAP_FACE_ORDER } else { &[0] };
+        for &halo_face in face_order {
+            let (start, size) = level_surfaces[halo_face];
+            let (width, height, depth) = bitmap_data.get_level_dimensions(level);
+            result.extend(convert_surface(&bytes[start..start + size], bitmap_data, width, height, depth, options));
+        }
+    }
+    result
+}
+
+fn convert_surface(bytes: &[u8], bitmap_data: &BitmapData, width: usize, height: usize, depth: usize, options: &BitmapConversionOptions) -> Vec<u8> {
+    let is_compressed = matches!(bitmap_data.format, BitmapFormat::Dxt1 | BitmapFormat::Dxt3 | BitmapFormat::Dxt5);
+    let unswizzled;
+    let bytes = if bitmap_data.is_swizzled() && !is_compressed {
+        unswizzled = bitmap_utils::unswizzle_data(bytes, width, height, depth, bitmap_data.format.pitch() as usize);
+        &unswizzled
+    } else {
+        bytes
+    };
     match bitmap_data.format {
-        BitmapFormat::P8 | BitmapFormat::P8Bump => bitmap_utils::convert_p8_data(&bytes),
-        BitmapFormat::A8r8g8b8 => bitmap_utils::convert_a8r8g8b8_data(&bytes),
-        BitmapFormat::X8r8g8b8 => bitmap_utils::convert_x8r8g8b8_data(&bytes),
-        BitmapFormat::A8 => bitmap_utils::convert_a8_data(&bytes),
-        BitmapFormat::Y8 => bitmap_utils::convert_y8_data(&bytes),
-        BitmapFormat::A8y8 => bitmap_utils::convert_a8y8_data(&bytes),
-        BitmapFormat::R5g6b5 => bitmap_utils::convert_r5g6b5_data(&bytes),
-        _ => bytes,
+        BitmapFormat::P8 | BitmapFormat::P8Bump => bitmap_utils::convert_p8_data(bytes),
+        BitmapFormat::A8r8g8b8 => bitmap_utils::convert_a8r8g8b8_data(bytes),
+        BitmapFormat::X8r8g8b8 => bitmap_utils::convert_x8r8g8b8_data(bytes),
+        BitmapFormat::A8 => bitmap_utils::convert_a8_data(bytes),
+        BitmapFormat::Y8 => bitmap_utils::convert_y8_data(bytes),
+        BitmapFormat::A8y8 => bitmap_utils::convert_a8y8_data(bytes),
+        BitmapFormat::R5g6b5 => bitmap_utils::convert_r5g6b5_data(bytes),
+        BitmapFormat::Dxt1 | BitmapFormat::Dxt3 | BitmapFormat::Dxt5 if options.decode_dxt => {
+            let slice_size = bytes.len() / depth;
+            let mut result = Vec::with_capacity(width * height * depth * 4);
+            for slice in bytes.chunks_exact(slice_size) {
+                result.extend(match bitmap_data.format {
+                    BitmapFormat::Dxt1 => bitmap_utils::decode_dxt1_data(slice, width, height),
+                    BitmapFormat::Dxt3 => bitmap_utils::decode_dxt3_data(slice, width, height),
+                    _ => bitmap_utils::decode_dxt5_data(slice, width, height),
+                });
+            }
+            result
+        },
+        _ => bytes.to_vec(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // an 8x8 DXT1 cube map with 2 mip levels, which are 32 and 8 bytes
+    fn make_cube_map(pixel_data_size: u32) -> BitmapData {
+        BitmapData {
+            bitmap_class: BitmapClass::Bitmap,
+            width: 8,
+            height: 8,
+            depth: 1,
+            bitmap_type: BitmapDataType::CubeMap,
+            format: BitmapFormat::Dxt1,
+            flags: 0,
+            registration_point: Point2DInt { x: 0, y: 0 },
+            mipmap_count: 1,
+            pixel_data_offset: 0,
+            pixel_data_size,
+            bitmap_tag_id: 0,
+            pointer: 0,
+        }
+    }
+
+    // every byte is marked with the Halo face index and the mip level
+    fn mark(data: &mut [u8], start: usize, size: usize, face: usize, level: usize) {
+        data[start..start + size].fill((face * 16 + level) as u8);
+    }
+
+    // faces should come out as +X, -X, +Y, -Y, +Z, -Z for each level
+    fn assert_cube_map(result: &[u8]) {
+        let mut expected = Vec::new();
+        for (level, size) in [(0, 32), (1, 8)] {
+            for face in CUBE_MAP_FACE_ORDER {
+                expected.extend(std::iter::repeat_n((face * 16 + level) as u8, size));
+            }
+        }
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_cube_map_layout() {
+        let options = BitmapConversionOptions::default();
+        // PC maps store each level's faces together
+        let mut data = vec![0; 6 * 40];
+        for face in 0..6 {
+            mark(&mut data, face * 32, 32, face, 0);
+            mark(&mut data, 6 * 32 + face * 8, 8, face, 1);
+        }
+        let bitmap_data = make_cube_map(data.len() as u32);
+        let mut reader = deku::reader::Reader::new(Cursor::new(data));
+        assert_cube_map(&get_and_convert_bitmap_data(&mut reader, &bitmap_data, &options));
+
+        // Xbox maps store each face's levels together, padded to 128 bytes
+        let options = BitmapConversionOptions { xbox_layout: true, ..options };
+        let mut data = vec![0xFF; 5 * 0x80 + 40];
+        for face in 0..6 {
+            mark(&mut data, face * 0x80, 32, face, 0);
+            mark(&mut data, face * 0x80 + 32, 8, face, 1);
+        }
+        let bitmap_data = make_cube_map(data.len() as u32);
+        let mut reader = deku::reader::Reader::new(Cursor::new(data));
+        assert_cube_map(&get_and_convert_bitmap_data(&mut reader, &bitmap_data, &options));
     }
 }
@@ -93,10 +93,196 @@ pub fn convert_r5g6b5_data(input: &[u8]) -> Vec<u8> {
     let mut result = Vec::with_capacity(input.len() * 2);
     for i in (0..input.len()).step_by(2) {
         let p = ((input[i + 1] as u16) << 8) | (input[i + 0] as u16);
-        result.push(util::expand_n_to_8(5, ((p >> 11) & 0x1F) as u8));
-        result.push(util::expand_n_to_8(6, ((p >>  5) & 0x3F) as u8));
-        result.push(util::expand_n_to_8(5, ((p >>  0) & 0x1F) as u8));
-        result.push(0xFF);
+        result.extend(unpack_r5g6b5(p));
+    }
+    result
+}
+
+fn unpack_r5g6b5(p: u16) -> [u8; 4] {
+    [
+        util::expand_n_to_8(5, ((p >> 11) & 0x1F) as u8),
+        util::expand_n_to_8(6, ((p >>  5) & 0x3F) as u8),
+        util::expand_n_to_8(5, (p & 0x1F) as u8),
+        0xFF,
+    ]
+}
+
+// The four colors of a DXT color block. DXT1 blocks whose first endpoint is
+// smaller than the second use 1-bit alpha, with the last color transparent.
+fn get_dxt_palette(block: &[u8], allow_one_bit_alpha: bool) -> [[u8; 4]; 4] {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let p0 = unpack_r5g6b5(c0);
+    let p1 = unpack_r5g6b5(c1);
+    let mix = |a: u8, b: u8, wa: u16, wb: u16| ((a as u16 * wa + b as u16 * wb) / (wa + wb)) as u8;
+    let mut palette = [p0, p1, [0; 4], [0; 4]];
+    if c0 > c1 || !allow_one_bit_alpha {
+        for i in 0..3 {
+            palette[2][i] = mix(p0[i], p1[i], 2, 1);
+            palette[3][i] = mix(p0[i], p1[i], 1, 2);
+        }
+        palette[2][3] = 0xFF;
+        palette[3][3] = 0xFF;
+    } else {
+        for i in 0..3 {
+            palette[2][i] = mix(p0[i], p1[i], 1, 1);
+        }
+        palette[2][3] = 0xFF;
+    }
+    palette
+}
+
+// The alpha values of a DXT5 alpha block
+fn get_dxt5_alphas(block: &[u8]) -> [u8; 8] {
+    let (a0, a1) = (block[0] as usize, block[1] as usize);
+    let mut alphas = [block[0], block[1], 0, 0, 0, 0, 0, 0xFF];
+    if a0 > a1 {
+        for i in 1..7 {
+            alphas[i + 1] = (((7 - i) * a0 + i * a1) / 7) as u8;
+        }
+    } else {
+        for i in 1..5 {
+            alphas[i + 1] = (((5 - i) * a0 + i * a1) / 5) as u8;
+        }
+        alphas[6] = 0;
+    }
+    alphas
+}
+
+fn decode_dxt(input: &[u8], width: usize, height: usize, block_size: usize, decode_block: impl Fn(&[u8], usize) -> [u8; 4]) -> Vec<u8> {
+    let mut result = vec![0; width * height * 4];
+    let blocks_x = width.div_ceil(4);
+    for (block_index, block) in input.chunks_exact(block_size).take(blocks_x * height.div_ceil(4)).enumerate() {
+        let (bx, by) = (block_index % blocks_x * 4, block_index / blocks_x * 4);
+        for texel in 0..16 {
+            let (x, y) = (bx + texel % 4, by + texel / 4);
+            if x < width && y < height {
+                let offset = (y * width + x) * 4;
+                result[offset..offset + 4].copy_from_slice(&decode_block(block, texel));
+            }
+        }
+    }
+    result
+}
+
+fn get_color_index(color_block: &[u8], texel: usize) -> usize {
+    let indices = u32::from_le_bytes([color_block[4], color_block[5], color_block[6], color_block[7]]);
+    ((indices >> (texel * 2)) & 0x3) as usize
+}
+
+pub fn decode_dxt1_data(input: &[u8], width: usize, height: usize) -> Vec<u8> {
+    decode_dxt(input, width, height, 8, |block, texel| {
+        get_dxt_palette(block, true)[get_color_index(block, texel)]
+    })
+}
+
+pub fn decode_dxt3_data(input: &[u8], width: usize, height: usize) -> Vec<u8> {
+    decode_dxt(input, width, height, 16, |block, texel| {
+        let mut color = get_dxt_palette(&block[8..], false)[get_color_index(&block[8..], texel)];
+        let alpha = (block[texel / 2] >> ((texel % 2) * 4)) & 0xF;
+        color[3] = util::expand_n_to_8(4, alpha);
+        color
+    })
+}
+
+pub fn decode_dxt5_data(input: &[u8], width: usize, height: usize) -> Vec<u8> {
+    decode_dxt(input, width, height, 16, |block, texel| {
+        let mut color = get_dxt_palette(&block[8..], false)[get_color_index(&block[8..], texel)];
+        let mut index_bytes = [0; 8];
+        index_bytes[..6].copy_from_slice(&block[2..8]);
+        let alpha_index = (u64::from_le_bytes(index_bytes) >> (texel * 3)) & 0x7;
+        color[3] = get_dxt5_alphas(block)[alpha_index as usize];
+        color
+    })
+}
+
+// Scatters the bits of v into the set bits of mask, lowest first
+fn deposit_bits(mut v: usize, mask: usize) -> usize {
+    let mut result = 0;
+    let mut bit = 1;
+    while bit <= mask && bit != 0 {
+        if mask & bit != 0 {
+            if v & 1 != 0 {
+                result |= bit;
+            }
+            v >>= 1;
+        }
+        bit <<= 1;
+    }
+    result
+}
+
+// Xbox textures store texels in Morton order: the bits of each coordinate are
+// interleaved (x, then y, then z), until a dimension runs out of bits
+pub fn unswizzle_data(input: &[u8], width: usize, height: usize, depth: usize, bytes_per_texel: usize) -> Vec<u8> {
+    let (mut mask_x, mut mask_y, mut mask_z) = (0, 0, 0);
+    let mut mask_bit = 1;
+    let mut size = 1;
+    while size < width || size < height || size < depth {
+        if size < width {
+            mask_x |= mask_bit;
+            mask_bit <<= 1;
+        }
+        if size < height {
+            mask_y |= mask_bit;
+            mask_bit <<= 1;
+        }
+        if size < depth {
+            mask_z |= mask_bit;
+            mask_bit <<= 1;
+        }
+        size <<= 1;
+    }
+
+    let mut result = vec![0; width * height * depth * bytes_per_texel];
+    for z in 0..depth {
+        for y in 0..height {
+            for x in 0..width {
+                let src = (deposit_bits(x, mask_x) | deposit_bits(y, mask_y) | deposit_bits(z, mask_z)) * bytes_per_texel;
+                let dst = ((z * height + y) * width + x) * bytes_per_texel;
+                if src + bytes_per_texel <= input.len() {
+                    result[dst..dst + bytes_per_texel].copy_from_slice(&input[src..src + bytes_per_texel]);
+                }
+            }
+        }
     }
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_dxt1() {
+        // c0 = white, c1 = black, with texels cycling through all four indices
+        let block = [0xFF, 0xFF, 0x00, 0x00, 0xE4, 0xE4, 0xE4, 0xE4];
+        let rgba = decode_dxt1_data(&block, 4, 4);
+        assert_eq!(&rgba[0..4], &[0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(&rgba[4..8], &[0x00, 0x00, 0x00, 0xFF]);
+        assert_eq!(&rgba[8..12], &[0xAA, 0xAA, 0xAA, 0xFF]);
+        assert_eq!(&rgba[12..16], &[0x55, 0x55, 0x55, 0xFF]);
+
+        // with c0 <= c1, index 3 is transparent black
+        let block = [0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+        assert_eq!(&decode_dxt1_data(&block, 4, 4)[0..4], &[0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_decode_dxt5_alpha() {
+        // a0 = 255, a1 = 0, first texel uses index 1 and the second index 2
+        let mut block = [0; 16];
+        block[0] = 0xFF;
+        block[2] = 0b010_001;
+        let rgba = decode_dxt5_data(&block, 4, 4);
+        assert_eq!(rgba[3], 0);
+        assert_eq!(rgba[7], 218);
+    }
+
+    #[test]
+    fn test_unswizzle() {
+        // a 4x2 texture: the swizzled order visits 2x2 squares left to right
+        let swizzled = [0, 1, 4, 5, 2, 3, 6, 7];
+        assert_eq!(unswizzle_data(&swizzled, 4, 2, 1, 1), vec![0, 1, 2, 3, 4, 5, 6, 7]);
+    }
+}
@@ -0,0 +1,261 @@
+use nalgebra_glm::{cross, dot, make_vec3, vec3, Vec3};
+use wasm_bindgen::prelude::*;
+
+use crate::halo::common::*;
+use crate::halo::scenario::*;
+
+const CHILD_IS_LEAF: u32 = 0x80000000;
+const NULL_CHILD: u32 = 0xFFFFFFFF;
+const PLANE_FLIPPED: u32 = 0x80000000;
+const EPSILON: f32 = 0.0001;
+
+#[wasm_bindgen(js_name = "HaloRaycastHit", getter_with_clone)]
+#[derive(Debug, Clone)]
+pub struct RaycastHit {
+    pub distance: f32,
+    pub position: Point3D,
+    pub normal: Vector3D,
+    pub surface: u32,
+    pub material: u16,
+}
+
+#[wasm_bindgen(js_class = "HaloBSP")]
+impl BSP {
+    pub fn find_leaf_for_point(&self, point_slice: &[f32]) -> Option<u32> {
+        self.get_collision_bsp()?.find_leaf(&make_vec3(point_slice))
+    }
+
+    pub fn find_cluster_for_point(&self, point_slice: &[f32]) -> Option<u32> {
+        let leaf_index = self.find_leaf_for_point(point_slice)?;
+        let leaf = self.leaves.items.as_ref()?.get(leaf_index as usize)?;
+        if leaf.cluster < 0 {
+            return None;
+        }
+        Some(leaf.cluster as u32)
+    }
+
+    pub fn cast_ray(&self, origin_slice: &[f32], direction_slice: &[f32], max_distance: f32) -> Option<RaycastHit> {
+        let direction = make_vec3(direction_slice).normalize();
+        self.get_collision_bsp()?.cast_ray(&make_vec3(origin_slice), &direction, max_distance)
+    }
+
+    // Finds the height of the closest collision surface directly below the point
+    pub fn pick_closest_surface_neg_z(&self, point_slice: &[f32]) -> Option<f32> {
+        let hit = self.cast_ray(point_slice, &[0.0, 0.0, -1.0], f32::INFINITY)?;
+        Some(hit.position.z)
+    }
+
+    pub fn get_clusters(&self) -> Vec<BSPCluster> {
+        self.clusters.items.as_ref().cloned().unwrap_or_default()
+    }
+
+    pub fn get_cluster_portals(&self) -> Vec<BSPClusterPortal> {
+        self.cluster_portals.items.as_ref().cloned().unwrap_or_default()
+    }
+
+    // Returns the clusters that share a portal with the given cluster
+    pub fn get_adjacent_clusters(&self, cluster_index: u32) -> Vec<u32> {
+        let (Some(clusters), Some(portals)) = (&self.clusters.items, &self.cluster_portals.items) else {
+            return Vec::new();
+        };
+        let Some(cluster) = clusters.get(cluster_index as usize) else {
+            return Vec::new();
+        };
+        let mut result = Vec::new();
+        for portal_index in cluster.portals.items.as_ref().unwrap() {
+            let Some(portal) = portals.get(portal_index.portal as usize) else {
+                continue;
+            };
+            let other = if portal.front_cluster as u32 == cluster_index { portal.back_cluster } else { portal.front_cluster };
+            if other >= 0 && !result.contains(&(other as u32)) {
+                result.push(other as u32);
+            }
+        }
+        result
+    }
+}
+
+// rust-only interface
+impl BSP {
+    fn get_collision_bsp(&self) -> Option<&CollisionBSP> {
+        self.collision_bsp.items.as_ref()?.first()
+    }
+}
+
+#[wasm_bindgen(js_class = "HaloBSPClusterPortal")]
+impl BSPClusterPortal {
+    pub fn get_vertices(&self) -> Vec<f32> {
+        let mut result = Vec::new();
+        for v in self.vertices.items.as_ref().unwrap() {
+            result.extend([v.x, v.y, v.z]);
+        }
+        result
+    }
+}
+
+impl CollisionBSP {
+    pub fn find_leaf(&self, p: &Vec3) -> Option<u32> {
+        let nodes = self.bsp3d_nodes.items.as_ref()?;
+        let planes = self.planes.items.as_ref()?;
+        if nodes.is_empty() {
+            return None;
+        }
+        let mut node_index = 0;
+        // guard against malformed trees by never taking more steps than there are nodes
+        for _ in 0..=nodes.len() {
+            if node_index == NULL_CHILD {
+                return None;
+            }
+            if node_index & CHILD_IS_LEAF != 0 {
+                return Some(node_index & !CHILD_IS_LEAF);
+            }
+            let node = nodes.get(node_index as usize)?;
+            let plane = planes.get((node.plane & !PLANE_FLIPPED) as usize)?;
+            node_index = if plane_distance(plane, p) >= 0.0 { node.front_child } else { node.back_child };
+        }
+        None
+    }
+
+    pub fn cast_ray(&self, origin: &Vec3, direction: &Vec3, max_distance: f32) -> Option<RaycastHit> {
+        let surfaces = self.surfaces.items.as_ref()?;
+        let mut result: Option<RaycastHit> = None;
+        for (surface_index, surface) in surfaces.iter().enumerate() {
+            let Some((normal, w)) = self.get_surface_plane(surface) else {
+                continue;
+            };
+            let denom = dot(&normal, direction);
+            if denom.abs() < EPSILON {
+                continue;
+            }
+            let t = (w - dot(&normal, origin)) / denom;
+            let closest = result.as_ref().map_or(max_distance, |hit| hit.distance);
+            if t < 0.0 || t > closest {
+                continue;
+            }
+            let p = origin + direction * t;
+            let vertices = self.get_surface_vertices(surface_index as u32);
+            if !is_point_in_polygon(&p, &normal, &vertices) {
+                continue;
+            }
+            result = Some(RaycastHit {
+                distance: t,
+                position: Point3D { x: p.x, y: p.y, z: p.z },
+                normal: Vector3D { i: normal.x, j: normal.y, k: normal.z },
+                surface: surface_index as u32,
+                material: surface.material,
+            });
+        }
+        result
+    }
+
+    fn get_surface_plane(&self, surface: &CollisionSurface) -> Option<(Vec3, f32)> {
+        let plane = self.planes.items.as_ref()?.get((surface.plane & !PLANE_FLIPPED) as usize)?;
+        let normal = vec3(plane.norm.i, plane.norm.j, plane.norm.k);
+        if surface.plane & PLANE_FLIPPED != 0 {
+            Some((-normal, -plane.w))
+        } else {
+            Some((normal, plane.w))
+        }
+    }
+
+    // Walks the edge ring around a surface. Each edge borders two surfaces,
+    // and which way it's traversed depends on which side this surface is on
+    fn get_surface_vertices(&self, surface_index: u32) -> Vec<Vec3> {
+        let mut result = Vec::new();
+        let (Some(surfaces), Some(edges), Some(vertices)) = (&self.surfaces.items, &self.edges.items, &self.vertices.items) else {
+            return result;
+        };
+        let first_edge = surfaces[surface_index as usize].first_edge;
+        let mut edge_index = first_edge;
+        for _ in 0..edges.len() {
+            let Some(edge) = edges.get(edge_index as usize) else {
+                break;
+            };
+            let (vertex_index, next_edge) = if edge.left_surface == surface_index {
+                (edge.start_vertex, edge.forward_edge)
+            } else {
+                (edge.end_vertex, edge.reverse_edge)
+            };
+            if let Some(vertex) = vertices.get(vertex_index as usize) {
+                result.push(vec3(vertex.point.x, vertex.point.y, vertex.point.z));
+            }
+            edge_index = next_edge;
+            if edge_index == first_edge {
+                break;
+            }
+        }
+        result
+    }
+}
+
+fn plane_distance(plane: &Plane3D, p: &Vec3) -> f32 {
+    plane.norm.i * p.x + plane.norm.j * p.y + plane.norm.k * p.z - plane.w
+}
+
+// Collision surfaces are convex, so the point is inside if it's on the same
+// side of every edge
+fn is_point_in_polygon(p: &Vec3, normal: &Vec3, vertices: &[Vec3]) -> bool {
+    if vertices.len() < 3 {
+        return false;
+    }
+    let mut has_positive = false;
+    let mut has_negative = false;
+    for i in 0..vertices.len() {
+        let a = vertices[i];
+        let b = vertices[(i + 1) % vertices.len()];
+        let side = dot(&cross(&(b - a), &(p - a)), normal);
+        if side > EPSILON {
+            has_positive = true;
+        } else if side < -EPSILON {
+            has_negative = true;
+        }
+    }
+    !(has_positive && has_negative)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a single unit square floor at z = 0, with everything above it in leaf 0
+    fn make_floor() -> CollisionBSP {
+        let corners = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        let vertices = corners.iter().enumerate()
+            .map(|(i, &(x, y))| CollisionVertex { point: Point3D { x, y, z: 0.0 }, first_edge: i as u32 })
+            .collect();
+        let edges = (0..4)
+            .map(|i| CollisionEdge {
+                start_vertex: i,
+                end_vertex: (i + 1) % 4,
+                forward_edge: (i + 1) % 4,
+                reverse_edge: (i + 3) % 4,
+                left_surface: 0,
+                right_surface: NULL_CHILD,
+            })
+            .collect();
+        CollisionBSP {
+            bsp3d_nodes: Block::from_items(vec![BSP3DNode { plane: 0, back_child: NULL_CHILD, front_child: CHILD_IS_LEAF }]),
+            planes: Block::from_items(vec![Plane3D { norm: Vector3D { i: 0.0, j: 0.0, k: 1.0 }, w: 0.0 }]),
+            leaves: Block::from_items(vec![]),
+            bsp2d_references: Block::from_items(vec![]),
+            bsp2d_nodes: Block::from_items(vec![]),
+            surfaces: Block::from_items(vec![CollisionSurface { plane: 0, first_edge: 0, flags: 0, breakable_surface: -1, material: 3 }]),
+            edges: Block::from_items(edges),
+            vertices: Block::from_items(vertices),
+        }
+    }
+
+    #[test]
+    fn test_collision_queries() {
+        let bsp = make_floor();
+        assert_eq!(bsp.find_leaf(&vec3(0.5, 0.5, 1.0)), Some(0));
+        assert_eq!(bsp.find_leaf(&vec3(0.5, 0.5, -1.0)), None);
+
+        let down = vec3(0.0, 0.0, -1.0);
+        let hit = bsp.cast_ray(&vec3(0.25, 0.75, 10.0), &down, f32::INFINITY).unwrap();
+        assert_eq!(hit.distance, 10.0);
+        assert_eq!(hit.material, 3);
+        assert!(bsp.cast_ray(&vec3(0.25, 0.75, 10.0), &down, 5.0).is_none());
+        assert!(bsp.cast_ray(&vec3(2.0, 0.5, 10.0), &down, f32::INFINITY).is_none());
+    }
+}
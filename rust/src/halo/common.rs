@@ -2,6 +2,7 @@ use std::{error::Error, fmt::Display, io::{Cursor, Seek, SeekFrom}};
 use deku::prelude::*;
 use anyhow::Result;
 use wasm_bindgen::prelude::*;
+use crate::halo::json::*;
 
 pub type Pointer = u32;
 
@@ -47,30 +48,58 @@ impl<'a, T: DekuContainerRead<'a> + std::fmt::Debug> Block<T> {
     }
 }
 
+#[cfg(test)]
+impl<T> Block<T> {
+    // a block that's already been read, for building tags in tests
+    pub fn from_items(items: Vec<T>) -> Self {
+        Block { count: items.len() as u32, base_pointer: 0, items: Some(items) }
+    }
+}
+
 #[wasm_bindgen(js_name = "HaloVector3D")]
-#[derive(Debug, Copy, Clone, DekuRead)]
+#[derive(Debug, Copy, Clone, DekuRead, ToJson)]
 pub struct Vector3D {
     pub i: f32,
     pub j: f32,
     pub k: f32,
 }
 
-#[derive(Debug, Clone, DekuRead)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
 pub struct TagDataOffset {
     pub size: u32,
     pub external: u32,
-    #[deku(pad_bytes_after = "8")]
     pub file_offset: u32,
+    #[deku(pad_bytes_after = "4")]
+    pub pointer: Pointer,
+}
+
+impl TagDataOffset {
+    // reads data stored inline with the tag, which is addressed by pointer
+    pub fn read_data(&self, data: &mut Reader<Cursor<Vec<u8>>>, offset: i64) -> Result<Vec<u8>> {
+        let mut buf = vec![0; self.size as usize];
+        if self.size > 0 {
+            data.seek(SeekFrom::Start((self.pointer as i64 + offset) as u64))?;
+            data.read_bytes(buf.len(), &mut buf, deku::ctx::Order::Msb0)?;
+        }
+        Ok(buf)
+    }
+}
+
+// fixed-size, null-terminated 32 character strings
+pub fn read_tag_string<R: std::io::Read + std::io::Seek>(reader: &mut Reader<R>) -> Result<String, DekuError> {
+    let bytes = <[u8; 32]>::from_reader_with_ctx(reader, ())?;
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    Ok(String::from_utf8_lossy(&bytes[..end]).to_string())
 }
 
 #[wasm_bindgen(js_name = "HaloPlane3D")]
-#[derive(Debug, Copy, Clone, DekuRead)]
+#[derive(Debug, Copy, Clone, DekuRead, ToJson)]
 pub struct Plane3D {
     pub norm: Vector3D,
     pub w: f32, // distance from origin (along normal)
 }
 
-#[derive(Debug, Clone, Copy, DekuRead)]
+#[derive(Debug, Clone, Copy, DekuRead, ToJson)]
 pub struct Tri {
     pub v0: u16,
     pub v1: u16,
@@ -78,7 +107,7 @@ pub struct Tri {
 }
 
 #[wasm_bindgen(js_name = "HaloColorRGB")]
-#[derive(Debug, Clone, Copy, DekuRead)]
+#[derive(Debug, Clone, Copy, DekuRead, ToJson)]
 pub struct ColorRGB {
     pub r: f32,
     pub g: f32,
@@ -86,7 +115,7 @@ pub struct ColorRGB {
 }
 
 #[wasm_bindgen(js_name = "HaloColorARGB")]
-#[derive(Debug, Clone, Copy, DekuRead)]
+#[derive(Debug, Clone, Copy, DekuRead, ToJson)]
 #[wasm_bindgen]
 pub struct ColorARGB {
     pub a: f32,
@@ -96,29 +125,46 @@ pub struct ColorARGB {
 }
 
 #[wasm_bindgen(js_name = "HaloPoint2D")]
-#[derive(Debug, Copy, Clone, DekuRead)]
+#[derive(Debug, Copy, Clone, DekuRead, ToJson)]
 pub struct Point2D {
     pub x: f32,
     pub y: f32,
 }
 
+// a range of values, from which a random value is usually picked
+#[wasm_bindgen(js_name = "HaloBounds")]
+#[derive(Debug, Copy, Clone, DekuRead, ToJson)]
+pub struct Bounds {
+    pub lower: f32,
+    pub upper: f32,
+}
+
 #[wasm_bindgen(js_name = "HaloPoint2DInt")]
-#[derive(Debug, Copy, Clone, DekuRead)]
+#[derive(Debug, Copy, Clone, DekuRead, ToJson)]
 pub struct Point2DInt {
     pub x: i16,
     pub y: i16,
 }
 
 #[wasm_bindgen(js_name = "HaloPoint3D")]
-#[derive(Debug, Copy, Clone, DekuRead)]
+#[derive(Debug, Copy, Clone, DekuRead, ToJson)]
 pub struct Point3D {
     pub x: f32,
     pub y: f32,
     pub z: f32,
 }
 
+#[wasm_bindgen(js_name = "HaloQuaternion")]
+#[derive(Debug, Copy, Clone, DekuRead, ToJson)]
+pub struct Quaternion {
+    pub i: f32,
+    pub j: f32,
+    pub k: f32,
+    pub w: f32,
+}
+
 #[wasm_bindgen(js_name = "HaloEuler3D")]
-#[derive(Debug, Clone, Copy, DekuRead)]
+#[derive(Debug, Clone, Copy, DekuRead, ToJson)]
 pub struct Euler3D {
     pub yaw: f32,
     pub pitch: f32,
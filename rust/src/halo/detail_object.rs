@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+use deku::prelude::*;
+use wasm_bindgen::prelude::*;
+
+use crate::halo::common::*;
+use crate::halo::scenario::*;
+use crate::halo::tag::*;
+use crate::halo::json::*;
+
+// Detail object cells are cubes this many world units on a side, and
+// instance positions are quantized to 1/256th of a cell
+const CELL_SIZE: f32 = 8.0;
+const INSTANCE_TYPE_MASK: u8 = 0x3F;
+
+#[wasm_bindgen(js_name = "HaloDetailObjectCollectionType")]
+#[derive(Debug, Copy, Clone, PartialEq, DekuRead, ToJson)]
+#[deku(id_type = "u16")]
+#[repr(u16)]
+pub enum DetailObjectCollectionType {
+    ScreenFacing = 0,
+    ViewerFacing = 1,
+}
+
+#[wasm_bindgen(js_name = "HaloDetailObjectCollection")]
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct DetailObjectCollection {
+    #[deku(pad_bytes_after = "2")]
+    pub collection_type: DetailObjectCollectionType,
+    #[deku(pad_bytes_after = "44")]
+    pub global_z_offset: f32,
+    pub sprite_plate: TagDependency,
+    #[deku(pad_bytes_after = "48")]
+    pub(crate) types: Block<DetailObjectType>,
+}
+
+#[wasm_bindgen(js_class = "HaloDetailObjectCollection")]
+impl DetailObjectCollection {
+    pub fn get_types(&self) -> Vec<DetailObjectType> {
+        self.types.items.as_ref().cloned().unwrap()
+    }
+}
+
+#[wasm_bindgen(js_name = "HaloDetailObjectType", getter_with_clone)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct DetailObjectType {
+    #[deku(reader = "read_tag_string(deku::reader)")]
+    pub name: String,
+    pub sequence_index: i8,
+    pub type_flags: u8,
+    pub first_sprite_index: u8,
+    pub sprite_count: u8,
+    #[deku(pad_bytes_after = "8")]
+    pub color_override_factor: f32,
+    pub near_fade_distance: f32,
+    pub far_fade_distance: f32,
+    #[deku(pad_bytes_after = "4")]
+    pub size: f32,
+    pub minimum_color: ColorRGB,
+    pub maximum_color: ColorRGB,
+    #[deku(pad_bytes_after = "4")]
+    pub ambient_color: u32, // packed ARGB
+}
+
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct BSPDetailObjectData {
+    pub(crate) cells: Block<DetailObjectCell>,
+    pub(crate) instances: Block<DetailObjectInstance>,
+    pub(crate) counts: Block<DetailObjectCount>,
+    #[deku(pad_bytes_after = "16")]
+    pub(crate) z_reference_vectors: Block<DetailObjectZReferenceVector>,
+}
+
+impl BSPDetailObjectData {
+    fn expand_cell(&self, cell_index: usize, result: &mut DetailObjectInstances) {
+        let instances = self.instances.items.as_ref().unwrap();
+        let counts = self.counts.items.as_ref().unwrap();
+        let cell = &self.cells.items.as_ref().unwrap()[cell_index];
+        let z_reference = self.z_reference_vectors.items.as_ref().unwrap().get(cell_index);
+        let origin = cell.get_origin();
+
+        let mut instance_index = cell.start_index.max(0) as usize;
+        let mut count_index = cell.count_index.max(0) as usize;
+        for layer in 0..32 {
+            if cell.valid_layers_flags & (1 << layer) == 0 {
+                continue;
+            }
+            let count = counts.get(count_index).map_or(0, |c| c.count.max(0) as usize);
+            count_index += 1;
+            for instance in instances.iter().skip(instance_index).take(count) {
+                let x = instance.position_x as f32 / 256.0 * CELL_SIZE;
+                let y = instance.position_y as f32 / 256.0 * CELL_SIZE;
+                let z = match z_reference {
+                    Some(v) => v.i * x + v.j * y + v.k + instance.position_z as f32 * v.w,
+                    None => instance.position_z as f32 / 256.0 * CELL_SIZE,
+                };
+                result.positions.extend([origin[0] + x, origin[1] + y, origin[2] + z]);
+                result.colors.extend(unpack_r5g6b5(instance.color));
+                result.collection_indices.push(layer);
+                result.type_indices.push(instance.data & INSTANCE_TYPE_MASK);
+            }
+            instance_index += count;
+        }
+    }
+}
+
+// Each bit in valid_layers_flags is a layer, i.e. an entry in the scenario's
+// detail object collection palette. For every set bit there's a consecutive
+// instance count starting at count_index, and those instances are stored
+// consecutively starting at start_index.
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct DetailObjectCell {
+    pub cell_x: i16,
+    pub cell_y: i16,
+    pub cell_z: i16,
+    pub offset_z: i16,
+    pub valid_layers_flags: u32,
+    pub start_index: i32,
+    #[deku(pad_bytes_after = "12")]
+    pub count_index: i32,
+}
+
+impl DetailObjectCell {
+    fn get_origin(&self) -> [f32; 3] {
+        [
+            self.cell_x as f32 * CELL_SIZE,
+            self.cell_y as f32 * CELL_SIZE,
+            self.cell_z as f32 * CELL_SIZE + self.offset_z as f32 / 256.0 * CELL_SIZE,
+        ]
+    }
+}
+
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct DetailObjectInstance {
+    pub position_x: u8,
+    pub position_y: u8,
+    pub position_z: u8,
+    pub data: u8, // low 6 bits are the index into the collection's types
+    pub color: u16, // r5g6b5
+}
+
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct DetailObjectCount {
+    pub count: i16,
+}
+
+// Approximates the ground within a cell as z = i * x + j * y + k, with
+// instance z offsets scaled by w
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct DetailObjectZReferenceVector {
+    pub i: f32,
+    pub j: f32,
+    pub k: f32,
+    pub w: f32,
+}
+
+#[wasm_bindgen(js_name = "HaloDetailObjectInstances", getter_with_clone)]
+#[derive(Debug, Clone, Default)]
+pub struct DetailObjectInstances {
+    pub cluster: i32,
+    pub positions: Vec<f32>,
+    pub colors: Vec<f32>,
+    pub collection_indices: Vec<u8>,
+    pub type_indices: Vec<u8>,
+}
+
+#[wasm_bindgen(js_class = "HaloBSP")]
+impl BSP {
+    // Expands the BSP's detail object cells into instances, grouped by the
+    // cluster each cell falls in (-1 if it's outside of every cluster)
+    pub fn get_detail_object_instances(&self) -> Vec<DetailObjectInstances> {
+        let mut clusters: HashMap<i32, DetailObjectInstances> = HashMap::new();
+        for data in self.detail_objects.items.as_ref().unwrap() {
+            for (cell_index, cell) in data.cells.items.as_ref().unwrap().iter().enumerate() {
+                let origin = cell.get_origin();
+                let center = [origin[0] + CELL_SIZE / 2.0, origin[1] + CELL_SIZE / 2.0, origin[2] + CELL_SIZE / 2.0];
+                let cluster = self.find_cluster_for_point(&center).map_or(-1, |c| c as i32);
+                let result = clusters.entry(cluster).or_insert_with(|| DetailObjectInstances { cluster, ..Default::default() });
+                data.expand_cell(cell_index, result);
+            }
+        }
+        let mut result: Vec<DetailObjectInstances> = clusters.into_values()
+            .filter(|instances| !instances.type_indices.is_empty())
+            .collect();
+        result.sort_by_key(|instances| instances.cluster);
+        result
+    }
+}
+
+fn unpack_r5g6b5(color: u16) -> [f32; 3] {
+    [
+        ((color >> 11) & 0x1F) as f32 / 31.0,
+        ((color >> 5) & 0x3F) as f32 / 63.0,
+        (color & 0x1F) as f32 / 31.0,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_data(z_reference_vectors: Vec<DetailObjectZReferenceVector>) -> BSPDetailObjectData {
+        // layers 1 and 3 are set, with one and two instances respectively
+        let cell = DetailObjectCell {
+            cell_x: 1,
+            cell_y: -2,
+            cell_z: 3,
+            offset_z: 128,
+            valid_layers_flags: 0b1010,
+            start_index: 1,
+            count_index: 1,
+        };
+        let instance = |position_x, position_y, position_z, data| DetailObjectInstance { position_x, position_y, position_z, data, color: 0xFFFF };
+        BSPDetailObjectData {
+            cells: Block::from_items(vec![cell]),
+            instances: Block::from_items(vec![
+                instance(255, 255, 255, 0),
+                instance(128, 64, 32, 0x45),
+                instance(0, 0, 0, 2),
+                instance(32, 0, 16, 3),
+            ]),
+            counts: Block::from_items(vec![DetailObjectCount { count: 9 }, DetailObjectCount { count: 1 }, DetailObjectCount { count: 2 }]),
+            z_reference_vectors: Block::from_items(z_reference_vectors),
+        }
+    }
+
+    #[test]
+    fn test_expand_cell() {
+        let data = make_data(Vec::new());
+        let mut result = DetailObjectInstances::default();
+        data.expand_cell(0, &mut result);
+        // the cell's origin is (8, -16, 28), since offset_z is in 1/256ths of a cell
+        assert_eq!(result.positions, vec![
+            12.0, -14.0, 29.0,
+            8.0, -16.0, 28.0,
+            9.0, -16.0, 28.5,
+        ]);
+        assert_eq!(result.collection_indices, vec![1, 3, 3]);
+        assert_eq!(result.type_indices, vec![5, 2, 3]);
+        assert_eq!(&result.colors[..3], &[1.0, 1.0, 1.0]);
+
+        // z = i * x + j * y + k + position_z * w, with x and y relative to the cell
+        let data = make_data(vec![DetailObjectZReferenceVector { i: 0.5, j: 0.25, k: 2.0, w: 0.0625 }]);
+        let mut result = DetailObjectInstances::default();
+        data.expand_cell(0, &mut result);
+        assert_eq!(&result.positions[..3], &[12.0, -14.0, 28.0 + 2.0 + 0.5 + 2.0 + 2.0]);
+        assert_eq!(result.positions[8], 28.0 + 0.5 + 2.0 + 1.0);
+    }
+}
@@ -0,0 +1,161 @@
+use crate::halo::common::*;
+use crate::unity::types::common::NullTerminatedAsciiString;
+
+pub use noclip_macros::ToJson;
+
+// A minimal JSON serializer for tag data, so tags can be dumped for inspection
+// without pulling in serde
+pub trait ToJson {
+    fn write_json(&self, out: &mut String);
+
+    fn to_json(&self) -> String {
+        let mut out = String::new();
+        self.write_json(&mut out);
+        out
+    }
+}
+
+macro_rules! impl_to_json_with_to_string {
+    ($($t:ty),*) => {
+        $(
+            impl ToJson for $t {
+                fn write_json(&self, out: &mut String) {
+                    out.push_str(&self.to_string());
+                }
+            }
+        )*
+    };
+}
+
+impl_to_json_with_to_string!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, bool);
+
+macro_rules! impl_to_json_for_floats {
+    ($($t:ty),*) => {
+        $(
+            impl ToJson for $t {
+                fn write_json(&self, out: &mut String) {
+                    // JSON has no NaN or infinity
+                    if self.is_finite() {
+                        out.push_str(&self.to_string());
+                    } else {
+                        out.push_str("null");
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_to_json_for_floats!(f32, f64);
+
+impl ToJson for str {
+    fn write_json(&self, out: &mut String) {
+        out.push('"');
+        for c in self.chars() {
+            match c {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                '\t' => out.push_str("\\t"),
+                c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+                c => out.push(c),
+            }
+        }
+        out.push('"');
+    }
+}
+
+impl ToJson for String {
+    fn write_json(&self, out: &mut String) {
+        self.as_str().write_json(out);
+    }
+}
+
+impl ToJson for NullTerminatedAsciiString {
+    fn write_json(&self, out: &mut String) {
+        let end = self.bytes.iter().position(|&b| b == 0).unwrap_or(self.bytes.len());
+        String::from_utf8_lossy(&self.bytes[..end]).as_ref().write_json(out);
+    }
+}
+
+impl<T: ToJson> ToJson for [T] {
+    fn write_json(&self, out: &mut String) {
+        out.push('[');
+        for (i, item) in self.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            item.write_json(out);
+        }
+        out.push(']');
+    }
+}
+
+impl<T: ToJson, const N: usize> ToJson for [T; N] {
+    fn write_json(&self, out: &mut String) {
+        self.as_slice().write_json(out);
+    }
+}
+
+impl<T: ToJson> ToJson for Vec<T> {
+    fn write_json(&self, out: &mut String) {
+        self.as_slice().write_json(out);
+    }
+}
+
+impl<T: ToJson> ToJson for Option<T> {
+    fn write_json(&self, out: &mut String) {
+        match self {
+            Some(value) => value.write_json(out),
+            None => out.push_str("null"),
+        }
+    }
+}
+
+impl<T: ToJson + ?Sized> ToJson for Box<T> {
+    fn write_json(&self, out: &mut String) {
+        self.as_ref().write_json(out);
+    }
+}
+
+impl<T: ToJson> ToJson for Block<T> {
+    fn write_json(&self, out: &mut String) {
+        out.push_str("{\"count\":");
+        self.count.write_json(out);
+        out.push_str(",\"base_pointer\":");
+        self.base_pointer.write_json(out);
+        out.push_str(",\"items\":");
+        self.items.write_json(out);
+        out.push('}');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(ToJson)]
+    struct Point {
+        x: f32,
+        name: String,
+        tags: Vec<u16>,
+    }
+
+    #[derive(ToJson)]
+    enum Shape {
+        Empty,
+        Point(Point),
+        Pair(u8, u8),
+    }
+
+    #[test]
+    fn test_to_json() {
+        let point = Point { x: 1.5, name: "a \"b\"".to_string(), tags: vec![1, 2] };
+        assert_eq!(point.to_json(), r#"{"x":1.5,"name":"a \"b\"","tags":[1,2]}"#);
+        assert_eq!(Shape::Empty.to_json(), r#""Empty""#);
+        assert_eq!(Shape::Pair(1, 2).to_json(), r#"{"Pair":[1,2]}"#);
+        assert_eq!(Shape::Point(point).to_json(), r#"{"Point":{"x":1.5,"name":"a \"b\"","tags":[1,2]}}"#);
+        assert_eq!(Some(f32::NAN).to_json(), "null");
+    }
+}
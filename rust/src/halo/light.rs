@@ -0,0 +1,282 @@
+use deku::prelude::*;
+use wasm_bindgen::prelude::*;
+
+use crate::halo::common::*;
+use crate::halo::scenario::*;
+use crate::halo::tag::*;
+use crate::halo::json::*;
+
+#[wasm_bindgen(js_name = "HaloLight")]
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct Light {
+    pub flags: u32,
+    pub radius: f32,
+    pub radius_modifier: Bounds,
+    pub falloff_angle: f32,
+    pub cutoff_angle: f32,
+    #[deku(pad_bytes_after = "36")]
+    pub lens_flare_only_radius: f32,
+    pub interpolation_flags: u32,
+    pub color_lower_bound: ColorARGB,
+    #[deku(pad_bytes_after = "12")]
+    pub color_upper_bound: ColorARGB,
+    #[deku(pad_bytes_after = "2")]
+    pub primary_cube_map: TagDependency,
+    pub texture_animation_function: u16,
+    pub texture_animation_period: f32,
+    #[deku(pad_bytes_after = "2")]
+    pub secondary_cube_map: TagDependency,
+    pub yaw_function: u16,
+    #[deku(pad_bytes_after = "2")]
+    pub yaw_period: f32,
+    pub roll_function: u16,
+    #[deku(pad_bytes_after = "2")]
+    pub roll_period: f32,
+    pub pitch_function: u16,
+    #[deku(pad_bytes_after = "8")]
+    pub pitch_period: f32,
+    #[deku(pad_bytes_after = "24")]
+    pub lens_flare: TagDependency,
+    pub radiosity_intensity: f32,
+    #[deku(pad_bytes_after = "16")]
+    pub radiosity_color: ColorRGB,
+    #[deku(pad_bytes_after = "2")]
+    pub duration: f32,
+    #[deku(pad_bytes_after = "88")]
+    pub falloff_function: u16,
+}
+
+#[wasm_bindgen(js_name = "HaloLensFlare")]
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct LensFlare {
+    pub falloff_angle: f32,
+    #[deku(pad_bytes_after = "16")]
+    pub cutoff_angle: f32,
+    pub occlusion_radius: f32,
+    #[deku(pad_bytes_after = "2")]
+    pub occlusion_offset_direction: u16,
+    pub near_fade_distance: f32,
+    pub far_fade_distance: f32,
+    pub bitmap: TagDependency,
+    #[deku(pad_bytes_after = "78")]
+    pub flags: u16,
+    #[deku(pad_bytes_after = "2")]
+    pub rotation_function: u16,
+    #[deku(pad_bytes_after = "24")]
+    pub rotation_function_scale: f32,
+    pub horizontal_scale: f32,
+    #[deku(pad_bytes_after = "28")]
+    pub vertical_scale: f32,
+    #[deku(pad_bytes_after = "24")]
+    pub(crate) reflections: Block<LensFlareReflection>,
+}
+
+#[wasm_bindgen(js_class = "HaloLensFlare")]
+impl LensFlare {
+    pub fn get_reflections(&self) -> Vec<LensFlareReflection> {
+        self.reflections.items.as_ref().cloned().unwrap()
+    }
+}
+
+// A single sprite in a lens flare. Its position is along the line from the
+// flare's source (0) through the center of the screen (1)
+#[wasm_bindgen(js_name = "HaloLensFlareReflection")]
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct LensFlareReflection {
+    #[deku(pad_bytes_after = "2")]
+    pub flags: u16,
+    #[deku(pad_bytes_after = "22")]
+    pub bitmap_index: i16,
+    pub position: f32,
+    #[deku(pad_bytes_after = "4")]
+    pub rotation_offset: f32,
+    pub radius: Bounds,
+    #[deku(pad_bytes_after = "2")]
+    pub radius_scaled_by: u16,
+    pub brightness: Bounds,
+    #[deku(pad_bytes_after = "2")]
+    pub brightness_scaled_by: u16,
+    pub tint_color: ColorARGB,
+    pub color_lower_bound: ColorARGB,
+    pub color_upper_bound: ColorARGB,
+    pub more_flags: u16,
+    pub animation_function: u16,
+    pub animation_period: f32,
+    #[deku(pad_bytes_after = "4")]
+    pub animation_phase: f32,
+}
+
+// Glows are particles orbiting an object's marker, e.g. the motes around a
+// lamp or a plasma weapon
+#[wasm_bindgen(js_name = "HaloGlow", getter_with_clone)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct Glow {
+    #[deku(reader = "read_tag_string(deku::reader)")]
+    pub attachment_marker: String,
+    pub number_of_particles: u16,
+    pub boundary_effect: u16,
+    pub normal_particle_distribution: u16,
+    pub trailing_particle_distribution: u16,
+    #[deku(pad_bytes_after = "36")]
+    pub glow_flags: u32,
+    pub particle_rotational_velocity: f32,
+    pub particle_rotational_velocity_multiplier: Bounds,
+    #[deku(pad_bytes_before = "4")]
+    pub effect_rotational_velocity: f32,
+    pub effect_rotational_velocity_multiplier: Bounds,
+    #[deku(pad_bytes_before = "4")]
+    pub effect_translational_velocity: f32,
+    pub effect_translational_velocity_multiplier: Bounds,
+    #[deku(pad_bytes_before = "4")]
+    pub minimum_distance_particle_to_object: f32,
+    pub maximum_distance_particle_to_object: f32,
+    #[deku(pad_bytes_after = "8")]
+    pub distance_to_object_multiplier: Bounds,
+    #[deku(pad_bytes_before = "4")]
+    pub particle_size_bounds: Bounds,
+    pub size_attachment_multiplier: Bounds,
+    #[deku(pad_bytes_before = "4")]
+    pub color_bound_0: ColorARGB,
+    pub color_bound_1: ColorARGB,
+    pub scale_color_0: ColorARGB,
+    pub scale_color_1: ColorARGB,
+    pub color_rate_of_change: f32,
+    pub fading_percentage_of_glow: f32,
+    pub particle_generation_frequency: f32,
+    pub lifetime_of_trailing_particles: f32,
+    pub velocity_of_trailing_particles: f32,
+    pub trailing_particle_minimum_t: f32,
+    #[deku(pad_bytes_after = "52")]
+    pub trailing_particle_maximum_t: f32,
+    #[deku(pad_bytes_after = "4")]
+    pub texture: TagDependency,
+}
+
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct BSPLensFlare {
+    pub lens_flare: TagDependency,
+}
+
+// The direction is packed into signed bytes, and points back towards the
+// flare's source for directional flares (e.g. the sun)
+#[wasm_bindgen(js_name = "HaloBSPLensFlareMarker")]
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct BSPLensFlareMarker {
+    pub position: Point3D,
+    pub direction_i: i8,
+    pub direction_j: i8,
+    pub direction_k: i8,
+    pub lens_flare_index: i8,
+}
+
+#[wasm_bindgen(js_class = "HaloBSPLensFlareMarker")]
+impl BSPLensFlareMarker {
+    pub fn get_direction(&self) -> Vector3D {
+        let unpack = |x: i8| (x as f32 / i8::MAX as f32).max(-1.0);
+        Vector3D { i: unpack(self.direction_i), j: unpack(self.direction_j), k: unpack(self.direction_k) }
+    }
+}
+
+#[wasm_bindgen(js_class = "HaloBSP")]
+impl BSP {
+    pub fn get_lens_flare_palette(&self) -> Vec<TagDependency> {
+        self.lens_flares.items.as_ref().unwrap().iter()
+            .map(|flare| flare.lens_flare)
+            .collect()
+    }
+
+    pub fn get_lens_flare_markers(&self) -> Vec<BSPLensFlareMarker> {
+        self.lens_flare_markers.items.as_ref().cloned().unwrap_or_default()
+    }
+}
+
+#[wasm_bindgen(js_class = "HaloLightFixtureInstance")]
+impl ScenarioLightFixture {
+    // Light fixtures can override their lights' color; black means no override
+    pub fn get_light_color(&self, light: &Light) -> ColorRGB {
+        if self.color.r > 0.0 || self.color.g > 0.0 || self.color.b > 0.0 {
+            let intensity = if self.intensity > 0.0 { self.intensity } else { 1.0 };
+            ColorRGB { r: self.color.r * intensity, g: self.color.g * intensity, b: self.color.b * intensity }
+        } else {
+            let color = light.color_upper_bound;
+            ColorRGB { r: color.r, g: color.g, b: color.b }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put(data: &mut [u8], offset: usize, bytes: &[u8]) {
+        data[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    // parses a whole tag struct, making sure nothing's left over
+    fn parse<'a, T: DekuContainerRead<'a>>(data: &'a [u8]) -> T {
+        let ((rest, bit_offset), value) = T::from_bytes((data, 0)).unwrap();
+        assert!(rest.is_empty() && bit_offset == 0, "{} bytes left over", rest.len());
+        value
+    }
+
+    #[test]
+    fn test_light() {
+        let mut data = vec![0; 352];
+        put(&mut data, 4, &2.5f32.to_le_bytes());
+        put(&mut data, 84, &[1.0f32, 0.5, 0.25, 0.125].map(f32::to_le_bytes).concat());
+        put(&mut data, 184, &(TagClass::LensFlare as u32).to_le_bytes());
+        put(&mut data, 256, &3.0f32.to_le_bytes());
+        put(&mut data, 262, &4u16.to_le_bytes());
+        let light: Light = parse(&data);
+        assert_eq!(light.radius, 2.5);
+        assert_eq!((light.color_upper_bound.r, light.color_upper_bound.b), (0.5, 0.125));
+        assert_eq!(light.lens_flare.tag_class, TagClass::LensFlare);
+        assert_eq!(light.duration, 3.0);
+        assert_eq!(light.falloff_function, 4);
+    }
+
+    #[test]
+    fn test_lens_flare() {
+        let mut data = vec![0; 240];
+        put(&mut data, 32, &10.0f32.to_le_bytes());
+        put(&mut data, 40, &(TagClass::Bitmap as u32).to_le_bytes());
+        put(&mut data, 56, &1u16.to_le_bytes());
+        put(&mut data, 172, &0.5f32.to_le_bytes());
+        put(&mut data, 204, &3u32.to_le_bytes());
+        let flare: LensFlare = parse(&data);
+        assert_eq!(flare.near_fade_distance, 10.0);
+        assert_eq!(flare.bitmap.tag_class, TagClass::Bitmap);
+        assert_eq!(flare.flags, 1);
+        assert_eq!(flare.vertical_scale, 0.5);
+        assert_eq!(flare.reflections.count, 3);
+
+        let mut data = vec![0; 128];
+        put(&mut data, 4, &2i16.to_le_bytes());
+        put(&mut data, 28, &0.75f32.to_le_bytes());
+        put(&mut data, 112, &5u16.to_le_bytes());
+        put(&mut data, 120, &0.25f32.to_le_bytes());
+        let reflection: LensFlareReflection = parse(&data);
+        assert_eq!(reflection.bitmap_index, 2);
+        assert_eq!(reflection.position, 0.75);
+        assert_eq!(reflection.more_flags, 5);
+        assert_eq!(reflection.animation_phase, 0.25);
+    }
+
+    #[test]
+    fn test_glow() {
+        let mut data = vec![0; 340];
+        put(&mut data, 0, b"glow\0");
+        put(&mut data, 32, &12u16.to_le_bytes());
+        put(&mut data, 96, &1.5f32.to_le_bytes());
+        put(&mut data, 156, &[0.1f32, 0.2].map(f32::to_le_bytes).concat());
+        put(&mut data, 264, &8.0f32.to_le_bytes());
+        put(&mut data, 320, &(TagClass::Bitmap as u32).to_le_bytes());
+        let glow: Glow = parse(&data);
+        assert_eq!(glow.attachment_marker, "glow");
+        assert_eq!(glow.number_of_particles, 12);
+        assert_eq!(glow.effect_rotational_velocity, 1.5);
+        assert_eq!((glow.particle_size_bounds.lower, glow.particle_size_bounds.upper), (0.1, 0.2));
+        assert_eq!(glow.trailing_particle_maximum_t, 8.0);
+        assert_eq!(glow.texture.tag_class, TagClass::Bitmap);
+    }
+}
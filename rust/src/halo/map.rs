@@ -7,21 +7,28 @@ use crate::{halo::common::*, unity::types::common::NullTerminatedAsciiString};
 use crate::halo::tag::*;
 use crate::halo::bitmap::*;
 use crate::halo::model::*;
+use crate::halo::object::*;
+use crate::halo::animation::*;
+use crate::halo::detail_object::*;
+use crate::halo::particle::*;
+use crate::halo::light::*;
 use crate::halo::scenario::*;
 use crate::halo::shader::*;
+use crate::halo::json::*;
 
-const BASE_MEMORY_ADDRESS: Pointer = 0x50000000;
+const HEADER_SIZE: usize = 0x800;
 
 pub struct MapManager {
     pub reader: MapReader,
     pub header: Header,
     pub tag_index_header: TagIndexHeader,
     pub tag_headers: Vec<TagHeader>,
+    pub resource_maps: Vec<ResourceMap>,
 }
 
 impl MapManager {
     pub fn new(map: Vec<u8>) -> Result<Self> {
-        let mut reader = MapReader::new(map);
+        let mut reader = MapReader::new(map)?;
         let header = reader.read_header()?;
 
         let tag_index_header = reader.read_tag_index_header(&header)?;
@@ -32,17 +39,68 @@ impl MapManager {
             header,
             tag_index_header,
             tag_headers,
+            resource_maps: Vec::new(),
         })
     }
 
+    // Custom Edition maps pull some of their tags out of bitmaps.map and
+    // sounds.map, so those need to be added before reading such tags
+    pub fn add_resource_map(&mut self, data: Vec<u8>) -> Result<()> {
+        let resource_map = ResourceMap::new(data)?;
+        self.resource_maps.retain(|map| map.header.resource_type != resource_map.header.resource_type);
+        self.resource_maps.push(resource_map);
+        Ok(())
+    }
+
+    // returns the file offsets of the model vertex and index data
+    pub fn get_model_data_offsets(&self) -> Result<(u64, u64)> {
+        let tag_index_header = &self.tag_index_header;
+        match (tag_index_header.vertex_data_pointer, tag_index_header.index_data_pointer) {
+            (Some(vertices), Some(indices)) => Ok((self.header.resolve_pointer(vertices)?, self.header.resolve_pointer(indices)?)),
+            _ => {
+                let vertices = tag_index_header.model_data_file_offset.unwrap_or(0) as u64;
+                Ok((vertices, vertices + tag_index_header.vertex_data_size.unwrap_or(0) as u64))
+            },
+        }
+    }
+
     fn get_tag_data_offset(&self) -> i64 {
-        self.header.tag_data_offset as i64 - BASE_MEMORY_ADDRESS as i64
+        self.header.tag_data_offset as i64 - self.header.version.get_base_memory_address() as i64
+    }
+
+    pub fn is_tag_indexed(&self, tag_header: &TagHeader) -> bool {
+        self.header.version == MapVersion::CustomEdition && tag_header.indexed != 0
     }
 
     pub fn read_tag(&mut self, tag_header: &TagHeader) -> Result<Tag> {
+        if self.is_tag_indexed(tag_header) {
+            return self.read_indexed_tag(tag_header);
+        }
         self.read_tag_at_offset(tag_header, self.get_tag_data_offset())
     }
 
+    // For indexed tags, the tag data field is an index into the matching
+    // resource map, and the tag's pointers are relative to that resource
+    fn read_indexed_tag(&mut self, tag_header: &TagHeader) -> Result<Tag> {
+        let resource_type = match tag_header.primary_class {
+            TagClass::Bitmap => ResourceType::Bitmaps,
+            TagClass::Sound => ResourceType::Sounds,
+            _ => ResourceType::Localization,
+        };
+        let Some(resource_map) = self.resource_maps.iter_mut().find(|map| map.header.resource_type == resource_type) else {
+            return Err(MapReaderError::IO(format!("{:?} tag {} requires a {:?} resource map", tag_header.primary_class, tag_header.path, resource_type)).into());
+        };
+        let Some(resource) = resource_map.resources.get(tag_header.tag_data as usize) else {
+            return Err(MapReaderError::InvalidTag(format!("invalid resource index {} for {}", tag_header.tag_data, tag_header.path)).into());
+        };
+        let offset = resource.data_offset as i64;
+        let mut header = tag_header.clone();
+        header.tag_data = 0;
+        let mut tag = read_tag_at_offset(&mut resource_map.reader.data, &header, offset)?;
+        tag.header = tag_header.clone();
+        Ok(tag)
+    }
+
     pub fn read_map_bytes(&mut self, offset: u64, size: usize) -> Result<Vec<u8>> {
         self.reader.data.seek(SeekFrom::Start(offset))?;
         let mut buf = vec![0; size];
@@ -60,116 +118,19 @@ impl MapManager {
     }
 
     fn read_tag_at_offset(&mut self, tag_header: &TagHeader, offset: i64) -> Result<Tag> {
-        let tag_pointer = offset + tag_header.tag_data as i64;
-        if tag_pointer < 0 {
-            panic!("invalid tag pointer {} for header {:?}", tag_pointer, tag_header)
+        read_tag_at_offset(&mut self.reader.data, tag_header, offset)
+    }
+
+    // Reads just the shared object layout from any object tag
+    pub fn read_object(&mut self, tag_header: &TagHeader) -> Result<Object> {
+        if !tag_header.is_object() {
+            return Err(MapReaderError::InvalidTag(format!("expected object tag, got {:?}", tag_header.primary_class)).into());
         }
-        self.reader.data.seek(SeekFrom::Start(tag_pointer as u64))?;
-        let data = match tag_header.primary_class {
-            TagClass::Bitmap => {
-                let mut bitmap = Bitmap::from_reader_with_ctx(&mut self.reader.data, ())?;
-                bitmap.bitmap_group_sequence.read_items(&mut self.reader.data, offset)?;
-                bitmap.data.read_items(&mut self.reader.data, offset)?;
-                TagData::Bitmap(bitmap)
-            },
-            TagClass::Scenario => {
-                let mut scenario = Scenario::from_reader_with_ctx(&mut self.reader.data, ())?;
-                scenario.skies.read_items(&mut self.reader.data, offset)?;
-                scenario.scenery.read_items(&mut self.reader.data, offset)?;
-                scenario.scenery_palette.read_items(&mut self.reader.data, offset)?;
-                scenario.structure_bsp_references.read_items(&mut self.reader.data, offset)?;
-                dbg!(&scenario);
-                TagData::Scenario(scenario)
-            },
-            TagClass::ScenarioStructureBsp => {
-                let mut bsp = BSP::from_reader_with_ctx(&mut self.reader.data, ())?;
-                bsp.surfaces.read_items(&mut self.reader.data, offset)?;
-                bsp.lightmaps.read_items(&mut self.reader.data, offset)?;
-                for lightmap in bsp.lightmaps.items.as_mut().unwrap() {
-                    lightmap.materials.read_items(&mut self.reader.data, offset)?;
-                }
-                TagData::BSP(bsp)
-            },
-            TagClass::ShaderEnvironment => {
-                let shader = ShaderEnvironment::from_reader_with_ctx(&mut self.reader.data, ())?;
-                TagData::ShaderEnvironment(shader)
-            },
-            TagClass::ShaderModel => {
-                let shader = ShaderModel::from_reader_with_ctx(&mut self.reader.data, ())?;
-                TagData::ShaderModel(shader)
-            },
-            TagClass::ShaderTransparentChicago => {
-                let mut shader = ShaderTransparentChicago::from_reader_with_ctx(&mut self.reader.data, ())?;
-                shader.extra_layers.read_items(&mut self.reader.data, offset)?;
-                shader.bitmaps.read_items(&mut self.reader.data, offset)?;
-                TagData::ShaderTransparentChicago(shader)
-            },
-            TagClass::ShaderTransparentGeneric => {
-                let mut shader = ShaderTransparentGeneric::from_reader_with_ctx(&mut self.reader.data, ())?;
-                shader.extra_layers.read_items(&mut self.reader.data, offset)?;
-                shader.bitmaps.read_items(&mut self.reader.data, offset)?;
-                shader.stages.read_items(&mut self.reader.data, offset)?;
-
-                if shader.stages.count == 0 {
-                    let fallback: Vec<ShaderTransparentGenericStage> = vec![
-                        ShaderTransparentGenericStage {
-                            flags: 0,
-                            color0_source: FunctionSource::None,
-                            color0_animation_function: AnimationFunction::Zero,
-                            color0_animation_period: 0.0,
-                            color0_animation_lower_bound: ColorARGB{ r: 0.0, g: 0.0, b: 0.0, a: 0.0 },
-                            color0_animation_upper_bound: ColorARGB{ r: 0.0, g: 0.0, b: 0.0, a: 0.0 },
-                            color1: ColorARGB{ r: 0.0, g: 0.0, b: 0.0, a: 0.0 },
-                            input_a: ShaderInput::Texture0Color, input_a_mapping: ShaderMapping::SignedIdentity,
-                            input_b: ShaderInput::One, input_b_mapping: ShaderMapping::SignedIdentity,
-                            input_c: ShaderInput::Zero, input_c_mapping: ShaderMapping::SignedIdentity,
-                            input_d: ShaderInput::Zero, input_d_mapping: ShaderMapping::SignedIdentity,
-                            input_a_alpha: ShaderAlphaInput::Texture0Alpha, input_a_mapping_alpha: ShaderMapping::SignedIdentity,
-                            input_b_alpha: ShaderAlphaInput::One, input_b_mapping_alpha: ShaderMapping::SignedIdentity,
-                            input_c_alpha: ShaderAlphaInput::Zero, input_c_mapping_alpha: ShaderMapping::SignedIdentity,
-                            input_d_alpha: ShaderAlphaInput::Zero, input_d_mapping_alpha: ShaderMapping::SignedIdentity,
-
-                            output_ab_function: ShaderOutputFunction::Multiply, output_cd_function: ShaderOutputFunction::Multiply,
-                            output_ab: ShaderOutput::Scratch0, output_cd: ShaderOutput::Discard, output_ab_cd_mux_sum: ShaderOutput::Discard,
-                            output_ab_alpha: ShaderOutput::Scratch0, output_cd_alpha: ShaderOutput::Discard, output_ab_cd_mux_sum_alpha: ShaderOutput::Discard,
-                            output_mapping_color: ShaderOutputMapping::Identity, output_mapping_alpha: ShaderOutputMapping::Identity,
-                        }
-                    ];
-                    shader.stages.items = Some(fallback);
-                    shader.stages.count = 1;
-                }
-                TagData::ShaderTransparentGeneric(shader)
-            },
-            TagClass::ShaderTransparentWater => {
-                let mut shader = ShaderTransparentWater::from_reader_with_ctx(&mut self.reader.data, ())?;
-                shader.ripples.read_items(&mut self.reader.data, offset)?;
-                TagData::ShaderTransparentWater(shader)
-            },
-            TagClass::Scenery => {
-                let scenery = Scenery::from_reader_with_ctx(&mut self.reader.data, ())?;
-                TagData::Scenery(scenery)
-            },
-            TagClass::Sky => {
-                let sky = Sky::from_reader_with_ctx(&mut self.reader.data, ())?;
-                TagData::Sky(sky)
-            },
-            TagClass::GbxModel => {
-                let mut model = GbxModel::from_reader_with_ctx(&mut self.reader.data, ())?;
-                model.geometries.read_items(&mut self.reader.data, offset)?;
-                match &mut model.geometries.items {
-                    Some(geometries) => {
-                        for geometry in geometries {
-                            geometry.parts.read_items(&mut self.reader.data, offset)?;
-                        }
-                    },
-                    None => panic!("failed to load geometries for {:?}", model),
-                }
-                model.shaders.read_items(&mut self.reader.data, offset)?;
-                TagData::GbxModel(model)
-            },
-            _ => return Err(MapReaderError::UnimplementedTag(format!("can't yet read {:?}", tag_header)).into()),
-        };
-        Ok(Tag { header: tag_header.clone(), data })
+        let offset = self.get_tag_data_offset();
+        self.reader.data.seek(SeekFrom::Start((offset + tag_header.tag_data as i64) as u64))?;
+        let mut object = Object::from_reader_with_ctx(&mut self.reader.data, ())?;
+        read_object_blocks(&mut object, &mut self.reader.data, offset)?;
+        Ok(object)
     }
 
     pub fn get_scenario(&mut self) -> Result<Tag> {
@@ -209,6 +170,296 @@ impl MapManager {
         }
         return Ok(result);
     }
+
+    pub fn get_tag_headers(&self) -> &[TagHeader] {
+        &self.tag_headers
+    }
+
+    // Like read_tag, but also handles BSPs, which can only be read through
+    // the scenario that references them
+    pub fn read_any_tag(&mut self, tag_header: &TagHeader) -> Result<Tag> {
+        if tag_header.primary_class != TagClass::ScenarioStructureBsp {
+            return self.read_tag(tag_header);
+        }
+        let scenario_tag = self.get_scenario()?;
+        self.get_scenario_bsps(&scenario_tag)?.into_iter()
+            .find(|tag| tag.header.tag_id == tag_header.tag_id)
+            .ok_or_else(|| MapReaderError::InvalidTag(format!("{} isn't referenced by the scenario", tag_header.path)).into())
+    }
+
+    pub fn read_tag_json(&mut self, tag_header: &TagHeader) -> Result<String> {
+        Ok(self.read_any_tag(tag_header)?.to_json())
+    }
+
+    // External bitmaps live in bitmaps.map, which needs to have been added
+    pub fn get_and_convert_bitmap_data(&mut self, bitmap_data: &BitmapData, decode_dxt: bool) -> Result<Vec<u8>> {
+        let xbox_layout = self.header.version == MapVersion::Xbox;
+        let options = BitmapConversionOptions { decode_dxt, xbox_layout };
+        if !bitmap_data.is_external() {
+            return Ok(get_and_convert_bitmap_data(&mut self.reader.data, bitmap_data, &options));
+        }
+        let Some(resource_map) = self.resource_maps.iter_mut().find(|map| map.header.resource_type == ResourceType::Bitmaps) else {
+            return Err(MapReaderError::IO("external bitmap data requires a bitmaps resource map".to_string()).into());
+        };
+        Ok(get_and_convert_bitmap_data(&mut resource_map.reader.data, bitmap_data, &options))
+    }
+}
+
+fn read_tag_at_offset(reader: &mut deku::reader::Reader<Cursor<Vec<u8>>>, tag_header: &TagHeader, offset: i64) -> Result<Tag> {
+    let tag_pointer = offset + tag_header.tag_data as i64;
+    if tag_pointer < 0 {
+        panic!("invalid tag pointer {} for header {:?}", tag_pointer, tag_header)
+    }
+    reader.seek(SeekFrom::Start(tag_pointer as u64))?;
+    let data = match tag_header.primary_class {
+        TagClass::Bitmap => {
+            let mut bitmap = Bitmap::from_reader_with_ctx(reader, ())?;
+            bitmap.bitmap_group_sequence.read_items(reader, offset)?;
+            bitmap.data.read_items(reader, offset)?;
+            TagData::Bitmap(bitmap)
+        },
+        TagClass::Scenario => {
+            let mut scenario = Scenario::from_reader_with_ctx(reader, ())?;
+            scenario.skies.read_items(reader, offset)?;
+            scenario.scenery.read_items(reader, offset)?;
+            scenario.scenery_palette.read_items(reader, offset)?;
+            scenario.bipeds.read_items(reader, offset)?;
+            scenario.biped_palette.read_items(reader, offset)?;
+            scenario.vehicles.read_items(reader, offset)?;
+            scenario.vehicle_palette.read_items(reader, offset)?;
+            scenario.equipment.read_items(reader, offset)?;
+            scenario.equipment_palette.read_items(reader, offset)?;
+            scenario.weapons.read_items(reader, offset)?;
+            scenario.weapon_palette.read_items(reader, offset)?;
+            scenario.machines.read_items(reader, offset)?;
+            scenario.machine_palette.read_items(reader, offset)?;
+            scenario.controls.read_items(reader, offset)?;
+            scenario.control_palette.read_items(reader, offset)?;
+            scenario.light_fixtures.read_items(reader, offset)?;
+            scenario.light_fixture_palette.read_items(reader, offset)?;
+            scenario.sound_scenery.read_items(reader, offset)?;
+            scenario.sound_scenery_palette.read_items(reader, offset)?;
+            scenario.detail_object_collection_palette.read_items(reader, offset)?;
+            scenario.structure_bsp_references.read_items(reader, offset)?;
+            TagData::Scenario(Box::new(scenario))
+        },
+        TagClass::ScenarioStructureBsp => {
+            let mut bsp = BSP::from_reader_with_ctx(reader, ())?;
+            bsp.surfaces.read_items(reader, offset)?;
+            bsp.lightmaps.read_items(reader, offset)?;
+            for lightmap in bsp.lightmaps.items.as_mut().unwrap() {
+                lightmap.materials.read_items(reader, offset)?;
+            }
+            bsp.collision_bsp.read_items(reader, offset)?;
+            for collision_bsp in bsp.collision_bsp.items.as_mut().unwrap() {
+                collision_bsp.bsp3d_nodes.read_items(reader, offset)?;
+                collision_bsp.planes.read_items(reader, offset)?;
+                collision_bsp.leaves.read_items(reader, offset)?;
+                collision_bsp.bsp2d_references.read_items(reader, offset)?;
+                collision_bsp.bsp2d_nodes.read_items(reader, offset)?;
+                collision_bsp.surfaces.read_items(reader, offset)?;
+                collision_bsp.edges.read_items(reader, offset)?;
+                collision_bsp.vertices.read_items(reader, offset)?;
+            }
+            bsp.leaves.read_items(reader, offset)?;
+            bsp.leaf_surfaces.read_items(reader, offset)?;
+            bsp.lens_flares.read_items(reader, offset)?;
+            bsp.lens_flare_markers.read_items(reader, offset)?;
+            bsp.clusters.read_items(reader, offset)?;
+            for cluster in bsp.clusters.items.as_mut().unwrap() {
+                cluster.portals.read_items(reader, offset)?;
+            }
+            bsp.cluster_portals.read_items(reader, offset)?;
+            for portal in bsp.cluster_portals.items.as_mut().unwrap() {
+                portal.vertices.read_items(reader, offset)?;
+            }
+            bsp.weather_palette.read_items(reader, offset)?;
+            bsp.weather_polyhedra.read_items(reader, offset)?;
+            for polyhedron in bsp.weather_polyhedra.items.as_mut().unwrap() {
+                polyhedron.planes.read_items(reader, offset)?;
+            }
+            bsp.detail_objects.read_items(reader, offset)?;
+            for detail_objects in bsp.detail_objects.items.as_mut().unwrap() {
+                detail_objects.cells.read_items(reader, offset)?;
+                detail_objects.instances.read_items(reader, offset)?;
+                detail_objects.counts.read_items(reader, offset)?;
+                detail_objects.z_reference_vectors.read_items(reader, offset)?;
+            }
+            TagData::BSP(Box::new(bsp))
+        },
+        TagClass::ShaderEnvironment => {
+            let shader = ShaderEnvironment::from_reader_with_ctx(reader, ())?;
+            TagData::ShaderEnvironment(shader)
+        },
+        TagClass::ShaderModel => {
+            let shader = ShaderModel::from_reader_with_ctx(reader, ())?;
+            TagData::ShaderModel(shader)
+        },
+        TagClass::ShaderTransparentChicago => {
+            let mut shader = ShaderTransparentChicago::from_reader_with_ctx(reader, ())?;
+            shader.extra_layers.read_items(reader, offset)?;
+            shader.bitmaps.read_items(reader, offset)?;
+            TagData::ShaderTransparentChicago(shader)
+        },
+        TagClass::ShaderTransparentChicagoExtended => {
+            let mut shader = ShaderTransparentChicagoExtended::from_reader_with_ctx(reader, ())?;
+            shader.extra_layers.read_items(reader, offset)?;
+            shader.bitmaps_4_stage.read_items(reader, offset)?;
+            shader.bitmaps_2_stage.read_items(reader, offset)?;
+            TagData::ShaderTransparentChicagoExtended(shader)
+        },
+        TagClass::ShaderTransparentMeter => {
+            let shader = ShaderTransparentMeter::from_reader_with_ctx(reader, ())?;
+            TagData::ShaderTransparentMeter(shader)
+        },
+        TagClass::ShaderTransparentGlass => {
+            let shader = ShaderTransparentGlass::from_reader_with_ctx(reader, ())?;
+            TagData::ShaderTransparentGlass(shader)
+        },
+        TagClass::ShaderTransparentPlasma => {
+            let shader = ShaderTransparentPlasma::from_reader_with_ctx(reader, ())?;
+            TagData::ShaderTransparentPlasma(shader)
+        },
+        TagClass::ShaderTransparentGeneric => {
+            let mut shader = ShaderTransparentGeneric::from_reader_with_ctx(reader, ())?;
+            shader.extra_layers.read_items(reader, offset)?;
+            shader.bitmaps.read_items(reader, offset)?;
+            shader.stages.read_items(reader, offset)?;
+
+            if shader.stages.count == 0 {
+                let fallback: Vec<ShaderTransparentGenericStage> = vec![
+                    ShaderTransparentGenericStage {
+                        flags: 0,
+                        color0_source: FunctionSource::None,
+                        color0_animation_function: AnimationFunction::Zero,
+                        color0_animation_period: 0.0,
+                        color0_animation_lower_bound: ColorARGB{ r: 0.0, g: 0.0, b: 0.0, a: 0.0 },
+                        color0_animation_upper_bound: ColorARGB{ r: 0.0, g: 0.0, b: 0.0, a: 0.0 },
+                        color1: ColorARGB{ r: 0.0, g: 0.0, b: 0.0, a: 0.0 },
+                        input_a: ShaderInput::Texture0Color, input_a_mapping: ShaderMapping::SignedIdentity,
+                        input_b: ShaderInput::One, input_b_mapping: ShaderMapping::SignedIdentity,
+                        input_c: ShaderInput::Zero, input_c_mapping: ShaderMapping::SignedIdentity,
+                        input_d: ShaderInput::Zero, input_d_mapping: ShaderMapping::SignedIdentity,
+                        input_a_alpha: ShaderAlphaInput::Texture0Alpha, input_a_mapping_alpha: ShaderMapping::SignedIdentity,
+                        input_b_alpha: ShaderAlphaInput::One, input_b_mapping_alpha: ShaderMapping::SignedIdentity,
+                        input_c_alpha: ShaderAlphaInput::Zero, input_c_mapping_alpha: ShaderMapping::SignedIdentity,
+                        input_d_alpha: ShaderAlphaInput::Zero, input_d_mapping_alpha: ShaderMapping::SignedIdentity,
+
+                        output_ab_function: ShaderOutputFunction::Multiply, output_cd_function: ShaderOutputFunction::Multiply,
+                        output_ab: ShaderOutput::Scratch0, output_cd: ShaderOutput::Discard, output_ab_cd_mux_sum: ShaderOutput::Discard,
+                        output_ab_alpha: ShaderOutput::Scratch0, output_cd_alpha: ShaderOutput::Discard, output_ab_cd_mux_sum_alpha: ShaderOutput::Discard,
+                        output_mapping_color: ShaderOutputMapping::Identity, output_mapping_alpha: ShaderOutputMapping::Identity,
+                    }
+                ];
+                shader.stages.items = Some(fallback);
+                shader.stages.count = 1;
+            }
+            TagData::ShaderTransparentGeneric(shader)
+        },
+        TagClass::ShaderTransparentWater => {
+            let mut shader = ShaderTransparentWater::from_reader_with_ctx(reader, ())?;
+            shader.ripples.read_items(reader, offset)?;
+            TagData::ShaderTransparentWater(shader)
+        },
+        TagClass::Scenery => {
+            let scenery = Scenery::from_reader_with_ctx(reader, ())?;
+            TagData::Scenery(scenery)
+        },
+        TagClass::Sky => {
+            let mut sky = Sky::from_reader_with_ctx(reader, ())?;
+            sky.animations.read_items(reader, offset)?;
+            TagData::Sky(sky)
+        },
+        TagClass::GbxModel => {
+            let mut model = GbxModel::from_reader_with_ctx(reader, ())?;
+            model.geometries.read_items(reader, offset)?;
+            match &mut model.geometries.items {
+                Some(geometries) => {
+                    for geometry in geometries {
+                        geometry.parts.read_items(reader, offset)?;
+                    }
+                },
+                None => panic!("failed to load geometries for {:?}", model),
+            }
+            model.shaders.read_items(reader, offset)?;
+            model.nodes.read_items(reader, offset)?;
+            model.regions.read_items(reader, offset)?;
+            for region in model.regions.items.as_mut().unwrap() {
+                region.permutations.read_items(reader, offset)?;
+            }
+            TagData::GbxModel(model)
+        },
+        TagClass::DetailObjectCollection => {
+            let mut collection = DetailObjectCollection::from_reader_with_ctx(reader, ())?;
+            collection.types.read_items(reader, offset)?;
+            TagData::DetailObjectCollection(collection)
+        },
+        TagClass::WeatherParticleSystem => {
+            let mut weather = WeatherParticleSystem::from_reader_with_ctx(reader, ())?;
+            weather.particle_types.read_items(reader, offset)?;
+            TagData::WeatherParticleSystem(weather)
+        },
+        TagClass::ParticleSystem => {
+            let mut system = ParticleSystem::from_reader_with_ctx(reader, ())?;
+            system.particle_types.read_items(reader, offset)?;
+            TagData::ParticleSystem(system)
+        },
+        TagClass::Particle => {
+            let particle = Particle::from_reader_with_ctx(reader, ())?;
+            TagData::Particle(particle)
+        },
+        TagClass::Light => {
+            let light = Light::from_reader_with_ctx(reader, ())?;
+            TagData::Light(light)
+        },
+        TagClass::LensFlare => {
+            let mut lens_flare = LensFlare::from_reader_with_ctx(reader, ())?;
+            lens_flare.reflections.read_items(reader, offset)?;
+            TagData::LensFlare(lens_flare)
+        },
+        TagClass::Glow => {
+            let glow = Glow::from_reader_with_ctx(reader, ())?;
+            TagData::Glow(glow)
+        },
+        TagClass::ModelAnimations => {
+            let mut animations = ModelAnimations::from_reader_with_ctx(reader, ())?;
+            animations.nodes.read_items(reader, offset)?;
+            animations.animations.read_items(reader, offset)?;
+            for animation in animations.animations.items.as_mut().unwrap() {
+                animation.default_data = animation.default_data_offset.read_data(reader, offset)?;
+                animation.frame_data = animation.frame_data_offset.read_data(reader, offset)?;
+            }
+            TagData::ModelAnimations(animations)
+        },
+        TagClass::Biped | TagClass::Vehicle => {
+            let mut unit = Unit::from_reader_with_ctx(reader, ())?;
+            read_object_blocks(&mut unit.object, reader, offset)?;
+            TagData::Unit(unit)
+        },
+        TagClass::Weapon | TagClass::Equipment | TagClass::Garbage => {
+            let mut item = Item::from_reader_with_ctx(reader, ())?;
+            read_object_blocks(&mut item.object, reader, offset)?;
+            TagData::Item(item)
+        },
+        TagClass::DeviceMachine | TagClass::DeviceControl | TagClass::DeviceLightFixture => {
+            let mut device = Device::from_reader_with_ctx(reader, ())?;
+            read_object_blocks(&mut device.object, reader, offset)?;
+            TagData::Device(device)
+        },
+        TagClass::Projectile | TagClass::SoundScenery | TagClass::Placeholder => {
+            let mut object = Object::from_reader_with_ctx(reader, ())?;
+            read_object_blocks(&mut object, reader, offset)?;
+            TagData::Object(object)
+        },
+        _ => return Err(MapReaderError::UnimplementedTag(format!("can't yet read {:?}", tag_header)).into()),
+    };
+    Ok(Tag { header: tag_header.clone(), data })
+}
+
+fn read_object_blocks(object: &mut Object, reader: &mut deku::reader::Reader<Cursor<Vec<u8>>>, offset: i64) -> Result<()> {
+    object.attachments.read_items(reader, offset)?;
+    object.widgets.read_items(reader, offset)?;
+    Ok(())
 }
 
 #[wasm_bindgen(js_name = "HaloBitmapReader")]
@@ -222,21 +473,58 @@ impl ResourceMapReader {
         ResourceMapReader { data: deku::reader::Reader::new(Cursor::new(data)) }
     }
 
-    pub fn get_and_convert_bitmap_data(&mut self, bitmap: &Bitmap, submap: usize) -> Vec<u8> {
+    // resource maps only exist for PC maps, so never use the Xbox layout
+    pub fn get_and_convert_bitmap_data(&mut self, bitmap: &Bitmap, submap: usize, decode_dxt: bool) -> Vec<u8> {
         let bitmap_data = &bitmap.data.items.as_ref().unwrap()[submap];
-        get_and_convert_bitmap_data(&mut self.data, bitmap_data)
+        let options = BitmapConversionOptions { decode_dxt, xbox_layout: false };
+        get_and_convert_bitmap_data(&mut self.data, bitmap_data, &options)
     }
 
     pub fn destroy(self) {}
 }
 
+pub struct ResourceMap {
+    reader: MapReader,
+    pub header: ResourcesHeader,
+    pub resources: Vec<ResourceHeader>,
+}
+
+impl ResourceMap {
+    pub fn new(data: Vec<u8>) -> Result<Self> {
+        let mut reader = MapReader { data: deku::reader::Reader::new(Cursor::new(data)) };
+        reader.data.seek(SeekFrom::Start(0))?;
+        let header = ResourcesHeader::from_reader_with_ctx(&mut reader.data, ())?;
+        let mut resources = Vec::with_capacity(header.resource_count as usize);
+        for i in 0..header.resource_count {
+            reader.data.seek(SeekFrom::Start(header.resources_offset as u64 + i as u64 * 12))?;
+            let mut resource = ResourceHeader::from_reader_with_ctx(&mut reader.data, ())?;
+            reader.data.seek(SeekFrom::Start((header.paths_offset + resource.path_offset) as u64))?;
+            let path = NullTerminatedAsciiString::from_reader_with_ctx(&mut reader.data, ())?;
+            resource.path = Some(path.into());
+            resources.push(resource);
+        }
+        Ok(ResourceMap { reader, header, resources })
+    }
+}
+
 pub struct MapReader {
     pub data: deku::reader::Reader<Cursor<Vec<u8>>>,
 }
 
 impl MapReader {
-    fn new(data: Vec<u8>) -> MapReader {
-        MapReader { data: deku::reader::Reader::new(Cursor::new(data)) }
+    // Original Xbox maps zlib-compress everything following the header
+    fn new(mut data: Vec<u8>) -> Result<MapReader> {
+        if data.len() < HEADER_SIZE {
+            return Err(MapReaderError::IO(format!("map too small ({} bytes)", data.len())).into());
+        }
+        let version = u32::from_le_bytes(data[4..8].try_into()?);
+        if version == MapVersion::Xbox as u32 {
+            let body = inflate::inflate_bytes_zlib(&data[HEADER_SIZE..])
+                .map_err(|err| MapReaderError::IO(format!("failed to decompress map: {}", err)))?;
+            data.truncate(HEADER_SIZE);
+            data.extend(body);
+        }
+        Ok(MapReader { data: deku::reader::Reader::new(Cursor::new(data)) })
     }
 
     fn read_header(&mut self) -> Result<Header> {
@@ -246,17 +534,17 @@ impl MapReader {
 
     fn read_tag_index_header(&mut self, header: &Header) -> Result<TagIndexHeader> {
         self.data.seek(SeekFrom::Start(header.tag_data_offset as u64))?;
-        Ok(TagIndexHeader::from_reader_with_ctx(&mut self.data, ())?)
+        Ok(TagIndexHeader::from_reader_with_ctx(&mut self.data, header.version)?)
     }
 
     fn read_tag_headers(&mut self, header: &Header, tag_index_header: &TagIndexHeader) -> Result<Vec<TagHeader>> {
         let mut result = Vec::with_capacity(tag_index_header.tag_count as usize);
-        for i in 0..tag_index_header.tag_count {
-            let data_offset = header.tag_data_offset + 40 + (i * 32);
-            self.data.seek(SeekFrom::Start(data_offset as u64))?;
+        let tag_array_offset = header.resolve_pointer(tag_index_header.tag_array_pointer)?;
+        for i in 0..tag_index_header.tag_count as u64 {
+            self.data.seek(SeekFrom::Start(tag_array_offset + i * 32))?;
             let mut tag_header = TagHeader::from_reader_with_ctx(&mut self.data, ())?;
-            let path_offset = header.tag_data_offset + tag_header.tag_path - BASE_MEMORY_ADDRESS;
-            self.data.seek(SeekFrom::Start(path_offset as u64))?;
+            let path_offset = header.resolve_pointer(tag_header.tag_path)?;
+            self.data.seek(SeekFrom::Start(path_offset))?;
             let path = NullTerminatedAsciiString::from_reader_with_ctx(&mut self.data, ())?;
             tag_header.path = path.try_into()?;
             result.push(tag_header);
@@ -265,7 +553,7 @@ impl MapReader {
     }
 }
 
-#[derive(Debug, Copy, Clone, DekuRead)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, DekuRead, ToJson)]
 #[deku(id_type = "u32")]
 #[repr(u32)]
 pub enum ResourceType {
@@ -274,7 +562,7 @@ pub enum ResourceType {
     Localization = 0x3,
 }
 
-#[derive(Debug, DekuRead)]
+#[derive(Debug, DekuRead, ToJson)]
 pub struct ResourcesHeader {
     pub resource_type: ResourceType,
     pub paths_offset: Pointer,
@@ -282,7 +570,7 @@ pub struct ResourcesHeader {
     pub resource_count: u32,
 }
 
-#[derive(Debug, DekuRead)]
+#[derive(Debug, DekuRead, ToJson)]
 pub struct ResourceHeader {
     pub path_offset: Pointer,
     pub size: u32,
@@ -291,7 +579,7 @@ pub struct ResourceHeader {
     pub path: Option<String>,
 }
 
-#[derive(Debug, Copy, Clone, DekuRead)]
+#[derive(Debug, Copy, Clone, DekuRead, ToJson)]
 #[deku(id_type = "u16")]
 #[repr(u16)]
 pub enum ScenarioType {
@@ -300,11 +588,32 @@ pub enum ScenarioType {
     UserInterface = 2,
 }
 
-#[derive(Debug, DekuRead)]
+#[wasm_bindgen(js_name = "HaloMapVersion")]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, DekuRead, ToJson)]
+#[deku(id_type = "u32")]
+#[repr(u32)]
+pub enum MapVersion {
+    Xbox = 5,
+    Retail = 7,
+    MCC = 0xD,
+    CustomEdition = 609,
+}
+
+impl MapVersion {
+    // the address the tag data gets loaded at, which all tag pointers are relative to
+    pub fn get_base_memory_address(&self) -> Pointer {
+        match self {
+            MapVersion::Xbox => 0x803A6000,
+            MapVersion::Retail | MapVersion::CustomEdition => 0x40440000,
+            MapVersion::MCC => 0x50000000,
+        }
+    }
+}
+
+#[derive(Debug, DekuRead, ToJson)]
 #[deku(magic = b"daeh")]
 pub struct Header {
-    #[deku(assert_eq = "0xD")]
-    pub mcc: u32,
+    pub version: MapVersion,
     pub uncompressed_file_size: u32,
     pub _padding_length: u32,
     pub tag_data_offset: Pointer,
@@ -321,17 +630,38 @@ pub struct Header {
     pub _footer: u32,
 }
 
-#[derive(Debug, Clone, DekuRead)]
+impl Header {
+    // converts a pointer into the loaded tag data to a file offset
+    pub fn resolve_pointer(&self, pointer: Pointer) -> Result<u64> {
+        pointer.checked_sub(self.version.get_base_memory_address())
+            .and_then(|offset| offset.checked_add(self.tag_data_offset))
+            .map(|offset| offset as u64)
+            .ok_or_else(|| MapReaderError::IO(format!("pointer {:#x} is outside the tag data", pointer)).into())
+    }
+}
+
+// Xbox maps keep their model data in the tag data, so instead of the PC's
+// model data file offset and sizes, their 36 byte header has vertex and index
+// data pointers
+#[derive(Debug, Clone, DekuRead, ToJson)]
+#[deku(ctx = "version: MapVersion")]
 pub struct TagIndexHeader {
     pub tag_array_pointer: Pointer,
     pub _checksum: u32,
     pub scenario_tag_id: u32,
     pub tag_count: u32,
     pub model_part_count: u32,
-    pub model_data_file_offset: Pointer,
-    pub _model_part_count_pc: u32,
-    pub vertex_data_size: u32,
-    pub model_data_size: u32,
+    #[deku(cond = "version == MapVersion::Xbox")]
+    pub vertex_data_pointer: Option<Pointer>,
+    #[deku(cond = "version != MapVersion::Xbox")]
+    pub model_data_file_offset: Option<Pointer>,
+    pub _model_part_count_again: u32,
+    #[deku(cond = "version == MapVersion::Xbox")]
+    pub index_data_pointer: Option<Pointer>,
+    #[deku(cond = "version != MapVersion::Xbox")]
+    pub vertex_data_size: Option<u32>,
+    #[deku(cond = "version != MapVersion::Xbox")]
+    pub model_data_size: Option<u32>,
     // footer == "sgat"
     #[deku(assert_eq = "1952540531")]
     pub _footer: u32,
@@ -345,6 +675,67 @@ mod tests {
         std::fs::read(&format!("../data/Halo1/maps/{}", path)).unwrap()
     }
 
+    // a map header, tag index header and a single scenario tag, already
+    // decompressed in the Xbox case
+    fn make_map(version: MapVersion, tag_index_header_size: u32) -> Vec<u8> {
+        let base = version.get_base_memory_address();
+        let mut data = Vec::new();
+        data.extend(b"daeh");
+        data.extend((version as u32).to_le_bytes());
+        data.extend([0; 8]);
+        data.extend((HEADER_SIZE as u32).to_le_bytes());
+        data.resize(0x60, 0);
+        data.extend((ScenarioType::Singleplayer as u16).to_le_bytes());
+        data.resize(HEADER_SIZE - 4, 0);
+        data.extend(b"toof");
+
+        let tag_array_pointer = base + tag_index_header_size;
+        data.extend(tag_array_pointer.to_le_bytes());
+        data.extend([0; 8]);
+        data.extend(1u32.to_le_bytes());
+        data.extend(2u32.to_le_bytes());
+        let model_fields = match version {
+            MapVersion::Xbox => vec![base + 0x100, 2, base + 0x200],
+            _ => vec![0x1000, 2, 0x300, 0x400],
+        };
+        for value in model_fields {
+            data.extend(value.to_le_bytes());
+        }
+        data.extend(b"sgat");
+
+        for _ in 0..3 {
+            data.extend((TagClass::Scenario as u32).to_le_bytes());
+        }
+        data.extend(0xE1740000u32.to_le_bytes());
+        data.extend((tag_array_pointer + 32).to_le_bytes());
+        data.extend([0; 12]);
+        data.extend(b"levels\\test\\test\0");
+        data
+    }
+
+    #[test]
+    fn test_tag_index_header() {
+        for (version, size) in [(MapVersion::Xbox, 36), (MapVersion::Retail, 40), (MapVersion::CustomEdition, 40), (MapVersion::MCC, 40)] {
+            let mut reader = MapReader { data: deku::reader::Reader::new(Cursor::new(make_map(version, size))) };
+            let header = reader.read_header().unwrap();
+            assert_eq!(header.version, version);
+            let tag_index_header = reader.read_tag_index_header(&header).unwrap();
+            assert_eq!(tag_index_header.tag_count, 1);
+            assert_eq!(tag_index_header.model_part_count, 2);
+            let tag_headers = reader.read_tag_headers(&header, &tag_index_header).unwrap();
+            assert_eq!(tag_headers.len(), 1);
+            assert_eq!(tag_headers[0].primary_class, TagClass::Scenario);
+            assert_eq!(tag_headers[0].path, "levels\\test\\test");
+
+            let mgr = MapManager { reader, header, tag_index_header, tag_headers, resource_maps: Vec::new() };
+            let expected = match version {
+                MapVersion::Xbox => (HEADER_SIZE as u64 + 0x100, HEADER_SIZE as u64 + 0x200),
+                _ => (0x1000, 0x1300),
+            };
+            assert_eq!(mgr.get_model_data_offsets().unwrap(), expected);
+        }
+    }
+
     #[test]
     fn test() {
         let mut mgr = MapManager::new(read_map("b30.map")).unwrap();
@@ -381,7 +772,7 @@ mod tests {
         let scenario_tag = mgr.get_scenario().unwrap();
         let bsps: Vec<BSP> = mgr.get_scenario_bsps(&scenario_tag).unwrap().iter()
             .map(|tag| match &tag.data {
-                TagData::BSP(bsp) => bsp.clone(),
+                TagData::BSP(bsp) => bsp.as_ref().clone(),
                 _ => unreachable!(),
             }).collect();
         assert!(bsps.len() > 0);
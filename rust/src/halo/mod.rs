@@ -7,9 +7,16 @@ pub mod tag;
 pub mod bitmap;
 pub mod scenario;
 pub mod model;
+pub mod object;
+pub mod animation;
 pub mod shader;
 pub mod wasm;
 pub mod bitmap_utils;
+pub mod collision;
+pub mod detail_object;
+pub mod particle;
+pub mod light;
+pub mod json;
 
 #[wasm_bindgen]
 pub fn init_panic_hook() {
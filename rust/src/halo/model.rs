@@ -1,16 +1,20 @@
 use deku::prelude::*;
 use crate::halo::common::*;
 use crate::halo::tag::*;
+use crate::halo::json::*;
 use wasm_bindgen::prelude::*;
 
-#[derive(Debug, Clone, DekuRead)]
+#[wasm_bindgen(js_name = "HaloSkyAnimation")]
+#[derive(Debug, Clone, DekuRead, ToJson)]
 pub struct SkyAnimations {
+    #[deku(pad_bytes_after = "2")]
     pub animation_index: i16,
+    #[deku(pad_bytes_after = "28")]
     pub period: f32,
 }
 
 #[wasm_bindgen(js_name = "HaloSky")]
-#[derive(Debug, Clone, DekuRead)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
 pub struct Sky {
     pub model: TagDependency,
     pub animation_graph: TagDependency,
@@ -28,29 +32,193 @@ pub struct Sky {
     #[deku(pad_bytes_before = "8")]
     pub indoor_fog_max_density: f32,
     pub indoor_fog_start_distance: f32,
-    #[deku(pad_bytes_after = "56")]
     pub indoor_fog_opaque_distance: f32,
+    #[deku(pad_bytes_before = "32", pad_bytes_after = "12")]
+    pub(crate) animations: Block<SkyAnimations>,
+}
+
+#[wasm_bindgen(js_class = "HaloSky")]
+impl Sky {
+    pub fn get_animations(&self) -> Vec<SkyAnimations> {
+        self.animations.items.as_ref().cloned().unwrap()
+    }
 }
 
 #[wasm_bindgen(js_name = "HaloModel")]
-#[derive(Debug, Clone, DekuRead)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
 pub struct GbxModel {
-    #[deku(pad_bytes_before = "48")]
+    pub flags: u32,
+    pub node_list_checksum: i32,
+    pub super_high_detail_cutoff: f32,
+    pub high_detail_cutoff: f32,
+    pub medium_detail_cutoff: f32,
+    pub low_detail_cutoff: f32,
+    pub super_low_detail_cutoff: f32,
+    pub super_low_detail_node_count: u16,
+    pub low_detail_node_count: u16,
+    pub medium_detail_node_count: u16,
+    pub high_detail_node_count: u16,
+    #[deku(pad_bytes_after = "10")]
+    pub super_high_detail_node_count: u16,
     pub base_bitmap_u_scale: f32,
     pub base_bitmap_v_scale: f32,
-    #[deku(pad_bytes_before = "152")]
+    #[deku(pad_bytes_before = "128")]
+    pub(crate) nodes: Block<GbxModelNode>,
+    pub(crate) regions: Block<GbxModelRegion>,
     pub(crate) geometries: Block<GbxModelGeometry>,
     pub(crate) shaders: Block<GbxModelShader>,
 }
 
-#[derive(Debug, Clone, DekuRead)]
+#[wasm_bindgen(js_name = "HaloModelLOD")]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ModelLOD {
+    SuperLow = 0,
+    Low = 1,
+    Medium = 2,
+    High = 3,
+    SuperHigh = 4,
+}
+
+#[wasm_bindgen(js_class = "HaloModel")]
+impl GbxModel {
+    pub fn get_nodes(&self) -> Vec<GbxModelNode> {
+        self.nodes.items.as_ref().cloned().unwrap()
+    }
+
+    pub fn get_region_names(&self) -> Vec<String> {
+        self.regions.items.as_ref().unwrap().iter()
+            .map(|region| region.name.clone())
+            .collect()
+    }
+
+    pub fn get_permutation_names(&self, region_index: usize) -> Vec<String> {
+        match self.regions.items.as_ref().unwrap().get(region_index) {
+            Some(region) => region.permutations.items.as_ref().unwrap().iter()
+                .map(|permutation| permutation.name.clone())
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // Picks an LOD based on how many pixels tall the object appears on screen
+    pub fn select_lod(&self, pixel_size: f32) -> ModelLOD {
+        if self.super_high_detail_cutoff > 0.0 && pixel_size >= self.super_high_detail_cutoff {
+            ModelLOD::SuperHigh
+        } else if pixel_size >= self.high_detail_cutoff {
+            ModelLOD::High
+        } else if pixel_size >= self.medium_detail_cutoff {
+            ModelLOD::Medium
+        } else if pixel_size >= self.low_detail_cutoff {
+            ModelLOD::Low
+        } else {
+            ModelLOD::SuperLow
+        }
+    }
+
+    pub fn select_lod_for_distance(&self, bounding_radius: f32, distance: f32, fov_y: f32, viewport_height: f32) -> ModelLOD {
+        let pixel_size = if distance > 0.0 {
+            bounding_radius * viewport_height / (distance * (fov_y / 2.0).tan())
+        } else {
+            f32::INFINITY
+        };
+        self.select_lod(pixel_size)
+    }
+}
+
+// rust-only interface
+impl GbxModel {
+    // Finds the geometry to draw for each region. Regions without the
+    // requested permutation fall back to their first one.
+    pub fn get_geometry_indices<F>(&self, lod: ModelLOD, select_permutation: F) -> Vec<usize>
+        where F: Fn(&[GbxModelPermutation]) -> Option<usize>
+    {
+        let geometry_count = self.geometries.items.as_ref().unwrap().len();
+        let mut result = Vec::new();
+        for region in self.regions.items.as_ref().unwrap() {
+            let permutations = region.permutations.items.as_ref().unwrap();
+            let permutation_index = select_permutation(permutations).unwrap_or(0);
+            let Some(permutation) = permutations.get(permutation_index) else {
+                continue;
+            };
+            if let Some(index) = permutation.get_geometry_index(lod) {
+                if index < geometry_count {
+                    result.push(index);
+                }
+            }
+        }
+        result
+    }
+
+    pub fn get_parts_for_geometries(&self, geometry_indices: &[usize]) -> Vec<GbxModelPart> {
+        let geometries = self.geometries.items.as_ref().unwrap();
+        geometry_indices.iter()
+            .flat_map(|&index| geometries[index].parts.items.as_ref().unwrap().iter().cloned())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct GbxModelRegion {
+    #[deku(reader = "read_tag_string(deku::reader)", pad_bytes_after = "32")]
+    pub name: String,
+    pub(crate) permutations: Block<GbxModelPermutation>,
+}
+
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct GbxModelPermutation {
+    #[deku(reader = "read_tag_string(deku::reader)")]
+    pub name: String,
+    #[deku(pad_bytes_after = "28")]
+    pub flags: u32,
+    pub super_low_geometry: i16,
+    pub low_geometry: i16,
+    pub medium_geometry: i16,
+    pub high_geometry: i16,
+    #[deku(pad_bytes_after = "14")]
+    pub super_high_geometry: i16,
+}
+
+impl GbxModelPermutation {
+    // If there's no geometry for the requested LOD, use the next best one
+    pub fn get_geometry_index(&self, lod: ModelLOD) -> Option<usize> {
+        let geometries = [
+            self.super_low_geometry,
+            self.low_geometry,
+            self.medium_geometry,
+            self.high_geometry,
+            self.super_high_geometry,
+        ];
+        let lod = lod as usize;
+        geometries[..=lod].iter().rev()
+            .chain(geometries[lod + 1..].iter())
+            .find(|&&index| index >= 0)
+            .map(|&index| index as usize)
+    }
+}
+
+#[wasm_bindgen(js_name = "HaloModelNode", getter_with_clone)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct GbxModelNode {
+    #[deku(reader = "read_tag_string(deku::reader)")]
+    pub name: String,
+    pub next_sibling_node_index: i16,
+    pub first_child_node_index: i16,
+    #[deku(pad_bytes_after = "2")]
+    pub parent_node_index: i16,
+    pub default_translation: Point3D,
+    pub default_rotation: Quaternion,
+    #[deku(pad_bytes_after = "84")]
+    pub node_distance_from_parent: f32,
+}
+
+#[derive(Debug, Clone, DekuRead, ToJson)]
 pub struct GbxModelGeometry {
     #[deku(pad_bytes_before = "36")]
     pub parts: Block<GbxModelPart>,
 }
 
 #[wasm_bindgen(js_name = "HaloModelPart")]
-#[derive(Debug, Clone, DekuRead)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
 pub struct GbxModelPart {
     #[deku(pad_bytes_before = "4")]
     pub shader_index: u16,
@@ -61,8 +229,13 @@ pub struct GbxModelPart {
     pub tri_offset: u32,
     #[deku(pad_bytes_before = "8")]
     pub vert_count: u32,
-    #[deku(pad_bytes_before = "8", pad_bytes_after = "28")]
+    #[deku(pad_bytes_before = "8")]
     pub vert_offset: u32,
+    // vertex node indices index into this list when it's non-empty
+    #[deku(pad_bytes_before = "3")]
+    pub local_node_count: u8,
+    #[deku(pad_bytes_after = "2")]
+    pub(crate) local_node_indices: [u8; 22],
 }
 
 #[wasm_bindgen(js_class = "HaloModelPart")]
@@ -70,9 +243,13 @@ impl GbxModelPart {
     pub fn tri_count(&self) -> u32 {
         self.off_by_two_tri_count + 2
     }
+
+    pub fn get_local_node_indices(&self) -> Vec<u8> {
+        self.local_node_indices[..(self.local_node_count as usize).min(22)].to_vec()
+    }
 }
 
-#[derive(Debug, Clone, DekuRead)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
 pub struct GbxModelShader {
     pub shader: TagDependency,
     #[deku(pad_bytes_after = "14")]
@@ -80,7 +257,7 @@ pub struct GbxModelShader {
 }
 
 #[wasm_bindgen(js_name = "HaloScenery")]
-#[derive(Debug, Clone, DekuRead)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
 pub struct Scenery {
     #[deku(pad_bytes_before = "2")]
     pub flags: u16,
@@ -92,3 +269,24 @@ pub struct Scenery {
     #[deku(assert = "modifier_shader.tag_class == TagClass::Shader", pad_bytes_before = "88")]
     pub modifier_shader: TagDependency,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_geometry_index_fallback() {
+        let permutation = GbxModelPermutation {
+            name: "base".to_string(),
+            flags: 0,
+            super_low_geometry: 0,
+            low_geometry: -1,
+            medium_geometry: 1,
+            high_geometry: -1,
+            super_high_geometry: -1,
+        };
+        assert_eq!(permutation.get_geometry_index(ModelLOD::SuperHigh), Some(1));
+        assert_eq!(permutation.get_geometry_index(ModelLOD::Low), Some(0));
+        assert_eq!(permutation.get_geometry_index(ModelLOD::Medium), Some(1));
+    }
+}
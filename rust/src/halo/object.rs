@@ -0,0 +1,134 @@
+use deku::prelude::*;
+use wasm_bindgen::prelude::*;
+
+use crate::halo::common::*;
+use crate::halo::tag::*;
+use crate::halo::shader::FunctionSource;
+use crate::halo::json::*;
+
+// The layout shared by the start of every object tag (scenery, units, items,
+// devices, etc.)
+#[wasm_bindgen(js_name = "HaloObject")]
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct Object {
+    #[deku(pad_bytes_before = "2")]
+    pub flags: u16,
+    pub bounding_radius: f32,
+    pub bounding_offset: Point3D,
+    pub origin_offset: Point3D,
+    #[deku(pad_bytes_after = "4")]
+    pub acceleration_scale: f32,
+    pub(crate) model: TagDependency,
+    #[deku(pad_bytes_after = "40")]
+    pub animation_graph: TagDependency,
+    pub collision_model: TagDependency,
+    pub physics: TagDependency,
+    pub modifier_shader: TagDependency,
+    #[deku(pad_bytes_after = "84")]
+    pub creation_effect: TagDependency,
+    #[deku(pad_bytes_after = "52")]
+    pub render_bounding_radius: f32,
+    pub hud_text_message_index: i16,
+    pub forced_shader_permutation_index: i16,
+    pub(crate) attachments: Block<ObjectAttachment>,
+    #[deku(pad_bytes_after = "36")]
+    pub(crate) widgets: Block<ObjectWidget>,
+}
+
+#[wasm_bindgen(js_class = "HaloObject")]
+impl Object {
+    pub fn get_attachments(&self) -> Vec<ObjectAttachment> {
+        self.attachments.items.as_ref().cloned().unwrap()
+    }
+
+    // Lights (e.g. a light fixture's) are attachments, placed at the
+    // attachment's marker
+    pub fn get_light_attachments(&self) -> Vec<ObjectAttachment> {
+        self.get_attachments_of_class(TagClass::Light)
+    }
+
+    pub fn get_glow_attachments(&self) -> Vec<ObjectAttachment> {
+        self.get_attachments_of_class(TagClass::Glow)
+    }
+
+    pub fn get_widgets(&self) -> Vec<TagDependency> {
+        self.widgets.items.as_ref().unwrap().iter()
+            .map(|widget| widget.reference)
+            .collect()
+    }
+}
+
+// rust-only interface
+impl Object {
+    fn get_attachments_of_class(&self, class: TagClass) -> Vec<ObjectAttachment> {
+        self.attachments.items.as_ref().unwrap().iter()
+            .filter(|attachment| attachment.attachment_type.tag_class == class)
+            .cloned()
+            .collect()
+    }
+}
+
+#[wasm_bindgen(js_name = "HaloObjectAttachment")]
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct ObjectAttachment {
+    pub attachment_type: TagDependency,
+    #[deku(count = "32")]
+    pub(crate) marker: Vec<u8>,
+    pub primary_scale: FunctionSource,
+    pub secondary_scale: FunctionSource,
+    #[deku(pad_bytes_after = "18")]
+    pub change_color: FunctionSource,
+}
+
+#[wasm_bindgen(js_class = "HaloObjectAttachment")]
+impl ObjectAttachment {
+    pub fn get_marker_name(&self) -> String {
+        let end = self.marker.iter().position(|&c| c == 0).unwrap_or(self.marker.len());
+        String::from_utf8_lossy(&self.marker[..end]).to_string()
+    }
+}
+
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct ObjectWidget {
+    #[deku(pad_bytes_after = "16")]
+    pub reference: TagDependency,
+}
+
+// Bipeds and vehicles
+#[wasm_bindgen(js_name = "HaloUnit", getter_with_clone)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct Unit {
+    pub object: Object,
+    pub unit_flags: u32,
+    pub default_team: u16,
+    pub constant_sound_volume: u16,
+    pub rider_damage_fraction: f32,
+    #[deku(pad_bytes_after = "8")]
+    pub integrated_light_toggle: TagDependency,
+    pub camera_field_of_view: f32,
+}
+
+// Weapons, equipment and garbage
+#[wasm_bindgen(js_name = "HaloItem", getter_with_clone)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct Item {
+    pub object: Object,
+    pub item_flags: u32,
+    pub pickup_text_index: i16,
+    pub sort_order: i16,
+    pub scale: f32,
+}
+
+// Machines, controls and light fixtures
+#[wasm_bindgen(js_name = "HaloDevice", getter_with_clone)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct Device {
+    pub object: Object,
+    pub device_flags: u32,
+    pub power_transition_time: f32,
+    pub power_acceleration_time: f32,
+    pub position_transition_time: f32,
+    pub position_acceleration_time: f32,
+    pub depowered_position_transition_time: f32,
+    pub depowered_position_acceleration_time: f32,
+}
@@ -0,0 +1,622 @@
+use deku::prelude::*;
+use js_sys::Float32Array;
+use nalgebra_glm::{make_vec3, vec3, vec4, Vec3, Vec4};
+use rand::{rngs::ThreadRng, thread_rng, Rng};
+use wasm_bindgen::prelude::*;
+
+use crate::halo::common::*;
+use crate::halo::scenario::*;
+use crate::halo::shader::{FramebufferBlendFunction, FramebufferFadeMode};
+use crate::halo::tag::*;
+use crate::halo::json::*;
+
+// Each particle takes up three vec4s in the instance buffer:
+// position + radius, color, and rotation + sequence + frame
+pub const FLOATS_PER_PARTICLE: usize = 12;
+const MAX_PARTICLES: usize = 4096;
+
+// Neither the weather nor particle tags say how fast things fall (that's up
+// to the point physics tag), so particles drift towards this velocity
+const DEFAULT_FALL_VELOCITY: f32 = 1.0;
+const WEATHER_DRAG: f32 = 2.0;
+const EMITTER_DRAG: f32 = 0.25;
+
+#[wasm_bindgen(js_name = "HaloParticleOrientation")]
+#[derive(Debug, Copy, Clone, PartialEq, DekuRead, ToJson)]
+#[deku(id_type = "u16")]
+#[repr(u16)]
+pub enum ParticleOrientation {
+    ScreenFacing = 0,
+    ParallelToDirection = 1,
+    PerpendicularToDirection = 2,
+}
+
+#[wasm_bindgen(js_name = "HaloParticleDirectionSource")]
+#[derive(Debug, Copy, Clone, PartialEq, DekuRead, ToJson)]
+#[deku(id_type = "u16")]
+#[repr(u16)]
+pub enum ParticleDirectionSource {
+    FromVelocity = 0,
+    FromAcceleration = 1,
+}
+
+#[wasm_bindgen(js_name = "HaloWeatherParticleSystem")]
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct WeatherParticleSystem {
+    #[deku(pad_bytes_after = "32")]
+    pub flags: u32,
+    pub(crate) particle_types: Block<WeatherParticleType>,
+}
+
+#[wasm_bindgen(js_class = "HaloWeatherParticleSystem")]
+impl WeatherParticleSystem {
+    pub fn get_particle_types(&self) -> Vec<WeatherParticleType> {
+        self.particle_types.items.as_ref().cloned().unwrap()
+    }
+}
+
+#[wasm_bindgen(js_name = "HaloWeatherParticleType", getter_with_clone)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct WeatherParticleType {
+    #[deku(reader = "read_tag_string(deku::reader)")]
+    pub name: String,
+    pub flags: u32,
+    pub fade_in_start_distance: f32,
+    pub fade_in_end_distance: f32,
+    pub fade_out_start_distance: f32,
+    pub fade_out_end_distance: f32,
+    pub fade_in_start_height: f32,
+    pub fade_in_end_height: f32,
+    pub fade_out_start_height: f32,
+    #[deku(pad_bytes_after = "96")]
+    pub fade_out_end_height: f32,
+    pub particle_count: Bounds, // particles per cubic world unit
+    #[deku(pad_bytes_after = "16")]
+    pub physics: TagDependency,
+    pub acceleration_magnitude: Bounds,
+    pub acceleration_turning_rate: f32,
+    #[deku(pad_bytes_after = "32")]
+    pub acceleration_change_rate: f32,
+    pub particle_radius: Bounds,
+    pub animation_rate: Bounds,
+    #[deku(pad_bytes_after = "32")]
+    pub rotation_rate: Bounds,
+    pub color_lower_bound: ColorARGB,
+    #[deku(pad_bytes_after = "64")]
+    pub color_upper_bound: ColorARGB,
+    pub sprite_bitmap: TagDependency,
+    pub render_mode: ParticleOrientation,
+    #[deku(pad_bytes_after = "40")]
+    pub render_direction_source: ParticleDirectionSource,
+    pub shader_flags: u16,
+    pub framebuffer_blend_function: FramebufferBlendFunction,
+    pub framebuffer_fade_mode: FramebufferFadeMode,
+    #[deku(pad_bytes_after = "132")]
+    pub map_flags: u16,
+}
+
+#[wasm_bindgen(js_name = "HaloParticle")]
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct Particle {
+    pub flags: u32,
+    pub bitmap: TagDependency,
+    pub physics: TagDependency,
+    #[deku(pad_bytes_after = "4")]
+    pub material_effects: TagDependency,
+    pub lifespan: Bounds,
+    pub fade_in_time: f32,
+    pub fade_out_time: f32,
+    pub collision_effect: TagDependency,
+    pub death_effect: TagDependency,
+    #[deku(pad_bytes_after = "8")]
+    pub minimum_size: f32,
+    #[deku(pad_bytes_after = "4")]
+    pub radius_animation: Bounds,
+    pub animation_rate: Bounds,
+    pub contact_deterioration: f32,
+    pub fade_start_size: f32,
+    #[deku(pad_bytes_after = "4")]
+    pub fade_end_size: f32,
+    pub first_sequence_index: u16,
+    pub initial_sequence_count: u16,
+    pub looping_sequence_count: u16,
+    #[deku(pad_bytes_after = "196")]
+    pub final_sequence_count: u16,
+}
+
+// Only the particle types are decoded here; their states and the system's
+// physics constants (skipped) drive scripted point physics we don't simulate
+#[wasm_bindgen(js_name = "HaloParticleSystem")]
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct ParticleSystem {
+    #[deku(pad_bytes_before = "56")]
+    pub point_physics: TagDependency,
+    #[deku(pad_bytes_after = "18")]
+    pub system_update_physics: u16,
+    pub(crate) particle_types: Block<ParticleSystemType>,
+}
+
+#[wasm_bindgen(js_class = "HaloParticleSystem")]
+impl ParticleSystem {
+    pub fn get_particle_types(&self) -> Vec<ParticleSystemType> {
+        self.particle_types.items.as_ref().cloned().unwrap()
+    }
+}
+
+#[wasm_bindgen(js_name = "HaloParticleSystemType", getter_with_clone)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct ParticleSystemType {
+    #[deku(reader = "read_tag_string(deku::reader)")]
+    pub name: String,
+    pub flags: u32,
+    #[deku(pad_bytes_after = "2")]
+    pub initial_particle_count: u16,
+    #[deku(pad_bytes_after = "2")]
+    pub complex_sprite_render_modes: u16,
+    #[deku(pad_bytes_after = "80")]
+    pub radius: f32,
+}
+
+#[wasm_bindgen(js_name = "HaloBSPWeatherPaletteEntry", getter_with_clone)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct BSPWeatherPaletteEntry {
+    #[deku(reader = "read_tag_string(deku::reader)")]
+    pub name: String,
+    #[deku(pad_bytes_after = "4")]
+    pub particle_system: TagDependency,
+    #[deku(reader = "read_tag_string(deku::reader)", pad_bytes_after = "44")]
+    pub particle_system_scale_function: String,
+    pub wind: TagDependency,
+    pub wind_direction: Vector3D,
+    #[deku(pad_bytes_after = "4")]
+    pub wind_magnitude: f32,
+    #[deku(reader = "read_tag_string(deku::reader)", pad_bytes_after = "44")]
+    pub wind_scale_function: String,
+}
+
+// Convex volumes in which weather isn't drawn, e.g. under overhangs
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct BSPWeatherPolyhedron {
+    pub bounding_sphere_center: Point3D,
+    #[deku(pad_bytes_after = "4")]
+    pub bounding_sphere_radius: f32,
+    pub(crate) planes: Block<Plane3D>,
+}
+
+impl BSPWeatherPolyhedron {
+    pub fn contains_point(&self, p: &Vec3) -> bool {
+        let center = vec3(self.bounding_sphere_center.x, self.bounding_sphere_center.y, self.bounding_sphere_center.z);
+        if (p - center).magnitude() > self.bounding_sphere_radius {
+            return false;
+        }
+        self.planes.items.as_ref().unwrap().iter()
+            .all(|plane| plane.norm.i * p.x + plane.norm.j * p.y + plane.norm.k * p.z - plane.w <= 0.0)
+    }
+}
+
+#[wasm_bindgen(js_class = "HaloBSP")]
+impl BSP {
+    pub fn get_weather_palette(&self) -> Vec<BSPWeatherPaletteEntry> {
+        self.weather_palette.items.as_ref().cloned().unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone)]
+enum SpawnMode {
+    // particles fill a cube around the camera, wrapping around as it moves
+    Weather { half_extent: f32 },
+    // particles are emitted from a point at a fixed rate
+    Emitter { position: Vec3, rate: f32, velocity: f32, particles_to_emit: f32 },
+}
+
+// The subset of a weather particle type or particle tag needed to simulate it
+#[derive(Debug, Clone)]
+struct ParticleParams {
+    lifespan: Bounds,
+    fade_in_time: f32,
+    fade_out_time: f32,
+    fade_in_distance: Bounds,
+    fade_out_distance: Bounds,
+    fade_in_height: Bounds,
+    fade_out_height: Bounds,
+    radius: Bounds,
+    animation_rate: Bounds,
+    rotation_rate: Bounds,
+    acceleration_magnitude: Bounds,
+    acceleration_turning_rate: f32,
+    acceleration_change_rate: f32,
+    color_lower_bound: Vec4,
+    color_upper_bound: Vec4,
+    first_sequence_index: usize,
+    sequence_count: usize,
+    final_sequence_count: usize,
+}
+
+#[derive(Debug, Clone)]
+struct ParticleState {
+    position: Vec3,
+    velocity: Vec3,
+    acceleration: Vec3,
+    acceleration_target: Vec3,
+    acceleration_magnitude: f32,
+    age: f32,
+    lifespan: f32,
+    radius: f32,
+    rotation: f32,
+    rotation_rate: f32,
+    animation_rate: f32,
+    sequence: usize,
+    color: Vec4,
+    alpha: f32,
+}
+
+#[wasm_bindgen(js_name = "HaloParticleSimulator")]
+#[derive(Debug, Clone)]
+pub struct ParticleSimulator {
+    params: ParticleParams,
+    mode: SpawnMode,
+    particles: Vec<ParticleState>,
+    max_particles: usize,
+    camera_position: Vec3,
+    wind: Vec3,
+    fall_velocity: f32,
+    occluders: Vec<BSPWeatherPolyhedron>,
+    rng: ThreadRng,
+}
+
+#[wasm_bindgen(js_class = "HaloParticleSimulator")]
+impl ParticleSimulator {
+    // density_scale is applied to the type's particle count, e.g. to scale
+    // with the BSP palette entry's particle system scale function
+    pub fn new_weather(particle_type: &WeatherParticleType, density_scale: f32) -> Self {
+        let half_extent = particle_type.fade_out_end_distance.max(1.0);
+        let volume = (half_extent * 2.0).powi(3);
+        let density = (particle_type.particle_count.lower + particle_type.particle_count.upper) / 2.0;
+        let max_particles = ((density * density_scale * volume) as usize).min(MAX_PARTICLES);
+        let params = ParticleParams {
+            lifespan: Bounds { lower: f32::INFINITY, upper: f32::INFINITY },
+            fade_in_time: 0.0,
+            fade_out_time: 0.0,
+            fade_in_distance: Bounds { lower: particle_type.fade_in_start_distance, upper: particle_type.fade_in_end_distance },
+            fade_out_distance: Bounds { lower: particle_type.fade_out_start_distance, upper: particle_type.fade_out_end_distance },
+            fade_in_height: Bounds { lower: particle_type.fade_in_start_height, upper: particle_type.fade_in_end_height },
+            fade_out_height: Bounds { lower: particle_type.fade_out_start_height, upper: particle_type.fade_out_end_height },
+            radius: particle_type.particle_radius,
+            animation_rate: particle_type.animation_rate,
+            rotation_rate: particle_type.rotation_rate,
+            acceleration_magnitude: particle_type.acceleration_magnitude,
+            acceleration_turning_rate: particle_type.acceleration_turning_rate,
+            acceleration_change_rate: particle_type.acceleration_change_rate,
+            color_lower_bound: argb_to_vec4(&particle_type.color_lower_bound),
+            color_upper_bound: argb_to_vec4(&particle_type.color_upper_bound),
+            first_sequence_index: 0,
+            sequence_count: 1,
+            final_sequence_count: 0,
+        };
+        let mut simulator = Self::new(params, SpawnMode::Weather { half_extent }, max_particles);
+        for _ in 0..max_particles {
+            simulator.spawn_particle();
+        }
+        simulator
+    }
+
+    // rate is in particles per second, velocity in world units per second
+    pub fn new_emitter(particle: &Particle, rate: f32, velocity: f32, max_particles: usize) -> Self {
+        let params = ParticleParams {
+            lifespan: particle.lifespan,
+            fade_in_time: particle.fade_in_time,
+            fade_out_time: particle.fade_out_time,
+            fade_in_distance: Bounds { lower: 0.0, upper: 0.0 },
+            fade_out_distance: Bounds { lower: f32::INFINITY, upper: f32::INFINITY },
+            fade_in_height: Bounds { lower: 0.0, upper: 0.0 },
+            fade_out_height: Bounds { lower: f32::INFINITY, upper: f32::INFINITY },
+            radius: Bounds {
+                lower: particle.radius_animation.lower.max(particle.minimum_size),
+                upper: particle.radius_animation.upper.max(particle.minimum_size),
+            },
+            animation_rate: particle.animation_rate,
+            rotation_rate: Bounds { lower: 0.0, upper: 0.0 },
+            acceleration_magnitude: Bounds { lower: 0.0, upper: 0.0 },
+            acceleration_turning_rate: 0.0,
+            acceleration_change_rate: 0.0,
+            color_lower_bound: vec4(1.0, 1.0, 1.0, 1.0),
+            color_upper_bound: vec4(1.0, 1.0, 1.0, 1.0),
+            first_sequence_index: particle.first_sequence_index as usize,
+            sequence_count: (particle.initial_sequence_count as usize + particle.looping_sequence_count as usize).max(1),
+            final_sequence_count: particle.final_sequence_count as usize,
+        };
+        let mode = SpawnMode::Emitter { position: vec3(0.0, 0.0, 0.0), rate, velocity, particles_to_emit: 0.0 };
+        Self::new(params, mode, max_particles.min(MAX_PARTICLES))
+    }
+
+    pub fn set_camera_position(&mut self, position: &[f32]) {
+        self.camera_position = make_vec3(position);
+    }
+
+    pub fn set_emitter_position(&mut self, position_slice: &[f32]) {
+        if let SpawnMode::Emitter { position, .. } = &mut self.mode {
+            *position = make_vec3(position_slice);
+        }
+    }
+
+    pub fn set_wind(&mut self, direction: &[f32], magnitude: f32) {
+        self.wind = make_vec3(direction).try_normalize(f32::EPSILON)
+            .map_or(vec3(0.0, 0.0, 0.0), |direction| direction * magnitude);
+    }
+
+    pub fn set_fall_velocity(&mut self, fall_velocity: f32) {
+        self.fall_velocity = fall_velocity;
+    }
+
+    // weather isn't drawn inside the BSP's weather polyhedra
+    pub fn set_occluders_from_bsp(&mut self, bsp: &BSP) {
+        self.occluders = bsp.weather_polyhedra.items.as_ref().cloned().unwrap_or_default();
+    }
+
+    pub fn update(&mut self, dt_ms: f32) {
+        let dt_secs = dt_ms / 1000.0;
+        if let SpawnMode::Emitter { rate, particles_to_emit, .. } = &mut self.mode {
+            *particles_to_emit += *rate * dt_secs;
+            let count = *particles_to_emit as usize;
+            *particles_to_emit -= count as f32;
+            for _ in 0..count {
+                if self.particles.len() >= self.max_particles {
+                    break;
+                }
+                self.spawn_particle();
+            }
+        }
+
+        let drag = match self.mode {
+            SpawnMode::Weather { .. } => WEATHER_DRAG,
+            SpawnMode::Emitter { .. } => EMITTER_DRAG,
+        };
+        let terminal_velocity = self.wind + vec3(0.0, 0.0, -self.fall_velocity);
+        let change_chance = (self.params.acceleration_change_rate * dt_secs).min(1.0);
+        let turn_amount = (self.params.acceleration_turning_rate * dt_secs).min(1.0);
+        let mut particles = std::mem::take(&mut self.particles);
+        particles.retain_mut(|particle| {
+            particle.age += dt_secs;
+            if particle.age >= particle.lifespan {
+                return false;
+            }
+
+            // the acceleration direction wanders towards a randomly changing target
+            if change_chance > 0.0 && self.rng.gen::<f32>() < change_chance {
+                particle.acceleration_target = random_direction(&mut self.rng);
+            }
+            particle.acceleration = particle.acceleration.lerp(&particle.acceleration_target, turn_amount);
+            let acceleration = particle.acceleration * particle.acceleration_magnitude;
+
+            particle.velocity += (terminal_velocity - particle.velocity) * (drag * dt_secs).min(1.0);
+            particle.velocity += acceleration * dt_secs;
+            particle.position += particle.velocity * dt_secs;
+            particle.rotation += particle.rotation_rate * dt_secs;
+            true
+        });
+        for particle in particles.iter_mut() {
+            if let SpawnMode::Weather { half_extent } = self.mode {
+                particle.position = wrap_around(&particle.position, &self.camera_position, half_extent);
+            }
+            particle.alpha = self.compute_alpha(particle);
+        }
+        self.particles = particles;
+    }
+
+    pub fn fill_instance_buffer(&self, buffer: &Float32Array) {
+        buffer.copy_from(&self.get_instance_data());
+    }
+
+    pub fn get_floats_per_particle() -> usize {
+        FLOATS_PER_PARTICLE
+    }
+
+    pub fn get_max_particles(&self) -> usize {
+        self.max_particles
+    }
+
+    pub fn num_particles(&self) -> usize {
+        self.particles.len()
+    }
+}
+
+// rust-only interface
+impl ParticleSimulator {
+    fn new(params: ParticleParams, mode: SpawnMode, max_particles: usize) -> Self {
+        ParticleSimulator {
+            params,
+            mode,
+            particles: Vec::with_capacity(max_particles),
+            max_particles,
+            camera_position: vec3(0.0, 0.0, 0.0),
+            wind: vec3(0.0, 0.0, 0.0),
+            fall_velocity: DEFAULT_FALL_VELOCITY,
+            occluders: Vec::new(),
+            rng: thread_rng(),
+        }
+    }
+
+    // Returns FLOATS_PER_PARTICLE floats for each of max_particles, with
+    // unused slots zeroed
+    pub fn get_instance_data(&self) -> Vec<f32> {
+        let mut data = vec![0.0; self.max_particles * FLOATS_PER_PARTICLE];
+        for (particle, chunk) in self.particles.iter().zip(data.chunks_exact_mut(FLOATS_PER_PARTICLE)) {
+            let frame = (particle.age * particle.animation_rate).floor();
+            let sequence = if self.is_fading_out(particle) && self.params.final_sequence_count > 0 {
+                self.params.first_sequence_index + self.params.sequence_count + particle.sequence % self.params.final_sequence_count
+            } else {
+                self.params.first_sequence_index + particle.sequence
+            };
+            chunk.copy_from_slice(&[
+                particle.position.x, particle.position.y, particle.position.z, particle.radius,
+                particle.color.x, particle.color.y, particle.color.z, particle.color.w * particle.alpha,
+                particle.rotation, sequence as f32, frame, 0.0,
+            ]);
+        }
+        data
+    }
+
+    fn random_in(&mut self, bounds: &Bounds) -> f32 {
+        if bounds.upper > bounds.lower {
+            self.rng.gen_range(bounds.lower..bounds.upper)
+        } else {
+            bounds.lower
+        }
+    }
+
+    fn spawn_particle(&mut self) {
+        let (position, velocity) = match self.mode {
+            SpawnMode::Weather { half_extent } => {
+                let offset = vec3(
+                    self.rng.gen_range(-half_extent..half_extent),
+                    self.rng.gen_range(-half_extent..half_extent),
+                    self.rng.gen_range(-half_extent..half_extent),
+                );
+                (self.camera_position + offset, self.wind + vec3(0.0, 0.0, -self.fall_velocity))
+            },
+            SpawnMode::Emitter { position, velocity, .. } => (position, random_direction(&mut self.rng) * velocity),
+        };
+        let direction = random_direction(&mut self.rng);
+        let params = self.params.clone();
+        let t = self.rng.gen::<f32>();
+        let particle = ParticleState {
+            position,
+            velocity,
+            acceleration: direction,
+            acceleration_target: direction,
+            acceleration_magnitude: self.random_in(&params.acceleration_magnitude),
+            age: 0.0,
+            lifespan: self.random_in(&params.lifespan),
+            radius: self.random_in(&params.radius),
+            rotation: self.rng.gen_range(0.0..std::f32::consts::TAU),
+            rotation_rate: self.random_in(&params.rotation_rate),
+            animation_rate: self.random_in(&params.animation_rate),
+            sequence: self.rng.gen_range(0..params.sequence_count),
+            color: params.color_lower_bound.lerp(&params.color_upper_bound, t),
+            alpha: 0.0,
+        };
+        self.particles.push(particle);
+    }
+
+    fn is_fading_out(&self, particle: &ParticleState) -> bool {
+        particle.lifespan - particle.age < self.params.fade_out_time
+    }
+
+    fn compute_alpha(&self, particle: &ParticleState) -> f32 {
+        if self.occluders.iter().any(|polyhedron| polyhedron.contains_point(&particle.position)) {
+            return 0.0;
+        }
+        let params = &self.params;
+        let mut alpha = 1.0;
+        if params.fade_in_time > 0.0 {
+            alpha *= (particle.age / params.fade_in_time).min(1.0);
+        }
+        if params.fade_out_time > 0.0 {
+            alpha *= ((particle.lifespan - particle.age) / params.fade_out_time).clamp(0.0, 1.0);
+        }
+        let distance = (particle.position - self.camera_position).magnitude();
+        let height = (particle.position.z - self.camera_position.z).abs();
+        alpha *= ramp(distance, &params.fade_in_distance) * (1.0 - ramp(distance, &params.fade_out_distance));
+        alpha *= ramp(height, &params.fade_in_height) * (1.0 - ramp(height, &params.fade_out_height));
+        alpha
+    }
+}
+
+// 0 before bounds.lower, 1 after bounds.upper, and linear in between
+fn ramp(x: f32, bounds: &Bounds) -> f32 {
+    if bounds.upper <= bounds.lower {
+        return if x >= bounds.lower { 1.0 } else { 0.0 };
+    }
+    ((x - bounds.lower) / (bounds.upper - bounds.lower)).clamp(0.0, 1.0)
+}
+
+fn wrap_around(p: &Vec3, center: &Vec3, half_extent: f32) -> Vec3 {
+    let size = half_extent * 2.0;
+    let wrap = |x: f32, c: f32| c - half_extent + (x - c + half_extent).rem_euclid(size);
+    vec3(wrap(p.x, center.x), wrap(p.y, center.y), wrap(p.z, center.z))
+}
+
+fn random_direction(rng: &mut ThreadRng) -> Vec3 {
+    let z: f32 = rng.gen_range(-1.0..1.0);
+    let theta: f32 = rng.gen_range(0.0..std::f32::consts::TAU);
+    let r = (1.0 - z * z).sqrt();
+    vec3(r * theta.cos(), r * theta.sin(), z)
+}
+
+fn argb_to_vec4(color: &ColorARGB) -> Vec4 {
+    vec4(color.r, color.g, color.b, color.a)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_particle(lifespan: f32) -> Particle {
+        let dependency = TagDependency { tag_class: TagClass::Bitmap, path_pointer: 0, global_id: 0, tag_id: 0 };
+        Particle {
+            flags: 0,
+            bitmap: dependency,
+            physics: dependency,
+            material_effects: dependency,
+            lifespan: Bounds { lower: lifespan, upper: lifespan },
+            fade_in_time: 0.0,
+            fade_out_time: 0.5,
+            collision_effect: dependency,
+            death_effect: dependency,
+            minimum_size: 0.1,
+            radius_animation: Bounds { lower: 0.0, upper: 0.0 },
+            animation_rate: Bounds { lower: 10.0, upper: 10.0 },
+            contact_deterioration: 0.0,
+            fade_start_size: 0.0,
+            fade_end_size: 0.0,
+            first_sequence_index: 2,
+            initial_sequence_count: 1,
+            looping_sequence_count: 0,
+            final_sequence_count: 1,
+        }
+    }
+
+    #[test]
+    fn test_emitter() {
+        let mut simulator = ParticleSimulator::new_emitter(&make_particle(1.0), 10.0, 1.0, 100);
+        simulator.set_emitter_position(&[5.0, 0.0, 0.0]);
+        simulator.update(500.0);
+        assert_eq!(simulator.num_particles(), 5);
+        simulator.update(250.0);
+        let data = simulator.get_instance_data();
+        assert_eq!(data.len(), 100 * FLOATS_PER_PARTICLE);
+        // the first particles are 0.75s into a 1s life, so they're fading out
+        // and have switched to the final sequence
+        let first = &data[..FLOATS_PER_PARTICLE];
+        assert_eq!(first[3], 0.1);
+        assert_eq!(first[7], 0.5);
+        assert_eq!(first[9], 3.0);
+        assert_eq!(first[10], 7.0);
+        simulator.update(500.0);
+        assert!(simulator.particles.iter().all(|p| p.age < 1.0));
+    }
+
+    #[test]
+    fn test_degenerate_params() {
+        let mut particle = make_particle(1.0);
+        particle.first_sequence_index = u16::MAX;
+        particle.initial_sequence_count = u16::MAX;
+        particle.looping_sequence_count = 1;
+        let mut simulator = ParticleSimulator::new_emitter(&particle, 10.0, 1.0, 100);
+        assert_eq!(simulator.params.sequence_count, 0x10000);
+        simulator.set_wind(&[0.0, 0.0, 0.0], 5.0);
+        assert_eq!(simulator.wind, vec3(0.0, 0.0, 0.0));
+        simulator.update(500.0);
+        let data = simulator.get_instance_data();
+        assert!(data.iter().all(|x| x.is_finite()));
+        assert!(data[9] >= u16::MAX as f32);
+    }
+
+    #[test]
+    fn test_wrap_around() {
+        let center = vec3(10.0, 0.0, 0.0);
+        let p = wrap_around(&vec3(13.0, -1.0, 0.5), &center, 2.0);
+        assert!((p - vec3(9.0, -1.0, 0.5)).magnitude() < 1e-5);
+        assert_eq!(ramp(1.5, &Bounds { lower: 1.0, upper: 2.0 }), 0.5);
+    }
+}
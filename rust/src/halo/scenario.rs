@@ -3,8 +3,12 @@ use wasm_bindgen::prelude::*;
 
 use crate::{halo::common::*, unity::types::common::NullTerminatedAsciiString};
 use crate::halo::tag::*;
+use crate::halo::detail_object::*;
+use crate::halo::particle::*;
+use crate::halo::light::*;
+use crate::halo::json::*;
 
-#[derive(Debug, Copy, Clone, DekuRead)]
+#[derive(Debug, Copy, Clone, DekuRead, ToJson)]
 #[deku(id_type = "u16")]
 #[repr(u16)]
 pub enum ObjectType {
@@ -22,7 +26,7 @@ pub enum ObjectType {
     SoundScenery = 0xB,
 }
 
-#[derive(Debug, DekuRead)]
+#[derive(Debug, DekuRead, ToJson)]
 pub struct ObjectName {
     pub name: NullTerminatedAsciiString,
     pub object_type: ObjectType,
@@ -30,7 +34,7 @@ pub struct ObjectName {
 }
 
 #[wasm_bindgen(js_name = "HaloSceneryInstance")]
-#[derive(Debug, Clone, DekuRead)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
 pub struct ScenarioScenery {
     pub scenery_type: u16,
     pub name_index: u16,
@@ -41,23 +45,145 @@ pub struct ScenarioScenery {
     pub _appearance_player_index: u16,
 }
 
-#[derive(Debug, Clone, DekuRead)]
+#[wasm_bindgen(js_name = "HaloBipedInstance")]
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct ScenarioBiped {
+    pub biped_type: u16,
+    pub name_index: u16,
+    pub not_placed: u16,
+    pub desired_permutation: i16,
+    pub position: Point3D,
+    pub rotation: Euler3D,
+    #[deku(pad_bytes_before = "40")]
+    pub body_vitality: f32,
+    #[deku(pad_bytes_after = "40")]
+    pub flags: u32,
+}
+
+#[wasm_bindgen(js_name = "HaloVehicleInstance")]
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct ScenarioVehicle {
+    pub vehicle_type: u16,
+    pub name_index: u16,
+    pub not_placed: u16,
+    pub desired_permutation: i16,
+    pub position: Point3D,
+    pub rotation: Euler3D,
+    #[deku(pad_bytes_before = "40")]
+    pub body_vitality: f32,
+    pub flags: u32,
+    pub multiplayer_team_index: i8,
+    #[deku(pad_bytes_before = "1", pad_bytes_after = "36")]
+    pub multiplayer_spawn_flags: u16,
+}
+
+#[wasm_bindgen(js_name = "HaloEquipmentInstance")]
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct ScenarioEquipment {
+    pub equipment_type: u16,
+    pub name_index: u16,
+    pub not_placed: u16,
+    pub desired_permutation: i16,
+    pub position: Point3D,
+    pub rotation: Euler3D,
+    #[deku(pad_bytes_before = "2", pad_bytes_after = "4")]
+    pub flags: u16,
+}
+
+#[wasm_bindgen(js_name = "HaloWeaponInstance")]
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct ScenarioWeapon {
+    pub weapon_type: u16,
+    pub name_index: u16,
+    pub not_placed: u16,
+    pub desired_permutation: i16,
+    pub position: Point3D,
+    pub rotation: Euler3D,
+    #[deku(pad_bytes_before = "40")]
+    pub rounds_left: i16,
+    pub rounds_loaded: i16,
+    #[deku(pad_bytes_after = "14")]
+    pub flags: u16,
+}
+
+#[wasm_bindgen(js_name = "HaloMachineInstance")]
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct ScenarioMachine {
+    pub machine_type: u16,
+    pub name_index: u16,
+    pub not_placed: u16,
+    pub desired_permutation: i16,
+    pub position: Point3D,
+    pub rotation: Euler3D,
+    #[deku(pad_bytes_before = "8")]
+    pub power_group: i16,
+    pub position_group: i16,
+    pub device_flags: u32,
+    #[deku(pad_bytes_after = "12")]
+    pub machine_flags: u32,
+}
+
+#[wasm_bindgen(js_name = "HaloControlInstance")]
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct ScenarioControl {
+    pub control_type: u16,
+    pub name_index: u16,
+    pub not_placed: u16,
+    pub desired_permutation: i16,
+    pub position: Point3D,
+    pub rotation: Euler3D,
+    #[deku(pad_bytes_before = "8")]
+    pub power_group: i16,
+    pub position_group: i16,
+    pub device_flags: u32,
+    pub control_flags: u16,
+    #[deku(pad_bytes_after = "12")]
+    pub dont_touch_this: i16,
+}
+
+#[wasm_bindgen(js_name = "HaloSoundSceneryInstance")]
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct ScenarioSoundScenery {
+    pub sound_scenery_type: u16,
+    pub name_index: u16,
+    pub not_placed: u16,
+    pub desired_permutation: i16,
+    pub position: Point3D,
+    #[deku(pad_bytes_after = "8")]
+    pub rotation: Euler3D,
+}
+
+#[derive(Debug, Clone, DekuRead, ToJson)]
 pub struct ObjectSwatch {
     #[deku(pad_bytes_after = "32")]
     pub obj: TagDependency,
 }
 
-#[derive(Debug, Clone, DekuRead)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
 pub struct Scenario {
     #[deku(pad_bytes_before = "48")]
     pub skies: Block<TagDependency>,
     #[deku(pad_bytes_before = "468")]
     pub scenery: Block<ScenarioScenery>,
     pub scenery_palette: Block<ObjectSwatch>,
-    #[deku(pad_bytes_before = "156")]
+    pub bipeds: Block<ScenarioBiped>,
+    pub biped_palette: Block<ObjectSwatch>,
+    pub vehicles: Block<ScenarioVehicle>,
+    pub vehicle_palette: Block<ObjectSwatch>,
+    pub equipment: Block<ScenarioEquipment>,
+    pub equipment_palette: Block<ObjectSwatch>,
+    pub weapons: Block<ScenarioWeapon>,
+    pub weapon_palette: Block<ObjectSwatch>,
+    #[deku(pad_bytes_before = "12")]
+    pub machines: Block<ScenarioMachine>,
+    pub machine_palette: Block<ObjectSwatch>,
+    pub controls: Block<ScenarioControl>,
+    pub control_palette: Block<ObjectSwatch>,
     pub light_fixtures: Block<ScenarioLightFixture>,
     pub light_fixture_palette: Block<ObjectSwatch>,
-    #[deku(pad_bytes_before = "204")]
+    pub sound_scenery: Block<ScenarioSoundScenery>,
+    pub sound_scenery_palette: Block<ObjectSwatch>,
+    #[deku(pad_bytes_before = "180")]
     pub decals: Block<ScenarioDecal>,
     pub decal_palette: Block<ObjectSwatch>,
     pub detail_object_collection_palette: Block<ObjectSwatch>,
@@ -65,7 +191,7 @@ pub struct Scenario {
     pub structure_bsp_references: Block<ScenarioStructureBSPReference>,
 }
 
-#[derive(Debug, Clone, DekuRead)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
 pub struct ScenarioStructureBSPReference {
     pub start: u32,
     pub size: u32,
@@ -74,7 +200,7 @@ pub struct ScenarioStructureBSPReference {
     pub structure_bsp: TagDependency,
 }
 
-#[derive(Debug, Clone, DekuRead)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
 pub struct BSPHeader {
     pub bsp_offset: u32,
     #[deku(pad_bytes_before = "4")]
@@ -86,7 +212,7 @@ pub struct BSPHeader {
 }
 
 #[wasm_bindgen(js_name = "HaloBSP")]
-#[derive(Debug, Clone, DekuRead)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
 pub struct BSP {
     pub lightmaps_bitmap: TagDependency,
     #[deku(pad_bytes_before = "28")]
@@ -100,16 +226,152 @@ pub struct BSP {
     pub default_reflection_tint: ColorARGB,
     pub default_shadow_vector: Vector3D,
     pub default_shadow_color: ColorRGB,
-    #[deku(pad_bytes_before = "88")]
+    #[deku(pad_bytes_before = "16")]
+    pub(crate) collision_bsp: Block<CollisionBSP>,
+    #[deku(pad_bytes_before = "12")]
+    pub world_bounds_x: Point2D,
+    pub world_bounds_y: Point2D,
+    pub world_bounds_z: Point2D,
+    pub(crate) leaves: Block<BSPLeaf>,
+    pub(crate) leaf_surfaces: Block<BSPLeafSurface>,
     pub(crate) surfaces: Block<Tri>,
-    #[deku(pad_bytes_after = "364")]
     pub(crate) lightmaps: Block<BSPLightmap>,
+    #[deku(pad_bytes_before = "12")]
+    pub(crate) lens_flares: Block<BSPLensFlare>,
+    pub(crate) lens_flare_markers: Block<BSPLensFlareMarker>,
+    pub(crate) clusters: Block<BSPCluster>,
+    #[deku(pad_bytes_before = "20", pad_bytes_after = "84")]
+    pub(crate) cluster_portals: Block<BSPClusterPortal>,
+    pub(crate) weather_palette: Block<BSPWeatherPaletteEntry>,
+    #[deku(pad_bytes_after = "128")]
+    pub(crate) weather_polyhedra: Block<BSPWeatherPolyhedron>,
+    #[deku(pad_bytes_after = "36")]
+    pub(crate) detail_objects: Block<BSPDetailObjectData>,
     #[deku(skip)]
     pub(crate) header: Option<BSPHeader>,
 }
 
+// The collision BSP shares its leaf indices with the structure BSP's leaves.
+// Node children and surface references with the high bit set refer to leaves
+// (or surfaces), and 0xFFFFFFFF marks a child outside of the level.
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct CollisionBSP {
+    pub(crate) bsp3d_nodes: Block<BSP3DNode>,
+    pub(crate) planes: Block<Plane3D>,
+    pub(crate) leaves: Block<CollisionLeaf>,
+    pub(crate) bsp2d_references: Block<BSP2DReference>,
+    pub(crate) bsp2d_nodes: Block<BSP2DNode>,
+    pub(crate) surfaces: Block<CollisionSurface>,
+    pub(crate) edges: Block<CollisionEdge>,
+    pub(crate) vertices: Block<CollisionVertex>,
+}
+
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct BSP3DNode {
+    pub plane: u32,
+    pub back_child: u32,
+    pub front_child: u32,
+}
+
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct CollisionLeaf {
+    pub flags: u16,
+    pub bsp2d_reference_count: u16,
+    pub first_bsp2d_reference: u32,
+}
+
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct BSP2DReference {
+    pub plane: u32,
+    pub bsp2d_node: u32,
+}
+
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct BSP2DNode {
+    pub plane_i: f32,
+    pub plane_j: f32,
+    pub plane_d: f32,
+    pub left_child: u32,
+    pub right_child: u32,
+}
+
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct CollisionSurface {
+    pub plane: u32,
+    pub first_edge: u32,
+    pub flags: u8,
+    pub breakable_surface: i8,
+    pub material: u16,
+}
+
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct CollisionEdge {
+    pub start_vertex: u32,
+    pub end_vertex: u32,
+    pub forward_edge: u32,
+    pub reverse_edge: u32,
+    pub left_surface: u32,
+    pub right_surface: u32,
+}
+
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct CollisionVertex {
+    pub point: Point3D,
+    pub first_edge: u32,
+}
+
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct BSPLeaf {
+    #[deku(pad_bytes_before = "8")]
+    pub cluster: i16,
+    pub surface_reference_count: u16,
+    pub first_surface_reference: u32,
+}
+
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct BSPLeafSurface {
+    pub surface: i32,
+    pub node: i32,
+}
+
+#[wasm_bindgen(js_name = "HaloBSPCluster")]
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct BSPCluster {
+    pub sky: i16,
+    pub fog: i16,
+    pub background_sound: i16,
+    pub sound_environment: i16,
+    pub weather: i16,
+    pub transition_structure_bsp: i16,
+    pub first_decal_index: i16,
+    pub decal_count: i16,
+    #[deku(pad_bytes_before = "48")]
+    pub first_lens_flare_marker_index: i16,
+    pub lens_flare_marker_count: i16,
+    #[deku(pad_bytes_before = "24")]
+    pub(crate) portals: Block<BSPClusterPortalIndex>,
+}
+
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct BSPClusterPortalIndex {
+    pub portal: i16,
+}
+
+#[wasm_bindgen(js_name = "HaloBSPClusterPortal")]
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct BSPClusterPortal {
+    pub front_cluster: i16,
+    pub back_cluster: i16,
+    pub plane: u32,
+    pub centroid: Point3D,
+    pub bounding_radius: f32,
+    #[deku(pad_bytes_after = "24")]
+    pub flags: u32,
+    pub(crate) vertices: Block<Point3D>,
+}
+
 #[wasm_bindgen(js_name = "HaloLightmap")]
-#[derive(Debug, Clone, DekuRead)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
 pub struct BSPLightmap {
     pub bitmap_index: u16,
     #[deku(pad_bytes_before = "18")]
@@ -117,7 +379,7 @@ pub struct BSPLightmap {
 }
 
 #[wasm_bindgen(js_name = "HaloMaterial")]
-#[derive(Debug, Clone, DekuRead)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
 pub struct BSPMaterial {
     pub(crate) shader: TagDependency,
     pub shader_permutation: u16,
@@ -159,7 +421,7 @@ impl BSPMaterial {
     }
 }
 
-#[derive(Debug, Clone, DekuRead)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
 pub struct RenderedVertex {
     pub position: Vector3D,
     pub normal: Vector3D,
@@ -169,7 +431,7 @@ pub struct RenderedVertex {
     pub v: f32,
 }
 
-#[derive(Debug, Clone, DekuRead)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
 pub struct LightmapVertex {
     pub normal: Vector3D,
     pub u: f32,
@@ -177,7 +439,7 @@ pub struct LightmapVertex {
 }
 
 #[wasm_bindgen]
-#[derive(Debug, Clone, Copy, PartialEq, DekuRead)]
+#[derive(Debug, Clone, Copy, PartialEq, DekuRead, ToJson)]
 #[deku(id_type = "u16")]
 #[repr(u16)]
 pub enum RenderedVerticesType {
@@ -189,7 +451,7 @@ pub enum RenderedVerticesType {
     ModelCompressed = 5,
 }
 
-#[derive(Debug, Clone, DekuRead)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
 pub struct ScenarioDecal {
     pub decal_type: u16,
     pub yaw: i8,
@@ -197,20 +459,22 @@ pub struct ScenarioDecal {
     pub position: Point3D,
 }
 
-#[derive(Debug, Clone, DekuRead)]
+#[wasm_bindgen(js_name = "HaloLightFixtureInstance")]
+#[derive(Debug, Clone, DekuRead, ToJson)]
 pub struct ScenarioLightFixture {
-    pub light_type: u16,
-    pub name: u16,
+    pub light_fixture_type: u16,
+    pub name_index: u16,
     pub not_placed: u16,
-    pub desired_permutation: u16,
+    pub desired_permutation: i16,
     pub position: Point3D,
     pub rotation: Euler3D,
-    pub bsp_indices: u16,
-    pub power_group: u16,
-    pub position_group: u16,
+    #[deku(pad_bytes_before = "8")]
+    pub power_group: i16,
+    pub position_group: i16,
     pub device_flags: u32,
     pub color: ColorRGB,
     pub intensity: f32,
     pub falloff_angle: f32,
+    #[deku(pad_bytes_after = "16")]
     pub cutoff_angle: f32,
 }
@@ -2,9 +2,10 @@ use deku::prelude::*;
 use wasm_bindgen::prelude::*;
 use super::tag::*;
 use super::common::*;
+use crate::halo::json::*;
 
 #[wasm_bindgen(js_name = "HaloShaderTransparencyChicago")]
-#[derive(Debug, Clone, DekuRead)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
 pub struct ShaderTransparentChicago {
     pub radiosity_flags: u16,
     pub radiosity_detail_level: RadiosityDetailLevel,
@@ -34,7 +35,7 @@ impl ShaderTransparentChicago {
 }
 
 #[wasm_bindgen]
-#[derive(Debug, Clone, Copy, DekuRead)]
+#[derive(Debug, Clone, Copy, DekuRead, ToJson)]
 #[deku(id_type = "u16")]
 #[repr(u16)]
 pub enum ShaderTransparentChicagoColorFunction {
@@ -54,7 +55,7 @@ pub enum ShaderTransparentChicagoColorFunction {
 }
 
 #[wasm_bindgen(js_name = "HaloShaderTransparentChicagoBitmap")]
-#[derive(Debug, Clone, DekuRead)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
 pub struct ShaderTransparentChicagoBitmap {
     pub flags: u16,
     #[deku(pad_bytes_before = "42")]
@@ -88,7 +89,7 @@ pub struct ShaderTransparentChicagoBitmap {
 }
 
 #[wasm_bindgen(js_name = "HaloShaderTransparencyGeneric")]
-#[derive(Debug, Clone, DekuRead)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
 pub struct ShaderTransparentGeneric {
     pub radiosity_flags: u16,
     pub radiosity_detail_level: RadiosityDetailLevel,
@@ -122,7 +123,7 @@ impl ShaderTransparentGeneric {
 }
 
 #[wasm_bindgen(js_name = "HaloShaderTransparentGenericMap")]
-#[derive(Debug, Clone, DekuRead)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
 pub struct ShaderTransparentGenericBitmap {
     pub flags: u16,
     #[deku(pad_bytes_before = "2")]
@@ -152,7 +153,7 @@ pub struct ShaderTransparentGenericBitmap {
 }
 
 #[wasm_bindgen(js_name = "HaloShaderTransparentGenericStage")]
-#[derive(Debug, Clone, DekuRead)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
 pub struct ShaderTransparentGenericStage {
     pub flags: u16,
     #[deku(pad_bytes_before = "2")]
@@ -191,7 +192,7 @@ pub struct ShaderTransparentGenericStage {
 }
 
 #[wasm_bindgen]
-#[derive(Debug, Copy, Clone, DekuRead)]
+#[derive(Debug, Copy, Clone, DekuRead, ToJson)]
 #[deku(id_type = "u16")]
 #[repr(u16)]
 pub enum ShaderOutputMapping {
@@ -204,7 +205,7 @@ pub enum ShaderOutputMapping {
 }
 
 #[wasm_bindgen]
-#[derive(Debug, Copy, Clone, DekuRead)]
+#[derive(Debug, Copy, Clone, DekuRead, ToJson)]
 #[deku(id_type = "u16")]
 #[repr(u16)]
 pub enum ShaderOutputFunction {
@@ -213,7 +214,7 @@ pub enum ShaderOutputFunction {
 }
 
 #[wasm_bindgen]
-#[derive(Debug, Copy, Clone, DekuRead)]
+#[derive(Debug, Copy, Clone, DekuRead, ToJson)]
 #[deku(id_type = "u16")]
 #[repr(u16)]
 pub enum ShaderOutput {
@@ -229,7 +230,7 @@ pub enum ShaderOutput {
 }
 
 #[wasm_bindgen]
-#[derive(Debug, Copy, Clone, DekuRead)]
+#[derive(Debug, Copy, Clone, DekuRead, ToJson)]
 #[deku(id_type = "u16")]
 #[repr(u16)]
 pub enum ShaderMapping {
@@ -244,7 +245,7 @@ pub enum ShaderMapping {
 }
 
 #[wasm_bindgen]
-#[derive(Debug, Copy, Clone, DekuRead)]
+#[derive(Debug, Copy, Clone, DekuRead, ToJson)]
 #[deku(id_type = "u16")]
 #[repr(u16)]
 pub enum ShaderInput {
@@ -276,7 +277,7 @@ pub enum ShaderInput {
 }
 
 #[wasm_bindgen]
-#[derive(Debug, Copy, Clone, DekuRead)]
+#[derive(Debug, Copy, Clone, DekuRead, ToJson)]
 #[deku(id_type = "u16")]
 #[repr(u16)]
 pub enum ShaderAlphaInput {
@@ -308,7 +309,7 @@ pub enum ShaderAlphaInput {
 }
 
 #[wasm_bindgen]
-#[derive(Debug, Copy, Clone, DekuRead)]
+#[derive(Debug, Copy, Clone, DekuRead, ToJson)]
 #[deku(id_type = "u16")]
 #[repr(u16)]
 pub enum FramebufferFadeMode {
@@ -318,7 +319,7 @@ pub enum FramebufferFadeMode {
 }
 
 #[wasm_bindgen]
-#[derive(Debug, Copy, Clone, DekuRead)]
+#[derive(Debug, Copy, Clone, DekuRead, ToJson)]
 #[deku(id_type = "u16")]
 #[repr(u16)]
 pub enum FramebufferBlendFunction {
@@ -333,7 +334,7 @@ pub enum FramebufferBlendFunction {
 }
 
 #[wasm_bindgen]
-#[derive(Debug, Copy, Clone, DekuRead)]
+#[derive(Debug, Copy, Clone, DekuRead, ToJson)]
 #[deku(id_type = "u16")]
 #[repr(u16)]
 pub enum ShaderTransparentGenericMapType {
@@ -344,7 +345,7 @@ pub enum ShaderTransparentGenericMapType {
 }
 
 #[wasm_bindgen]
-#[derive(Debug, Copy, Clone, DekuRead)]
+#[derive(Debug, Copy, Clone, DekuRead, ToJson)]
 #[deku(id_type = "u16")]
 #[repr(u16)]
 pub enum RadiosityDetailLevel {
@@ -355,7 +356,7 @@ pub enum RadiosityDetailLevel {
 }
 
 #[wasm_bindgen(js_name = "HaloShaderModel")]
-#[derive(Debug, Clone, DekuRead)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
 pub struct ShaderModel {
     pub radiosity_flags: u16,
     pub radiosity_detail_level: RadiosityDetailLevel,
@@ -412,7 +413,7 @@ pub struct ShaderModel {
 }
 
 #[wasm_bindgen]
-#[derive(Debug, Copy, Clone, DekuRead)]
+#[derive(Debug, Copy, Clone, DekuRead, ToJson)]
 #[deku(id_type = "u16")]
 #[repr(u16)]
 pub enum AnimationFunction {
@@ -431,7 +432,7 @@ pub enum AnimationFunction {
 }
 
 #[wasm_bindgen]
-#[derive(Debug, Copy, Clone, DekuRead)]
+#[derive(Debug, Copy, Clone, DekuRead, ToJson)]
 #[deku(id_type = "u16")]
 #[repr(u16)]
 pub enum DetailBitmapMask {
@@ -447,7 +448,7 @@ pub enum DetailBitmapMask {
 }
 
 #[wasm_bindgen]
-#[derive(Debug, Copy, Clone, DekuRead)]
+#[derive(Debug, Copy, Clone, DekuRead, ToJson)]
 #[deku(id_type = "u16")]
 #[repr(u16)]
 pub enum FunctionSource {
@@ -459,7 +460,7 @@ pub enum FunctionSource {
 }
 
 #[wasm_bindgen(js_name = "HaloShaderEnvironment")]
-#[derive(Debug, Clone, DekuRead)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
 pub struct ShaderEnvironment {
     pub radiosity_flags: u16,
     pub radiosity_detail_level: RadiosityDetailLevel,
@@ -510,7 +511,7 @@ pub struct ShaderEnvironment {
 }
 
 #[wasm_bindgen]
-#[derive(Copy, Clone, Debug, DekuRead)]
+#[derive(Copy, Clone, Debug, DekuRead, ToJson)]
 #[deku(id_type = "u16")]
 #[repr(u16)]
 pub enum ShaderEnvironmentReflectionType {
@@ -520,7 +521,7 @@ pub enum ShaderEnvironmentReflectionType {
 }
 
 #[wasm_bindgen]
-#[derive(Debug, Copy, Clone, DekuRead)]
+#[derive(Debug, Copy, Clone, DekuRead, ToJson)]
 #[deku(id_type = "u16")]
 #[repr(u16)]
 pub enum DetailBitmapFunction {
@@ -530,7 +531,7 @@ pub enum DetailBitmapFunction {
 }
 
 #[wasm_bindgen]
-#[derive(Debug, Copy, Clone, DekuRead)]
+#[derive(Debug, Copy, Clone, DekuRead, ToJson)]
 #[deku(id_type = "u16")]
 #[repr(u16)]
 pub enum ShaderEnvironmentType {
@@ -540,7 +541,7 @@ pub enum ShaderEnvironmentType {
 }
 
 #[wasm_bindgen(js_name = "HaloShaderTransparentWaterRipple")]
-#[derive(Debug, Clone, DekuRead)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
 pub struct ShaderTransparentWaterRipple {
     #[deku(pad_bytes_before = "4")]
     pub contribution_factor: f32,
@@ -555,7 +556,7 @@ pub struct ShaderTransparentWaterRipple {
 }
 
 #[wasm_bindgen(js_name = "HaloShaderTransparentWater")]
-#[derive(Debug, Clone, DekuRead)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
 pub struct ShaderTransparentWater {
     pub radiosity_flags: u16,
     pub radiosity_detail_level: RadiosityDetailLevel,
@@ -592,3 +593,251 @@ impl ShaderTransparentWater {
         self.ripples.items.as_ref().cloned().unwrap()
     }
 }
+
+// Like a chicago shader, but with separate map sets for hardware supporting
+// four texture stages and hardware only supporting two
+#[wasm_bindgen(js_name = "HaloShaderTransparentChicagoExtended")]
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct ShaderTransparentChicagoExtended {
+    pub radiosity_flags: u16,
+    pub radiosity_detail_level: RadiosityDetailLevel,
+    pub radiosity_light_power: f32,
+    pub radiosity_light_color: ColorRGB,
+    pub radiosity_tint_color: ColorRGB,
+    #[deku(pad_bytes_before = "8")]
+    pub numeric_counter_limit: u8,
+    pub flags: u8,
+    pub first_map_type: ShaderTransparentGenericMapType,
+    pub framebuffer_blend_function: FramebufferBlendFunction,
+    pub framebuffer_fade_mode: FramebufferFadeMode,
+    pub framebuffer_fade_source: FunctionSource,
+    #[deku(pad_bytes_before = "2")]
+    pub lens_flare_spacing: f32,
+    pub lens_flare: TagDependency,
+    pub(crate) extra_layers: Block<TagDependency>,
+    pub(crate) bitmaps_4_stage: Block<ShaderTransparentChicagoBitmap>, // max of 4
+    pub(crate) bitmaps_2_stage: Block<ShaderTransparentChicagoBitmap>, // max of 2
+    #[deku(pad_bytes_after = "8")]
+    pub extra_flags: u32,
+}
+
+#[wasm_bindgen(js_class = "HaloShaderTransparentChicagoExtended")]
+impl ShaderTransparentChicagoExtended {
+    // prefers the four stage maps, falling back to the two stage ones
+    pub fn get_bitmaps(&self) -> Vec<ShaderTransparentChicagoBitmap> {
+        match self.bitmaps_4_stage.items.as_ref() {
+            Some(bitmaps) if !bitmaps.is_empty() => bitmaps.clone(),
+            _ => self.get_bitmaps_2_stage(),
+        }
+    }
+
+    pub fn get_bitmaps_2_stage(&self) -> Vec<ShaderTransparentChicagoBitmap> {
+        self.bitmaps_2_stage.items.as_ref().cloned().unwrap()
+    }
+}
+
+#[wasm_bindgen(js_name = "HaloShaderTransparentMeter")]
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct ShaderTransparentMeter {
+    pub radiosity_flags: u16,
+    pub radiosity_detail_level: RadiosityDetailLevel,
+    pub radiosity_light_power: f32,
+    pub radiosity_light_color: ColorRGB,
+    pub radiosity_tint_color: ColorRGB,
+    #[deku(pad_bytes_before = "8", pad_bytes_after = "34")]
+    pub flags: u16,
+    #[deku(pad_bytes_after = "32")]
+    pub map: TagDependency,
+    pub gradient_min_color: ColorRGB,
+    pub gradient_max_color: ColorRGB,
+    pub background_color: ColorRGB,
+    pub flash_color: ColorRGB,
+    pub tint_color: ColorRGB,
+    pub meter_transparency: f32,
+    #[deku(pad_bytes_after = "24")]
+    pub background_transparency: f32,
+    pub meter_brightness_source: FunctionSource,
+    pub flash_brightness_source: FunctionSource,
+    pub value_source: FunctionSource,
+    pub gradient_source: FunctionSource,
+    #[deku(pad_bytes_after = "34")]
+    pub flash_extension_source: FunctionSource,
+}
+
+#[wasm_bindgen]
+#[derive(Debug, Copy, Clone, DekuRead, ToJson)]
+#[deku(id_type = "u16")]
+#[repr(u16)]
+pub enum ShaderGlassReflectionType {
+    BumpedCubeMap = 0,
+    FlatCubeMap = 1,
+    DynamicMirror = 2,
+}
+
+#[wasm_bindgen(js_name = "HaloShaderTransparentGlass")]
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct ShaderTransparentGlass {
+    pub radiosity_flags: u16,
+    pub radiosity_detail_level: RadiosityDetailLevel,
+    pub radiosity_light_power: f32,
+    pub radiosity_light_color: ColorRGB,
+    pub radiosity_tint_color: ColorRGB,
+    #[deku(pad_bytes_before = "8", pad_bytes_after = "42")]
+    pub flags: u16,
+    pub background_tint_color: ColorRGB,
+    pub background_tint_map_scale: f32,
+    #[deku(pad_bytes_after = "22")]
+    pub background_tint_map: TagDependency,
+    pub reflection_type: ShaderGlassReflectionType,
+    pub perpendicular_brightness: f32,
+    pub perpendicular_tint_color: ColorRGB,
+    pub parallel_brightness: f32,
+    pub parallel_tint_color: ColorRGB,
+    pub reflection_map: TagDependency,
+    pub bump_map_scale: f32,
+    #[deku(pad_bytes_after = "132")]
+    pub bump_map: TagDependency,
+    pub diffuse_map_scale: f32,
+    pub diffuse_map: TagDependency,
+    pub diffuse_detail_map_scale: f32,
+    #[deku(pad_bytes_after = "32")]
+    pub diffuse_detail_map: TagDependency,
+    pub specular_map_scale: f32,
+    pub specular_map: TagDependency,
+    pub specular_detail_map_scale: f32,
+    #[deku(pad_bytes_after = "28")]
+    pub specular_detail_map: TagDependency,
+}
+
+#[wasm_bindgen(js_name = "HaloShaderTransparentPlasma")]
+#[derive(Debug, Clone, DekuRead, ToJson)]
+pub struct ShaderTransparentPlasma {
+    pub radiosity_flags: u16,
+    pub radiosity_detail_level: RadiosityDetailLevel,
+    pub radiosity_light_power: f32,
+    pub radiosity_light_color: ColorRGB,
+    pub radiosity_tint_color: ColorRGB,
+    #[deku(pad_bytes_before = "12", pad_bytes_after = "2")]
+    pub intensity_source: FunctionSource,
+    pub intensity_exponent: f32,
+    #[deku(pad_bytes_after = "2")]
+    pub offset_source: FunctionSource,
+    pub offset_amount: f32,
+    #[deku(pad_bytes_after = "32")]
+    pub offset_exponent: f32,
+    pub perpendicular_brightness: f32,
+    pub perpendicular_tint_color: ColorRGB,
+    pub parallel_brightness: f32,
+    pub parallel_tint_color: ColorRGB,
+    #[deku(pad_bytes_after = "38")]
+    pub tint_color_source: FunctionSource,
+    pub primary_animation_period: f32,
+    pub primary_animation_direction: Vector3D,
+    pub primary_noise_map_scale: f32,
+    #[deku(pad_bytes_after = "36")]
+    pub primary_noise_map: TagDependency,
+    pub secondary_animation_period: f32,
+    pub secondary_animation_direction: Vector3D,
+    pub secondary_noise_map_scale: f32,
+    #[deku(pad_bytes_after = "56")]
+    pub secondary_noise_map: TagDependency,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put(data: &mut [u8], offset: usize, bytes: &[u8]) {
+        data[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    // parses a whole shader, making sure nothing's left over
+    fn parse<'a, T: DekuContainerRead<'a>>(data: &'a [u8]) -> T {
+        let ((rest, bit_offset), value) = T::from_bytes((data, 0)).unwrap();
+        assert!(rest.is_empty() && bit_offset == 0, "{} bytes left over", rest.len());
+        value
+    }
+
+    // every shader starts with the same radiosity properties
+    fn make_shader(size: usize) -> Vec<u8> {
+        let mut data = vec![0; size];
+        put(&mut data, 4, &0.5f32.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn test_chicago_extended() {
+        let mut data = make_shader(120);
+        put(&mut data, 40, &[4, 1]);
+        put(&mut data, 48, &(FunctionSource::B as u16).to_le_bytes());
+        put(&mut data, 56, &(TagClass::LensFlare as u32).to_le_bytes());
+        put(&mut data, 84, &2u32.to_le_bytes());
+        put(&mut data, 96, &1u32.to_le_bytes());
+        put(&mut data, 108, &3u32.to_le_bytes());
+        let shader: ShaderTransparentChicagoExtended = parse(&data);
+        assert_eq!(shader.radiosity_light_power, 0.5);
+        assert_eq!((shader.numeric_counter_limit, shader.flags), (4, 1));
+        assert!(matches!(shader.framebuffer_fade_source, FunctionSource::B));
+        assert_eq!(shader.lens_flare.tag_class, TagClass::LensFlare);
+        assert_eq!(shader.bitmaps_4_stage.count, 2);
+        assert_eq!(shader.bitmaps_2_stage.count, 1);
+        assert_eq!(shader.extra_flags, 3);
+    }
+
+    #[test]
+    fn test_meter() {
+        let mut data = make_shader(260);
+        put(&mut data, 40, &1u16.to_le_bytes());
+        put(&mut data, 76, &(TagClass::Bitmap as u32).to_le_bytes());
+        put(&mut data, 124, &[0.25f32, 0.5, 0.75].map(f32::to_le_bytes).concat());
+        put(&mut data, 188, &0.5f32.to_le_bytes());
+        put(&mut data, 224, &(FunctionSource::D as u16).to_le_bytes());
+        let shader: ShaderTransparentMeter = parse(&data);
+        assert_eq!(shader.radiosity_light_power, 0.5);
+        assert_eq!(shader.flags, 1);
+        assert_eq!(shader.map.tag_class, TagClass::Bitmap);
+        assert_eq!((shader.gradient_min_color.r, shader.gradient_min_color.b), (0.25, 0.75));
+        assert_eq!(shader.background_transparency, 0.5);
+        assert!(matches!(shader.flash_extension_source, FunctionSource::D));
+    }
+
+    #[test]
+    fn test_glass() {
+        let mut data = make_shader(480);
+        put(&mut data, 40, &2u16.to_le_bytes());
+        put(&mut data, 100, &(TagClass::Bitmap as u32).to_le_bytes());
+        put(&mut data, 138, &(ShaderGlassReflectionType::DynamicMirror as u16).to_le_bytes());
+        put(&mut data, 188, &2.0f32.to_le_bytes());
+        put(&mut data, 340, &3.0f32.to_le_bytes());
+        put(&mut data, 436, &(TagClass::Bitmap as u32).to_le_bytes());
+        let shader: ShaderTransparentGlass = parse(&data);
+        assert_eq!(shader.radiosity_light_power, 0.5);
+        assert_eq!(shader.flags, 2);
+        assert_eq!(shader.background_tint_map.tag_class, TagClass::Bitmap);
+        assert!(matches!(shader.reflection_type, ShaderGlassReflectionType::DynamicMirror));
+        assert_eq!(shader.bump_map_scale, 2.0);
+        assert_eq!(shader.diffuse_map_scale, 3.0);
+        assert_eq!(shader.specular_detail_map.tag_class, TagClass::Bitmap);
+    }
+
+    #[test]
+    fn test_plasma() {
+        let mut data = make_shader(332);
+        put(&mut data, 44, &(FunctionSource::A as u16).to_le_bytes());
+        put(&mut data, 60, &2.0f32.to_le_bytes());
+        put(&mut data, 128, &(FunctionSource::C as u16).to_le_bytes());
+        put(&mut data, 172, &[0.0f32, 1.0, 0.0].map(f32::to_le_bytes).concat());
+        put(&mut data, 188, &(TagClass::Bitmap as u32).to_le_bytes());
+        put(&mut data, 256, &4.0f32.to_le_bytes());
+        put(&mut data, 260, &(TagClass::Bitmap as u32).to_le_bytes());
+        let shader: ShaderTransparentPlasma = parse(&data);
+        assert_eq!(shader.radiosity_light_power, 0.5);
+        assert!(matches!(shader.intensity_source, FunctionSource::A));
+        assert_eq!(shader.offset_exponent, 2.0);
+        assert!(matches!(shader.tint_color_source, FunctionSource::C));
+        assert_eq!(shader.primary_animation_direction.j, 1.0);
+        assert_eq!(shader.primary_noise_map.tag_class, TagClass::Bitmap);
+        assert_eq!(shader.secondary_noise_map_scale, 4.0);
+        assert_eq!(shader.secondary_noise_map.tag_class, TagClass::Bitmap);
+    }
+}
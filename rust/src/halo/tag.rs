@@ -7,9 +7,15 @@ use crate::halo::scenario::*;
 use crate::halo::bitmap::*;
 use crate::halo::shader::*;
 use crate::halo::model::*;
+use crate::halo::object::*;
+use crate::halo::animation::*;
+use crate::halo::detail_object::*;
+use crate::halo::particle::*;
+use crate::halo::light::*;
+use crate::halo::json::*;
 
 #[wasm_bindgen(js_name = "HaloTagDependency")]
-#[derive(Debug, Clone, Copy, DekuRead)]
+#[derive(Debug, Clone, Copy, DekuRead, ToJson)]
 pub struct TagDependency {
     pub tag_class: TagClass,
     pub path_pointer: Pointer,
@@ -18,7 +24,7 @@ pub struct TagDependency {
 }
 
 #[wasm_bindgen(js_name = "HaloTagClass")]
-#[derive(Debug, Copy, Clone, PartialEq, Hash, Eq, DekuRead)]
+#[derive(Debug, Copy, Clone, PartialEq, Hash, Eq, DekuRead, ToJson)]
 #[deku(id_type = "u32")]
 #[repr(u32)]
 pub enum TagClass {
@@ -109,7 +115,8 @@ pub enum TagClass {
     WeaponHudInterface = 0x77706869,
 }
 
-#[derive(Debug, Clone, DekuRead)]
+#[wasm_bindgen(js_name = "HaloTagHeader", getter_with_clone)]
+#[derive(Debug, Clone, DekuRead, ToJson)]
 pub struct TagHeader {
     pub primary_class: TagClass,
     pub secondary_class: TagClass,
@@ -124,19 +131,42 @@ pub struct TagHeader {
     pub path: String, // read in after deserialization
 }
 
-#[derive(Debug, Clone)]
+impl TagHeader {
+    // object tags list their parent classes (e.g. vehicle -> unit -> object)
+    pub fn is_object(&self) -> bool {
+        [self.primary_class, self.secondary_class, self.tertiary_class].contains(&TagClass::Object)
+    }
+}
+
+#[derive(Debug, Clone, ToJson)]
 pub enum TagData {
-    Scenario(Scenario),
+    Scenario(Box<Scenario>),
     Bitmap(Bitmap),
-    BSP(BSP),
+    BSP(Box<BSP>),
     ShaderEnvironment(ShaderEnvironment),
     ShaderModel(ShaderModel),
     ShaderTransparentChicago(ShaderTransparentChicago),
     ShaderTransparentGeneric(ShaderTransparentGeneric),
     ShaderTransparentWater(ShaderTransparentWater),
+    ShaderTransparentChicagoExtended(ShaderTransparentChicagoExtended),
+    ShaderTransparentMeter(ShaderTransparentMeter),
+    ShaderTransparentGlass(ShaderTransparentGlass),
+    ShaderTransparentPlasma(ShaderTransparentPlasma),
     Scenery(Scenery),
     Sky(Sky),
     GbxModel(GbxModel),
+    Object(Object),
+    Unit(Unit),
+    Item(Item),
+    Device(Device),
+    ModelAnimations(ModelAnimations),
+    DetailObjectCollection(DetailObjectCollection),
+    WeatherParticleSystem(WeatherParticleSystem),
+    ParticleSystem(ParticleSystem),
+    Particle(Particle),
+    Light(Light),
+    LensFlare(LensFlare),
+    Glow(Glow),
 }
 
 impl<'a> TryFrom<&'a TagData> for &'a Scenario {
@@ -144,7 +174,7 @@ impl<'a> TryFrom<&'a TagData> for &'a Scenario {
 
     fn try_from(data: &'a TagData) -> std::result::Result<Self, Self::Error> {
         match data {
-            TagData::Scenario(x) => Ok(x),
+            TagData::Scenario(x) => Ok(x.as_ref()),
             t => Err(format!("invalid tag type: expected Scenario, got {:?}", t))
         }
     }
@@ -166,7 +196,7 @@ impl<'a> TryFrom<&'a TagData> for &'a BSP {
 
     fn try_from(data: &'a TagData) -> std::result::Result<Self, Self::Error> {
         match data {
-            TagData::BSP(x) => Ok(x),
+            TagData::BSP(x) => Ok(x.as_ref()),
             t => Err(format!("invalid tag type: expected BSP, got {:?}", t))
         }
     }
@@ -193,7 +223,7 @@ impl<'a> TryFrom<&'a TagData> for &'a Scenery {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, ToJson)]
 pub struct Tag {
     pub header: TagHeader,
     pub data: TagData,
@@ -6,6 +6,12 @@ use crate::halo::scenario::*;
 use crate::halo::bitmap::*;
 use crate::halo::tag::*;
 use crate::halo::model::*;
+use crate::halo::object::*;
+use crate::halo::animation::*;
+use crate::halo::detail_object::*;
+use crate::halo::particle::*;
+use crate::halo::light::*;
+use crate::halo::common::*;
 
 #[wasm_bindgen]
 pub struct HaloSceneManager {
@@ -19,6 +25,26 @@ impl HaloSceneManager {
         HaloSceneManager { mgr }
     }
 
+    pub fn get_map_version(&self) -> MapVersion {
+        self.mgr.header.version
+    }
+
+    pub fn add_resource_map(&mut self, data: Vec<u8>) -> Result<(), String> {
+        self.mgr.add_resource_map(data).map_err(|err| err.to_string())
+    }
+
+    pub fn get_tag_headers(&self) -> Vec<TagHeader> {
+        self.mgr.get_tag_headers().to_vec()
+    }
+
+    // returns None for tags we can't parse yet
+    pub fn get_tag_json(&mut self, tag_id: u32) -> Option<String> {
+        let header = self.mgr.get_tag_headers().iter()
+            .find(|header| header.tag_id == tag_id)?
+            .clone();
+        self.mgr.read_tag_json(&header).ok()
+    }
+
     fn get_shader(&mut self, shader_hdr: TagHeader) -> JsValue {
         match self.mgr.read_tag(&shader_hdr) {
             Ok(tag) => match tag.data {
@@ -29,6 +55,10 @@ impl HaloSceneManager {
                 TagData::ShaderTransparentGeneric(s) => JsValue::from(s),
                 TagData::ShaderTransparentChicago(s) => JsValue::from(s),
                 TagData::ShaderTransparentWater(s) => JsValue::from(s),
+                TagData::ShaderTransparentChicagoExtended(s) => JsValue::from(s),
+                TagData::ShaderTransparentMeter(s) => JsValue::from(s),
+                TagData::ShaderTransparentGlass(s) => JsValue::from(s),
+                TagData::ShaderTransparentPlasma(s) => JsValue::from(s),
                 _ => JsValue::NULL,
             },
             Err(_) => JsValue::NULL,
@@ -61,6 +91,21 @@ impl HaloSceneManager {
         result
     }
 
+    pub fn get_model_parts_for_permutation_name(&mut self, model: &GbxModel, permutation_name: &str, lod: ModelLOD) -> Vec<GbxModelPart> {
+        let geometry_indices = model.get_geometry_indices(lod, |permutations| {
+            permutations.iter().position(|permutation| permutation.name == permutation_name)
+        });
+        model.get_parts_for_geometries(&geometry_indices)
+    }
+
+    // e.g. for a placed object's desired permutation
+    pub fn get_model_parts_for_permutation_index(&mut self, model: &GbxModel, permutation_index: usize, lod: ModelLOD) -> Vec<GbxModelPart> {
+        let geometry_indices = model.get_geometry_indices(lod, |permutations| {
+            if permutation_index < permutations.len() { Some(permutation_index) } else { None }
+        });
+        model.get_parts_for_geometries(&geometry_indices)
+    }
+
     pub fn get_scenery_model(&mut self, scenery: &Scenery) -> Option<GbxModel> {
         self.resolve_model_dependency(&scenery.model)
     }
@@ -91,6 +136,95 @@ impl HaloSceneManager {
         scenario.scenery.items.as_ref().cloned().unwrap()
     }
 
+    fn get_scenario_data(&mut self) -> Scenario {
+        let TagData::Scenario(scenario) = self.mgr.get_scenario().unwrap().data else {
+            unreachable!();
+        };
+        *scenario
+    }
+
+    fn get_palette_dependencies(palette: &Block<ObjectSwatch>) -> Vec<TagDependency> {
+        palette.items.as_ref().unwrap().iter()
+            .map(|entry| entry.obj)
+            .collect()
+    }
+
+    pub fn get_biped_instances(&mut self) -> Vec<ScenarioBiped> {
+        self.get_scenario_data().bipeds.items.unwrap()
+    }
+
+    pub fn get_biped_palette(&mut self) -> Vec<TagDependency> {
+        Self::get_palette_dependencies(&self.get_scenario_data().biped_palette)
+    }
+
+    pub fn get_vehicle_instances(&mut self) -> Vec<ScenarioVehicle> {
+        self.get_scenario_data().vehicles.items.unwrap()
+    }
+
+    pub fn get_vehicle_palette(&mut self) -> Vec<TagDependency> {
+        Self::get_palette_dependencies(&self.get_scenario_data().vehicle_palette)
+    }
+
+    pub fn get_equipment_instances(&mut self) -> Vec<ScenarioEquipment> {
+        self.get_scenario_data().equipment.items.unwrap()
+    }
+
+    pub fn get_equipment_palette(&mut self) -> Vec<TagDependency> {
+        Self::get_palette_dependencies(&self.get_scenario_data().equipment_palette)
+    }
+
+    pub fn get_weapon_instances(&mut self) -> Vec<ScenarioWeapon> {
+        self.get_scenario_data().weapons.items.unwrap()
+    }
+
+    pub fn get_weapon_palette(&mut self) -> Vec<TagDependency> {
+        Self::get_palette_dependencies(&self.get_scenario_data().weapon_palette)
+    }
+
+    pub fn get_machine_instances(&mut self) -> Vec<ScenarioMachine> {
+        self.get_scenario_data().machines.items.unwrap()
+    }
+
+    pub fn get_machine_palette(&mut self) -> Vec<TagDependency> {
+        Self::get_palette_dependencies(&self.get_scenario_data().machine_palette)
+    }
+
+    pub fn get_control_instances(&mut self) -> Vec<ScenarioControl> {
+        self.get_scenario_data().controls.items.unwrap()
+    }
+
+    pub fn get_control_palette(&mut self) -> Vec<TagDependency> {
+        Self::get_palette_dependencies(&self.get_scenario_data().control_palette)
+    }
+
+    pub fn get_light_fixture_instances(&mut self) -> Vec<ScenarioLightFixture> {
+        self.get_scenario_data().light_fixtures.items.unwrap()
+    }
+
+    pub fn get_light_fixture_palette(&mut self) -> Vec<TagDependency> {
+        Self::get_palette_dependencies(&self.get_scenario_data().light_fixture_palette)
+    }
+
+    pub fn get_sound_scenery_instances(&mut self) -> Vec<ScenarioSoundScenery> {
+        self.get_scenario_data().sound_scenery.items.unwrap()
+    }
+
+    pub fn get_sound_scenery_palette(&mut self) -> Vec<TagDependency> {
+        Self::get_palette_dependencies(&self.get_scenario_data().sound_scenery_palette)
+    }
+
+    // Indexed by the collection indices of HaloBSP.get_detail_object_instances,
+    // with null entries for collections which fail to resolve
+    pub fn get_detail_object_collection_palette(&mut self) -> Array {
+        let scenario = self.get_scenario_data();
+        let result = Array::new();
+        for dependency in Self::get_palette_dependencies(&scenario.detail_object_collection_palette) {
+            let collection = self.resolve_detail_object_collection(&dependency);
+            result.push(&collection.map_or(JsValue::NULL, JsValue::from));
+        }
+        result
+    }
+
     pub fn get_skies(&mut self) -> Vec<Sky> {
         let mut result = Vec::new();
         let TagData::Scenario(scenario_data) = self.mgr.get_scenario().unwrap().data else {
@@ -112,7 +246,7 @@ impl HaloSceneManager {
         let scenario_tag = self.mgr.get_scenario().unwrap();
         self.mgr.get_scenario_bsps(&scenario_tag).unwrap().iter()
             .map(|tag| match &tag.data {
-                TagData::BSP(bsp) => bsp.clone(),
+                TagData::BSP(bsp) => bsp.as_ref().clone(),
                 _ => unreachable!(),
             }).collect()
     }
@@ -126,16 +260,18 @@ impl HaloSceneManager {
     }
 
     pub fn get_model_part_indices(&mut self, part: &GbxModelPart) -> Vec<u16> {
-        let offset = part.tri_offset + self.mgr.tag_index_header.model_data_file_offset + self.mgr.tag_index_header.vertex_data_size;
+        let (_, index_data_offset) = self.mgr.get_model_data_offsets().unwrap();
+        let offset = part.tri_offset as u64 + index_data_offset;
         let count = part.tri_count();
-        self.mgr.read_map_u16s(offset as u64, count as usize).unwrap()
+        self.mgr.read_map_u16s(offset, count as usize).unwrap()
     }
 
     pub fn get_model_part_vertices(&mut self, part: &GbxModelPart) -> Vec<u8> {
-        let offset = part.vert_offset + self.mgr.tag_index_header.model_data_file_offset;
+        let (vertex_data_offset, _) = self.mgr.get_model_data_offsets().unwrap();
+        let offset = part.vert_offset as u64 + vertex_data_offset;
         let count = part.vert_count;
         let item_size = 68;
-        self.mgr.read_map_bytes(offset as u64, item_size * count as usize).unwrap()
+        self.mgr.read_map_bytes(offset, item_size * count as usize).unwrap()
     }
 
     pub fn get_bsp_indices(&self, bsp: &BSP) -> Vec<u16> {
@@ -146,14 +282,117 @@ impl HaloSceneManager {
         indices
     }
 
+    // Accepts either a model dependency or an object dependency, in which
+    // case the object's model is resolved. Returns None for other tags, and for
+    // tags we can't parse yet
     pub fn resolve_model_dependency(&mut self, dependency: &TagDependency) -> Option<GbxModel> {
         let hdr = self.mgr.resolve_dependency(dependency)?;
-        match self.mgr.read_tag(&hdr).unwrap().data {
+        if hdr.is_object() {
+            let object = self.mgr.read_object(&hdr).ok()?;
+            return self.resolve_model_dependency(&object.model);
+        }
+        match self.mgr.read_tag(&hdr).ok()?.data {
             TagData::GbxModel(model) => Some(model),
-            _ => unreachable!(),
+            _ => None,
+        }
+    }
+
+    pub fn resolve_animation_dependency(&mut self, dependency: &TagDependency) -> Option<ModelAnimations> {
+        let hdr = self.mgr.resolve_dependency(dependency)?;
+        match self.mgr.read_tag(&hdr).ok()?.data {
+            TagData::ModelAnimations(animations) => Some(animations),
+            _ => None,
+        }
+    }
+
+    pub fn resolve_detail_object_collection(&mut self, dependency: &TagDependency) -> Option<DetailObjectCollection> {
+        let hdr = self.mgr.resolve_dependency(dependency)?;
+        match self.mgr.read_tag(&hdr).ok()?.data {
+            TagData::DetailObjectCollection(collection) => Some(collection),
+            _ => None,
+        }
+    }
+
+    pub fn resolve_weather_particle_system(&mut self, dependency: &TagDependency) -> Option<WeatherParticleSystem> {
+        let hdr = self.mgr.resolve_dependency(dependency)?;
+        match self.mgr.read_tag(&hdr).ok()?.data {
+            TagData::WeatherParticleSystem(weather) => Some(weather),
+            _ => None,
+        }
+    }
+
+    pub fn resolve_particle_system(&mut self, dependency: &TagDependency) -> Option<ParticleSystem> {
+        let hdr = self.mgr.resolve_dependency(dependency)?;
+        match self.mgr.read_tag(&hdr).ok()?.data {
+            TagData::ParticleSystem(system) => Some(system),
+            _ => None,
+        }
+    }
+
+    pub fn resolve_particle(&mut self, dependency: &TagDependency) -> Option<Particle> {
+        let hdr = self.mgr.resolve_dependency(dependency)?;
+        match self.mgr.read_tag(&hdr).ok()?.data {
+            TagData::Particle(particle) => Some(particle),
+            _ => None,
+        }
+    }
+
+    pub fn resolve_light(&mut self, dependency: &TagDependency) -> Option<Light> {
+        let hdr = self.mgr.resolve_dependency(dependency)?;
+        match self.mgr.read_tag(&hdr).ok()?.data {
+            TagData::Light(light) => Some(light),
+            _ => None,
+        }
+    }
+
+    pub fn resolve_lens_flare(&mut self, dependency: &TagDependency) -> Option<LensFlare> {
+        let hdr = self.mgr.resolve_dependency(dependency)?;
+        match self.mgr.read_tag(&hdr).ok()?.data {
+            TagData::LensFlare(lens_flare) => Some(lens_flare),
+            _ => None,
+        }
+    }
+
+    pub fn resolve_glow(&mut self, dependency: &TagDependency) -> Option<Glow> {
+        let hdr = self.mgr.resolve_dependency(dependency)?;
+        match self.mgr.read_tag(&hdr).ok()?.data {
+            TagData::Glow(glow) => Some(glow),
+            _ => None,
+        }
+    }
+
+    pub fn get_object_animations(&mut self, object: &Object) -> Option<ModelAnimations> {
+        self.resolve_animation_dependency(&object.animation_graph)
+    }
+
+    pub fn resolve_object_dependency(&mut self, dependency: &TagDependency) -> Option<Object> {
+        let hdr = self.mgr.resolve_dependency(dependency)?;
+        self.mgr.read_object(&hdr).ok()
+    }
+
+    // Returns the full object tag, i.e. a HaloUnit, HaloItem, HaloDevice,
+    // HaloScenery or HaloObject
+    pub fn resolve_object_tag(&mut self, dependency: &TagDependency) -> JsValue {
+        let Some(hdr) = self.mgr.resolve_dependency(dependency) else {
+            return JsValue::NULL;
+        };
+        match self.mgr.read_tag(&hdr) {
+            Ok(tag) => match tag.data {
+                TagData::Unit(u) => JsValue::from(u),
+                TagData::Item(i) => JsValue::from(i),
+                TagData::Device(d) => JsValue::from(d),
+                TagData::Scenery(s) => JsValue::from(s),
+                TagData::Object(o) => JsValue::from(o),
+                _ => JsValue::NULL,
+            },
+            Err(_) => JsValue::NULL,
         }
     }
 
+    pub fn get_object_model(&mut self, object: &Object) -> Option<GbxModel> {
+        self.resolve_model_dependency(&object.model)
+    }
+
     pub fn resolve_bitmap_dependency(&mut self, dependency: &TagDependency) -> Option<Bitmap> {
         let hdr = self.mgr.resolve_dependency(dependency)?;
         match self.mgr.read_tag(&hdr).unwrap().data {
@@ -162,9 +401,9 @@ impl HaloSceneManager {
         }
     }
 
-    pub fn get_and_convert_bitmap_data(&mut self, bitmap: &Bitmap, submap: usize) -> Vec<u8> {
+    pub fn get_and_convert_bitmap_data(&mut self, bitmap: &Bitmap, submap: usize, decode_dxt: bool) -> Result<Vec<u8>, String> {
         let bitmap_data = &bitmap.data.items.as_ref().unwrap()[submap];
-        get_and_convert_bitmap_data(&mut self.mgr.reader.data, bitmap_data)
+        self.mgr.get_and_convert_bitmap_data(bitmap_data, decode_dxt).map_err(|err| err.to_string())
     }
 
     pub fn get_material_vertex_data(&mut self, material: &BSPMaterial, bsp: &BSP) -> Vec<u8> {
@@ -0,0 +1,622 @@
+use js_sys::Float32Array;
+use wasm_bindgen::prelude::*;
+
+use crate::unity::types::binary::{AnimationCurve, Keyframe};
+use crate::unity::types::common::{Quaternion, Vec3};
+use crate::unity::types::wasm::AnimationClip;
+
+const TRANSFORM_CLASS_ID: i32 = 4;
+
+const ATTRIBUTE_POSITION: u32 = 1;
+const ATTRIBUTE_ROTATION: u32 = 2;
+const ATTRIBUTE_SCALE: u32 = 3;
+const ATTRIBUTE_EULER_ROTATION: u32 = 4;
+
+// Unity identifies transforms in animation bindings by the CRC32 of their
+// path relative to the animated root, e.g. "Armature/Hips/Spine"
+#[wasm_bindgen(js_name = "unity_path_hash")]
+pub fn path_hash(path: &str) -> u32 {
+    let mut crc = 0xFFFFFFFFu32;
+    for &byte in path.as_bytes() {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (!(crc & 1)).wrapping_add(1);
+            crc = (crc >> 1) ^ (0xEDB88320 & mask);
+        }
+    }
+    !crc
+}
+
+#[derive(Debug, Clone)]
+struct StreamedKey {
+    curve_index: usize,
+    time: f32,
+    coefficients: [f32; 4],
+}
+
+impl StreamedKey {
+    fn evaluate(&self, time: f32) -> f32 {
+        let [a, b, c, d] = self.coefficients;
+        // the first and last frames are at +/- infinity
+        if !self.time.is_finite() {
+            return d;
+        }
+        let t = time - self.time;
+        ((a * t + b) * t + c) * t + d
+    }
+}
+
+// The streamed clip is a flat list of u32s which is really a sequence of
+// frames: (time: f32, key_count: u32, keys: [(curve_index: u32, coefficients: [f32; 4])])
+fn parse_streamed_keys(data: &[u32]) -> Vec<StreamedKey> {
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i + 2 <= data.len() {
+        let time = f32::from_bits(data[i]);
+        let key_count = data[i + 1] as usize;
+        i += 2;
+        for _ in 0..key_count {
+            if i + 5 > data.len() {
+                return result;
+            }
+            result.push(StreamedKey {
+                curve_index: data[i] as usize,
+                time,
+                coefficients: [
+                    f32::from_bits(data[i + 1]),
+                    f32::from_bits(data[i + 2]),
+                    f32::from_bits(data[i + 3]),
+                    f32::from_bits(data[i + 4]),
+                ],
+            });
+            i += 5;
+        }
+    }
+    result
+}
+
+fn get_curve_dimension(type_id: i32, attribute: u32) -> usize {
+    if type_id != TRANSFORM_CLASS_ID {
+        return 1;
+    }
+    match attribute {
+        ATTRIBUTE_POSITION | ATTRIBUTE_SCALE | ATTRIBUTE_EULER_ROTATION => 3,
+        ATTRIBUTE_ROTATION => 4,
+        _ => 1,
+    }
+}
+
+// Unity applies euler rotations in Z, X, Y order
+fn euler_to_quat(degrees: Vec3) -> Quaternion {
+    let (sx, cx) = (degrees.x.to_radians() * 0.5).sin_cos();
+    let (sy, cy) = (degrees.y.to_radians() * 0.5).sin_cos();
+    let (sz, cz) = (degrees.z.to_radians() * 0.5).sin_cos();
+    Quaternion {
+        x: cy * sx * cz + sy * cx * sz,
+        y: sy * cx * cz - cy * sx * sz,
+        z: cy * cx * sz - sy * sx * cz,
+        w: cy * cx * cz + sy * sx * sz,
+    }
+}
+
+fn normalize_quat(q: Quaternion) -> Quaternion {
+    let len = (q.x * q.x + q.y * q.y + q.z * q.z + q.w * q.w).sqrt();
+    if len == 0.0 {
+        return Quaternion { x: 0.0, y: 0.0, z: 0.0, w: 1.0 };
+    }
+    Quaternion { x: q.x / len, y: q.y / len, z: q.z / len, w: q.w / len }
+}
+
+trait CurveValue: Copy {
+    fn hermite(v0: Self, m0: Self, v1: Self, m1: Self, dt: f32, t: f32) -> Self;
+    fn is_stepped(&self) -> bool;
+}
+
+fn hermite_f32(v0: f32, m0: f32, v1: f32, m1: f32, dt: f32, t: f32) -> f32 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    let h00 = 2.0 * t3 - 3.0 * t2 + 1.0;
+    let h10 = t3 - 2.0 * t2 + t;
+    let h01 = -2.0 * t3 + 3.0 * t2;
+    let h11 = t3 - t2;
+    h00 * v0 + h10 * m0 * dt + h01 * v1 + h11 * m1 * dt
+}
+
+impl CurveValue for f32 {
+    fn hermite(v0: Self, m0: Self, v1: Self, m1: Self, dt: f32, t: f32) -> Self {
+        hermite_f32(v0, m0, v1, m1, dt, t)
+    }
+
+    fn is_stepped(&self) -> bool {
+        !self.is_finite()
+    }
+}
+
+impl CurveValue for Vec3 {
+    fn hermite(v0: Self, m0: Self, v1: Self, m1: Self, dt: f32, t: f32) -> Self {
+        Vec3 {
+            x: hermite_f32(v0.x, m0.x, v1.x, m1.x, dt, t),
+            y: hermite_f32(v0.y, m0.y, v1.y, m1.y, dt, t),
+            z: hermite_f32(v0.z, m0.z, v1.z, m1.z, dt, t),
+        }
+    }
+
+    fn is_stepped(&self) -> bool {
+        !(self.x.is_finite() && self.y.is_finite() && self.z.is_finite())
+    }
+}
+
+impl CurveValue for Quaternion {
+    fn hermite(v0: Self, m0: Self, v1: Self, m1: Self, dt: f32, t: f32) -> Self {
+        normalize_quat(Quaternion {
+            x: hermite_f32(v0.x, m0.x, v1.x, m1.x, dt, t),
+            y: hermite_f32(v0.y, m0.y, v1.y, m1.y, dt, t),
+            z: hermite_f32(v0.z, m0.z, v1.z, m1.z, dt, t),
+            w: hermite_f32(v0.w, m0.w, v1.w, m1.w, dt, t),
+        })
+    }
+
+    fn is_stepped(&self) -> bool {
+        !(self.x.is_finite() && self.y.is_finite() && self.z.is_finite() && self.w.is_finite())
+    }
+}
+
+fn evaluate_curve<T>(curve: &AnimationCurve<T>, time: f32) -> Option<T>
+    where T: CurveValue + for<'a> deku::DekuReader<'a, deku::ctx::Endian>
+{
+    let keys: &Vec<Keyframe<T>> = &curve.curve.values;
+    let first = keys.first()?;
+    let last = keys.last()?;
+    if time <= first.time {
+        return Some(first.value);
+    }
+    if time >= last.time {
+        return Some(last.value);
+    }
+    let i = keys.iter().position(|key| key.time > time)?;
+    let (k0, k1) = (&keys[i - 1], &keys[i]);
+    if k0.out_slope.is_stepped() || k1.in_slope.is_stepped() {
+        return Some(k0.value);
+    }
+    let dt = k1.time - k0.time;
+    let t = (time - k0.time) / dt;
+    Some(T::hermite(k0.value, k0.out_slope, k1.value, k1.in_slope, dt, t))
+}
+
+#[derive(Debug, Clone, Copy)]
+enum BoneChannel {
+    Position,
+    Rotation,
+    Scale,
+    EulerRotation,
+}
+
+#[derive(Debug, Clone)]
+struct BoneBinding {
+    bone_index: usize,
+    channel: BoneChannel,
+    // for mecanim clips, the index of the first curve in the combined
+    // streamed/dense/constant curve list. for legacy clips, the index into
+    // the channel's keyframed curve list
+    curve_index: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BoneTransform {
+    pub translation: Vec3,
+    pub rotation: Quaternion,
+    pub scale: Vec3,
+}
+
+#[wasm_bindgen(js_name = "UnityAnimationClipSampler")]
+pub struct AnimationClipSampler {
+    clip: AnimationClip,
+    bone_path_hashes: Vec<u32>,
+    streamed_keys: Vec<StreamedKey>,
+    bindings: Vec<BoneBinding>,
+    legacy_bindings: Vec<BoneBinding>,
+    curve_values: Vec<f32>,
+}
+
+#[wasm_bindgen(js_class = "UnityAnimationClipSampler")]
+impl AnimationClipSampler {
+    // bone_path_hashes should be the path_hash() of each bone's transform
+    // path, in the same order as the TRS arrays passed to update_bones()
+    pub fn new(clip: &AnimationClip, bone_path_hashes: Vec<u32>) -> AnimationClipSampler {
+        let streamed_keys = parse_streamed_keys(&clip.clip.streamed_clip.data.values);
+
+        let mut bindings = Vec::new();
+        let mut curve_index = 0;
+        for binding in &clip.bindings {
+            let dimension = get_curve_dimension(binding.type_id, binding.attribute);
+            if binding.type_id == TRANSFORM_CLASS_ID {
+                let channel = match binding.attribute {
+                    ATTRIBUTE_POSITION => Some(BoneChannel::Position),
+                    ATTRIBUTE_ROTATION => Some(BoneChannel::Rotation),
+                    ATTRIBUTE_SCALE => Some(BoneChannel::Scale),
+                    ATTRIBUTE_EULER_ROTATION => Some(BoneChannel::EulerRotation),
+                    _ => None,
+                };
+                let bone_index = bone_path_hashes.iter().position(|hash| *hash == binding.path);
+                if let (Some(channel), Some(bone_index)) = (channel, bone_index) {
+                    bindings.push(BoneBinding { bone_index, channel, curve_index });
+                }
+            }
+            curve_index += dimension;
+        }
+
+        let mut legacy_bindings = Vec::new();
+        let mut add_legacy_bindings = |channel: BoneChannel, paths: Vec<String>| {
+            for (curve_index, path) in paths.iter().enumerate() {
+                let hash = path_hash(path);
+                if let Some(bone_index) = bone_path_hashes.iter().position(|h| *h == hash) {
+                    legacy_bindings.push(BoneBinding { bone_index, channel, curve_index });
+                }
+            }
+        };
+        add_legacy_bindings(BoneChannel::Position, clip.position_curves.iter().map(|c| c.path.clone().into()).collect());
+        add_legacy_bindings(BoneChannel::Scale, clip.scale_curves.iter().map(|c| c.path.clone().into()).collect());
+        add_legacy_bindings(BoneChannel::EulerRotation, clip.euler_curves.iter().map(|c| c.path.clone().into()).collect());
+        add_legacy_bindings(BoneChannel::Rotation, clip.rotation_curves.iter().map(|c| c.path.clone().into()).collect());
+
+        let curve_count = clip.clip.streamed_clip.curve_count as usize
+            + clip.clip.dense_clip.curve_count as usize
+            + clip.clip.constant_clip.data.values.len();
+        AnimationClipSampler {
+            clip: clip.clone(),
+            bone_path_hashes,
+            streamed_keys,
+            bindings,
+            legacy_bindings,
+            curve_values: vec![0.0; curve_count.max(curve_index)],
+        }
+    }
+
+    pub fn get_duration(&self) -> f32 {
+        self.clip.get_duration()
+    }
+
+    // Only animated channels are written, so the arrays should be filled with
+    // each bone's rest pose beforehand
+    pub fn update_bones(&mut self, time: f32, bone_translations: &Float32Array, bone_rotations: &Float32Array, bone_scalings: &Float32Array) {
+        let mut bones = Vec::with_capacity(self.bone_path_hashes.len());
+        for i in 0..self.bone_path_hashes.len() as u32 {
+            bones.push(BoneTransform {
+                translation: Vec3 {
+                    x: bone_translations.get_index(i * 3),
+                    y: bone_translations.get_index(i * 3 + 1),
+                    z: bone_translations.get_index(i * 3 + 2),
+                },
+                rotation: Quaternion {
+                    x: bone_rotations.get_index(i * 4),
+                    y: bone_rotations.get_index(i * 4 + 1),
+                    z: bone_rotations.get_index(i * 4 + 2),
+                    w: bone_rotations.get_index(i * 4 + 3),
+                },
+                scale: Vec3 {
+                    x: bone_scalings.get_index(i * 3),
+                    y: bone_scalings.get_index(i * 3 + 1),
+                    z: bone_scalings.get_index(i * 3 + 2),
+                },
+            });
+        }
+        self.sample_bones(time, &mut bones);
+        for (i, bone) in bones.iter().enumerate() {
+            let i = i as u32;
+            bone_translations.set_index(i * 3, bone.translation.x);
+            bone_translations.set_index(i * 3 + 1, bone.translation.y);
+            bone_translations.set_index(i * 3 + 2, bone.translation.z);
+            bone_rotations.set_index(i * 4, bone.rotation.x);
+            bone_rotations.set_index(i * 4 + 1, bone.rotation.y);
+            bone_rotations.set_index(i * 4 + 2, bone.rotation.z);
+            bone_rotations.set_index(i * 4 + 3, bone.rotation.w);
+            bone_scalings.set_index(i * 3, bone.scale.x);
+            bone_scalings.set_index(i * 3 + 1, bone.scale.y);
+            bone_scalings.set_index(i * 3 + 2, bone.scale.z);
+        }
+    }
+}
+
+// rust-only interface
+impl AnimationClipSampler {
+    fn wrap_time(&self, time: f32) -> f32 {
+        let start = self.clip.start_time;
+        let duration = self.clip.get_duration();
+        if duration <= 0.0 {
+            return start;
+        }
+        if self.clip.loop_time {
+            start + (time - start).rem_euclid(duration)
+        } else {
+            time.clamp(start, self.clip.stop_time)
+        }
+    }
+
+    fn sample_curves(&mut self, time: f32) {
+        let clip = &self.clip.clip;
+
+        // streamed keys are sorted by time, so the last key we see for each
+        // curve before passing the sample time is the active one
+        let streamed_count = clip.streamed_clip.curve_count as usize;
+        let mut active_keys: Vec<Option<&StreamedKey>> = vec![None; streamed_count];
+        for key in &self.streamed_keys {
+            if key.time > time {
+                break;
+            }
+            if let Some(active) = active_keys.get_mut(key.curve_index) {
+                *active = Some(key);
+            }
+        }
+        for (i, key) in active_keys.iter().enumerate() {
+            if let Some(key) = key {
+                self.curve_values[i] = key.evaluate(time);
+            }
+        }
+
+        let dense = &clip.dense_clip;
+        let dense_count = dense.curve_count as usize;
+        let samples = &dense.sample_array.values;
+        if dense.frame_count > 0 && dense_count > 0 {
+            let frame = ((time - dense.begin_time) * dense.sample_rate).max(0.0);
+            let frame0 = (frame.floor() as usize).min(dense.frame_count as usize - 1);
+            let frame1 = (frame0 + 1).min(dense.frame_count as usize - 1);
+            let t = (frame - frame0 as f32).min(1.0);
+            for i in 0..dense_count {
+                let v0 = samples.get(frame0 * dense_count + i).cloned().unwrap_or(0.0);
+                let v1 = samples.get(frame1 * dense_count + i).cloned().unwrap_or(0.0);
+                self.curve_values[streamed_count + i] = v0 + (v1 - v0) * t;
+            }
+        }
+
+        let constant_start = streamed_count + dense_count;
+        for (i, value) in clip.constant_clip.data.values.iter().enumerate() {
+            self.curve_values[constant_start + i] = *value;
+        }
+    }
+
+    pub fn sample_bones(&mut self, time: f32, bones: &mut [BoneTransform]) {
+        let time = self.wrap_time(time);
+        self.sample_curves(time);
+
+        let values = &self.curve_values;
+        let vec3_at = |i: usize| Vec3 { x: values[i], y: values[i + 1], z: values[i + 2] };
+        for binding in &self.bindings {
+            let bone = &mut bones[binding.bone_index];
+            let i = binding.curve_index;
+            match binding.channel {
+                BoneChannel::Position => bone.translation = vec3_at(i),
+                BoneChannel::Scale => bone.scale = vec3_at(i),
+                BoneChannel::EulerRotation => bone.rotation = euler_to_quat(vec3_at(i)),
+                BoneChannel::Rotation => bone.rotation = normalize_quat(Quaternion {
+                    x: values[i],
+                    y: values[i + 1],
+                    z: values[i + 2],
+                    w: values[i + 3],
+                }),
+            }
+        }
+
+        // legacy clips keep their curves in the editor-style keyframe format
+        for binding in &self.legacy_bindings {
+            let bone = &mut bones[binding.bone_index];
+            let i = binding.curve_index;
+            match binding.channel {
+                BoneChannel::Position => if let Some(v) = evaluate_curve(&self.clip.position_curves[i].curve, time) {
+                    bone.translation = v;
+                },
+                BoneChannel::Scale => if let Some(v) = evaluate_curve(&self.clip.scale_curves[i].curve, time) {
+                    bone.scale = v;
+                },
+                BoneChannel::EulerRotation => if let Some(v) = evaluate_curve(&self.clip.euler_curves[i].curve, time) {
+                    bone.rotation = euler_to_quat(v);
+                },
+                BoneChannel::Rotation => if let Some(v) = evaluate_curve(&self.clip.rotation_curves[i].curve, time) {
+                    bone.rotation = v;
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unity::types::binary;
+
+    #[test]
+    fn test_path_hash() {
+        // standard CRC32 check value
+        assert_eq!(path_hash("123456789"), 0xCBF43926);
+        assert_eq!(path_hash(""), 0);
+    }
+
+    #[test]
+    fn test_streamed_keys() {
+        let mut data = vec![f32::NEG_INFINITY.to_bits(), 1, 0];
+        data.extend([0.0f32, 0.0, 0.0, 1.0].map(f32::to_bits));
+        data.extend([0.0f32.to_bits(), 1, 0]);
+        data.extend([0.0f32, 0.0, 2.0, 1.0].map(f32::to_bits));
+        let keys = parse_streamed_keys(&data);
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[0].evaluate(-1.0), 1.0);
+        assert_eq!(keys[1].evaluate(0.5), 2.0);
+    }
+
+    fn push_f32s(data: &mut Vec<u8>, values: &[f32]) {
+        for v in values {
+            data.extend(v.to_le_bytes());
+        }
+    }
+
+    fn read_le<T>(data: Vec<u8>) -> T where T: for<'a> deku::DekuReader<'a, deku::ctx::Endian> {
+        let mut cursor = std::io::Cursor::new(data);
+        let mut reader = deku::reader::Reader::new(&mut cursor);
+        T::from_reader_with_ctx(&mut reader, deku::ctx::Endian::Little).unwrap()
+    }
+
+    fn make_binding(path: &str, attribute: u32) -> binary::GenericBinding {
+        let mut data = Vec::new();
+        for v in [path_hash(path), attribute, 0, 0, 0, TRANSFORM_CLASS_ID as u32, 0] {
+            data.extend(v.to_le_bytes());
+        }
+        read_le(data)
+    }
+
+    // keys are (time, value), with flat in tangents and the given out tangent
+    fn make_vector3_curve(path: &str, keys: &[(f32, [f32; 3])], out_slope: f32) -> binary::Vector3Curve {
+        let mut data = Vec::new();
+        data.extend((keys.len() as u32).to_le_bytes());
+        for (time, value) in keys {
+            push_f32s(&mut data, &[*time]);
+            push_f32s(&mut data, value);
+            push_f32s(&mut data, &[0.0; 3]);
+            push_f32s(&mut data, &[out_slope; 3]);
+            data.extend([0; 4]);
+            push_f32s(&mut data, &[0.0; 6]);
+        }
+        data.extend([0; 12]);
+        data.extend((path.len() as u32).to_le_bytes());
+        data.extend(path.as_bytes());
+        data.resize(data.len().next_multiple_of(4), 0);
+        read_le(data)
+    }
+
+    fn make_clip(clip: binary::Clip, bindings: Vec<binary::GenericBinding>) -> AnimationClip {
+        AnimationClip {
+            name: "test".to_string(),
+            legacy: false,
+            sample_rate: 30.0,
+            wrap_mode: 0,
+            start_time: 0.0,
+            stop_time: 1.0,
+            loop_time: false,
+            clip,
+            bindings,
+            rotation_curves: vec![],
+            euler_curves: vec![],
+            position_curves: vec![],
+            scale_curves: vec![],
+        }
+    }
+
+    fn rest_pose() -> BoneTransform {
+        BoneTransform {
+            translation: Vec3 { x: 0.0, y: 0.0, z: 0.0 },
+            rotation: Quaternion { x: 0.0, y: 0.0, z: 0.0, w: 1.0 },
+            scale: Vec3 { x: 1.0, y: 1.0, z: 1.0 },
+        }
+    }
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-5, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    fn translation(bone: &BoneTransform) -> [f32; 3] {
+        [bone.translation.x, bone.translation.y, bone.translation.z]
+    }
+
+    fn rotation(bone: &BoneTransform) -> [f32; 4] {
+        [bone.rotation.x, bone.rotation.y, bone.rotation.z, bone.rotation.w]
+    }
+
+    fn scale(bone: &BoneTransform) -> [f32; 3] {
+        [bone.scale.x, bone.scale.y, bone.scale.z]
+    }
+
+    // Root's position is streamed, its rotation dense and Arm's scale constant
+    fn make_mecanim_clip() -> AnimationClip {
+        let mut data = Vec::new();
+        let mut streamed = Vec::new();
+        let mut push_frame = |time: f32, keys: &[(u32, [f32; 4])]| {
+            streamed.extend([time.to_bits(), keys.len() as u32]);
+            for (curve_index, coefficients) in keys {
+                streamed.push(*curve_index);
+                streamed.extend(coefficients.map(f32::to_bits));
+            }
+        };
+        push_frame(f32::NEG_INFINITY, &[(0, [0.0, 0.0, 0.0, 0.0]), (1, [0.0, 0.0, 0.0, 5.0]), (2, [0.0; 4])]);
+        // x moves at 2 units per second until 0.5, then holds
+        push_frame(0.0, &[(0, [0.0, 0.0, 2.0, 0.0])]);
+        push_frame(0.5, &[(0, [0.0, 0.0, 0.0, 1.0])]);
+        data.extend((streamed.len() as u32).to_le_bytes());
+        for v in streamed {
+            data.extend(v.to_le_bytes());
+        }
+        data.extend(3u32.to_le_bytes());
+
+        // two frames a second apart, rotating 180 degrees around z
+        data.extend(2i32.to_le_bytes());
+        data.extend(4u32.to_le_bytes());
+        push_f32s(&mut data, &[1.0, 0.0]);
+        data.extend(8u32.to_le_bytes());
+        push_f32s(&mut data, &[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0]);
+
+        data.extend(3u32.to_le_bytes());
+        push_f32s(&mut data, &[2.0, 3.0, 4.0]);
+
+        let bindings = vec![
+            make_binding("Root", ATTRIBUTE_POSITION),
+            make_binding("Root", ATTRIBUTE_ROTATION),
+            make_binding("Root/Arm", ATTRIBUTE_SCALE),
+        ];
+        make_clip(read_le(data), bindings)
+    }
+
+    #[test]
+    fn test_sample_mecanim_clip() {
+        let clip = make_mecanim_clip();
+        let mut sampler = AnimationClipSampler::new(&clip, vec![path_hash("Root"), path_hash("Root/Arm")]);
+        assert_eq!(sampler.get_duration(), 1.0);
+
+        let mut bones = [rest_pose(); 2];
+        sampler.sample_bones(0.25, &mut bones);
+        assert_close(&translation(&bones[0]), &[0.5, 5.0, 0.0]);
+        let len = (0.25f32 * 0.25 + 0.75 * 0.75).sqrt();
+        assert_close(&rotation(&bones[0]), &[0.0, 0.0, 0.25 / len, 0.75 / len]);
+        assert_close(&scale(&bones[0]), &[1.0; 3]);
+        assert_close(&translation(&bones[1]), &[0.0; 3]);
+        assert_close(&scale(&bones[1]), &[2.0, 3.0, 4.0]);
+
+        // on the second streamed key, then past it
+        sampler.sample_bones(0.5, &mut bones);
+        assert_close(&translation(&bones[0]), &[1.0, 5.0, 0.0]);
+        assert_close(&rotation(&bones[0]), &[0.0, 0.0, 0.5f32.sqrt(), 0.5f32.sqrt()]);
+        sampler.sample_bones(0.75, &mut bones);
+        assert_close(&translation(&bones[0]), &[1.0, 5.0, 0.0]);
+
+        // clamped to the last frame, unless the clip loops
+        sampler.sample_bones(3.25, &mut bones);
+        assert_close(&rotation(&bones[0]), &[0.0, 0.0, 1.0, 0.0]);
+        let mut clip = clip;
+        clip.loop_time = true;
+        let mut sampler = AnimationClipSampler::new(&clip, vec![path_hash("Root"), path_hash("Root/Arm")]);
+        sampler.sample_bones(3.25, &mut bones);
+        assert_close(&translation(&bones[0]), &[0.5, 5.0, 0.0]);
+    }
+
+    #[test]
+    fn test_sample_legacy_clip() {
+        let empty: binary::Clip = read_le(vec![0; 32]);
+        let mut clip = make_clip(empty, vec![]);
+        clip.legacy = true;
+        clip.position_curves.push(make_vector3_curve("Root", &[(0.0, [0.0; 3]), (1.0, [1.0, 2.0, 3.0])], 0.0));
+        clip.scale_curves.push(make_vector3_curve("Root", &[(0.0, [1.0; 3]), (1.0, [3.0; 3])], f32::INFINITY));
+        let mut sampler = AnimationClipSampler::new(&clip, vec![path_hash("Other"), path_hash("Root")]);
+
+        let mut bones = [rest_pose(); 2];
+        // flat tangents put the midpoint halfway between the keys
+        sampler.sample_bones(0.5, &mut bones);
+        assert_close(&translation(&bones[1]), &[0.5, 1.0, 1.5]);
+        assert_close(&translation(&bones[0]), &[0.0; 3]);
+        // an infinite slope holds the previous key
+        assert_close(&scale(&bones[1]), &[1.0; 3]);
+        sampler.sample_bones(0.25, &mut bones);
+        assert_close(&translation(&bones[1]), &[0.15625, 0.3125, 0.46875]);
+        sampler.sample_bones(1.0, &mut bones);
+        assert_close(&translation(&bones[1]), &[1.0, 2.0, 3.0]);
+        assert_close(&scale(&bones[1]), &[3.0; 3]);
+    }
+}
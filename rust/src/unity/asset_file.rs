@@ -1,5 +1,6 @@
 use std::io::Cursor;
 
+use deku::ctx::Endian;
 use deku::reader::Reader;
 use deku::{DekuContainerRead, DekuReader};
 use wasm_bindgen::prelude::*;
@@ -7,6 +8,7 @@ use wasm_bindgen::prelude::*;
 use crate::unity::types::wasm::WasmFriendlyPPtr;
 use crate::unity::types::class_id::ClassID;
 use crate::unity::types::serialized_file::{SerializedFileHeader, SerializedFileMetadata};
+use crate::unity::type_tree::{read_type_tree_value, TypeTreeNode, TypeTreeObject};
 
 #[wasm_bindgen(js_name = "UnityAssetFile")]
 pub struct AssetFile {
@@ -21,7 +23,9 @@ impl AssetFile {
         match SerializedFileHeader::from_bytes((data, 0)) {
             Ok(((rest, _), header)) => {
                 let header_size = data.len() - rest.len();
-                assert_eq!(header.endianness, 0); // we're always expecting little endian files
+                if header.version < 9 && header.metadata_size as u32 > header.file_size {
+                    return Err(format!("bogus metadata size {} (file size {})", header.metadata_size, header.file_size));
+                }
                 Ok(Self {
                     header,
                     metadata_offset: header_size,
@@ -40,18 +44,43 @@ impl AssetFile {
     }
 
     pub fn append_metadata_chunk(&mut self, data: &[u8]) -> Result<(), String> {
+        let version = self.header.version;
+        if version < 9 {
+            // the endianness flag and metadata live at the end of the file, so data
+            // must be the full file here
+            let metadata_start = (self.header.file_size - self.header.metadata_size as u32) as usize;
+            let metadata = data.get(metadata_start..)
+                .filter(|metadata| !metadata.is_empty())
+                .ok_or(format!("metadata at {} is out of range", metadata_start))?;
+            self.header.endianness = metadata[0];
+            let mut cursor = Cursor::new(&metadata[1..]);
+            let mut reader = Reader::new(&mut cursor);
+            return match SerializedFileMetadata::from_reader_with_ctx(&mut reader, (version, self.header.get_endian())) {
+                Ok(metadata) => {
+                    self.metadata = Some(metadata);
+                    Ok(())
+                },
+                Err(err) => Err(format!("failed to parse metadata: {:?}", err)),
+            };
+        }
+
         // data will be the file from bytes 0..data_offset, so skip to where the metadata starts
         let mut cursor = Cursor::new(data);
         let mut reader = Reader::new(&mut cursor);
         let _header = SerializedFileHeader::from_reader_with_ctx(&mut reader, ())
             .map_err(|err| format!("failed to parse metadata file header: {:?}", err))?;
-        match SerializedFileMetadata::from_reader_with_ctx(&mut reader, self.header.version) {
+        match SerializedFileMetadata::from_reader_with_ctx(&mut reader, (version, self.header.get_endian())) {
             Ok(metadata) => self.metadata = Some(metadata),
             Err(err) => return Err(format!("failed to parse metadata: {:?}", err)),
         }
         Ok(())
     }
 
+    // Objects in big endian files must be read with create_with_endian()
+    pub fn is_big_endian(&self) -> bool {
+        self.header.get_endian() == Endian::Big
+    }
+
     pub fn get_version_string(&self) -> String {
         self.get_metadata().version_ascii.clone().into()
     }
@@ -65,11 +94,13 @@ impl AssetFile {
         let mut result = Vec::new();
         for obj in &metadata.objects {
             let byte_start = self.get_data_offset() as i64 + obj.get_byte_start();
-            let class_id = if obj.serialized_type_index >= 0 {
-                match metadata.type_tree.get(obj.serialized_type_index as usize) {
+            let class_id = if let Some(class_id) = obj.class_id {
+                ClassID::from_raw(class_id as i32)
+            } else if obj.serialized_type_index >= 0 {
+                match metadata.get_object_type(self.header.version, obj) {
                     Some(obj_type) => {
                         // println!("{}: got actual type {:?}", obj.file_id, obj_type.header.raw_type_id);
-                        obj_type.header.raw_type_id
+                        obj_type.header.raw_type_id.class_id()
                     },
                     None => {
                         println!("{}: bogus type: index {}, len {}", obj.file_id, obj.serialized_type_index, metadata.type_tree.len());
@@ -97,6 +128,33 @@ impl AssetFile {
             .get(idx)
             .map(|external_file| (&external_file.path_name_ascii).into())
     }
+
+    pub fn get_external_paths(&self) -> Vec<String> {
+        self.get_metadata().externals.values.iter()
+            .map(|external_file| (&external_file.path_name_ascii).into())
+            .collect()
+    }
+
+    // Reads any object using the type tree embedded in this file, for types
+    // without a hand-written binary reader (or whose layout has drifted)
+    pub fn read_type_tree_object(&self, file_id: i64, data: &[u8]) -> Result<TypeTreeObject, String> {
+        let metadata = self.get_metadata();
+        if metadata.enable_type_tree == 0 {
+            return Err("asset file has no embedded type trees".to_string());
+        }
+        let obj = metadata.objects.iter()
+            .find(|obj| obj.file_id == file_id)
+            .ok_or(format!("no object with file_id {}", file_id))?;
+        let serialized_type = metadata.get_object_type(self.header.version, obj)
+            .ok_or(format!("{}: bogus type index {}", file_id, obj.serialized_type_index))?;
+        let root = match (&serialized_type.header.old_type, &serialized_type.header.legacy_type) {
+            (Some(old_type), _) => TypeTreeNode::from_serialized_type(old_type)?,
+            (None, Some(legacy_type)) => TypeTreeNode::from_legacy(legacy_type),
+            (None, None) => return Err(format!("{}: missing type tree", file_id)),
+        };
+        let value = read_type_tree_value(&root, data, self.header.get_endian())?;
+        Ok(TypeTreeObject::new(root.type_name, value))
+    }
 }
 
 #[wasm_bindgen(js_name = "UnityAssetFileObject")]
@@ -172,4 +230,64 @@ mod tests {
             }
         }
     }
+
+    fn write_cstr(buf: &mut Vec<u8>, s: &str) {
+        buf.extend(s.as_bytes());
+        buf.push(0);
+    }
+
+    fn write_legacy_node(buf: &mut Vec<u8>, type_name: &str, name: &str, byte_size: i32, children: usize) {
+        write_cstr(buf, type_name);
+        write_cstr(buf, name);
+        buf.extend(byte_size.to_be_bytes());
+        buf.extend(0i32.to_be_bytes()); // index
+        buf.extend(0i32.to_be_bytes()); // type flags
+        buf.extend(1i32.to_be_bytes()); // version
+        buf.extend(0u32.to_be_bytes()); // meta flags
+        buf.extend((children as i32).to_be_bytes());
+    }
+
+    #[test]
+    fn test_big_endian_legacy() {
+        let mut metadata = Vec::new();
+        write_cstr(&mut metadata, "3.5.7f6");
+        metadata.extend(0u32.to_be_bytes()); // target platform
+        metadata.extend(1i32.to_be_bytes()); // type count
+        metadata.extend(1i32.to_be_bytes()); // GameObject
+        write_legacy_node(&mut metadata, "GameObject", "Base", 4, 1);
+        write_legacy_node(&mut metadata, "int", "m_Value", 4, 0);
+        metadata.extend(0i32.to_be_bytes()); // big IDs
+        metadata.extend(1i32.to_be_bytes()); // object count
+        metadata.extend(7i32.to_be_bytes()); // file ID
+        metadata.extend(0u32.to_be_bytes()); // byte start
+        metadata.extend(4i32.to_be_bytes()); // byte size
+        metadata.extend(1i32.to_be_bytes()); // type ID
+        metadata.extend(1u16.to_be_bytes()); // class ID
+        metadata.extend(0u16.to_be_bytes()); // is destroyed
+        metadata.extend(0i32.to_be_bytes()); // externals
+        write_cstr(&mut metadata, "");
+
+        let header_size = 20;
+        let data_offset = (header_size + metadata.len()) as u32;
+        let mut data = Vec::new();
+        data.extend((metadata.len() as i32).to_be_bytes());
+        data.extend((data_offset + 4).to_be_bytes());
+        data.extend(9i32.to_be_bytes());
+        data.extend(data_offset.to_be_bytes());
+        data.extend([1, 0, 0, 0]);
+        data.extend(&metadata);
+        data.extend(0x12345678i32.to_be_bytes());
+
+        let mut asset_file = AssetFile::initialize_with_header_chunk(&data).unwrap();
+        asset_file.append_metadata_chunk(&data).unwrap();
+        assert_eq!(asset_file.get_version_string(), "3.5.7f6");
+        let objects = asset_file.get_objects();
+        assert_eq!(objects.len(), 1);
+        assert_eq!(objects[0].file_id, 7);
+        assert_eq!(objects[0].class_id, ClassID::GameObject);
+        let start = objects[0].byte_start as usize;
+        let obj = asset_file.read_type_tree_object(7, &data[start..start + objects[0].byte_size]).unwrap();
+        assert_eq!(obj.get_type_name(), "GameObject");
+        assert_eq!(obj.get_number("m_Value"), Some(0x12345678 as f64));
+    }
 }
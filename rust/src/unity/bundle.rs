@@ -0,0 +1,350 @@
+use std::convert::TryFrom;
+
+use deku::prelude::*;
+use wasm_bindgen::prelude::*;
+
+use crate::unity::asset_file::AssetFile;
+use crate::unity::types::common::NullTerminatedAsciiString;
+
+// UnityFS asset bundles (.bundle/.unity3d), see
+// https://github.com/AssetRipper/AssetRipper/blob/master/Source/AssetRipper.IO.Files/BundleFiles/FileStream/
+
+const ARCHIVE_FLAGS_COMPRESSION_MASK: u32 = 0x3F;
+const ARCHIVE_FLAGS_BLOCKS_INFO_AT_END: u32 = 0x80;
+const ARCHIVE_FLAGS_BLOCK_INFO_NEEDS_PADDING_AT_START: u32 = 0x200;
+
+const NODE_FLAGS_SERIALIZED_FILE: u32 = 0x04;
+
+#[derive(DekuRead, Clone, Debug)]
+pub struct UnityFSHeader {
+    pub signature: NullTerminatedAsciiString,
+    #[deku(endian = "big")]
+    pub format_version: u32,
+    pub unity_version: NullTerminatedAsciiString,
+    pub unity_revision: NullTerminatedAsciiString,
+    #[deku(endian = "big")]
+    pub size: i64,
+    #[deku(endian = "big")]
+    pub compressed_blocks_info_size: u32,
+    #[deku(endian = "big")]
+    pub uncompressed_blocks_info_size: u32,
+    #[deku(endian = "big")]
+    pub flags: u32,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+pub struct BlocksInfo {
+    pub uncompressed_data_hash: [u8; 16],
+    #[deku(endian = "big")]
+    block_count: i32,
+    #[deku(count = "*block_count")]
+    pub blocks: Vec<StorageBlock>,
+    #[deku(endian = "big")]
+    node_count: i32,
+    #[deku(count = "*node_count")]
+    pub nodes: Vec<Node>,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "big")]
+pub struct StorageBlock {
+    pub uncompressed_size: u32,
+    pub compressed_size: u32,
+    pub flags: u16,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+pub struct Node {
+    #[deku(endian = "big")]
+    pub offset: i64,
+    #[deku(endian = "big")]
+    pub size: i64,
+    #[deku(endian = "big")]
+    pub flags: u32,
+    pub path: NullTerminatedAsciiString,
+}
+
+#[wasm_bindgen(js_name = "UnityCompressionType")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompressionType {
+    None = 0,
+    Lzma = 1,
+    Lz4 = 2,
+    Lz4HC = 3,
+}
+
+impl TryFrom<u32> for CompressionType {
+    type Error = String;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(CompressionType::None),
+            1 => Ok(CompressionType::Lzma),
+            2 => Ok(CompressionType::Lz4),
+            3 => Ok(CompressionType::Lz4HC),
+            _ => Err(format!("unsupported compression type {}", value)),
+        }
+    }
+}
+
+fn decompress(src: &[u8], compression_type: CompressionType, uncompressed_size: usize) -> Result<Vec<u8>, String> {
+    match compression_type {
+        CompressionType::None => Ok(src.to_vec()),
+        CompressionType::Lz4 | CompressionType::Lz4HC => {
+            let dst = lz4_flex::decompress(src, uncompressed_size)
+                .map_err(|err| format!("failed to decompress LZ4 block: {}", err))?;
+            if dst.len() != uncompressed_size {
+                return Err(format!("LZ4 block decompressed to {} bytes, expected {}", dst.len(), uncompressed_size));
+            }
+            Ok(dst)
+        },
+        CompressionType::Lzma => {
+            // 5 byte LZMA properties header, followed by the raw stream
+            if src.len() < 5 {
+                return Err(format!("LZMA block too small ({} bytes)", src.len()));
+            }
+            let mut props = src[0] as u32;
+            let lc = props % 9;
+            props /= 9;
+            let lp = props % 5;
+            let pb = props / 5;
+            if pb > 4 {
+                return Err(format!("invalid LZMA properties {:#x}", src[0]));
+            }
+            let dict_size = u32::from_le_bytes([src[1], src[2], src[3], src[4]]);
+            let properties = lzma_rs::decompress::raw::LzmaProperties { lc, lp, pb };
+            let params = lzma_rs::decompress::raw::LzmaParams::new(properties, dict_size, Some(uncompressed_size as u64));
+            let mut decoder = lzma_rs::decompress::raw::LzmaDecoder::new(params, None)
+                .map_err(|err| format!("failed to create LZMA decoder: {}", err))?;
+            let mut dst = Vec::new();
+            decoder.decompress(&mut &src[5..], &mut dst)
+                .map_err(|err| format!("failed to decompress LZMA block: {}", err))?;
+            Ok(dst)
+        },
+    }
+}
+
+fn align_to(offset: usize, alignment: usize) -> usize {
+    (offset + alignment - 1) & !(alignment - 1)
+}
+
+#[wasm_bindgen(js_name = "UnityBundle")]
+pub struct UnityBundle {
+    header: UnityFSHeader,
+    nodes: Vec<Node>,
+    data: Vec<u8>,
+}
+
+#[wasm_bindgen(js_class = "UnityBundle")]
+impl UnityBundle {
+    pub fn new(data: &[u8]) -> Result<UnityBundle, String> {
+        let ((rest, _), header) = UnityFSHeader::from_bytes((data, 0))
+            .map_err(|err| format!("failed to parse bundle header: {:?}", err))?;
+        let signature: String = (&header.signature).into();
+        if signature != "UnityFS" {
+            return Err(format!("unsupported bundle signature {}", signature));
+        }
+
+        let mut offset = data.len() - rest.len();
+        if header.format_version >= 7 {
+            offset = align_to(offset, 16);
+        }
+
+        let compressed_size = header.compressed_blocks_info_size as usize;
+        let blocks_info_start = if header.flags & ARCHIVE_FLAGS_BLOCKS_INFO_AT_END != 0 {
+            data.len().checked_sub(compressed_size)
+                .ok_or("blocks info out of range".to_string())?
+        } else {
+            offset
+        };
+        let blocks_info_data = blocks_info_start.checked_add(compressed_size)
+            .and_then(|end| data.get(blocks_info_start..end))
+            .ok_or("blocks info out of range".to_string())?;
+        let compression_type = CompressionType::try_from(header.flags & ARCHIVE_FLAGS_COMPRESSION_MASK)?;
+        let blocks_info_data = decompress(blocks_info_data, compression_type, header.uncompressed_blocks_info_size as usize)?;
+        let (_, blocks_info) = BlocksInfo::from_bytes((&blocks_info_data, 0))
+            .map_err(|err| format!("failed to parse blocks info: {:?}", err))?;
+
+        if header.flags & ARCHIVE_FLAGS_BLOCKS_INFO_AT_END == 0 {
+            offset += compressed_size;
+        }
+        if header.flags & ARCHIVE_FLAGS_BLOCK_INFO_NEEDS_PADDING_AT_START != 0 {
+            offset = align_to(offset, 16);
+        }
+
+        let total_size = blocks_info.blocks.iter()
+            .map(|block| block.uncompressed_size as usize)
+            .sum();
+        let mut block_data = Vec::with_capacity(total_size);
+        for block in &blocks_info.blocks {
+            let end = offset.checked_add(block.compressed_size as usize)
+                .ok_or("block out of range".to_string())?;
+            let src = data.get(offset..end)
+                .ok_or("block out of range".to_string())?;
+            let compression_type = CompressionType::try_from(block.flags as u32 & ARCHIVE_FLAGS_COMPRESSION_MASK)?;
+            block_data.extend(decompress(src, compression_type, block.uncompressed_size as usize)?);
+            offset = end;
+        }
+
+        Ok(UnityBundle {
+            header,
+            nodes: blocks_info.nodes,
+            data: block_data,
+        })
+    }
+
+    pub fn get_unity_revision(&self) -> String {
+        (&self.header.unity_revision).into()
+    }
+
+    pub fn get_nodes(&self) -> Vec<UnityBundleNode> {
+        self.nodes.iter()
+            .map(|node| UnityBundleNode {
+                path: (&node.path).into(),
+                size: node.size as usize,
+                is_serialized_file: node.flags & NODE_FLAGS_SERIALIZED_FILE != 0,
+            })
+            .collect()
+    }
+
+    pub fn get_node_data(&self, path: &str) -> Option<Vec<u8>> {
+        self.get_node_slice(path).map(|data| data.to_vec())
+    }
+
+    // For .resS resource blobs, returns the StreamingInfo-style range within the node
+    pub fn get_resource_data(&self, path: &str, offset: u64, size: usize) -> Option<Vec<u8>> {
+        let data = self.get_node_slice(path)?;
+        let start = usize::try_from(offset).ok()?;
+        data.get(start..start.checked_add(size)?).map(|data| data.to_vec())
+    }
+
+    pub fn get_asset_file(&self, path: &str) -> Result<AssetFile, String> {
+        let node = self.find_node(path)
+            .ok_or(format!("no node named {}", path))?;
+        if node.flags & NODE_FLAGS_SERIALIZED_FILE == 0 {
+            return Err(format!("node {} is not a SerializedFile", path));
+        }
+        let data = self.get_node_slice(path)
+            .ok_or(format!("node {} is out of range", path))?;
+        let mut asset_file = AssetFile::initialize_with_header_chunk(data)?;
+        asset_file.append_metadata_chunk(data)?;
+        Ok(asset_file)
+    }
+}
+
+impl UnityBundle {
+    fn find_node(&self, path: &str) -> Option<&Node> {
+        self.nodes.iter().find(|node| String::from(&node.path) == path)
+    }
+
+    pub fn get_node_slice(&self, path: &str) -> Option<&[u8]> {
+        let node = self.find_node(path)?;
+        let start = usize::try_from(node.offset).ok()?;
+        let end = start.checked_add(usize::try_from(node.size).ok()?)?;
+        self.data.get(start..end)
+    }
+}
+
+#[wasm_bindgen(js_name = "UnityBundleNode", getter_with_clone)]
+#[derive(Debug, Clone)]
+pub struct UnityBundleNode {
+    pub path: String,
+    pub size: usize,
+    pub is_serialized_file: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_cstr(buf: &mut Vec<u8>, s: &str) {
+        buf.extend(s.as_bytes());
+        buf.push(0);
+    }
+
+    fn build_blocks_info(node_data: &[u8]) -> Vec<u8> {
+        let mut blocks_info = vec![0; 16];
+        blocks_info.extend(1i32.to_be_bytes());
+        blocks_info.extend((node_data.len() as u32).to_be_bytes());
+        blocks_info.extend((node_data.len() as u32).to_be_bytes());
+        blocks_info.extend(0u16.to_be_bytes());
+        blocks_info.extend(1i32.to_be_bytes());
+        blocks_info.extend(0i64.to_be_bytes());
+        blocks_info.extend((node_data.len() as i64).to_be_bytes());
+        blocks_info.extend(0u32.to_be_bytes());
+        write_cstr(&mut blocks_info, "CAB-test.resS");
+        blocks_info
+    }
+
+    #[test]
+    fn test_uncompressed() {
+        let node_data = b"hello world.resS";
+        let blocks_info = build_blocks_info(node_data);
+
+        let mut data = Vec::new();
+        write_cstr(&mut data, "UnityFS");
+        data.extend(6u32.to_be_bytes());
+        write_cstr(&mut data, "5.x.x");
+        write_cstr(&mut data, "2019.4.39f1");
+        data.extend(0i64.to_be_bytes());
+        data.extend((blocks_info.len() as u32).to_be_bytes());
+        data.extend((blocks_info.len() as u32).to_be_bytes());
+        data.extend(0u32.to_be_bytes());
+        data.extend(&blocks_info);
+        data.extend(node_data);
+
+        let bundle = UnityBundle::new(&data).unwrap();
+        assert_eq!(bundle.get_unity_revision(), "2019.4.39f1");
+        let nodes = bundle.get_nodes();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].path, "CAB-test.resS");
+        assert!(!nodes[0].is_serialized_file);
+        assert_eq!(bundle.get_node_data("CAB-test.resS").unwrap(), node_data);
+        assert_eq!(bundle.get_resource_data("CAB-test.resS", 6, 5).unwrap(), b"world");
+        assert!(bundle.get_asset_file("CAB-test.resS").is_err());
+        assert!(bundle.get_resource_data("CAB-test.resS", u64::MAX, 2).is_none());
+        assert!(bundle.get_resource_data("CAB-test.resS", 6, usize::MAX).is_none());
+    }
+
+    #[test]
+    fn test_corrupt_blocks() {
+        let compressed = lz4_flex::compress(b"hello world.resS");
+        let mut truncated = compressed.clone();
+        truncated.truncate(compressed.len() - 4);
+        assert!(decompress(&truncated, CompressionType::Lz4, 16).is_err());
+        assert!(decompress(&compressed, CompressionType::Lz4, 32).is_err());
+        assert!(decompress(&[0xFF; 16], CompressionType::Lzma, 16).is_err());
+        assert!(decompress(&[0x5D, 0, 0, 1, 0, 0xFF, 0xFF, 0xFF], CompressionType::Lzma, 16).is_err());
+    }
+
+    #[test]
+    fn test_lz4_blocks_info_at_end() {
+        let node_data = b"hello world.resS";
+        let blocks_info = build_blocks_info(node_data);
+        let compressed = lz4_flex::compress(&blocks_info);
+
+        let mut data = Vec::new();
+        write_cstr(&mut data, "UnityFS");
+        data.extend(7u32.to_be_bytes());
+        write_cstr(&mut data, "5.x.x");
+        write_cstr(&mut data, "2021.3.5f1");
+        data.extend(0i64.to_be_bytes());
+        let compressed_size_offset = data.len();
+        data.extend((compressed.len() as u32).to_be_bytes());
+        data.extend((blocks_info.len() as u32).to_be_bytes());
+        data.extend((CompressionType::Lz4 as u32 | ARCHIVE_FLAGS_BLOCKS_INFO_AT_END).to_be_bytes());
+        // version 7 aligns the data after the header
+        data.resize(align_to(data.len(), 16), 0);
+        data.extend(node_data);
+        data.extend(&compressed);
+
+        let bundle = UnityBundle::new(&data).unwrap();
+        assert_eq!(bundle.get_unity_revision(), "2021.3.5f1");
+        assert_eq!(bundle.get_node_data("CAB-test.resS").unwrap(), node_data);
+
+        // a blocks info size larger than the whole file
+        let mut corrupt = data.clone();
+        corrupt[compressed_size_offset..compressed_size_offset + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(UnityBundle::new(&corrupt).is_err());
+    }
+}
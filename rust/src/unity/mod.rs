@@ -2,5 +2,11 @@
 
 mod version;
 mod asset_file;
+mod bundle;
+mod type_tree;
+mod animation;
+mod terrain;
+mod texture;
+mod scene_graph;
 pub mod types;
 mod util;
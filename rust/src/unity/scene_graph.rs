@@ -0,0 +1,392 @@
+use std::collections::{HashMap, HashSet};
+
+use nalgebra_glm::{quat, quat_to_mat4, scaling, translation, vec3, Mat4};
+use wasm_bindgen::prelude::*;
+
+use crate::unity::asset_file::AssetFile;
+use crate::unity::types::class_id::ClassID;
+use crate::unity::types::common::{UnityVersion, Vec4};
+use crate::unity::types::wasm::{GameObject, LightmapSettings, MeshFilter, MeshRenderer, SkinnedMeshRenderer, Transform, WasmFriendlyPPtr};
+
+// Guards against cyclic parent pointers in broken files
+const MAX_HIERARCHY_DEPTH: usize = 256;
+
+// An object within the graph. file_index is the order the file was added to
+// the graph in, not a PPtr file index.
+#[wasm_bindgen(js_name = "UnitySceneObjectRef")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SceneObjectRef {
+    pub file_index: usize,
+    pub path_id: i64,
+}
+
+struct SceneFile {
+    path: String,
+    asset_file: AssetFile,
+}
+
+struct Renderer {
+    game_object: WasmFriendlyPPtr,
+    enabled: bool,
+    mesh: Option<WasmFriendlyPPtr>,
+    materials: Vec<WasmFriendlyPPtr>,
+    lightmap_index: u16,
+    lightmap_scale_offset: Vec4,
+    is_skinned: bool,
+}
+
+#[wasm_bindgen(js_name = "UnityDrawItem", getter_with_clone)]
+#[derive(Debug, Clone)]
+pub struct DrawItem {
+    pub game_object: SceneObjectRef,
+    pub mesh: Option<SceneObjectRef>,
+    // unresolvable materials are kept (with a path_id of 0) so indices still
+    // line up with the mesh's submeshes
+    pub materials: Vec<SceneObjectRef>,
+    pub world_matrix: Vec<f32>,
+    pub is_skinned: bool,
+    pub lightmap_index: u16,
+    pub lightmap_scale_offset: Vec4,
+    pub lightmap: Option<SceneObjectRef>,
+    pub dir_lightmap: Option<SceneObjectRef>,
+    pub shadow_mask: Option<SceneObjectRef>,
+}
+
+#[wasm_bindgen(js_name = "UnitySceneGraph")]
+pub struct SceneGraph {
+    version: UnityVersion,
+    files: Vec<SceneFile>,
+    game_objects: HashMap<SceneObjectRef, GameObject>,
+    transforms: HashMap<SceneObjectRef, Transform>,
+    mesh_filters: HashMap<SceneObjectRef, MeshFilter>,
+    renderers: Vec<(usize, Renderer)>,
+    lightmap_settings: Vec<(usize, LightmapSettings)>,
+}
+
+#[wasm_bindgen(js_class = "UnitySceneGraph")]
+impl SceneGraph {
+    pub fn new(version: UnityVersion) -> SceneGraph {
+        SceneGraph {
+            version,
+            files: Vec::new(),
+            game_objects: HashMap::new(),
+            transforms: HashMap::new(),
+            mesh_filters: HashMap::new(),
+            renderers: Vec::new(),
+            lightmap_settings: Vec::new(),
+        }
+    }
+
+    // Adds a fully loaded file (metadata appended, data being the whole
+    // file), returning its file_index
+    pub fn add_file(&mut self, path: String, asset_file: AssetFile, data: &[u8]) -> usize {
+        let file_index = self.files.len();
+        let big_endian = asset_file.is_big_endian();
+        for obj in asset_file.get_objects() {
+            let start = obj.byte_start as usize;
+            let Some(obj_data) = data.get(start..start + obj.byte_size) else {
+                continue;
+            };
+            let key = SceneObjectRef { file_index, path_id: obj.file_id };
+            // objects which fail to parse are left out of the graph
+            let _ = match obj.class_id {
+                ClassID::GameObject => GameObject::create_with_endian(self.version, big_endian, obj_data)
+                    .map(|go| { self.game_objects.insert(key, go); }),
+                ClassID::Transform | ClassID::RectTransform => Transform::create_with_endian(self.version, big_endian, obj_data)
+                    .map(|transform| { self.transforms.insert(key, transform); }),
+                ClassID::MeshFilter => MeshFilter::create_with_endian(self.version, big_endian, obj_data)
+                    .map(|filter| { self.mesh_filters.insert(key, filter); }),
+                ClassID::MeshRenderer => MeshRenderer::create_with_endian(self.version, big_endian, obj_data)
+                    .map(|renderer| self.renderers.push((file_index, Renderer {
+                        game_object: renderer.game_object,
+                        enabled: renderer.enabled != 0,
+                        mesh: None,
+                        materials: renderer.materials,
+                        lightmap_index: renderer.lightmap_index,
+                        lightmap_scale_offset: renderer.lightmap_tiling_offset,
+                        is_skinned: false,
+                    }))),
+                ClassID::SkinnedMeshRenderer => SkinnedMeshRenderer::create_with_endian(self.version, big_endian, obj_data)
+                    .map(|renderer| self.renderers.push((file_index, Renderer {
+                        game_object: renderer.game_object,
+                        enabled: renderer.enabled != 0,
+                        mesh: Some(renderer.mesh),
+                        materials: renderer.materials,
+                        lightmap_index: renderer.lightmap_index,
+                        lightmap_scale_offset: renderer.lightmap_tiling_offset,
+                        is_skinned: true,
+                    }))),
+                ClassID::LightmapSettings => LightmapSettings::create_with_endian(self.version, big_endian, obj_data)
+                    .map(|settings| self.lightmap_settings.push((file_index, settings))),
+                _ => Ok(()),
+            };
+        }
+        self.files.push(SceneFile { path, asset_file });
+        file_index
+    }
+
+    pub fn get_file_path(&self, file_index: usize) -> Option<String> {
+        self.files.get(file_index).map(|file| file.path.clone())
+    }
+
+    // External files referenced by added files which haven't been added yet
+    pub fn get_unresolved_externals(&self) -> Vec<String> {
+        let mut result = HashSet::new();
+        for file in &self.files {
+            for external in file.asset_file.get_external_paths() {
+                if self.find_file(&external).is_none() {
+                    result.insert(external);
+                }
+            }
+        }
+        result.into_iter().collect()
+    }
+
+    pub fn get_roots(&self) -> Vec<SceneObjectRef> {
+        let mut result: Vec<SceneObjectRef> = self.transforms.iter()
+            .filter(|(_, transform)| transform.parent.path_id == 0)
+            .filter_map(|(&key, transform)| self.resolve(key.file_index, &transform.game_object))
+            .collect();
+        result.sort_by_key(|obj| (obj.file_index, obj.path_id));
+        result
+    }
+
+    pub fn get_children(&self, game_object: &SceneObjectRef) -> Vec<SceneObjectRef> {
+        let Some((key, transform)) = self.get_transform(game_object) else {
+            return Vec::new();
+        };
+        transform.children.iter()
+            .filter_map(|child| self.resolve(key.file_index, child))
+            .filter_map(|child| self.transforms.get(&child).map(|transform| (child, transform)))
+            .filter_map(|(child, transform)| self.resolve(child.file_index, &transform.game_object))
+            .collect()
+    }
+
+    pub fn get_name(&self, game_object: &SceneObjectRef) -> Option<String> {
+        self.game_objects.get(game_object).map(|go| go.name.clone())
+    }
+
+    pub fn get_world_matrix(&self, game_object: &SceneObjectRef) -> Option<Vec<f32>> {
+        let (key, _) = self.get_transform(game_object)?;
+        Some(self.compute_world_matrix(key, &mut HashMap::new()).as_slice().to_vec())
+    }
+
+    pub fn get_draw_list(&self) -> Vec<DrawItem> {
+        let mut world_matrices = HashMap::new();
+        let mut result = Vec::new();
+        for (file_index, renderer) in &self.renderers {
+            if !renderer.enabled {
+                continue;
+            }
+            let Some(game_object) = self.resolve(*file_index, &renderer.game_object) else {
+                continue;
+            };
+            if !self.is_active_in_hierarchy(&game_object) {
+                continue;
+            }
+            let Some((transform_key, _)) = self.get_transform(&game_object) else {
+                continue;
+            };
+
+            let mesh = match renderer.mesh {
+                Some(mesh) => self.resolve(*file_index, &mesh),
+                None => self.find_mesh_filter(&game_object)
+                    .and_then(|(key, filter)| self.resolve(key.file_index, &filter.mesh)),
+            };
+            let materials = renderer.materials.iter()
+                .map(|material| self.resolve(*file_index, material)
+                    .unwrap_or(SceneObjectRef { file_index: *file_index, path_id: 0 }))
+                .collect();
+            let world_matrix = self.compute_world_matrix(transform_key, &mut world_matrices);
+
+            let mut item = DrawItem {
+                game_object,
+                mesh,
+                materials,
+                world_matrix: world_matrix.as_slice().to_vec(),
+                is_skinned: renderer.is_skinned,
+                lightmap_index: renderer.lightmap_index,
+                lightmap_scale_offset: renderer.lightmap_scale_offset,
+                lightmap: None,
+                dir_lightmap: None,
+                shadow_mask: None,
+            };
+            if let Some((settings_file, settings)) = self.get_lightmap_settings(*file_index) {
+                if let Some(lightmap) = settings.get_lightmap(renderer.lightmap_index) {
+                    item.lightmap = self.resolve(settings_file, &lightmap.lightmap);
+                    item.dir_lightmap = self.resolve(settings_file, &lightmap.dir_lightmap);
+                    item.shadow_mask = self.resolve(settings_file, &lightmap.shadow_mask);
+                }
+            }
+            result.push(item);
+        }
+        result
+    }
+}
+
+// rust-only interface
+impl SceneGraph {
+    // External paths look like "archive:/CAB-xxx/CAB-xxx" or
+    // "Library/unity default resources", so match on the file name alone
+    fn find_file(&self, external_path: &str) -> Option<usize> {
+        let name = file_name(external_path);
+        self.files.iter().position(|file| file_name(&file.path).eq_ignore_ascii_case(name))
+    }
+
+    pub fn resolve(&self, file_index: usize, pptr: &WasmFriendlyPPtr) -> Option<SceneObjectRef> {
+        if pptr.path_id == 0 {
+            return None;
+        }
+        if pptr.file_index == 0 {
+            return Some(SceneObjectRef { file_index, path_id: pptr.path_id });
+        }
+        let external_path = self.files.get(file_index)?.asset_file.get_external_path(pptr)?;
+        let file_index = self.find_file(&external_path)?;
+        Some(SceneObjectRef { file_index, path_id: pptr.path_id })
+    }
+
+    fn get_transform(&self, game_object: &SceneObjectRef) -> Option<(SceneObjectRef, &Transform)> {
+        let go = self.game_objects.get(game_object)?;
+        go.components.iter()
+            .filter_map(|component| self.resolve(game_object.file_index, component))
+            .find_map(|key| self.transforms.get(&key).map(|transform| (key, transform)))
+    }
+
+    fn find_mesh_filter(&self, game_object: &SceneObjectRef) -> Option<(SceneObjectRef, &MeshFilter)> {
+        let go = self.game_objects.get(game_object)?;
+        go.components.iter()
+            .filter_map(|component| self.resolve(game_object.file_index, component))
+            .find_map(|key| self.mesh_filters.get(&key).map(|filter| (key, filter)))
+    }
+
+    fn is_active_in_hierarchy(&self, game_object: &SceneObjectRef) -> bool {
+        let mut current = *game_object;
+        for _ in 0..MAX_HIERARCHY_DEPTH {
+            match self.game_objects.get(&current) {
+                Some(go) if go.is_active == 0 => return false,
+                Some(_) => {},
+                None => return true,
+            }
+            let Some((key, transform)) = self.get_transform(&current) else {
+                return true;
+            };
+            let parent = self.resolve(key.file_index, &transform.parent)
+                .and_then(|parent| self.transforms.get(&parent).map(|transform| (parent, transform)))
+                .and_then(|(parent, transform)| self.resolve(parent.file_index, &transform.game_object));
+            match parent {
+                Some(parent) => current = parent,
+                None => return true,
+            }
+        }
+        true
+    }
+
+    fn compute_world_matrix(&self, transform_key: SceneObjectRef, cache: &mut HashMap<SceneObjectRef, Mat4>) -> Mat4 {
+        // walk up to the root (or the first cached ancestor), then compose back down
+        let mut chain = Vec::new();
+        let mut current = Some(transform_key);
+        let mut world = Mat4::identity();
+        while let Some(key) = current {
+            if let Some(cached) = cache.get(&key) {
+                world = *cached;
+                break;
+            }
+            let Some(transform) = self.transforms.get(&key) else {
+                break;
+            };
+            if chain.len() >= MAX_HIERARCHY_DEPTH {
+                break;
+            }
+            chain.push((key, transform));
+            current = self.resolve(key.file_index, &transform.parent);
+        }
+        for (key, transform) in chain.into_iter().rev() {
+            world *= get_local_matrix(transform);
+            cache.insert(key, world);
+        }
+        world
+    }
+
+    fn get_lightmap_settings(&self, file_index: usize) -> Option<(usize, &LightmapSettings)> {
+        self.lightmap_settings.iter()
+            .find(|(settings_file, _)| *settings_file == file_index)
+            .or(self.lightmap_settings.first())
+            .map(|(settings_file, settings)| (*settings_file, settings))
+    }
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit(['/', '\\']).next().unwrap_or(path)
+}
+
+fn get_local_matrix(transform: &Transform) -> Mat4 {
+    let p = transform.local_position;
+    let r = transform.local_rotation;
+    let s = transform.local_scale;
+    translation(&vec3(p.x, p.y, p.z)) * quat_to_mat4(&quat(r.x, r.y, r.z, r.w)) * scaling(&vec3(s.x, s.y, s.z))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unity::types::common::{Quaternion, Vec3};
+
+    fn pptr(path_id: i64) -> WasmFriendlyPPtr {
+        WasmFriendlyPPtr { file_index: 0, path_id }
+    }
+
+    fn add_object(graph: &mut SceneGraph, go_id: i64, transform_id: i64, parent_id: i64, position: Vec3, scale: f32) {
+        graph.game_objects.insert(SceneObjectRef { file_index: 0, path_id: go_id }, GameObject {
+            components: vec![pptr(transform_id)],
+            layer: 0,
+            name: format!("object {}", go_id),
+            tag: 0,
+            is_active: 1,
+        });
+        graph.transforms.insert(SceneObjectRef { file_index: 0, path_id: transform_id }, Transform {
+            game_object: pptr(go_id),
+            local_rotation: Quaternion { x: 0.0, y: 0.0, z: 0.0, w: 1.0 },
+            local_position: position,
+            local_scale: Vec3 { x: scale, y: scale, z: scale },
+            children: vec![],
+            parent: pptr(parent_id),
+        });
+    }
+
+    #[test]
+    fn test_world_matrix() {
+        let mut graph = SceneGraph::new(UnityVersion::V2019_4_39f1);
+        add_object(&mut graph, 1, 2, 0, Vec3 { x: 10.0, y: 0.0, z: 0.0 }, 2.0);
+        add_object(&mut graph, 3, 4, 2, Vec3 { x: 1.0, y: 2.0, z: 3.0 }, 1.0);
+        let parent = SceneObjectRef { file_index: 0, path_id: 1 };
+        let child = SceneObjectRef { file_index: 0, path_id: 3 };
+        assert_eq!(graph.get_roots(), vec![parent]);
+        let world = graph.get_world_matrix(&child).unwrap();
+        assert_eq!(&world[12..15], &[12.0, 4.0, 6.0]);
+        assert_eq!(world[0], 2.0);
+    }
+
+    #[test]
+    fn test_big_endian_transform() {
+        let mut data = Vec::new();
+        data.extend(0u32.to_be_bytes());
+        data.extend(7i64.to_be_bytes());
+        for v in [0.0f32, 0.0, 0.0, 1.0, 1.0, 2.0, 3.0, 1.0, 1.0, 1.0] {
+            data.extend(v.to_be_bytes());
+        }
+        data.extend(0i32.to_be_bytes());
+        data.extend(0u32.to_be_bytes());
+        data.extend(9i64.to_be_bytes());
+        let transform = Transform::create_with_endian(UnityVersion::V2019_4_39f1, true, &data).unwrap();
+        assert_eq!(transform.game_object.path_id, 7);
+        assert_eq!(transform.local_position.z, 3.0);
+        assert_eq!(transform.parent.path_id, 9);
+    }
+
+    #[test]
+    fn test_file_name() {
+        assert_eq!(file_name("archive:/CAB-1234/CAB-1234"), "CAB-1234");
+        assert_eq!(file_name("Library\\unity default resources"), "unity default resources");
+        assert_eq!(file_name("sharedassets0.assets"), "sharedassets0.assets");
+    }
+}
@@ -0,0 +1,316 @@
+use std::ops::Range;
+
+use nalgebra_glm::{normalize, vec3};
+use wasm_bindgen::prelude::*;
+
+use crate::unity::types::common::Vec3;
+use crate::unity::types::wasm::{TerrainData, WasmFriendlyPPtr};
+
+// Heights are stored normalized to [0, 32766]
+const MAX_HEIGHT: f32 = 32766.0;
+
+pub static TERRAIN_VBO_INFO: TerrainVBOInfo = TerrainVBOInfo {
+    stride:        (3 + 3 + 2) * 4,
+    vertex_offset: 0,
+    normal_offset: 3 * 4,
+    uv_offset:     (3 + 3) * 4,
+};
+
+#[wasm_bindgen(js_name = "UnityTerrainVBOInfo")]
+#[derive(Clone)]
+pub struct TerrainVBOInfo {
+    pub stride: usize,
+    pub vertex_offset: usize,
+    pub normal_offset: usize,
+    pub uv_offset: usize,
+}
+
+#[wasm_bindgen(js_name = "UnityTerrainRenderResult", getter_with_clone)]
+pub struct TerrainRenderResult {
+    pub vertex_buffer: Option<Vec<f32>>,
+    pub index_buffer: Option<Vec<u32>>,
+    pub layers: Vec<TerrainLayerDescriptor>,
+    pub size: Vec3,
+}
+
+#[wasm_bindgen(js_class = "UnityTerrainRenderResult")]
+impl TerrainRenderResult {
+    pub fn take_vertex_buffer(&mut self) -> Vec<f32> {
+        self.vertex_buffer.take().expect("Terrain RenderResult vertex buffer already taken")
+    }
+
+    pub fn take_index_buffer(&mut self) -> Vec<u32> {
+        self.index_buffer.take().expect("Terrain RenderResult index buffer already taken")
+    }
+}
+
+// Each alphamap texture packs the weights of four consecutive terrain layers
+// into its RGBA channels. The alphamaps are ordinary Texture2D objects, so
+// rather than decoding them here, JS resolves alpha_texture and loads it like
+// any other texture.
+#[wasm_bindgen(js_name = "UnityTerrainLayerDescriptor", getter_with_clone)]
+#[derive(Debug, Clone)]
+pub struct TerrainLayerDescriptor {
+    pub layer: WasmFriendlyPPtr,
+    pub alpha_texture: Option<WasmFriendlyPPtr>,
+    pub alpha_channel: usize,
+}
+
+#[wasm_bindgen(js_name = "UnityTerrainTreeInstance", getter_with_clone)]
+#[derive(Debug, Clone)]
+pub struct TerrainTreeInstance {
+    pub position: Vec3,
+    pub width_scale: f32,
+    pub height_scale: f32,
+    pub rotation: f32,
+    pub prototype_index: i32,
+}
+
+#[wasm_bindgen(js_class = "UnityTerrainData")]
+impl TerrainData {
+    pub fn get_vbo_info() -> TerrainVBOInfo {
+        TERRAIN_VBO_INFO.clone()
+    }
+
+    pub fn get_size(&self) -> Vec3 {
+        let quads = (self.heightmap_resolution - 1).max(0) as f32;
+        Vec3 {
+            x: self.heightmap_scale.x * quads,
+            y: self.heightmap_scale.y,
+            z: self.heightmap_scale.z * quads,
+        }
+    }
+
+    // Bilinearly samples the terrain height at a terrain-local position
+    pub fn sample_height(&self, x: f32, z: f32) -> Option<f32> {
+        let res = self.heightmap_resolution as usize;
+        if !self.has_valid_heightmap() {
+            return None;
+        }
+        let fx = (x / self.heightmap_scale.x).clamp(0.0, (res - 1) as f32);
+        let fz = (z / self.heightmap_scale.z).clamp(0.0, (res - 1) as f32);
+        let x0 = (fx.floor() as usize).min(res.saturating_sub(2));
+        let z0 = (fz.floor() as usize).min(res.saturating_sub(2));
+        let x1 = (x0 + 1).min(res - 1);
+        let z1 = (z0 + 1).min(res - 1);
+        let tx = fx - x0 as f32;
+        let tz = fz - z0 as f32;
+        let h0 = lerp(self.get_height(x0, z0), self.get_height(x1, z0), tx);
+        let h1 = lerp(self.get_height(x0, z1), self.get_height(x1, z1), tx);
+        Some(lerp(h0, h1, tz))
+    }
+
+    // Builds the heightmap mesh, skipping every 2^lod samples
+    pub fn get_render_result(&self, lod: u32) -> Result<TerrainRenderResult, String> {
+        let res = self.heightmap_resolution as usize;
+        if res < 2 || !self.has_valid_heightmap() {
+            return Err(format!("invalid heightmap ({} heights, resolution {})", self.heights.len(), res));
+        }
+        let step = 1 << lod.min(8);
+        let mut samples: Vec<usize> = (0..res).step_by(step).collect();
+        if *samples.last().unwrap() != res - 1 {
+            samples.push(res - 1);
+        }
+        let n = samples.len();
+
+        let mut vertex_buffer = Vec::with_capacity(n * n * TERRAIN_VBO_INFO.stride / 4);
+        for &z in &samples {
+            for &x in &samples {
+                vertex_buffer.push(x as f32 * self.heightmap_scale.x);
+                vertex_buffer.push(self.get_height(x, z));
+                vertex_buffer.push(z as f32 * self.heightmap_scale.z);
+
+                let normal = self.get_normal(x, z);
+                vertex_buffer.extend(normal);
+
+                vertex_buffer.push(x as f32 / (res - 1) as f32);
+                vertex_buffer.push(z as f32 / (res - 1) as f32);
+            }
+        }
+
+        let mut index_buffer = Vec::with_capacity((n - 1) * (n - 1) * 6);
+        for j in 0..n - 1 {
+            for i in 0..n - 1 {
+                if self.has_hole(samples[i]..samples[i + 1], samples[j]..samples[j + 1]) {
+                    continue;
+                }
+                let i0 = (j * n + i) as u32;
+                let i1 = i0 + 1;
+                let i2 = i0 + n as u32;
+                let i3 = i2 + 1;
+                index_buffer.extend([i0, i2, i1, i1, i2, i3]);
+            }
+        }
+
+        let layers = self.terrain_layers.iter().enumerate()
+            .map(|(i, &layer)| TerrainLayerDescriptor {
+                layer,
+                alpha_texture: self.alpha_textures.get(i / 4).cloned(),
+                alpha_channel: i % 4,
+            })
+            .collect();
+
+        Ok(TerrainRenderResult {
+            vertex_buffer: Some(vertex_buffer),
+            index_buffer: Some(index_buffer),
+            layers,
+            size: self.get_size(),
+        })
+    }
+
+    pub fn get_tree_instances(&self) -> Vec<TerrainTreeInstance> {
+        let size = self.get_size();
+        self.tree_instances.iter()
+            .map(|tree| TerrainTreeInstance {
+                position: Vec3 {
+                    x: tree.position.x * size.x,
+                    y: tree.position.y * size.y,
+                    z: tree.position.z * size.z,
+                },
+                width_scale: tree.width_scale,
+                height_scale: tree.height_scale,
+                rotation: tree.rotation,
+                prototype_index: tree.index,
+            })
+            .collect()
+    }
+
+    // Returns a square grid of per-sample object counts for the given detail
+    // prototype, (patch_count * patch_samples) samples on a side
+    pub fn get_detail_layer_density(&self, layer: u8) -> Vec<u8> {
+        let patch_count = self.detail_patch_count;
+        let samples = self.detail_patch_samples;
+        let width = patch_count * samples;
+        let mut result = vec![0; width * width];
+        for (patch_index, patch) in self.detail_patches.iter().enumerate().take(patch_count * patch_count) {
+            let px = patch_index % patch_count;
+            let py = patch_index / patch_count;
+            let Some(slot) = patch.layer_indices.values.iter().position(|&l| l == layer) else {
+                continue;
+            };
+            let counts = &patch.number_of_objects.values;
+            for sy in 0..samples {
+                for sx in 0..samples {
+                    let src = slot * samples * samples + sy * samples + sx;
+                    let dst = (py * samples + sy) * width + px * samples + sx;
+                    result[dst] = counts.get(src).cloned().unwrap_or(0);
+                }
+            }
+        }
+        result
+    }
+}
+
+// rust-only interface
+impl TerrainData {
+    // get_height() assumes every sample is present
+    fn has_valid_heightmap(&self) -> bool {
+        let res = self.heightmap_resolution.max(0) as usize;
+        res > 0 && self.heights.len() >= res * res
+    }
+
+    fn get_height(&self, x: usize, z: usize) -> f32 {
+        let res = self.heightmap_resolution as usize;
+        self.heights[z * res + x] as f32 / MAX_HEIGHT * self.heightmap_scale.y
+    }
+
+    fn get_normal(&self, x: usize, z: usize) -> [f32; 3] {
+        let res = self.heightmap_resolution as usize;
+        let x0 = x.saturating_sub(1);
+        let x1 = (x + 1).min(res - 1);
+        let z0 = z.saturating_sub(1);
+        let z1 = (z + 1).min(res - 1);
+        let dx = (self.get_height(x1, z) - self.get_height(x0, z)) / ((x1 - x0) as f32 * self.heightmap_scale.x);
+        let dz = (self.get_height(x, z1) - self.get_height(x, z0)) / ((z1 - z0) as f32 * self.heightmap_scale.z);
+        let normal = normalize(&vec3(-dx, 1.0, -dz));
+        [normal.x, normal.y, normal.z]
+    }
+
+    // The holes mask has one entry per quad, where 0 marks a hole. At lower
+    // LODs each mesh quad covers a block of source quads, any of which can
+    // punch a hole in it.
+    fn has_hole(&self, x: Range<usize>, z: Range<usize>) -> bool {
+        let quads = self.heightmap_resolution as usize - 1;
+        if self.holes.len() != quads * quads {
+            return false;
+        }
+        z.into_iter().any(|z| self.holes[z * quads + x.start..z * quads + x.end].contains(&0))
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_terrain(heights: Vec<i16>, holes: Vec<u8>) -> TerrainData {
+        TerrainData {
+            name: "test".to_string(),
+            terrain_layers: vec![WasmFriendlyPPtr { file_index: 0, path_id: 1 }; 5],
+            alpha_textures: vec![
+                WasmFriendlyPPtr { file_index: 0, path_id: 2 },
+                WasmFriendlyPPtr { file_index: 0, path_id: 3 },
+            ],
+            alphamap_resolution: 16,
+            base_map_resolution: 16,
+            detail_prototypes: vec![],
+            tree_prototypes: vec![],
+            heightmap_resolution: 3,
+            heightmap_scale: Vec3 { x: 10.0, y: 100.0, z: 10.0 },
+            heights,
+            holes,
+            detail_patches: vec![],
+            detail_patch_count: 0,
+            detail_patch_samples: 0,
+            tree_instances: vec![],
+        }
+    }
+
+    #[test]
+    fn test_render_result() {
+        let mut heights = vec![0; 9];
+        heights[4] = MAX_HEIGHT as i16;
+        let terrain = make_terrain(heights, vec![255, 255, 255, 0]);
+        let mut result = terrain.get_render_result(0).unwrap();
+        let vertices = result.take_vertex_buffer();
+        let indices = result.take_index_buffer();
+        let stride = TERRAIN_VBO_INFO.stride / 4;
+        assert_eq!(vertices.len(), 9 * stride);
+        assert_eq!(&vertices[4 * stride..4 * stride + 3], &[10.0, 100.0, 10.0]);
+        assert_eq!(indices.len(), 3 * 6);
+        assert_eq!(result.layers[4].alpha_texture.unwrap().path_id, 3);
+        assert_eq!(result.layers[4].alpha_channel, 0);
+        assert_eq!(terrain.sample_height(5.0, 10.0), Some(50.0));
+
+        let result = terrain.get_render_result(1).unwrap();
+        assert_eq!(result.vertex_buffer.unwrap().len(), 4 * stride);
+    }
+
+    #[test]
+    fn test_lod_holes() {
+        // a 5x5 heightmap has 4x4 quads, which LOD 1 draws as 2x2 quads
+        let mut terrain = make_terrain(vec![0; 25], vec![255; 16]);
+        terrain.heightmap_resolution = 5;
+        let mut result = terrain.get_render_result(1).unwrap();
+        assert_eq!(result.take_index_buffer().len(), 4 * 6);
+
+        // a hole in the bottom-right source quad of the top-right block
+        terrain.holes[4 + 3] = 0;
+        let mut result = terrain.get_render_result(1).unwrap();
+        let indices = result.take_index_buffer();
+        assert_eq!(indices.len(), 3 * 6);
+        // only the top-right quad uses the top-right corner
+        assert!(!indices.contains(&2));
+        assert_eq!(terrain.get_render_result(0).unwrap().take_index_buffer().len(), 15 * 6);
+    }
+
+    #[test]
+    fn test_short_heightmap() {
+        let terrain = make_terrain(vec![0; 4], vec![]);
+        assert_eq!(terrain.sample_height(5.0, 5.0), None);
+        assert!(terrain.get_render_result(0).is_err());
+    }
+}
@@ -0,0 +1,349 @@
+use texture2ddecoder::*;
+use wasm_bindgen::prelude::*;
+
+use crate::unity::types::wasm::{Texture2D, TextureFormat};
+
+// Software decoding for every Texture2D format, so the TS side only ever has
+// to upload RGBA8 (or RGBA32F for HDR formats)
+// https://github.com/Perfare/AssetStudio/blob/master/AssetStudio/Texture2DConverter.cs
+
+type BlockDecoder = fn(&[u8], usize, usize, &mut [u32]) -> Result<(), &'static str>;
+
+enum Decoder {
+    // bytes per pixel, and how to expand a single pixel to RGBA8
+    Pixel(usize, fn(&[u8]) -> [u8; 4]),
+    // bytes per pixel, and how to expand a single pixel to RGBA32F
+    PixelFloat(usize, fn(&[u8]) -> [f32; 4]),
+    // block width, block height, bytes per block
+    Block(usize, usize, usize, BlockDecoder),
+    Astc(usize),
+    Pvrtc(bool),
+    Yuy2,
+    Crunched(BlockDecoder),
+}
+
+fn get_decoder(format: TextureFormat) -> Result<Decoder, String> {
+    use TextureFormat::*;
+    let decoder = match format {
+        Alpha8 => Decoder::Pixel(1, |p| [0xFF, 0xFF, 0xFF, p[0]]),
+        ARGB16 => Decoder::Pixel(2, |p| {
+            let v = u16::from_le_bytes([p[0], p[1]]);
+            [expand4(v >> 8), expand4(v >> 4), expand4(v), expand4(v >> 12)]
+        }),
+        RGB24 => Decoder::Pixel(3, |p| [p[0], p[1], p[2], 0xFF]),
+        RGBA32 => Decoder::Pixel(4, |p| [p[0], p[1], p[2], p[3]]),
+        ARGB32 => Decoder::Pixel(4, |p| [p[1], p[2], p[3], p[0]]),
+        BGRA32 => Decoder::Pixel(4, |p| [p[2], p[1], p[0], p[3]]),
+        RGB16 => Decoder::Pixel(2, |p| {
+            let v = u16::from_le_bytes([p[0], p[1]]);
+            [expand5(v >> 11), expand6(v >> 5), expand5(v), 0xFF]
+        }),
+        RGBA16 => Decoder::Pixel(2, |p| {
+            let v = u16::from_le_bytes([p[0], p[1]]);
+            [expand4(v >> 12), expand4(v >> 8), expand4(v >> 4), expand4(v)]
+        }),
+        R8 => Decoder::Pixel(1, |p| [p[0], 0, 0, 0xFF]),
+        RG16 => Decoder::Pixel(2, |p| [p[0], p[1], 0, 0xFF]),
+        R16 => Decoder::Pixel(2, |p| [p[1], 0, 0, 0xFF]),
+        RG32 => Decoder::Pixel(4, |p| [p[1], p[3], 0, 0xFF]),
+        RGB48 => Decoder::Pixel(6, |p| [p[1], p[3], p[5], 0xFF]),
+        RGBA64 => Decoder::Pixel(8, |p| [p[1], p[3], p[5], p[7]]),
+        R8Signed => Decoder::Pixel(1, |p| [snorm8(p[0]), 0, 0, 0xFF]),
+        RG16Signed => Decoder::Pixel(2, |p| [snorm8(p[0]), snorm8(p[1]), 0, 0xFF]),
+        RGB24Signed => Decoder::Pixel(3, |p| [snorm8(p[0]), snorm8(p[1]), snorm8(p[2]), 0xFF]),
+        RGBA32Signed => Decoder::Pixel(4, |p| [snorm8(p[0]), snorm8(p[1]), snorm8(p[2]), snorm8(p[3])]),
+        R16Signed => Decoder::Pixel(2, |p| [snorm8(p[1]), 0, 0, 0xFF]),
+        RG32Signed => Decoder::Pixel(4, |p| [snorm8(p[1]), snorm8(p[3]), 0, 0xFF]),
+        RGB48Signed => Decoder::Pixel(6, |p| [snorm8(p[1]), snorm8(p[3]), snorm8(p[5]), 0xFF]),
+        RGBA64Signed => Decoder::Pixel(8, |p| [snorm8(p[1]), snorm8(p[3]), snorm8(p[5]), snorm8(p[7])]),
+        YUY2 => Decoder::Yuy2,
+
+        RHalf => Decoder::PixelFloat(2, |p| [half(&p[0..]), 0.0, 0.0, 1.0]),
+        RGHalf => Decoder::PixelFloat(4, |p| [half(&p[0..]), half(&p[2..]), 0.0, 1.0]),
+        RGBAHalf => Decoder::PixelFloat(8, |p| [half(&p[0..]), half(&p[2..]), half(&p[4..]), half(&p[6..])]),
+        RFloat => Decoder::PixelFloat(4, |p| [float(&p[0..]), 0.0, 0.0, 1.0]),
+        RGFloat => Decoder::PixelFloat(8, |p| [float(&p[0..]), float(&p[4..]), 0.0, 1.0]),
+        RGBAFloat => Decoder::PixelFloat(16, |p| [float(&p[0..]), float(&p[4..]), float(&p[8..]), float(&p[12..])]),
+        RGB9E5 => Decoder::PixelFloat(4, |p| {
+            let v = u32::from_le_bytes([p[0], p[1], p[2], p[3]]);
+            let scale = 2.0f32.powi((v >> 27) as i32 - 15 - 9);
+            [(v & 0x1FF) as f32 * scale, ((v >> 9) & 0x1FF) as f32 * scale, ((v >> 18) & 0x1FF) as f32 * scale, 1.0]
+        }),
+
+        DXT1 => Decoder::Block(4, 4, 8, decode_bc1),
+        DXT5 => Decoder::Block(4, 4, 16, decode_bc3),
+        BC4 => Decoder::Block(4, 4, 8, decode_bc4),
+        BC5 => Decoder::Block(4, 4, 16, decode_bc5),
+        BC7 => Decoder::Block(4, 4, 16, decode_bc7),
+        EtcRGB4 => Decoder::Block(4, 4, 8, decode_etc1),
+        Etc2RGB4 => Decoder::Block(4, 4, 8, decode_etc2_rgb),
+        Etc2RGB4PunchthroughAlpha => Decoder::Block(4, 4, 8, decode_etc2_rgba1),
+        Etc2RGBA8 => Decoder::Block(4, 4, 16, decode_etc2_rgba8),
+        EacR => Decoder::Block(4, 4, 8, decode_eacr),
+        EacRSigned => Decoder::Block(4, 4, 8, decode_eacr_signed),
+        EacRG => Decoder::Block(4, 4, 16, decode_eacrg),
+        EacRGSigned => Decoder::Block(4, 4, 16, decode_eacrg_signed),
+        AtcRGB4 => Decoder::Block(4, 4, 8, decode_atc_rgb4),
+        AtcRGBA8 => Decoder::Block(4, 4, 16, decode_atc_rgba8),
+        PvrtcRGB2 | PvrtcRGBA2 => Decoder::Pvrtc(true),
+        PvrtcRGB4 | PvrtcRGBA4 => Decoder::Pvrtc(false),
+        Astc4x4 => Decoder::Astc(4),
+        Astc5x5 => Decoder::Astc(5),
+        Astc6x6 => Decoder::Astc(6),
+        Astc8x8 => Decoder::Astc(8),
+        Astc10x10 => Decoder::Astc(10),
+        Astc12x12 => Decoder::Astc(12),
+
+        DXT1Crunched => Decoder::Crunched(decode_bc1),
+        DXT5Crunched => Decoder::Crunched(decode_bc3),
+        EtcRGB4Crunched => Decoder::Crunched(decode_etc1),
+        Etc2RGBA8Crunched => Decoder::Crunched(decode_etc2_rgba8),
+
+        // our block decoders only output 8 bits per channel, which would clamp
+        // these to LDR
+        BC6H | AstcHdr4x4 | AstcHdr5x5 | AstcHdr6x6 | AstcHdr8x8 | AstcHdr10x10 | AstcHdr12x12 => {
+            return Err(format!("HDR texture format {:?} isn't supported", format));
+        },
+    };
+    Ok(decoder)
+}
+
+fn expand4(v: u16) -> u8 {
+    ((v & 0x0F) * 0x11) as u8
+}
+
+fn expand5(v: u16) -> u8 {
+    let v = (v & 0x1F) as u8;
+    (v << 3) | (v >> 2)
+}
+
+fn expand6(v: u16) -> u8 {
+    let v = (v & 0x3F) as u8;
+    (v << 2) | (v >> 4)
+}
+
+fn snorm8(v: u8) -> u8 {
+    (v as i8 as i16 + 128) as u8
+}
+
+fn float(p: &[u8]) -> f32 {
+    f32::from_le_bytes([p[0], p[1], p[2], p[3]])
+}
+
+fn half(p: &[u8]) -> f32 {
+    let v = u16::from_le_bytes([p[0], p[1]]);
+    let sign = if v & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((v >> 10) & 0x1F) as i32;
+    let mantissa = (v & 0x3FF) as f32;
+    match exponent {
+        0 => sign * mantissa * 2.0f32.powi(-24),
+        0x1F if mantissa == 0.0 => sign * f32::INFINITY,
+        0x1F => f32::NAN,
+        _ => sign * (1.0 + mantissa / 1024.0) * 2.0f32.powi(exponent - 15),
+    }
+}
+
+fn get_level_size(decoder: &Decoder, width: usize, height: usize) -> usize {
+    match decoder {
+        Decoder::Pixel(bpp, _) | Decoder::PixelFloat(bpp, _) => width * height * bpp,
+        Decoder::Yuy2 => width.div_ceil(2) * height * 4,
+        Decoder::Block(bw, bh, block_size, _) => width.div_ceil(*bw) * height.div_ceil(*bh) * block_size,
+        Decoder::Astc(block) => width.div_ceil(*block) * height.div_ceil(*block) * 16,
+        Decoder::Pvrtc(true) => width.max(16) * height.max(8) / 4,
+        Decoder::Pvrtc(false) => width.max(8) * height.max(8) / 2,
+        Decoder::Crunched(_) => 0,
+    }
+}
+
+fn bgra32_to_rgba8(bgra32: &[u32]) -> Vec<u8> {
+    let mut rgba8 = Vec::with_capacity(bgra32.len() * 4);
+    for pixel in bgra32 {
+        let [b, g, r, a] = pixel.to_le_bytes();
+        rgba8.extend([r, g, b, a]);
+    }
+    rgba8
+}
+
+fn decode_yuy2(src: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let mut rgba8 = Vec::with_capacity(width * height * 4);
+    for pair in src.chunks_exact(4).take((width * height).div_ceil(2)) {
+        let (y0, u, y1, v) = (pair[0] as f32, pair[1] as f32 - 128.0, pair[2] as f32, pair[3] as f32 - 128.0);
+        for y in [y0, y1] {
+            let r = y + 1.402 * v;
+            let g = y - 0.344136 * u - 0.714136 * v;
+            let b = y + 1.772 * u;
+            rgba8.extend([r.clamp(0.0, 255.0) as u8, g.clamp(0.0, 255.0) as u8, b.clamp(0.0, 255.0) as u8, 0xFF]);
+        }
+    }
+    rgba8.truncate(width * height * 4);
+    rgba8
+}
+
+fn decode_level(decoder: &Decoder, format: TextureFormat, src: &[u8], width: usize, height: usize) -> Result<DecodedPixels, String> {
+    let mut bgra32 = vec![0u32; width * height];
+    let result = match decoder {
+        Decoder::Yuy2 => return Ok(DecodedPixels::Rgba8(decode_yuy2(src, width, height))),
+        Decoder::Pixel(bpp, decode_pixel) => {
+            let mut rgba8 = Vec::with_capacity(width * height * 4);
+            for pixel in src.chunks_exact(*bpp).take(width * height) {
+                rgba8.extend(decode_pixel(pixel));
+            }
+            return Ok(DecodedPixels::Rgba8(rgba8));
+        },
+        Decoder::PixelFloat(bpp, decode_pixel) => {
+            let mut rgba32f = Vec::with_capacity(width * height * 4);
+            for pixel in src.chunks_exact(*bpp).take(width * height) {
+                rgba32f.extend(decode_pixel(pixel));
+            }
+            return Ok(DecodedPixels::Rgba32F(rgba32f));
+        },
+        Decoder::Block(_, _, _, decode_block) | Decoder::Crunched(decode_block) => decode_block(src, width, height, &mut bgra32),
+        Decoder::Astc(block) => decode_astc(src, width, height, *block, *block, &mut bgra32),
+        Decoder::Pvrtc(true) => decode_pvrtc_2bpp(src, width, height, &mut bgra32),
+        Decoder::Pvrtc(false) => decode_pvrtc_4bpp(src, width, height, &mut bgra32),
+    };
+    result.map_err(|err| format!("failed to decode {:?} level ({}x{}): {}", format, width, height, err))?;
+    Ok(DecodedPixels::Rgba8(bgra32_to_rgba8(&bgra32)))
+}
+
+#[derive(Debug, Clone)]
+pub enum DecodedPixels {
+    Rgba8(Vec<u8>),
+    Rgba32F(Vec<f32>),
+}
+
+#[wasm_bindgen(js_name = "UnityDecodedTexture")]
+#[derive(Debug, Clone)]
+pub struct DecodedTexture {
+    pub width: usize,
+    pub height: usize,
+    pub image_count: usize,
+    pub mip_count: usize,
+    pub is_float: bool,
+    // image-major, i.e. all mips of image 0, then all mips of image 1, etc.
+    levels: Vec<DecodedPixels>,
+}
+
+#[wasm_bindgen(js_class = "UnityDecodedTexture")]
+impl DecodedTexture {
+    pub fn get_level_width(&self, mip: usize) -> usize {
+        (self.width >> mip).max(1)
+    }
+
+    pub fn get_level_height(&self, mip: usize) -> usize {
+        (self.height >> mip).max(1)
+    }
+
+    pub fn get_rgba8(&self, image: usize, mip: usize) -> Option<Vec<u8>> {
+        match self.get_level(image, mip)? {
+            DecodedPixels::Rgba8(data) => Some(data.clone()),
+            DecodedPixels::Rgba32F(_) => None,
+        }
+    }
+
+    pub fn get_rgba32f(&self, image: usize, mip: usize) -> Option<Vec<f32>> {
+        match self.get_level(image, mip)? {
+            DecodedPixels::Rgba32F(data) => Some(data.clone()),
+            DecodedPixels::Rgba8(_) => None,
+        }
+    }
+}
+
+// rust-only interface
+impl DecodedTexture {
+    pub fn get_level(&self, image: usize, mip: usize) -> Option<&DecodedPixels> {
+        if mip >= self.mip_count {
+            return None;
+        }
+        self.levels.get(image * self.mip_count + mip)
+    }
+}
+
+// Decodes every image and mip level of the texture. data is either the
+// texture's inline data, or its StreamingInfo range from the .resS file.
+pub fn decode(texture: &Texture2D, data: &[u8]) -> Result<DecodedTexture, String> {
+    let format = texture.texture_format;
+    let decoder = get_decoder(format)?;
+    let width = texture.width.max(0) as usize;
+    let height = texture.height.max(0) as usize;
+    let image_count = texture.image_count.max(1) as usize;
+    let is_float = matches!(decoder, Decoder::PixelFloat(..));
+
+    if let Decoder::Crunched(_) = decoder {
+        // crunched textures are a single image, unpacking to block data per level
+        let handle = CrunchHandle::new(data)
+            .map_err(|err| format!("failed to read crunch header: {:?}", err))?;
+        let mip_count = handle.get_num_levels() as usize;
+        let mut levels = Vec::with_capacity(mip_count);
+        for mip in 0..mip_count {
+            let block_data = handle.unpack_level(data, mip as u32)
+                .map_err(|err| format!("failed to unpack crunch level {}: {:?}", mip, err))?;
+            let level_width = (width >> mip).max(1);
+            let level_height = (height >> mip).max(1);
+            levels.push(decode_level(&decoder, format, &block_data, level_width, level_height)?);
+        }
+        return Ok(DecodedTexture { width, height, image_count: 1, mip_count, is_float, levels });
+    }
+
+    let mip_count = texture.mip_count.max(1) as usize;
+    let image_size = (0..mip_count)
+        .map(|mip| get_level_size(&decoder, (width >> mip).max(1), (height >> mip).max(1)))
+        .sum::<usize>();
+    if data.len() < image_size * image_count {
+        return Err(format!("texture data too small for {} {:?} images: {} < {}", image_count, format, data.len(), image_size * image_count));
+    }
+
+    let mut levels = Vec::with_capacity(image_count * mip_count);
+    for image in 0..image_count {
+        let mut offset = image * image_size;
+        for mip in 0..mip_count {
+            let level_width = (width >> mip).max(1);
+            let level_height = (height >> mip).max(1);
+            let size = get_level_size(&decoder, level_width, level_height);
+            levels.push(decode_level(&decoder, format, &data[offset..offset + size], level_width, level_height)?);
+            offset += size;
+        }
+    }
+    Ok(DecodedTexture { width, height, image_count, mip_count, is_float, levels })
+}
+
+#[wasm_bindgen(js_class = "UnityTexture2D")]
+impl Texture2D {
+    pub fn decode(&self, data: &[u8]) -> Result<DecodedTexture, String> {
+        decode(self, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pixel_formats() {
+        let argb16 = get_decoder(TextureFormat::ARGB16).unwrap();
+        let Decoder::Pixel(_, decode_pixel) = argb16 else { unreachable!() };
+        assert_eq!(decode_pixel(&0xF84Cu16.to_le_bytes()), [0x88, 0x44, 0xCC, 0xFF]);
+
+        let rgb16 = get_decoder(TextureFormat::RGB16).unwrap();
+        let Decoder::Pixel(_, decode_pixel) = rgb16 else { unreachable!() };
+        assert_eq!(decode_pixel(&0xF81Fu16.to_le_bytes()), [0xFF, 0x00, 0xFF, 0xFF]);
+
+        assert_eq!(half(&0x3C00u16.to_le_bytes()), 1.0);
+        assert_eq!(half(&0xC000u16.to_le_bytes()), -2.0);
+        assert_eq!(snorm8(0x7F), 0xFF);
+        assert_eq!(snorm8(0x80), 0x00);
+    }
+
+    #[test]
+    fn test_level_sizes() {
+        let dxt1 = get_decoder(TextureFormat::DXT1).unwrap();
+        assert_eq!(get_level_size(&dxt1, 16, 16), 128);
+        assert_eq!(get_level_size(&dxt1, 1, 1), 8);
+        let astc = get_decoder(TextureFormat::Astc6x6).unwrap();
+        assert_eq!(get_level_size(&astc, 13, 7), 3 * 2 * 16);
+        let pvrtc = get_decoder(TextureFormat::PvrtcRGB2).unwrap();
+        assert_eq!(get_level_size(&pvrtc, 4, 4), 32);
+        assert!(get_decoder(TextureFormat::BC6H).is_err());
+        assert!(get_decoder(TextureFormat::AstcHdr4x4).is_err());
+    }
+}
@@ -0,0 +1,492 @@
+use std::io::Cursor;
+
+use deku::ctx::Endian;
+use deku::reader::Reader;
+use deku::prelude::*;
+use wasm_bindgen::prelude::*;
+
+use crate::unity::types::serialized_file::{LegacyTypeTreeNode, OldSerializedType, TreeTypeNode};
+use crate::unity::types::wasm::WasmFriendlyPPtr;
+
+// Unity's built-in string table, referenced by type tree nodes whose string
+// offset has the high bit set. Offsets are into the null-separated
+// concatenation of these, in order.
+// https://github.com/Perfare/AssetStudio/blob/master/AssetStudio/CommonString.cs
+const COMMON_STRINGS: &[&str] = &[
+    "AABB", "AnimationClip", "AnimationCurve", "AnimationState", "Array", "Base", "BitField", "bitset",
+    "bool", "char", "ColorRGBA", "Component", "data", "deque", "double", "dynamic_array",
+    "FastPropertyName", "first", "float", "Font", "GameObject", "Generic Mono", "GradientNEW", "GUID",
+    "GUIStyle", "int", "list", "long long", "map", "Matrix4x4f", "MdFour", "MonoBehaviour",
+    "MonoScript", "m_ByteSize", "m_Curve", "m_EditorClassIdentifier", "m_EditorHideFlags", "m_Enabled",
+    "m_ExtensionPtr", "m_GameObject", "m_Index", "m_IsArray", "m_IsStatic", "m_MetaFlag", "m_Name",
+    "m_ObjectHideFlags", "m_PrefabInternal", "m_PrefabParentObject", "m_Script", "m_StaticEditorFlags",
+    "m_Type", "m_Version", "Object", "pair", "PPtr<Component>", "PPtr<GameObject>", "PPtr<Material>",
+    "PPtr<MonoBehaviour>", "PPtr<MonoScript>", "PPtr<Object>", "PPtr<Prefab>", "PPtr<Sprite>",
+    "PPtr<TextAsset>", "PPtr<Texture>", "PPtr<Texture2D>", "PPtr<Transform>", "Prefab", "Quaternionf",
+    "Rectf", "RectInt", "RectOffset", "second", "set", "short", "size", "SInt16", "SInt32", "SInt64",
+    "SInt8", "staticvector", "string", "TextAsset", "TextMesh", "Texture", "Texture2D", "Transform",
+    "TypelessData", "UInt16", "UInt32", "UInt64", "UInt8", "unsigned int", "unsigned long long",
+    "unsigned short", "vector", "Vector2f", "Vector3f", "Vector4f", "m_ScriptingClassIdentifier",
+    "Gradient", "Type*", "int2_storage", "int3_storage", "BoundsInt", "m_CorrespondingSourceObject",
+    "m_PrefabInstance", "m_PrefabAsset", "FileSize", "Hash128",
+];
+
+const TYPE_FLAGS_IS_ARRAY: u8 = 0x01;
+const META_FLAGS_ALIGN_BYTES: u32 = 0x4000;
+
+fn lookup_common_string(offset: u32) -> Option<&'static str> {
+    let mut current = 0;
+    for s in COMMON_STRINGS {
+        if current == offset {
+            return Some(s);
+        }
+        current += s.len() as u32 + 1;
+    }
+    None
+}
+
+fn lookup_string(string_buffer: &[u8], offset: u32) -> String {
+    if offset & 0x80000000 != 0 {
+        return lookup_common_string(offset & 0x7FFFFFFF)
+            .unwrap_or("<unknown>")
+            .to_string();
+    }
+    let Some(rest) = string_buffer.get(offset as usize..) else {
+        return "<unknown>".to_string();
+    };
+    let end = rest.iter()
+        .position(|b| *b == 0)
+        .unwrap_or(rest.len());
+    String::from_utf8_lossy(&rest[..end]).into_owned()
+}
+
+#[derive(Debug, Clone)]
+pub struct TypeTreeNode {
+    pub type_name: String,
+    pub name: String,
+    pub byte_size: i32,
+    pub type_flags: u8,
+    pub meta_flags: u32,
+    pub children: Vec<TypeTreeNode>,
+}
+
+impl TypeTreeNode {
+    pub fn from_serialized_type(serialized_type: &OldSerializedType) -> Result<TypeTreeNode, String> {
+        TypeTreeNode::from_nodes(&serialized_type.nodes, &serialized_type.string_buffer)
+    }
+
+    pub fn from_legacy(node: &LegacyTypeTreeNode) -> TypeTreeNode {
+        TypeTreeNode {
+            type_name: (&node.type_name).into(),
+            name: (&node.name).into(),
+            byte_size: node.byte_size,
+            type_flags: node.type_flags as u8,
+            meta_flags: node.meta_flags,
+            children: node.children.iter().map(TypeTreeNode::from_legacy).collect(),
+        }
+    }
+
+    // Nodes are stored flattened in depth-first order, with each node's
+    // level giving its depth in the tree
+    pub fn from_nodes(nodes: &[TreeTypeNode], string_buffer: &[u8]) -> Result<TypeTreeNode, String> {
+        let mut stack: Vec<(u8, TypeTreeNode)> = Vec::new();
+        for node in nodes {
+            let tree_node = TypeTreeNode {
+                type_name: lookup_string(string_buffer, node.type_string_offset),
+                name: lookup_string(string_buffer, node.name_string_offset),
+                byte_size: node.byte_size,
+                type_flags: node.type_flags,
+                meta_flags: node.meta_flags,
+                children: Vec::new(),
+            };
+            // pop finished siblings (and their descendants) into their parents
+            while stack.len() > 1 && stack.last().unwrap().0 >= node.level {
+                let (_, finished) = stack.pop().unwrap();
+                stack.last_mut().unwrap().1.children.push(finished);
+            }
+            if !stack.is_empty() && node.level == 0 {
+                return Err("type tree has more than one root node".to_string());
+            }
+            stack.push((node.level, tree_node));
+        }
+        while stack.len() > 1 {
+            let (_, finished) = stack.pop().unwrap();
+            stack.last_mut().unwrap().1.children.push(finished);
+        }
+        stack.pop()
+            .map(|(_, root)| root)
+            .ok_or("empty type tree".to_string())
+    }
+
+    fn is_array(&self) -> bool {
+        self.type_flags & TYPE_FLAGS_IS_ARRAY != 0
+    }
+
+    fn needs_align(&self) -> bool {
+        self.meta_flags & META_FLAGS_ALIGN_BYTES != 0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeTreeValue {
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    Array(Vec<TypeTreeValue>),
+    Map(Vec<(TypeTreeValue, TypeTreeValue)>),
+    PPtr(WasmFriendlyPPtr),
+    Struct(Vec<(String, TypeTreeValue)>),
+}
+
+impl TypeTreeValue {
+    pub fn get(&self, key: &str) -> Option<&TypeTreeValue> {
+        match self {
+            TypeTreeValue::Struct(fields) => fields.iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            TypeTreeValue::Array(values) => values.get(key.parse::<usize>().ok()?),
+            TypeTreeValue::Map(pairs) => pairs.iter()
+                .find(|(k, _)| k.matches_key(key))
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    // Looks up a dot-separated path, e.g. "m_Materials.0" or
+    // "m_SavedProperties.m_TexEnvs._MainTex.m_Texture"
+    pub fn get_path(&self, path: &str) -> Option<&TypeTreeValue> {
+        if path.is_empty() {
+            return Some(self);
+        }
+        let mut value = self;
+        for key in path.split('.') {
+            value = value.get(key)?;
+        }
+        Some(value)
+    }
+
+    fn matches_key(&self, key: &str) -> bool {
+        match self {
+            TypeTreeValue::String(s) => s == key,
+            TypeTreeValue::Int(v) => key.parse::<i64>().ok() == Some(*v),
+            TypeTreeValue::UInt(v) => key.parse::<u64>().ok() == Some(*v),
+            _ => false,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            TypeTreeValue::Bool(v) => Some(if *v { 1.0 } else { 0.0 }),
+            TypeTreeValue::Int(v) => Some(*v as f64),
+            TypeTreeValue::UInt(v) => Some(*v as f64),
+            TypeTreeValue::Float(v) => Some(*v),
+            _ => None,
+        }
+    }
+}
+
+pub struct TypeTreeReader<R: std::io::Read + std::io::Seek> {
+    reader: Reader<R>,
+    endian: Endian,
+    data_size: usize,
+}
+
+impl<R: std::io::Read + std::io::Seek> TypeTreeReader<R> {
+    pub fn new(reader: Reader<R>, endian: Endian, data_size: usize) -> Self {
+        Self { reader, endian, data_size }
+    }
+
+    fn align(&mut self) -> Result<(), DekuError> {
+        let offset = self.reader.bits_read / 8;
+        let padding = (4 - offset % 4) % 4;
+        self.reader.skip_bits(padding * 8)
+    }
+
+    fn read_bytes(&mut self, count: usize) -> Result<Vec<u8>, DekuError> {
+        let mut buf = vec![0x00; count];
+        self.reader.read_bytes(count, &mut buf, deku::ctx::Order::Msb0)?;
+        Ok(buf)
+    }
+
+    fn read_count(&mut self) -> Result<usize, DekuError> {
+        let count = i32::from_reader_with_ctx(&mut self.reader, self.endian)?;
+        if count < 0 {
+            return Err(DekuError::Assertion(format!("negative array count {}", count).into()));
+        }
+        // every element takes at least a byte, so don't trust counts that
+        // couldn't possibly fit in what's left
+        let remaining = self.data_size.saturating_sub(self.reader.bits_read / 8);
+        if count as usize > remaining {
+            return Err(DekuError::Assertion(format!("array count {} exceeds the {} bytes left", count, remaining).into()));
+        }
+        Ok(count as usize)
+    }
+
+    pub fn read_value(&mut self, node: &TypeTreeNode) -> Result<TypeTreeValue, DekuError> {
+        let endian = self.endian;
+        let reader = &mut self.reader;
+        let mut align = node.needs_align();
+        let value = match node.type_name.as_str() {
+            "bool" => TypeTreeValue::Bool(u8::from_reader_with_ctx(reader, endian)? != 0),
+            "SInt8" => TypeTreeValue::Int(i8::from_reader_with_ctx(reader, endian)? as i64),
+            "UInt8" | "char" => TypeTreeValue::UInt(u8::from_reader_with_ctx(reader, endian)? as u64),
+            "SInt16" | "short" => TypeTreeValue::Int(i16::from_reader_with_ctx(reader, endian)? as i64),
+            "UInt16" | "unsigned short" => TypeTreeValue::UInt(u16::from_reader_with_ctx(reader, endian)? as u64),
+            "SInt32" | "int" => TypeTreeValue::Int(i32::from_reader_with_ctx(reader, endian)? as i64),
+            "UInt32" | "unsigned int" | "Type*" => TypeTreeValue::UInt(u32::from_reader_with_ctx(reader, endian)? as u64),
+            "SInt64" | "long long" => TypeTreeValue::Int(i64::from_reader_with_ctx(reader, endian)?),
+            "UInt64" | "unsigned long long" | "FileSize" => TypeTreeValue::UInt(u64::from_reader_with_ctx(reader, endian)?),
+            "float" => TypeTreeValue::Float(f32::from_reader_with_ctx(reader, endian)? as f64),
+            "double" => TypeTreeValue::Float(f64::from_reader_with_ctx(reader, endian)?),
+            "string" => {
+                let count = self.read_count()?;
+                let bytes = self.read_bytes(count)?;
+                // the alignment flag lives on the string's inner Array node
+                align |= node.children.iter().any(|child| child.needs_align());
+                TypeTreeValue::String(String::from_utf8_lossy(&bytes).into_owned())
+            },
+            "TypelessData" => {
+                let count = self.read_count()?;
+                TypeTreeValue::Bytes(self.read_bytes(count)?)
+            },
+            "map" => {
+                let array = node.children.first()
+                    .ok_or(DekuError::Assertion("map without Array child".into()))?;
+                let pair = array.children.get(1)
+                    .ok_or(DekuError::Assertion("map Array without data child".into()))?;
+                let (first, second) = match pair.children.as_slice() {
+                    [first, second] => (first, second),
+                    _ => return Err(DekuError::Assertion("map pair without two children".into())),
+                };
+                let count = self.read_count()?;
+                let mut pairs = Vec::with_capacity(count);
+                for _ in 0..count {
+                    let key = self.read_value(first)?;
+                    let value = self.read_value(second)?;
+                    pairs.push((key, value));
+                }
+                align |= array.needs_align();
+                TypeTreeValue::Map(pairs)
+            },
+            _ => match node.children.first() {
+                Some(array) if array.is_array() => {
+                    align |= array.needs_align();
+                    self.read_array(array)?
+                },
+                _ if node.is_array() => self.read_array(node)?,
+                _ => {
+                    let mut fields = Vec::with_capacity(node.children.len());
+                    for child in &node.children {
+                        fields.push((child.name.clone(), self.read_value(child)?));
+                    }
+                    if node.type_name.starts_with("PPtr<") {
+                        pptr_from_fields(&fields).unwrap_or(TypeTreeValue::Struct(fields))
+                    } else {
+                        TypeTreeValue::Struct(fields)
+                    }
+                },
+            },
+        };
+        if align {
+            self.align()?;
+        }
+        Ok(value)
+    }
+
+    fn read_array(&mut self, array: &TypeTreeNode) -> Result<TypeTreeValue, DekuError> {
+        let data = array.children.get(1)
+            .ok_or(DekuError::Assertion("Array without data child".into()))?;
+        let count = self.read_count()?;
+        if matches!(data.type_name.as_str(), "UInt8" | "char") {
+            return Ok(TypeTreeValue::Bytes(self.read_bytes(count)?));
+        }
+        let mut values = Vec::with_capacity(count);
+        for _ in 0..count {
+            values.push(self.read_value(data)?);
+        }
+        Ok(TypeTreeValue::Array(values))
+    }
+}
+
+fn pptr_from_fields(fields: &[(String, TypeTreeValue)]) -> Option<TypeTreeValue> {
+    let mut file_index = None;
+    let mut path_id = None;
+    for (name, value) in fields {
+        match name.as_str() {
+            "m_FileID" => file_index = value.as_f64().map(|v| v as u32),
+            "m_PathID" => path_id = match value {
+                TypeTreeValue::Int(v) => Some(*v),
+                TypeTreeValue::UInt(v) => Some(*v as i64),
+                _ => None,
+            },
+            _ => {},
+        }
+    }
+    Some(TypeTreeValue::PPtr(WasmFriendlyPPtr {
+        file_index: file_index?,
+        path_id: path_id?,
+    }))
+}
+
+pub fn read_type_tree_value(node: &TypeTreeNode, data: &[u8], endian: Endian) -> Result<TypeTreeValue, String> {
+    let mut cursor = Cursor::new(data);
+    let reader = Reader::new(&mut cursor);
+    let mut tree_reader = TypeTreeReader::new(reader, endian, data.len());
+    tree_reader.read_value(node)
+        .map_err(|err| format!("failed to read {}: {:?}", node.type_name, err))
+}
+
+#[wasm_bindgen(js_name = "UnityTypeTreeObject")]
+pub struct TypeTreeObject {
+    type_name: String,
+    value: TypeTreeValue,
+}
+
+impl TypeTreeObject {
+    pub fn new(type_name: String, value: TypeTreeValue) -> Self {
+        Self { type_name, value }
+    }
+
+    pub fn get_value(&self) -> &TypeTreeValue {
+        &self.value
+    }
+}
+
+#[wasm_bindgen(js_class = "UnityTypeTreeObject")]
+impl TypeTreeObject {
+    pub fn get_type_name(&self) -> String {
+        self.type_name.clone()
+    }
+
+    pub fn has_field(&self, path: &str) -> bool {
+        self.value.get_path(path).is_some()
+    }
+
+    // Field names of a struct, or stringified keys of a map
+    pub fn get_field_names(&self, path: &str) -> Option<Vec<String>> {
+        match self.value.get_path(path)? {
+            TypeTreeValue::Struct(fields) => Some(fields.iter().map(|(name, _)| name.clone()).collect()),
+            TypeTreeValue::Map(pairs) => Some(pairs.iter()
+                .filter_map(|(key, _)| match key {
+                    TypeTreeValue::String(s) => Some(s.clone()),
+                    TypeTreeValue::Int(v) => Some(v.to_string()),
+                    TypeTreeValue::UInt(v) => Some(v.to_string()),
+                    _ => None,
+                })
+                .collect()),
+            _ => None,
+        }
+    }
+
+    pub fn get_length(&self, path: &str) -> Option<usize> {
+        match self.value.get_path(path)? {
+            TypeTreeValue::Array(values) => Some(values.len()),
+            TypeTreeValue::Map(pairs) => Some(pairs.len()),
+            TypeTreeValue::Bytes(bytes) => Some(bytes.len()),
+            TypeTreeValue::String(s) => Some(s.len()),
+            _ => None,
+        }
+    }
+
+    pub fn get_number(&self, path: &str) -> Option<f64> {
+        self.value.get_path(path)?.as_f64()
+    }
+
+    pub fn get_string(&self, path: &str) -> Option<String> {
+        match self.value.get_path(path)? {
+            TypeTreeValue::String(s) => Some(s.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn get_bytes(&self, path: &str) -> Option<Vec<u8>> {
+        match self.value.get_path(path)? {
+            TypeTreeValue::Bytes(bytes) => Some(bytes.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn get_pptr(&self, path: &str) -> Option<WasmFriendlyPPtr> {
+        match self.value.get_path(path)? {
+            TypeTreeValue::PPtr(pptr) => Some(*pptr),
+            _ => None,
+        }
+    }
+
+    pub fn get_f32_array(&self, path: &str) -> Option<Vec<f32>> {
+        match self.value.get_path(path)? {
+            TypeTreeValue::Array(values) => values.iter()
+                .map(|v| v.as_f64().map(|v| v as f32))
+                .collect(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(level: u8, type_flags: u8, type_name: u32, name: u32, meta_flags: u32) -> TreeTypeNode {
+        TreeTypeNode {
+            version: 1,
+            level,
+            type_flags,
+            type_string_offset: type_name,
+            name_string_offset: name,
+            byte_size: -1,
+            index: 0,
+            meta_flags,
+            ref_type_hash: 0,
+        }
+    }
+
+    #[test]
+    fn test_common_strings() {
+        assert_eq!(lookup_common_string(0), Some("AABB"));
+        assert_eq!(lookup_common_string(427), Some("m_Name"));
+        assert_eq!(lookup_common_string(1161), Some("Hash128"));
+        assert_eq!(lookup_common_string(1), None);
+    }
+
+    #[test]
+    fn test_read_value() {
+        // Base { string m_Name; vector m_Values { Array { int size; float data; } } }
+        let common = 0x80000000;
+        let string_buffer = b"Base\0m_Values\0".to_vec();
+        let nodes = vec![
+            node(0, 0, 0, 0, 0),
+            node(1, 0, common | 840, common | 427, 0),
+            node(2, 1, common | 49, common | 49, META_FLAGS_ALIGN_BYTES),
+            node(3, 0, common | 222, common | 795, 0),
+            node(3, 0, common | 81, common | 106, 0),
+            node(1, 0, common | 981, 5, 0),
+            node(2, 1, common | 49, common | 49, 0),
+            node(3, 0, common | 222, common | 795, 0),
+            node(3, 0, common | 161, common | 106, 0),
+        ];
+        let root = TypeTreeNode::from_nodes(&nodes, &string_buffer).unwrap();
+        assert_eq!(root.children.len(), 2);
+
+        let mut data = Vec::new();
+        data.extend(3i32.to_le_bytes());
+        data.extend(b"foo\0");
+        data.extend(2i32.to_le_bytes());
+        data.extend(1.5f32.to_le_bytes());
+        data.extend(2.5f32.to_le_bytes());
+        let value = read_type_tree_value(&root, &data, Endian::Little).unwrap();
+        let object = TypeTreeObject::new(root.type_name.clone(), value);
+        assert_eq!(object.get_type_name(), "Base");
+        assert_eq!(object.get_string("m_Name").unwrap(), "foo");
+        assert_eq!(object.get_length("m_Values"), Some(2));
+        assert_eq!(object.get_number("m_Values.1"), Some(2.5));
+        assert_eq!(object.get_f32_array("m_Values").unwrap(), vec![1.5, 2.5]);
+
+        // a corrupt count fails instead of allocating the whole array up front
+        data[8..12].copy_from_slice(&i32::MAX.to_le_bytes());
+        assert!(read_type_tree_value(&root, &data, Endian::Little).is_err());
+    }
+}
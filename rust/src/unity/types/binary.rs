@@ -1,13 +1,13 @@
 
-use deku::{ctx::Order, prelude::*};
+use deku::{ctx::{Endian, Order}, prelude::*};
 
 // https://github.com/AssetRipper/TypeTreeDumps/blob/main/StructsDump/release/2019.4.39f1.dump
 // e.g. Outer Wilds
 
-use super::common::{CharArray, ColorRGBA, Map, Matrix4x4, PPtr, Packedf32Vec, Packedi32Vec, Quaternion, UnityArray, Vec2, Vec3, Vec4, AABB, UnityVersion};
+use super::common::{CharArray, EndianCtx, ColorRGBA, Map, Matrix4x4, PPtr, Packedf32Vec, Packedi32Vec, Quaternion, UnityArray, Vec2, Vec3, Vec4, AABB, UnityVersion};
 
 #[derive(DekuRead, Clone, Debug)]
-#[deku(ctx = "_version: UnityVersion")]
+#[deku(ctx = "endian: Endian, _version: UnityVersion", endian = "endian")]
 pub struct GameObject {
     pub components: UnityArray<PPtr<Component>>,
     pub layer: u32,
@@ -17,12 +17,13 @@ pub struct GameObject {
 }
 
 #[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
 pub struct Component {
     pub game_object: PPtr<GameObject>,
 }
 
 #[derive(DekuRead, Clone, Debug)]
-#[deku(ctx = "_version: UnityVersion")]
+#[deku(ctx = "endian: Endian, _version: UnityVersion", endian = "endian")]
 pub struct Transform {
     pub game_object: PPtr<GameObject>,
     pub local_rotation: Quaternion,
@@ -33,7 +34,7 @@ pub struct Transform {
 }
 
 #[derive(DekuRead, Clone, Debug)]
-#[deku(ctx = "version: UnityVersion")]
+#[deku(ctx = "endian: Endian, version: UnityVersion", endian = "endian")]
 pub struct Material {
     pub name: CharArray,
     #[deku(count = "(4 - deku::byte_offset % 4) % 4")] _alignment0: Vec<u8>,
@@ -65,12 +66,14 @@ pub struct Material {
 }
 
 #[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
 pub struct BuildTextureStackReference {
     pub group_name: CharArray,
     pub item_name: CharArray,
 }
 
 #[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
 pub struct TexEnv {
     pub texture: PPtr<Texture>,
     pub scale: Vec2,
@@ -78,6 +81,7 @@ pub struct TexEnv {
 }
 
 #[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
 pub struct Texture {
     pub name: CharArray,
     pub forced_fallback_format: i32,
@@ -85,7 +89,7 @@ pub struct Texture {
 }
 
 #[derive(DekuRead, Clone, Debug)]
-#[deku(ctx = "version: UnityVersion")]
+#[deku(ctx = "endian: Endian, version: UnityVersion", endian = "endian")]
 pub struct MeshRenderer {
     pub game_object: PPtr<GameObject>,
     pub enabled: u8,
@@ -121,7 +125,7 @@ pub struct MeshRenderer {
 }
 
 #[derive(DekuRead, Clone, Debug)]
-#[deku(ctx = "version: UnityVersion")]
+#[deku(ctx = "endian: Endian, version: UnityVersion", endian = "endian")]
 pub struct Mesh {
     pub name: CharArray,
     pub submeshes: UnityArray<SubMesh>,
@@ -155,7 +159,7 @@ pub struct Mesh {
 
 #[derive(DekuRead, Clone, Copy, Debug)]
 #[repr(i32)]
-#[deku(id_type = "i32")]
+#[deku(id_type = "i32", endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
 pub enum IndexFormat {
     UInt16 = 0,
     UInt32 = 1,
@@ -163,7 +167,7 @@ pub enum IndexFormat {
 
 #[derive(DekuRead, Clone, Copy, Debug)]
 #[repr(u8)]
-#[deku(id_type = "u8")]
+#[deku(id_type = "u8", endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
 pub enum MeshCompression {
     Off = 0,
     Low = 1,
@@ -172,7 +176,7 @@ pub enum MeshCompression {
 }
 
 #[derive(DekuRead, Clone, Debug)]
-#[deku(ctx = "version: UnityVersion")]
+#[deku(ctx = "endian: Endian, version: UnityVersion", endian = "endian")]
 pub struct StreamingInfo {
     #[deku(ctx = "version")]
     pub offset: StreamingInfoOffset,
@@ -181,7 +185,7 @@ pub struct StreamingInfo {
 }
 
 #[derive(DekuRead, Clone, Debug)]
-#[deku(ctx = "version: UnityVersion", id = "version")]
+#[deku(ctx = "endian: Endian, version: UnityVersion", id = "version", endian = "endian")]
 pub enum StreamingInfoOffset {
     #[deku(id_pat = "UnityVersion::V2019_4_39f1")]
     Small(u32),
@@ -199,6 +203,7 @@ impl From<StreamingInfoOffset> for u64 {
 }
 
 #[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
 pub struct SubMesh {
     pub first_byte: u32,
     pub index_count: u32,
@@ -210,7 +215,7 @@ pub struct SubMesh {
 }
 
 #[derive(DekuRead, Clone, Debug)]
-#[deku(ctx = "_version: UnityVersion")]
+#[deku(ctx = "endian: Endian, _version: UnityVersion", endian = "endian")]
 pub struct VertexData {
     pub vertex_count: u32,
     pub channels: UnityArray<ChannelInfo>,
@@ -223,9 +228,9 @@ pub struct ByteArray {
     pub data: Vec<u8>,
 }
 
-impl<'a, Ctx> DekuReader<'a, Ctx> for ByteArray where Ctx: Copy {
-    fn from_reader_with_ctx<R: std::io::Read + std::io::Seek>(reader: &mut Reader<R>, _ctx: Ctx) -> Result<Self, DekuError> {
-        let count = i32::from_reader_with_ctx(reader, ())? as usize;
+impl<'a, Ctx> DekuReader<'a, Ctx> for ByteArray where Ctx: EndianCtx {
+    fn from_reader_with_ctx<R: std::io::Read + std::io::Seek>(reader: &mut Reader<R>, ctx: Ctx) -> Result<Self, DekuError> {
+        let count = i32::from_reader_with_ctx(reader, ctx.endian())? as usize;
         let mut buf = vec![0x00; count];
         reader.read_bytes(count, &mut buf, Order::Msb0)?;
         Ok(ByteArray{ data: buf })
@@ -239,6 +244,7 @@ impl From<ByteArray> for Vec<u8> {
 }
 
 #[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
 pub struct CompressedMesh {
     pub vertices: Packedf32Vec,
     pub uv: Packedf32Vec,
@@ -254,6 +260,7 @@ pub struct CompressedMesh {
 }
 
 #[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
 pub struct ChannelInfo {
     pub stream: u8,
     pub offset: u8,
@@ -265,7 +272,7 @@ pub struct ChannelInfo {
 }
 
 #[derive(DekuRead, Clone, Debug)]
-#[deku(id_type = "u8")]
+#[deku(id_type = "u8", endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
 pub enum VertexFormat {
     #[deku(id = "0")] Float,
     #[deku(id = "1")] Float16,
@@ -282,6 +289,7 @@ pub enum VertexFormat {
 }
 
 #[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
 pub struct BlendShapeData {
     pub vertices: UnityArray<BlendShapeVertex>,
     pub shapes: UnityArray<MeshBlendShape>,
@@ -290,6 +298,7 @@ pub struct BlendShapeData {
 }
 
 #[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
 pub struct MeshBlendShape {
     pub first_vertex: u32,
     pub vertex_count: u32,
@@ -299,6 +308,7 @@ pub struct MeshBlendShape {
 }
 
 #[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
 pub struct MeshBlendShapeChannel {
     pub name: CharArray,
     pub name_hash: i32,
@@ -307,6 +317,7 @@ pub struct MeshBlendShapeChannel {
 }
 
 #[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
 pub struct BlendShapeVertex {
     pub vertex: Vec3,
     pub normal: Vec3,
@@ -315,13 +326,14 @@ pub struct BlendShapeVertex {
 }
 
 #[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
 pub struct StaticBatchInfo {
     pub first_submesh: u16,
     pub submesh_count: u16,
 }
 
 #[derive(DekuRead, Clone, Debug)]
-#[deku(ctx = "version: UnityVersion")]
+#[deku(ctx = "endian: Endian, version: UnityVersion", endian = "endian")]
 pub struct Texture2D {
     pub name: CharArray,
     #[deku(count = "(4 - deku::byte_offset % 4) % 4")] _alignment0: Vec<u8>,
@@ -355,7 +367,7 @@ pub struct Texture2D {
 }
 
 #[derive(DekuRead, Debug, Clone)]
-#[deku(ctx = "version: UnityVersion", id = "version")]
+#[deku(ctx = "endian: Endian, version: UnityVersion", id = "version", endian = "endian")]
 pub enum TextureBooleanSettings {
     #[deku(id_pat = "UnityVersion::V2019_4_39f1")]
     V2019 {
@@ -374,6 +386,7 @@ pub enum TextureBooleanSettings {
 }
 
 #[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
 pub struct GLTextureSettings {
     pub filter_mode: TextureFilterMode,
     pub aniso: i32,
@@ -385,7 +398,7 @@ pub struct GLTextureSettings {
 
 #[derive(DekuRead, Clone, Debug)]
 #[repr(i32)]
-#[deku(id_type = "i32")]
+#[deku(id_type = "i32", endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
 pub enum TextureFilterMode {
     Nearest = 0,
     Bilinear = 1,
@@ -394,7 +407,7 @@ pub enum TextureFilterMode {
 
 #[derive(DekuRead, Clone, Debug)]
 #[repr(i32)]
-#[deku(id_type = "i32")]
+#[deku(id_type = "i32", endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
 pub enum TextureWrapMode {
     Repeat = 0,
     Clamp = 1,
@@ -405,7 +418,7 @@ pub enum TextureWrapMode {
 // copied from https://github.com/Unity-Technologies/UnityCsReference/blob/129a67089d125df5b95b659d3535deaf9968e86c/Editor/Mono/AssetPipeline/TextureImporterEnums.cs#L37
 #[derive(DekuRead, Clone, Debug)]
 #[repr(i32)]
-#[deku(id_type = "i32")]
+#[deku(id_type = "i32", endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
 pub enum TextureFormat {
     // Alpha 8 bit texture format.
     Alpha8 = 1,
@@ -427,6 +440,8 @@ pub enum TextureFormat {
     DXT5 = 12,
     // RGBA 16 bit (4444) texture format.
     RGBA16 = 13,
+    // BGRA 32 bit texture format.
+    BGRA32 = 14,
 
     // R 16 bit texture format.
     RHalf = 15,
@@ -442,6 +457,9 @@ pub enum TextureFormat {
     // RGBA 128 bit texture format.
     RGBAFloat = 20,
 
+    // YUV 4:2:2 texture format.
+    YUY2 = 21,
+
     // RGB 32 bit packed float format.
     RGB9E5 = 22,
 
@@ -458,8 +476,20 @@ pub enum TextureFormat {
     DXT1Crunched = 28,
     // DXT5 crunched texture format.
     DXT5Crunched = 29,
+    // PowerVR (iOS) 2 bits/pixel compressed color texture format.
+    PvrtcRGB2 = 30,
+    // PowerVR (iOS) 2 bits/pixel compressed with alpha channel texture format.
+    PvrtcRGBA2 = 31,
+    // PowerVR (iOS) 4 bits/pixel compressed color texture format.
+    PvrtcRGB4 = 32,
+    // PowerVR (iOS) 4 bits/pixel compressed with alpha channel texture format.
+    PvrtcRGBA4 = 33,
     // ETC (GLES2.0) 4 bits/pixel compressed RGB texture format.
     EtcRGB4 = 34,
+    // ATC (Adreno) 4 bits/pixel compressed RGB texture format.
+    AtcRGB4 = 35,
+    // ATC (Adreno) 8 bits/pixel compressed RGBA texture format.
+    AtcRGBA8 = 36,
     // EAC 4 bits/pixel compressed 16-bit R texture format
     EacR = 41,
     // EAC 4 bits/pixel compressed 16-bit signed R texture format
@@ -521,22 +551,724 @@ pub enum TextureFormat {
 
 #[derive(DekuRead, Clone, Debug)]
 #[repr(i32)]
-#[deku(id_type = "i32")]
+#[deku(id_type = "i32", endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
 pub enum ColorSpace {
     Linear = 0x00,
     SRGB   = 0x01,
 }
 
 #[derive(DekuRead, Clone, Debug)]
-#[deku(ctx = "_version: UnityVersion")]
+#[deku(ctx = "endian: Endian, _version: UnityVersion", endian = "endian")]
 pub struct MeshFilter {
     pub game_object: PPtr<GameObject>,
     pub mesh: PPtr<Mesh>,
 }
 
 #[derive(DekuRead, Clone, Debug)]
-#[deku(ctx = "_version: UnityVersion")]
+#[deku(ctx = "endian: Endian, _version: UnityVersion", endian = "endian")]
 pub struct ScriptMapper {
     pub shader_to_name_map: Map<PPtr<()>, CharArray>,
     pub preload_shaders: bool,
 }
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(ctx = "endian: Endian, version: UnityVersion", endian = "endian")]
+pub struct SkinnedMeshRenderer {
+    pub game_object: PPtr<GameObject>,
+    pub enabled: u8,
+    pub cast_shadows: u8,
+    pub receive_shadows: u8,
+    pub dynamic_occludee: u8,
+    #[deku(cond = "version >= UnityVersion::V2021_3_27f1")]
+    pub static_shadow_caster: Option<u8>,
+    pub motion_vectors: u8,
+    pub light_probe_usage: u8,
+    pub reflection_probe_usage: u8,
+    pub ray_tracing_mode: u8,
+    #[deku(cond = "version >= UnityVersion::V2020_3_16f1")]
+    pub ray_trace_procedural: Option<u8>,
+    #[deku(count = "(4 - deku::byte_offset % 4) % 4")] _alignment0: Vec<u8>,
+    pub rendering_layer_mask: u32,
+    pub renderer_priority: i32,
+    pub lightmap_index: u16,
+    pub lightmap_index_dynamic: u16,
+    pub lightmap_tiling_offset: Vec4,
+    pub lightmap_tiling_offset_dynamic: Vec4,
+    pub materials: UnityArray<PPtr<Material>>,
+    pub static_batch_info: StaticBatchInfo,
+    pub static_batch_root: PPtr<Transform>,
+    pub probe_anchor: PPtr<Transform>,
+    pub light_probe_volume_override: PPtr<GameObject>,
+    pub sorting_layer_id: i32,
+    pub sorting_layer: i16,
+    pub sorting_order: i16,
+    pub quality: i32,
+    pub update_when_offscreen: u8,
+    pub skinned_motion_vectors: u8,
+    #[deku(count = "(4 - deku::byte_offset % 4) % 4")] _alignment1: Vec<u8>,
+    pub mesh: PPtr<Mesh>,
+    pub bones: UnityArray<PPtr<Transform>>,
+    pub blend_shape_weights: UnityArray<f32>,
+    pub root_bone: PPtr<Transform>,
+    pub aabb: AABB,
+    pub dirty_aabb: u8,
+    #[deku(count = "(4 - deku::byte_offset % 4) % 4")] _alignment2: Vec<u8>,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(ctx = "endian: Endian, version: UnityVersion", endian = "endian")]
+pub struct Animator {
+    pub game_object: PPtr<GameObject>,
+    pub enabled: u8,
+    #[deku(count = "(4 - deku::byte_offset % 4) % 4")] _alignment0: Vec<u8>,
+    pub avatar: PPtr<Avatar>,
+    pub controller: PPtr<()>,
+    pub culling_mode: i32,
+    pub update_mode: i32,
+    pub apply_root_motion: u8,
+    pub linear_velocity_blending: u8,
+    #[deku(cond = "version >= UnityVersion::V2021_3_27f1")]
+    pub stabilize_feet: Option<u8>,
+    #[deku(count = "(4 - deku::byte_offset % 4) % 4")] _alignment1: Vec<u8>,
+    pub has_transform_hierarchy: u8,
+    pub allow_constant_clip_sampling_optimization: u8,
+    pub keep_animator_controller_state_on_disable: u8,
+    #[deku(count = "(4 - deku::byte_offset % 4) % 4")] _alignment2: Vec<u8>,
+}
+
+// The "mecanim" animation runtime types, see
+// https://github.com/Perfare/AssetStudio/blob/master/AssetStudio/Classes/Avatar.cs
+// and https://github.com/Perfare/AssetStudio/blob/master/AssetStudio/Classes/AnimationClip.cs
+
+#[derive(DekuRead, Clone, Copy, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct XForm {
+    pub t: Vec3,
+    pub q: Quaternion,
+    pub s: Vec3,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(ctx = "endian: Endian, _version: UnityVersion", endian = "endian")]
+pub struct Avatar {
+    pub name: CharArray,
+    pub avatar_size: u32,
+    pub avatar: AvatarConstant,
+    pub tos: Map<u32, CharArray>,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct AvatarConstant {
+    pub avatar_skeleton: Skeleton,
+    pub avatar_skeleton_pose: SkeletonPose,
+    pub default_pose: SkeletonPose,
+    pub skeleton_name_id_array: UnityArray<u32>,
+    pub human: Human,
+    pub human_skeleton_index_array: UnityArray<i32>,
+    pub human_skeleton_reverse_index_array: UnityArray<i32>,
+    pub root_motion_bone_index: i32,
+    pub root_motion_bone_x: XForm,
+    pub root_motion_skeleton: Skeleton,
+    pub root_motion_skeleton_pose: SkeletonPose,
+    pub root_motion_skeleton_index_array: UnityArray<i32>,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct Skeleton {
+    pub node: UnityArray<SkeletonNode>,
+    pub id: UnityArray<u32>,
+    pub axes_array: UnityArray<Axes>,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct SkeletonNode {
+    pub parent_id: i32,
+    pub axes_id: i32,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct Axes {
+    pub pre_q: Vec4,
+    pub post_q: Vec4,
+    pub sgn: Vec3,
+    pub limit_min: Vec3,
+    pub limit_max: Vec3,
+    pub length: f32,
+    pub axes_type: u32,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct SkeletonPose {
+    pub x: UnityArray<XForm>,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct Human {
+    pub root_x: XForm,
+    pub skeleton: Skeleton,
+    pub skeleton_pose: SkeletonPose,
+    pub left_hand: UnityArray<i32>,
+    pub right_hand: UnityArray<i32>,
+    pub human_bone_index: UnityArray<i32>,
+    pub human_bone_mass: UnityArray<f32>,
+    pub scale: f32,
+    pub arm_twist: f32,
+    pub fore_arm_twist: f32,
+    pub upper_leg_twist: f32,
+    pub leg_twist: f32,
+    pub arm_stretch: f32,
+    pub leg_stretch: f32,
+    pub feet_spacing: f32,
+    pub has_left_hand: u8,
+    pub has_right_hand: u8,
+    pub has_tdof: u8,
+    #[deku(count = "(4 - deku::byte_offset % 4) % 4")] _alignment: Vec<u8>,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(ctx = "endian: Endian, _version: UnityVersion", endian = "endian")]
+pub struct AnimationClip {
+    pub name: CharArray,
+    pub legacy: u8,
+    pub compressed: u8,
+    pub use_high_quality_curve: u8,
+    #[deku(count = "(4 - deku::byte_offset % 4) % 4")] _alignment0: Vec<u8>,
+    pub rotation_curves: UnityArray<QuaternionCurve>,
+    pub compressed_rotation_curves: UnityArray<CompressedAnimationCurve>,
+    pub euler_curves: UnityArray<Vector3Curve>,
+    pub position_curves: UnityArray<Vector3Curve>,
+    pub scale_curves: UnityArray<Vector3Curve>,
+    pub float_curves: UnityArray<FloatCurve>,
+    pub pptr_curves: UnityArray<PPtrCurve>,
+    pub sample_rate: f32,
+    pub wrap_mode: i32,
+    pub bounds: AABB,
+    pub muscle_clip_size: u32,
+    pub muscle_clip: ClipMuscleConstant,
+    pub clip_binding_constant: AnimationClipBindingConstant,
+    pub has_generic_root_transform: u8,
+    pub has_motion_float_curves: u8,
+    #[deku(count = "(4 - deku::byte_offset % 4) % 4")] _alignment1: Vec<u8>,
+    pub events: UnityArray<AnimationEvent>,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct Keyframe<T> where T: for<'a> DekuReader<'a, Endian> {
+    pub time: f32,
+    pub value: T,
+    pub in_slope: T,
+    pub out_slope: T,
+    pub weighted_mode: i32,
+    pub in_weight: T,
+    pub out_weight: T,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct AnimationCurve<T> where T: for<'a> DekuReader<'a, Endian> {
+    pub curve: UnityArray<Keyframe<T>>,
+    pub pre_infinity: i32,
+    pub post_infinity: i32,
+    pub rotation_order: i32,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct QuaternionCurve {
+    pub curve: AnimationCurve<Quaternion>,
+    pub path: CharArray,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct Vector3Curve {
+    pub curve: AnimationCurve<Vec3>,
+    pub path: CharArray,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct FloatCurve {
+    pub curve: AnimationCurve<f32>,
+    pub attribute: CharArray,
+    pub path: CharArray,
+    pub class_id: i32,
+    pub script: PPtr<()>,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct PPtrKeyframe {
+    pub time: f32,
+    pub value: PPtr<()>,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct PPtrCurve {
+    pub curve: UnityArray<PPtrKeyframe>,
+    pub attribute: CharArray,
+    pub path: CharArray,
+    pub class_id: i32,
+    pub script: PPtr<()>,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct CompressedAnimationCurve {
+    pub path: CharArray,
+    pub times: Packedi32Vec,
+    pub values: PackedQuatVec,
+    pub slopes: Packedf32Vec,
+    pub pre_infinity: i32,
+    pub post_infinity: i32,
+}
+
+// We don't currently sample compressed (legacy) rotation curves, so just keep
+// the packed bits around.
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct PackedQuatVec {
+    pub num_items: u32,
+    pub data: ByteArray,
+    #[deku(count = "(4 - deku::byte_offset % 4) % 4")] _alignment: Vec<u8>,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct ClipMuscleConstant {
+    pub delta_pose: HumanPose,
+    pub start_x: XForm,
+    pub stop_x: XForm,
+    pub left_foot_start_x: XForm,
+    pub right_foot_start_x: XForm,
+    pub average_speed: Vec3,
+    pub clip: Clip,
+    pub start_time: f32,
+    pub stop_time: f32,
+    pub orientation_offset_y: f32,
+    pub level: f32,
+    pub cycle_offset: f32,
+    pub average_angular_speed: f32,
+    pub index_array: UnityArray<i32>,
+    pub value_array_delta: UnityArray<ValueDelta>,
+    pub value_array_reference_pose: UnityArray<f32>,
+    pub mirror: u8,
+    pub loop_time: u8,
+    pub loop_blend: u8,
+    pub loop_blend_orientation: u8,
+    pub loop_blend_position_y: u8,
+    pub loop_blend_position_xz: u8,
+    pub start_at_origin: u8,
+    pub keep_original_orientation: u8,
+    pub keep_original_position_y: u8,
+    pub keep_original_position_xz: u8,
+    pub height_from_feet: u8,
+    #[deku(count = "(4 - deku::byte_offset % 4) % 4")] _alignment: Vec<u8>,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct HumanPose {
+    pub root_x: XForm,
+    pub look_at_position: Vec3,
+    pub look_at_weight: Vec4,
+    pub goal_array: UnityArray<HumanGoal>,
+    pub left_hand_pose: HandPose,
+    pub right_hand_pose: HandPose,
+    pub dof_array: UnityArray<f32>,
+    pub tdof_array: UnityArray<Vec3>,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct HumanGoal {
+    pub x: XForm,
+    pub weight_t: f32,
+    pub weight_r: f32,
+    pub hint_t: Vec3,
+    pub hint_weight_t: f32,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct HandPose {
+    pub grab_x: XForm,
+    pub dof_array: UnityArray<f32>,
+    pub override_: f32,
+    pub close_open: f32,
+    pub in_out: f32,
+    pub grab: f32,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct ValueDelta {
+    pub start: f32,
+    pub stop: f32,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct Clip {
+    pub streamed_clip: StreamedClip,
+    pub dense_clip: DenseClip,
+    pub constant_clip: ConstantClip,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct StreamedClip {
+    pub data: UnityArray<u32>,
+    pub curve_count: u32,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct DenseClip {
+    pub frame_count: i32,
+    pub curve_count: u32,
+    pub sample_rate: f32,
+    pub begin_time: f32,
+    pub sample_array: UnityArray<f32>,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct ConstantClip {
+    pub data: UnityArray<f32>,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct AnimationClipBindingConstant {
+    pub generic_bindings: UnityArray<GenericBinding>,
+    pub pptr_curve_mapping: UnityArray<PPtr<()>>,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct GenericBinding {
+    pub path: u32,
+    pub attribute: u32,
+    pub script: PPtr<()>,
+    pub type_id: i32,
+    pub custom_type: u8,
+    pub is_pptr_curve: u8,
+    #[deku(count = "(4 - deku::byte_offset % 4) % 4")] _alignment: Vec<u8>,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct AnimationEvent {
+    pub time: f32,
+    pub function_name: CharArray,
+    pub data: CharArray,
+    pub object_reference_parameter: PPtr<()>,
+    pub float_parameter: f32,
+    pub int_parameter: i32,
+    pub message_options: i32,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(ctx = "endian: Endian, _version: UnityVersion", endian = "endian")]
+pub struct Terrain {
+    pub game_object: PPtr<GameObject>,
+    pub enabled: u8,
+    #[deku(count = "(4 - deku::byte_offset % 4) % 4")] _alignment: Vec<u8>,
+    pub terrain_data: PPtr<TerrainData>,
+    pub tree_distance: f32,
+    pub tree_billboard_distance: f32,
+    pub tree_cross_fade_length: f32,
+    pub tree_maximum_full_lod_count: i32,
+    pub detail_object_distance: f32,
+    pub detail_object_density: f32,
+    pub heightmap_pixel_error: f32,
+    pub splat_map_distance: f32,
+    pub heightmap_maximum_lod: i32,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(ctx = "endian: Endian, version: UnityVersion", endian = "endian")]
+pub struct TerrainData {
+    pub name: CharArray,
+    pub splat_database: SplatDatabase,
+    #[deku(ctx = "version")]
+    pub detail_database: DetailDatabase,
+    pub heightmap: Heightmap,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct SplatDatabase {
+    pub terrain_layers: UnityArray<PPtr<TerrainLayer>>,
+    pub alpha_textures: UnityArray<PPtr<Texture2D>>,
+    pub alphamap_resolution: i32,
+    pub base_map_resolution: i32,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(ctx = "endian: Endian, version: UnityVersion", endian = "endian")]
+pub struct DetailDatabase {
+    pub patches: UnityArray<DetailPatch>,
+    #[deku(ctx = "version")]
+    pub detail_prototypes: UnityArray<DetailPrototype>,
+    pub patch_count: i32,
+    pub patch_samples: i32,
+    pub random_rotations: UnityArray<Vec3>,
+    pub waving_grass_tint: ColorRGBA,
+    pub waving_grass_strength: f32,
+    pub waving_grass_amount: f32,
+    pub waving_grass_speed: f32,
+    pub tree_instances: UnityArray<TreeInstance>,
+    #[deku(ctx = "version")]
+    pub tree_prototypes: UnityArray<TreePrototype>,
+    pub preload_texture_atlas_data: UnityArray<PPtr<Texture2D>>,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct DetailPatch {
+    pub bounds: AABB,
+    pub layer_indices: UnityArray<u8>,
+    #[deku(count = "(4 - deku::byte_offset % 4) % 4")] _alignment0: Vec<u8>,
+    pub number_of_objects: UnityArray<u8>,
+    #[deku(count = "(4 - deku::byte_offset % 4) % 4")] _alignment1: Vec<u8>,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(ctx = "endian: Endian, version: UnityVersion", endian = "endian")]
+pub struct DetailPrototype {
+    pub prototype: PPtr<GameObject>,
+    pub prototype_texture: PPtr<Texture2D>,
+    pub min_width: f32,
+    pub max_width: f32,
+    pub min_height: f32,
+    pub max_height: f32,
+    #[deku(cond = "version >= UnityVersion::V2020_3_16f1")]
+    pub noise_seed: Option<i32>,
+    pub noise_spread: f32,
+    pub bend_factor: f32,
+    #[deku(cond = "version >= UnityVersion::V2020_3_16f1")]
+    pub hole_edge_padding: Option<f32>,
+    pub healthy_color: ColorRGBA,
+    pub dry_color: ColorRGBA,
+    pub render_mode: i32,
+    pub use_prototype_mesh: i32,
+    #[deku(cond = "version >= UnityVersion::V2021_3_27f1")]
+    pub use_instancing: Option<u8>,
+    #[deku(count = "(4 - deku::byte_offset % 4) % 4")] _alignment: Vec<u8>,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct TreeInstance {
+    // normalized [0, 1] position within the terrain
+    pub position: Vec3,
+    pub width_scale: f32,
+    pub height_scale: f32,
+    pub rotation: f32,
+    pub color: [u8; 4],
+    pub lightmap_color: [u8; 4],
+    pub index: i32,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(ctx = "endian: Endian, version: UnityVersion", endian = "endian")]
+pub struct TreePrototype {
+    pub prototype: PPtr<GameObject>,
+    pub bend_factor: f32,
+    #[deku(cond = "version >= UnityVersion::V2020_3_16f1")]
+    pub nav_mesh_lod: Option<i32>,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct Heightmap {
+    pub heights: UnityArray<i16>,
+    #[deku(count = "(4 - deku::byte_offset % 4) % 4")] _alignment0: Vec<u8>,
+    pub holes: UnityArray<u8>,
+    #[deku(count = "(4 - deku::byte_offset % 4) % 4")] _alignment1: Vec<u8>,
+    pub holes_lod: UnityArray<u8>,
+    #[deku(count = "(4 - deku::byte_offset % 4) % 4")] _alignment2: Vec<u8>,
+    pub enable_holes_texture_compression: u8,
+    #[deku(count = "(4 - deku::byte_offset % 4) % 4")] _alignment3: Vec<u8>,
+    pub precomputed_error: UnityArray<f32>,
+    pub min_max_patch_heights: UnityArray<f32>,
+    pub resolution: i32,
+    pub levels: i32,
+    pub scale: Vec3,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(ctx = "endian: Endian, _version: UnityVersion", endian = "endian")]
+pub struct TerrainLayer {
+    pub name: CharArray,
+    pub diffuse_texture: PPtr<Texture2D>,
+    pub normal_map_texture: PPtr<Texture2D>,
+    pub mask_map_texture: PPtr<Texture2D>,
+    pub tile_size: Vec2,
+    pub tile_offset: Vec2,
+    pub specular: ColorRGBA,
+    pub metallic: f32,
+    pub smoothness: f32,
+    pub normal_scale: f32,
+    pub diffuse_remap_min: Vec4,
+    pub diffuse_remap_max: Vec4,
+    pub mask_map_remap_min: Vec4,
+    pub mask_map_remap_max: Vec4,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(ctx = "endian: Endian, _version: UnityVersion", endian = "endian")]
+pub struct LightmapSettings {
+    pub enlighten_scene_mapping: EnlightenSceneMapping,
+    pub light_probes: PPtr<()>,
+    pub lightmaps: UnityArray<LightmapData>,
+    pub lightmaps_mode: i32,
+    pub gi_settings: GISettings,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct EnlightenSceneMapping {
+    pub renderers: UnityArray<EnlightenRendererInformation>,
+    pub systems: UnityArray<EnlightenSystemInformation>,
+    pub probesets: UnityArray<Hash128>,
+    pub system_atlases: UnityArray<EnlightenSystemAtlasInformation>,
+    pub terrain_chunks: UnityArray<EnlightenTerrainChunksInformation>,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct Hash128 {
+    pub bytes: [u8; 16],
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct EnlightenRendererInformation {
+    pub renderer: PPtr<()>,
+    pub dynamic_lightmap_st_in_system: Vec4,
+    pub system_id: i32,
+    pub instance_hash: Hash128,
+    pub geometry_hash: Hash128,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct EnlightenSystemInformation {
+    pub renderer_index: u32,
+    pub renderer_size: u32,
+    pub atlas_index: i32,
+    pub atlas_offset_x: i32,
+    pub atlas_offset_y: i32,
+    pub input_system_hash: Hash128,
+    pub radiosity_system_hash: Hash128,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct EnlightenSystemAtlasInformation {
+    pub atlas_size: i32,
+    pub atlas_hash: Hash128,
+    pub first_system_id: i32,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct EnlightenTerrainChunksInformation {
+    pub first_system_id: i32,
+    pub num_chunks_in_x: i32,
+    pub num_chunks_in_y: i32,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct LightmapData {
+    pub lightmap: PPtr<Texture2D>,
+    pub dir_lightmap: PPtr<Texture2D>,
+    pub shadow_mask: PPtr<Texture2D>,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct GISettings {
+    pub bounce_scale: f32,
+    pub indirect_output_scale: f32,
+    pub albedo_boost: f32,
+    pub environment_lighting_mode: u32,
+    pub enable_baked_lightmaps: u8,
+    pub enable_realtime_lightmaps: u8,
+    #[deku(count = "(4 - deku::byte_offset % 4) % 4")] _alignment: Vec<u8>,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(ctx = "endian: Endian, _version: UnityVersion", endian = "endian")]
+pub struct Light {
+    pub game_object: PPtr<GameObject>,
+    pub enabled: u8,
+    #[deku(count = "(4 - deku::byte_offset % 4) % 4")] _alignment: Vec<u8>,
+    pub light_type: LightType,
+    pub shape: i32,
+    pub color: ColorRGBA,
+    pub intensity: f32,
+    pub range: f32,
+    pub spot_angle: f32,
+    pub inner_spot_angle: f32,
+    pub cookie_size: f32,
+    pub shadows: ShadowSettings,
+    pub cookie: PPtr<Texture>,
+    pub draw_halo: u8,
+    #[deku(count = "(4 - deku::byte_offset % 4) % 4")] _alignment2: Vec<u8>,
+    pub baking_output: LightBakingOutput,
+    pub flare: PPtr<()>,
+    pub render_mode: i32,
+    pub culling_mask: u32,
+    pub rendering_layer_mask: i32,
+    pub lightmapping: i32,
+}
+
+#[derive(DekuRead, Clone, Copy, Debug)]
+#[repr(i32)]
+#[deku(id_type = "i32", endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub enum LightType {
+    Spot = 0,
+    Directional = 1,
+    Point = 2,
+    Area = 3,
+    Disc = 4,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct ShadowSettings {
+    pub shadow_type: i32,
+    pub resolution: i32,
+    pub custom_resolution: i32,
+    pub strength: f32,
+    pub bias: f32,
+    pub normal_bias: f32,
+    pub near_plane: f32,
+    pub culling_matrix_override: Matrix4x4,
+    pub use_culling_matrix_override: u8,
+    #[deku(count = "(4 - deku::byte_offset % 4) % 4")] _alignment: Vec<u8>,
+}
+
+#[derive(DekuRead, Clone, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
+pub struct LightBakingOutput {
+    pub probe_occlusion_light_index: i32,
+    pub occlusion_mask_channel: i32,
+    pub lightmap_bake_type: i32,
+    pub mixed_lighting_mode: i32,
+    pub is_baked: u8,
+    #[deku(count = "(4 - deku::byte_offset % 4) % 4")] _alignment: Vec<u8>,
+}
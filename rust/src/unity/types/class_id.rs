@@ -3,7 +3,7 @@ use deku::prelude::*;
 
 #[wasm_bindgen(js_name = "UnityClassID")]
 #[derive(DekuRead, Debug, Copy, Clone, PartialEq)]
-#[deku(id_type = "i32")]
+#[deku(id_type = "i32", endian = "endian", ctx = "endian: deku::ctx::Endian", ctx_default = "deku::ctx::Endian::Little")]
 #[repr(i32)]
 pub enum ClassID {
     #[deku(id = "-1")] UnknownType,
@@ -411,3 +411,52 @@ pub enum ClassID {
     #[deku(id = "2083778819")] LocalizationAsset,
     #[deku(id = "2089858483")] ScriptedImporter,
 }
+
+impl ClassID {
+    // Variant discriminants don't match the serialized IDs, so go through deku
+    pub fn from_raw(class_id: i32) -> ClassID {
+        match ClassID::from_bytes((&class_id.to_le_bytes(), 0)) {
+            Ok((_, class_id)) => class_id,
+            Err(_) => ClassID::UnknownType,
+        }
+    }
+}
+
+// A class ID as stored in a type list. Before v16, script types are stored
+// with negative IDs, which don't correspond to any ClassID.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SerializedClassID {
+    Known(ClassID),
+    Unknown(i32),
+}
+
+impl SerializedClassID {
+    pub fn from_raw(raw: i32) -> SerializedClassID {
+        if raw < 0 {
+            return SerializedClassID::Unknown(raw);
+        }
+        match ClassID::from_bytes((&raw.to_le_bytes(), 0)) {
+            Ok((_, class_id)) => SerializedClassID::Known(class_id),
+            Err(_) => SerializedClassID::Unknown(raw),
+        }
+    }
+
+    pub fn is_legacy_script_type(self) -> bool {
+        matches!(self, SerializedClassID::Unknown(raw) if raw < 0)
+    }
+
+    pub fn class_id(self) -> ClassID {
+        match self {
+            SerializedClassID::Known(class_id) => class_id,
+            SerializedClassID::Unknown(raw) if raw < 0 => ClassID::MonoBehavior,
+            SerializedClassID::Unknown(_) => ClassID::UnknownType,
+        }
+    }
+}
+
+impl<'a> DekuReader<'a, deku::ctx::Endian> for SerializedClassID {
+    fn from_reader_with_ctx<R: std::io::Read + std::io::Seek>(reader: &mut Reader<R>, endian: deku::ctx::Endian) -> Result<Self, DekuError> {
+        let raw = i32::from_reader_with_ctx(reader, endian)?;
+        Ok(SerializedClassID::from_raw(raw))
+    }
+}
@@ -4,7 +4,7 @@ use std::{collections::HashMap, fmt::Debug, hash::Hash, marker::PhantomData};
 use std::clone::Clone;
 
 use wasm_bindgen::prelude::*;
-use deku::{ctx::BitSize, prelude::*};
+use deku::{ctx::{BitSize, Endian}, prelude::*};
 
 // Important: these must be ordered by chronological release date, so
 // PartialOrd can correctly compare them.
@@ -16,6 +16,35 @@ pub enum UnityVersion {
     V2021_3_27f1,
 }
 
+// Contexts which know the byte order of the file being read
+pub trait EndianCtx: Clone {
+    fn endian(&self) -> Endian;
+}
+
+impl EndianCtx for () {
+    fn endian(&self) -> Endian {
+        Endian::Little
+    }
+}
+
+impl EndianCtx for Endian {
+    fn endian(&self) -> Endian {
+        *self
+    }
+}
+
+impl EndianCtx for (Endian, UnityVersion) {
+    fn endian(&self) -> Endian {
+        self.0
+    }
+}
+
+impl EndianCtx for (i32, Endian) {
+    fn endian(&self) -> Endian {
+        self.1
+    }
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct UnityArray<T> {
     pub values: Vec<T>,
@@ -28,9 +57,9 @@ fn check_count(count: usize, limit: usize) -> Result<(), DekuError> {
     Ok(())
 }
 
-impl<'a, T, Ctx> DekuReader<'a, Ctx> for UnityArray<T> where T: DekuReader<'a, Ctx>, Ctx: Clone {
+impl<'a, T, Ctx> DekuReader<'a, Ctx> for UnityArray<T> where T: DekuReader<'a, Ctx>, Ctx: EndianCtx {
     fn from_reader_with_ctx<R: std::io::Read + std::io::Seek>(reader: &mut Reader<R>, ctx: Ctx) -> Result<Self, DekuError> {
-        let count = i32::from_reader_with_ctx(reader, ())? as usize;
+        let count = i32::from_reader_with_ctx(reader, ctx.endian())? as usize;
         let mut values = Vec::new();
         for _ in 0..count {
             values.push(T::from_reader_with_ctx(reader, ctx.clone())?);
@@ -54,10 +83,10 @@ pub struct Map<K, V> {
 }
 
 impl<'a, K, V, Ctx> DekuReader<'a, Ctx> for Map<K, V>
-    where K: DekuReader<'a, Ctx>, V: DekuReader<'a, Ctx>, Ctx: Clone
+    where K: DekuReader<'a, Ctx>, V: DekuReader<'a, Ctx>, Ctx: EndianCtx
 {
     fn from_reader_with_ctx<R: std::io::Read + std::io::Seek>(reader: &mut Reader<R>, ctx: Ctx) -> Result<Self, DekuError> {
-        let count = i32::from_reader_with_ctx(reader, ())?;
+        let count = i32::from_reader_with_ctx(reader, ctx.endian())?;
         let mut keys = Vec::new();
         let mut values = Vec::new();
         for _ in 0..count {
@@ -87,6 +116,7 @@ impl<PreK, PreV, ResK, ResV> From<Map<PreK, PreV>> for HashMap<ResK, ResV>
 }
 
 #[derive(DekuRead, Clone, Default)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
 pub struct CharArray {
     count: u32,
     #[deku(count = "*count")]
@@ -107,7 +137,7 @@ impl Debug for CharArray {
     }
 }
 
-#[derive(DekuRead, Clone)]
+#[derive(DekuRead, Clone, Default)]
 pub struct NullTerminatedAsciiString {
     #[deku(until = "|v: &u8| *v == 0")]
     pub bytes: Vec<u8>,
@@ -134,6 +164,7 @@ impl Debug for NullTerminatedAsciiString {
 }
 
 #[derive(DekuRead, Debug, Clone, Copy)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
 pub struct PPtr<T> {
     pub file_index: u32,
     pub path_id: i64,
@@ -143,6 +174,7 @@ pub struct PPtr<T> {
 
 #[wasm_bindgen(js_name = "UnityVec4")]
 #[derive(DekuRead, Debug, Copy, Clone)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
 pub struct Vec4 {
     pub x: f32,
     pub y: f32,
@@ -152,6 +184,7 @@ pub struct Vec4 {
 
 #[wasm_bindgen(js_name = "UnityVec3")]
 #[derive(DekuRead, Debug, Copy, Clone)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
 pub struct Vec3 {
     pub x: f32,
     pub y: f32,
@@ -160,6 +193,7 @@ pub struct Vec3 {
 
 #[wasm_bindgen(js_name = "UnityVec2")]
 #[derive(DekuRead, Debug, Copy, Clone)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
 pub struct Vec2 {
     pub x: f32,
     pub y: f32,
@@ -167,6 +201,7 @@ pub struct Vec2 {
 
 #[wasm_bindgen(js_name = "UnityColorRGBA")]
 #[derive(DekuRead, Debug, Copy, Clone)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
 pub struct ColorRGBA {
     pub r: f32,
     pub g: f32,
@@ -176,6 +211,7 @@ pub struct ColorRGBA {
 
 #[wasm_bindgen(js_name = "UnityQuaternion")]
 #[derive(DekuRead, Clone, Copy, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
 pub struct Quaternion {
     pub x: f32,
     pub y: f32,
@@ -185,6 +221,7 @@ pub struct Quaternion {
 
 #[wasm_bindgen(js_name = "UnityAABB")]
 #[derive(DekuRead, Clone, Copy, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
 pub struct AABB {
     pub center: Vec3,
     pub extent: Vec3,
@@ -192,6 +229,7 @@ pub struct AABB {
 
 #[wasm_bindgen(js_name = "UnityMat4")]
 #[derive(DekuRead, Clone, Copy, Debug)]
+#[deku(endian = "endian", ctx = "endian: Endian", ctx_default = "Endian::Little")]
 pub struct Matrix4x4 {
     pub e0: Vec4,
     pub e1: Vec4,
@@ -219,10 +257,10 @@ impl From<Packedi32Vec> for Vec<i32> {
     }
 }
 
-impl<'a, Ctx> DekuReader<'a, Ctx> for Packedi32Vec where Ctx: Clone {
-    fn from_reader_with_ctx<R: std::io::Read + std::io::Seek>(reader: &mut Reader<R>, _ctx: Ctx) -> Result<Self, DekuError> {
-        let num_items = u32::from_reader_with_ctx(reader, ())? as usize;
-        let byte_array_count = u32::from_reader_with_ctx(reader, ())? as usize;
+impl<'a, Ctx> DekuReader<'a, Ctx> for Packedi32Vec where Ctx: EndianCtx {
+    fn from_reader_with_ctx<R: std::io::Read + std::io::Seek>(reader: &mut Reader<R>, ctx: Ctx) -> Result<Self, DekuError> {
+        let num_items = u32::from_reader_with_ctx(reader, ctx.endian())? as usize;
+        let byte_array_count = u32::from_reader_with_ctx(reader, ctx.endian())? as usize;
         reader.seek(SeekFrom::Current(byte_array_count as i64)).unwrap();
         let bit_size: u8 = u8::from_reader_with_ctx(reader, ())?;
         reader.seek(SeekFrom::Current(-(byte_array_count as i64) - 1)).unwrap();
@@ -247,12 +285,13 @@ impl From<Packedf32Vec> for Vec<f32> {
     }
 }
 
-impl<'a, Ctx> DekuReader<'a, Ctx> for Packedf32Vec where Ctx: Clone {
-    fn from_reader_with_ctx<R: std::io::Read + std::io::Seek>(reader: &mut Reader<R>, _ctx: Ctx) -> Result<Self, DekuError> {
-        let num_items = u32::from_reader_with_ctx(reader, ())?;
-        let scale = f32::from_reader_with_ctx(reader, ())?;
-        let start = f32::from_reader_with_ctx(reader, ())?;
-        let byte_array_count = u32::from_reader_with_ctx(reader, ())? as usize;
+impl<'a, Ctx> DekuReader<'a, Ctx> for Packedf32Vec where Ctx: EndianCtx {
+    fn from_reader_with_ctx<R: std::io::Read + std::io::Seek>(reader: &mut Reader<R>, ctx: Ctx) -> Result<Self, DekuError> {
+        let endian = ctx.endian();
+        let num_items = u32::from_reader_with_ctx(reader, endian)?;
+        let scale = f32::from_reader_with_ctx(reader, endian)?;
+        let start = f32::from_reader_with_ctx(reader, endian)?;
+        let byte_array_count = u32::from_reader_with_ctx(reader, endian)? as usize;
         reader.seek(SeekFrom::Current(byte_array_count as i64)).unwrap();
         let bit_size = u8::from_reader_with_ctx(reader, ())?;
         reader.seek(SeekFrom::Current(-(byte_array_count as i64) - 1)).unwrap();
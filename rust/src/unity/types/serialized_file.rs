@@ -1,10 +1,14 @@
+use std::convert::TryFrom;
 use std::fmt::Debug;
+use deku::ctx::Endian;
 use deku::prelude::*;
 
 use crate::unity::types::common::{NullTerminatedAsciiString, UnityArray};
-use crate::unity::types::class_id::ClassID;
+use crate::unity::types::class_id::{ClassID, SerializedClassID};
 
-// Supports v21, v22, and above
+// Supports v9 and above in either byte order. Files older than v9 keep their
+// endianness flag and metadata at the end of the file, see
+// AssetFile::append_metadata_chunk
 
 #[derive(DekuRead, Clone, Debug)]
 #[deku(endian = "big")]
@@ -13,8 +17,10 @@ pub struct SerializedFileHeader {
     pub file_size: u32,
     pub version: i32,
     pub data_offset: u32,
-    #[deku(pad_bytes_after = "3")]
+    #[deku(cond = "*version >= 9", default = "0")]
     pub endianness: u8,
+    #[deku(cond = "*version >= 9", default = "[0; 3]")]
+    _reserved: [u8; 3],
     #[deku(cond = "*version >= 22")]
     pub large_files_metadata_size: Option<u32>,
     #[deku(cond = "*version >= 22")]
@@ -25,37 +31,89 @@ pub struct SerializedFileHeader {
     _unk0: Option<i64>,
 }
 
+impl SerializedFileHeader {
+    pub fn get_endian(&self) -> Endian {
+        if self.endianness == 0 { Endian::Little } else { Endian::Big }
+    }
+}
+
 #[derive(DekuRead, Clone, Debug)]
-#[deku(ctx = "version: i32")]
+#[deku(ctx = "version: i32, endian: Endian")]
 pub struct SerializedFileMetadata {
+    #[deku(cond = "version >= 7")]
     pub version_ascii: NullTerminatedAsciiString,
+    #[deku(cond = "version >= 8", endian = "endian")]
     pub target_platform: u32,
+    #[deku(cond = "version >= 13", default = "1")]
     pub enable_type_tree: u8,
+    #[deku(endian = "endian")]
     type_tree_count: i32,
-    #[deku(count = "*type_tree_count", ctx = "*enable_type_tree > 0")]
+    #[deku(count = "*type_tree_count", ctx = "version, endian, *enable_type_tree > 0")]
     pub type_tree: Vec<SerializedType>,
+    #[deku(cond = "(7..14).contains(&version)", endian = "endian")]
+    big_id_enabled: i32,
+    #[deku(endian = "endian")]
     object_count: i32,
-    #[deku(count = "(4 - deku::byte_offset % 4) % 4")] _alignment: Vec<u8>,
-    #[deku(ctx = "version", count = "*object_count")]
+    #[deku(ctx = "version, endian, *big_id_enabled != 0", count = "*object_count")]
     pub objects: Vec<ObjectInfo>,
+    #[deku(cond = "version >= 11", default = "UnityArray { values: Vec::new() }", ctx = "(version, endian)")]
     pub script_types: UnityArray<LocalSerializedObjectIdentifier>,
+    #[deku(ctx = "(version, endian)")]
     pub externals: UnityArray<FileIdentifier>,
+    #[deku(cond = "version >= 20", endian = "endian")]
     ref_types_count: i32,
-    #[deku(count = "*ref_types_count", ctx = "*enable_type_tree > 0")]
+    #[deku(count = "*ref_types_count", ctx = "version, endian, *enable_type_tree > 0")]
     pub ref_types: Vec<SerializedTypeReference>,
+    #[deku(cond = "version >= 5")]
     pub user_information: NullTerminatedAsciiString,
 }
 
+impl SerializedFileMetadata {
+    // Before v16, objects refer to their type by class ID rather than by
+    // index into the type list
+    pub fn get_object_type(&self, version: i32, obj: &ObjectInfo) -> Option<&SerializedType> {
+        if version < 16 {
+            let class_id = SerializedClassID::from_raw(obj.serialized_type_index);
+            self.type_tree.iter()
+                .find(|serialized_type| serialized_type.header.raw_type_id == class_id)
+        } else {
+            usize::try_from(obj.serialized_type_index).ok()
+                .and_then(|index| self.type_tree.get(index))
+        }
+    }
+}
+
 #[derive(DekuRead, Clone, Debug)]
-#[deku(ctx = "version: i32")]
+#[deku(ctx = "version: i32, endian: Endian, big_id_enabled: bool")]
 pub struct ObjectInfo {
+    #[deku(cond = "version >= 14", count = "(4 - deku::byte_offset % 4) % 4")] _alignment: Vec<u8>,
+    #[deku(reader = "read_object_file_id(deku::reader, version >= 14 || big_id_enabled, endian)")]
     pub file_id: i64,
-    #[deku(cond = "version <= 21")]
+    #[deku(cond = "version <= 21", endian = "endian")]
     pub small_file_byte_start: Option<u32>,
-    #[deku(cond = "version >= 22")]
+    #[deku(cond = "version >= 22", endian = "endian")]
     pub large_file_byte_start: Option<i64>,
+    #[deku(endian = "endian")]
     pub byte_size: i32,
+    // the class ID before v16
+    #[deku(endian = "endian")]
     pub serialized_type_index: i32,
+    #[deku(cond = "version < 16", endian = "endian")]
+    pub class_id: Option<u16>,
+    #[deku(cond = "version < 11", endian = "endian")]
+    pub is_destroyed: Option<u16>,
+    #[deku(cond = "(11..17).contains(&version)", endian = "endian")]
+    pub script_type_index: Option<i16>,
+    #[deku(cond = "version == 15 || version == 16")]
+    pub stripped: Option<u8>,
+}
+
+fn read_object_file_id<R: std::io::Read + std::io::Seek>(reader: &mut Reader<R>, is_large: bool, endian: Endian) -> Result<i64, DekuError> {
+    if is_large {
+        i64::from_reader_with_ctx(reader, endian)
+    } else {
+        Ok(i32::from_reader_with_ctx(reader, endian)? as i64)
+    }
 }
 
 impl ObjectInfo {
@@ -68,44 +126,52 @@ impl ObjectInfo {
 }
 
 #[derive(DekuRead, Clone, Debug)]
+#[deku(ctx = "version: i32, endian: Endian")]
 pub struct LocalSerializedObjectIdentifier {
+    #[deku(endian = "endian")]
     pub local_serialized_file_index: i32,
+    #[deku(cond = "version >= 14", count = "(4 - deku::byte_offset % 4) % 4")] _alignment: Vec<u8>,
+    #[deku(reader = "read_object_file_id(deku::reader, version >= 14, endian)")]
     pub local_identifier_in_file: i64,
 }
 
 #[derive(DekuRead, Clone, Debug)]
-#[deku(ctx = "has_type_tree: bool")]
+#[deku(ctx = "version: i32, endian: Endian, has_type_tree: bool")]
 pub struct SerializedTypeHeader {
-    pub raw_type_id: ClassID,
+    #[deku(ctx = "endian")]
+    pub raw_type_id: SerializedClassID,
+    #[deku(cond = "version >= 16")]
     pub is_stripped_type: u8,
+    #[deku(cond = "version >= 17", default = "-1", endian = "endian")]
     pub script_type_index: i16,
-    #[deku(cond = "*raw_type_id == ClassID::MonoBehavior")]
+    #[deku(cond = "version >= 13 && ((version < 16 && raw_type_id.is_legacy_script_type()) || (version >= 16 && *raw_type_id == SerializedClassID::Known(ClassID::MonoBehavior)))")]
     pub script_id: Option<[u8; 16]>,
+    #[deku(cond = "version >= 13")]
     pub old_type_hash: [u8; 16],
-    #[deku(cond = "has_type_tree")]
+    #[deku(cond = "has_type_tree && (version >= 12 || version == 10)", ctx = "version, endian")]
     pub old_type: Option<OldSerializedType>,
+    #[deku(cond = "has_type_tree && version < 12 && version != 10", ctx = "version, endian")]
+    pub legacy_type: Option<LegacyTypeTreeNode>,
 }
 
 #[derive(DekuRead, Clone, Debug)]
-#[deku(ctx = "has_type_tree: bool")]
+#[deku(ctx = "version: i32, endian: Endian, has_type_tree: bool")]
 pub struct SerializedType {
-    #[deku(ctx = "has_type_tree")]
+    #[deku(ctx = "version, endian, has_type_tree")]
     pub header: SerializedTypeHeader,
-    #[deku(cond = "has_type_tree", default = "0")]
+    #[deku(cond = "has_type_tree && version >= 21", endian = "endian")]
     type_dependencies_count: i32,
-    #[deku(count = "*type_dependencies_count")]
+    #[deku(count = "*type_dependencies_count", endian = "endian")]
     pub type_dependencies: Vec<i32>,
 }
 
 #[derive(DekuRead, Clone, Debug)]
-#[deku(ctx = "has_type_tree: bool")]
+#[deku(ctx = "version: i32, endian: Endian, has_type_tree: bool")]
 pub struct SerializedTypeReference {
-    #[deku(ctx = "has_type_tree")]
+    #[deku(ctx = "version, endian, has_type_tree")]
     pub header: SerializedTypeHeader,
-    #[deku(cond = "has_type_tree", default = "0")]
-    type_dependencies_count: i32,
-    #[deku(count = "*type_dependencies_count")]
-    pub type_dependencies: Vec<i32>,
+    #[deku(cond = "has_type_tree && version >= 21")]
+    pub dependency: Option<SerializedTypeReferenceDependency>,
 }
 
 #[derive(DekuRead, Clone, Debug)]
@@ -115,30 +181,67 @@ pub struct SerializedTypeReferenceDependency {
     pub asm_name: NullTerminatedAsciiString,
 }
 
+// The flattened "blob" type tree format used by v10 and v12+
 #[derive(DekuRead, Clone, Debug)]
+#[deku(ctx = "version: i32, endian: Endian")]
 pub struct OldSerializedType {
+    #[deku(endian = "endian")]
     nodes_count: i32,
+    #[deku(endian = "endian")]
     string_buffer_size: i32,
-    #[deku(count = "*nodes_count")]
-    nodes: Vec<TreeTypeNode>,
+    #[deku(count = "*nodes_count", ctx = "version, endian")]
+    pub nodes: Vec<TreeTypeNode>,
     #[deku(count = "*string_buffer_size")]
-    string_buffer: Vec<u8>,
+    pub string_buffer: Vec<u8>,
 }
 
 #[derive(DekuRead, Clone, Debug)]
+#[deku(ctx = "format_version: i32, endian: Endian")]
 pub struct TreeTypeNode {
-    version: u16,
-    level: u8,
-    type_flags: u8,
-    type_string_offset: u32,
-    name_string_offset: u32,
-    byte_size: i32,
-    index: i32,
-    meta_flags: u32,
-    ref_type_hash: u64,
+    #[deku(endian = "endian")]
+    pub version: u16,
+    pub level: u8,
+    pub type_flags: u8,
+    #[deku(endian = "endian")]
+    pub type_string_offset: u32,
+    #[deku(endian = "endian")]
+    pub name_string_offset: u32,
+    #[deku(endian = "endian")]
+    pub byte_size: i32,
+    #[deku(endian = "endian")]
+    pub index: i32,
+    #[deku(endian = "endian")]
+    pub meta_flags: u32,
+    #[deku(cond = "format_version >= 19", endian = "endian")]
+    pub ref_type_hash: u64,
 }
 
+// The recursive type tree format used before v12 (other than v10)
 #[derive(DekuRead, Clone, Debug)]
+#[deku(ctx = "version: i32, endian: Endian")]
+pub struct LegacyTypeTreeNode {
+    pub type_name: NullTerminatedAsciiString,
+    pub name: NullTerminatedAsciiString,
+    #[deku(endian = "endian")]
+    pub byte_size: i32,
+    #[deku(cond = "version == 2", endian = "endian")]
+    pub variable_count: i32,
+    #[deku(cond = "version != 3", endian = "endian")]
+    pub index: i32,
+    #[deku(endian = "endian")]
+    pub type_flags: i32,
+    #[deku(endian = "endian")]
+    pub node_version: i32,
+    #[deku(cond = "version != 3", endian = "endian")]
+    pub meta_flags: u32,
+    #[deku(endian = "endian")]
+    children_count: i32,
+    #[deku(count = "*children_count", ctx = "version, endian")]
+    pub children: Vec<LegacyTypeTreeNode>,
+}
+
+#[derive(DekuRead, Clone, Debug, Default)]
+#[deku(endian = "endian", ctx = "endian: Endian")]
 pub struct Guid {
     pub data0: u32,
     pub data1: u32,
@@ -147,9 +250,13 @@ pub struct Guid {
 }
 
 #[derive(DekuRead, Clone, Debug)]
+#[deku(ctx = "version: i32, endian: Endian")]
 pub struct FileIdentifier {
+    #[deku(cond = "version >= 6")]
     pub asset_path_ascii: NullTerminatedAsciiString,
+    #[deku(cond = "version >= 5", ctx = "endian")]
     pub guid: Guid,
+    #[deku(cond = "version >= 5", endian = "endian")]
     pub file_type: i32,
     pub path_name_ascii: NullTerminatedAsciiString,
 }
@@ -1,8 +1,8 @@
 use std::collections::HashMap;
 use std::io::Cursor;
 
+use deku::ctx::Endian;
 use deku::reader::Reader;
-use deku::DekuContainerRead;
 use noclip_macros::{FromStructPerField, FromEnumPerVariant};
 use wasm_bindgen::prelude::*;
 use deku::DekuReader;
@@ -16,9 +16,14 @@ macro_rules! define_create {
         #[wasm_bindgen(js_class = $u)]
         impl $t {
             pub fn create(version: UnityVersion, data: &[u8]) -> Result<$t, String> {
+                $t::create_with_endian(version, false, data)
+            }
+
+            pub fn create_with_endian(version: UnityVersion, big_endian: bool, data: &[u8]) -> Result<$t, String> {
+                let endian = if big_endian { Endian::Big } else { Endian::Little };
                 let mut cursor = Cursor::new(data);
                 let mut reader = Reader::new(&mut cursor);
-                match binary::$t::from_reader_with_ctx(&mut reader, version) {
+                match binary::$t::from_reader_with_ctx(&mut reader, (endian, version)) {
                     Ok(value) => Ok(value.into()),
                     Err(err) => return Err(format!("Couldn't create {}: {:?}", $u, err)),
                 }
@@ -161,8 +166,15 @@ pub struct Shader {
 
 #[wasm_bindgen(js_class = "UnityShader")]
 impl Shader {
-    pub fn create(_version: UnityVersion, data: &[u8]) -> Result<Self, String> {
-        let (_, name) = CharArray::from_bytes((data, 0))
+    pub fn create(version: UnityVersion, data: &[u8]) -> Result<Self, String> {
+        Shader::create_with_endian(version, false, data)
+    }
+
+    pub fn create_with_endian(_version: UnityVersion, big_endian: bool, data: &[u8]) -> Result<Self, String> {
+        let endian = if big_endian { Endian::Big } else { Endian::Little };
+        let mut cursor = Cursor::new(data);
+        let mut reader = Reader::new(&mut cursor);
+        let name = CharArray::from_reader_with_ctx(&mut reader, endian)
             .map_err(|err| format!("{:?}", err))?;
         Ok(Shader {
             name: name.into(),
@@ -233,6 +245,20 @@ impl Mesh {
         }
     }
 
+    pub fn unpack_bone_weights(&self) -> Option<Vec<f32>> {
+        match self.mesh_compression {
+            MeshCompression::Off => None,
+            _ => Some(self.compressed_mesh.unpack_skin().0)
+        }
+    }
+
+    pub fn unpack_bone_indices(&self) -> Option<Vec<i32>> {
+        match self.mesh_compression {
+            MeshCompression::Off => None,
+            _ => Some(self.compressed_mesh.unpack_skin().1)
+        }
+    }
+
     pub fn get_vertex_data(&self) -> Vec<u8> {
         self.vertex_data.data.clone()
     }
@@ -311,6 +337,43 @@ impl CompressedMesh {
         }
         result
     }
+
+    // Returns 4 weights and 4 bone indices per vertex. Weights are packed as
+    // 5-bit fractions of 31, with the 4th weight (when present) implied by the
+    // first three.
+    pub fn unpack_skin(&self) -> (Vec<f32>, Vec<i32>) {
+        let n = self.vertices.len() / 3;
+        let mut weights = vec![0.0; 4 * n];
+        let mut indices = vec![0; 4 * n];
+        let mut vertex = 0;
+        let mut bone_index_pos = 0;
+        let mut j = 0;
+        let mut sum = 0;
+        for &weight in &self.weights {
+            if vertex >= n || bone_index_pos >= self.bone_indices.len() {
+                break;
+            }
+            weights[4*vertex + j] = weight as f32 / 31.0;
+            indices[4*vertex + j] = self.bone_indices[bone_index_pos];
+            bone_index_pos += 1;
+            j += 1;
+            sum += weight;
+
+            if sum >= 31 {
+                vertex += 1;
+                j = 0;
+                sum = 0;
+            } else if j == 3 {
+                weights[4*vertex + j] = (31 - sum) as f32 / 31.0;
+                indices[4*vertex + j] = self.bone_indices.get(bone_index_pos).cloned().unwrap_or(0);
+                bone_index_pos += 1;
+                vertex += 1;
+                j = 0;
+                sum = 0;
+            }
+        }
+        (weights, indices)
+    }
 }
 
 #[wasm_bindgen(js_name = "UnityVertexData", getter_with_clone)]
@@ -555,6 +618,8 @@ pub enum TextureFormat {
     DXT5 = 12,
     // RGBA 16 bit (4444) texture format.
     RGBA16 = 13,
+    // BGRA 32 bit texture format.
+    BGRA32 = 14,
 
     // R 16 bit texture format.
     RHalf = 15,
@@ -570,6 +635,9 @@ pub enum TextureFormat {
     // RGBA 128 bit texture format.
     RGBAFloat = 20,
 
+    // YUV 4:2:2 texture format.
+    YUY2 = 21,
+
     // RGB 32 bit packed float format.
     RGB9E5 = 22,
 
@@ -586,8 +654,20 @@ pub enum TextureFormat {
     DXT1Crunched = 28,
     // DXT5 crunched texture format.
     DXT5Crunched = 29,
+    // PowerVR (iOS) 2 bits/pixel compressed color texture format.
+    PvrtcRGB2 = 30,
+    // PowerVR (iOS) 2 bits/pixel compressed with alpha channel texture format.
+    PvrtcRGBA2 = 31,
+    // PowerVR (iOS) 4 bits/pixel compressed color texture format.
+    PvrtcRGB4 = 32,
+    // PowerVR (iOS) 4 bits/pixel compressed with alpha channel texture format.
+    PvrtcRGBA4 = 33,
     // ETC (GLES2.0) 4 bits/pixel compressed RGB texture format.
     EtcRGB4 = 34,
+    // ATC (Adreno) 4 bits/pixel compressed RGB texture format.
+    AtcRGB4 = 35,
+    // ATC (Adreno) 8 bits/pixel compressed RGBA texture format.
+    AtcRGBA8 = 36,
     // EAC 4 bits/pixel compressed 16-bit R texture format
     EacR = 41,
     // EAC 4 bits/pixel compressed 16-bit signed R texture format
@@ -693,6 +773,17 @@ pub struct MeshRenderer {
     pub additional_vertex_streams: WasmFriendlyPPtr,
 }
 
+#[wasm_bindgen(js_class = "UnityMeshRenderer")]
+impl MeshRenderer {
+    pub fn has_lightmap(&self) -> bool {
+        is_lightmap_index_valid(self.lightmap_index)
+    }
+
+    pub fn apply_lightmap_scale_offset(&self, uvs: &mut [f32]) {
+        apply_lightmap_scale_offset(&self.lightmap_tiling_offset, uvs);
+    }
+}
+
 #[wasm_bindgen(js_name = "UnityStaticBatchInfo", getter_with_clone)]
 #[derive(Clone, Debug, FromStructPerField)]
 #[from(binary::StaticBatchInfo)]
@@ -719,6 +810,382 @@ impl ScriptMapper {
     }
 }
 
+#[wasm_bindgen(js_name = "UnitySkinnedMeshRenderer", getter_with_clone)]
+#[derive(Clone, Debug, FromStructPerField)]
+#[from(binary::SkinnedMeshRenderer)]
+pub struct SkinnedMeshRenderer {
+    pub game_object: WasmFriendlyPPtr,
+    pub enabled: u8,
+    pub cast_shadows: u8,
+    pub receive_shadows: u8,
+    pub lightmap_index: u16,
+    pub lightmap_tiling_offset: Vec4,
+    pub materials: Vec<WasmFriendlyPPtr>,
+    pub static_batch_info: StaticBatchInfo,
+    pub static_batch_root: WasmFriendlyPPtr,
+    pub sorting_layer_id: i32,
+    pub sorting_layer: i16,
+    pub sorting_order: i16,
+    pub quality: i32,
+    pub update_when_offscreen: u8,
+    pub mesh: WasmFriendlyPPtr,
+    pub bones: Vec<WasmFriendlyPPtr>,
+    pub blend_shape_weights: Vec<f32>,
+    pub root_bone: WasmFriendlyPPtr,
+    pub aabb: AABB,
+}
+
+#[wasm_bindgen(js_class = "UnitySkinnedMeshRenderer")]
+impl SkinnedMeshRenderer {
+    pub fn has_lightmap(&self) -> bool {
+        is_lightmap_index_valid(self.lightmap_index)
+    }
+
+    pub fn apply_lightmap_scale_offset(&self, uvs: &mut [f32]) {
+        apply_lightmap_scale_offset(&self.lightmap_tiling_offset, uvs);
+    }
+}
+
+#[wasm_bindgen(js_name = "UnityAnimator", getter_with_clone)]
+#[derive(Clone, Debug, FromStructPerField)]
+#[from(binary::Animator)]
+pub struct Animator {
+    pub game_object: WasmFriendlyPPtr,
+    pub enabled: u8,
+    pub avatar: WasmFriendlyPPtr,
+    pub controller: WasmFriendlyPPtr,
+    pub culling_mode: i32,
+    pub update_mode: i32,
+    pub apply_root_motion: u8,
+    pub has_transform_hierarchy: u8,
+}
+
+#[wasm_bindgen(js_name = "UnityAvatar", getter_with_clone)]
+#[derive(Clone, Debug)]
+pub struct Avatar {
+    pub name: String,
+    tos: HashMap<u32, String>,
+    skeleton: binary::Skeleton,
+    default_pose: Vec<binary::XForm>,
+}
+
+impl From<binary::Avatar> for Avatar {
+    fn from(value: binary::Avatar) -> Self {
+        Self {
+            name: value.name.into(),
+            tos: value.tos.into(),
+            skeleton: value.avatar.avatar_skeleton,
+            default_pose: value.avatar.default_pose.x.values,
+        }
+    }
+}
+
+#[wasm_bindgen(js_class = "UnityAvatar")]
+impl Avatar {
+    // The "table of strings", mapping CRC32 hashes of transform paths (as
+    // used by AnimationClip bindings) back to their paths
+    pub fn get_path(&self, path_hash: u32) -> Option<String> {
+        self.tos.get(&path_hash).cloned()
+    }
+
+    pub fn get_path_hashes(&self) -> Vec<u32> {
+        self.tos.keys().cloned().collect()
+    }
+
+    pub fn get_skeleton_ids(&self) -> Vec<u32> {
+        self.skeleton.id.values.clone()
+    }
+
+    pub fn get_skeleton_parents(&self) -> Vec<i32> {
+        self.skeleton.node.values.iter().map(|node| node.parent_id).collect()
+    }
+
+    // Per skeleton node translation (3 floats), rotation (4 floats) and scale (3 floats)
+    pub fn get_default_pose(&self) -> Vec<f32> {
+        let mut result = Vec::with_capacity(self.default_pose.len() * 10);
+        for x in &self.default_pose {
+            result.extend([x.t.x, x.t.y, x.t.z]);
+            result.extend([x.q.x, x.q.y, x.q.z, x.q.w]);
+            result.extend([x.s.x, x.s.y, x.s.z]);
+        }
+        result
+    }
+}
+
+#[wasm_bindgen(js_name = "UnityAnimationClip", getter_with_clone)]
+#[derive(Clone, Debug)]
+pub struct AnimationClip {
+    pub name: String,
+    pub legacy: bool,
+    pub sample_rate: f32,
+    pub wrap_mode: i32,
+    pub start_time: f32,
+    pub stop_time: f32,
+    pub loop_time: bool,
+    pub(crate) clip: binary::Clip,
+    pub(crate) bindings: Vec<binary::GenericBinding>,
+    pub(crate) rotation_curves: Vec<binary::QuaternionCurve>,
+    pub(crate) euler_curves: Vec<binary::Vector3Curve>,
+    pub(crate) position_curves: Vec<binary::Vector3Curve>,
+    pub(crate) scale_curves: Vec<binary::Vector3Curve>,
+}
+
+impl From<binary::AnimationClip> for AnimationClip {
+    fn from(value: binary::AnimationClip) -> Self {
+        let muscle_clip = value.muscle_clip;
+        Self {
+            name: value.name.into(),
+            legacy: value.legacy != 0,
+            sample_rate: value.sample_rate,
+            wrap_mode: value.wrap_mode,
+            start_time: muscle_clip.start_time,
+            stop_time: muscle_clip.stop_time,
+            loop_time: muscle_clip.loop_time != 0,
+            clip: muscle_clip.clip,
+            bindings: value.clip_binding_constant.generic_bindings.values,
+            rotation_curves: value.rotation_curves.values,
+            euler_curves: value.euler_curves.values,
+            position_curves: value.position_curves.values,
+            scale_curves: value.scale_curves.values,
+        }
+    }
+}
+
+#[wasm_bindgen(js_class = "UnityAnimationClip")]
+impl AnimationClip {
+    pub fn get_binding_path_hashes(&self) -> Vec<u32> {
+        self.bindings.iter().map(|binding| binding.path).collect()
+    }
+
+    pub fn get_duration(&self) -> f32 {
+        self.stop_time - self.start_time
+    }
+}
+
+#[wasm_bindgen(js_name = "UnityTerrain", getter_with_clone)]
+#[derive(Clone, Debug, FromStructPerField)]
+#[from(binary::Terrain)]
+pub struct Terrain {
+    pub game_object: WasmFriendlyPPtr,
+    pub enabled: u8,
+    pub terrain_data: WasmFriendlyPPtr,
+    pub tree_distance: f32,
+    pub tree_billboard_distance: f32,
+    pub detail_object_distance: f32,
+    pub detail_object_density: f32,
+    pub heightmap_pixel_error: f32,
+    pub heightmap_maximum_lod: i32,
+}
+
+#[wasm_bindgen(js_name = "UnityTerrainLayer", getter_with_clone)]
+#[derive(Clone, Debug, FromStructPerField)]
+#[from(binary::TerrainLayer)]
+pub struct TerrainLayer {
+    pub name: String,
+    pub diffuse_texture: WasmFriendlyPPtr,
+    pub normal_map_texture: WasmFriendlyPPtr,
+    pub mask_map_texture: WasmFriendlyPPtr,
+    pub tile_size: Vec2,
+    pub tile_offset: Vec2,
+    pub specular: ColorRGBA,
+    pub metallic: f32,
+    pub smoothness: f32,
+    pub normal_scale: f32,
+    pub diffuse_remap_min: Vec4,
+    pub diffuse_remap_max: Vec4,
+}
+
+#[wasm_bindgen(js_name = "UnityTerrainData", getter_with_clone)]
+#[derive(Clone, Debug)]
+pub struct TerrainData {
+    pub name: String,
+    pub terrain_layers: Vec<WasmFriendlyPPtr>,
+    pub alpha_textures: Vec<WasmFriendlyPPtr>,
+    pub alphamap_resolution: i32,
+    pub base_map_resolution: i32,
+    pub detail_prototypes: Vec<TerrainDetailPrototype>,
+    pub tree_prototypes: Vec<WasmFriendlyPPtr>,
+    pub heightmap_resolution: i32,
+    pub heightmap_scale: Vec3,
+    pub(crate) heights: Vec<i16>,
+    pub(crate) holes: Vec<u8>,
+    pub(crate) detail_patches: Vec<binary::DetailPatch>,
+    pub(crate) detail_patch_count: usize,
+    pub(crate) detail_patch_samples: usize,
+    pub(crate) tree_instances: Vec<binary::TreeInstance>,
+}
+
+impl From<binary::TerrainData> for TerrainData {
+    fn from(value: binary::TerrainData) -> Self {
+        let splat = value.splat_database;
+        let detail = value.detail_database;
+        let heightmap = value.heightmap;
+        Self {
+            name: value.name.into(),
+            terrain_layers: splat.terrain_layers.into(),
+            alpha_textures: splat.alpha_textures.into(),
+            alphamap_resolution: splat.alphamap_resolution,
+            base_map_resolution: splat.base_map_resolution,
+            detail_prototypes: detail.detail_prototypes.into(),
+            tree_prototypes: detail.tree_prototypes.values.into_iter()
+                .map(|prototype| prototype.prototype.into())
+                .collect(),
+            heightmap_resolution: heightmap.resolution,
+            heightmap_scale: heightmap.scale,
+            heights: heightmap.heights.values,
+            holes: heightmap.holes.values,
+            detail_patches: detail.patches.values,
+            detail_patch_count: detail.patch_count as usize,
+            detail_patch_samples: detail.patch_samples as usize,
+            tree_instances: detail.tree_instances.values,
+        }
+    }
+}
+
+#[wasm_bindgen(js_name = "UnityTerrainDetailPrototype", getter_with_clone)]
+#[derive(Clone, Debug)]
+pub struct TerrainDetailPrototype {
+    pub prototype: WasmFriendlyPPtr,
+    pub prototype_texture: WasmFriendlyPPtr,
+    pub min_width: f32,
+    pub max_width: f32,
+    pub min_height: f32,
+    pub max_height: f32,
+    pub noise_spread: f32,
+    pub healthy_color: ColorRGBA,
+    pub dry_color: ColorRGBA,
+    pub render_mode: i32,
+    pub use_prototype_mesh: bool,
+}
+
+impl From<binary::DetailPrototype> for TerrainDetailPrototype {
+    fn from(value: binary::DetailPrototype) -> Self {
+        Self {
+            prototype: value.prototype.into(),
+            prototype_texture: value.prototype_texture.into(),
+            min_width: value.min_width,
+            max_width: value.max_width,
+            min_height: value.min_height,
+            max_height: value.max_height,
+            noise_spread: value.noise_spread,
+            healthy_color: value.healthy_color,
+            dry_color: value.dry_color,
+            render_mode: value.render_mode,
+            use_prototype_mesh: value.use_prototype_mesh != 0,
+        }
+    }
+}
+
+#[wasm_bindgen(js_name = "UnityLightmapSettings", getter_with_clone)]
+#[derive(Clone, Debug)]
+pub struct LightmapSettings {
+    pub lightmaps: Vec<LightmapData>,
+    pub lightmaps_mode: i32,
+    pub light_probes: WasmFriendlyPPtr,
+}
+
+impl From<binary::LightmapSettings> for LightmapSettings {
+    fn from(value: binary::LightmapSettings) -> Self {
+        Self {
+            lightmaps: value.lightmaps.into(),
+            lightmaps_mode: value.lightmaps_mode,
+            light_probes: value.light_probes.into(),
+        }
+    }
+}
+
+#[wasm_bindgen(js_class = "UnityLightmapSettings")]
+impl LightmapSettings {
+    pub fn get_lightmap(&self, lightmap_index: u16) -> Option<LightmapData> {
+        if is_lightmap_index_valid(lightmap_index) {
+            self.lightmaps.get(lightmap_index as usize).cloned()
+        } else {
+            None
+        }
+    }
+
+    pub fn get_renderer_lightmap(&self, renderer: &MeshRenderer) -> Option<LightmapData> {
+        self.get_lightmap(renderer.lightmap_index)
+    }
+}
+
+// 0xFFFF marks renderers without a lightmap, 0xFFFE ones that only use
+// realtime GI
+fn is_lightmap_index_valid(lightmap_index: u16) -> bool {
+    lightmap_index < 0xFFFE
+}
+
+// Transforms the renderer's lightmap UVs (UV1, or UV0 if the mesh has none)
+// into its region of the lightmap atlas
+fn apply_lightmap_scale_offset(scale_offset: &Vec4, uvs: &mut [f32]) {
+    for uv in uvs.chunks_exact_mut(2) {
+        uv[0] = uv[0] * scale_offset.x + scale_offset.z;
+        uv[1] = uv[1] * scale_offset.y + scale_offset.w;
+    }
+}
+
+#[wasm_bindgen(js_name = "UnityLightmapData", getter_with_clone)]
+#[derive(Clone, Debug, FromStructPerField)]
+#[from(binary::LightmapData)]
+pub struct LightmapData {
+    pub lightmap: WasmFriendlyPPtr,
+    pub dir_lightmap: WasmFriendlyPPtr,
+    pub shadow_mask: WasmFriendlyPPtr,
+}
+
+#[wasm_bindgen(js_name = "UnityLight", getter_with_clone)]
+#[derive(Clone, Debug)]
+pub struct Light {
+    pub game_object: WasmFriendlyPPtr,
+    pub enabled: u8,
+    pub light_type: LightType,
+    pub color: ColorRGBA,
+    pub intensity: f32,
+    pub range: f32,
+    pub spot_angle: f32,
+    pub inner_spot_angle: f32,
+    pub cookie: WasmFriendlyPPtr,
+    pub draw_halo: bool,
+    pub shadow_type: i32,
+    pub shadow_strength: f32,
+    pub culling_mask: u32,
+    pub lightmap_bake_type: i32,
+    pub is_baked: bool,
+}
+
+impl From<binary::Light> for Light {
+    fn from(value: binary::Light) -> Self {
+        Self {
+            game_object: value.game_object.into(),
+            enabled: value.enabled,
+            light_type: value.light_type.into(),
+            color: value.color,
+            intensity: value.intensity,
+            range: value.range,
+            spot_angle: value.spot_angle,
+            inner_spot_angle: value.inner_spot_angle,
+            cookie: value.cookie.into(),
+            draw_halo: value.draw_halo != 0,
+            shadow_type: value.shadows.shadow_type,
+            shadow_strength: value.shadows.strength,
+            culling_mask: value.culling_mask,
+            lightmap_bake_type: value.baking_output.lightmap_bake_type,
+            is_baked: value.baking_output.is_baked != 0,
+        }
+    }
+}
+
+#[wasm_bindgen(js_name = "UnityLightType")]
+#[derive(FromEnumPerVariant, Clone, Copy, Debug, PartialEq)]
+#[from(binary::LightType)]
+pub enum LightType {
+    Spot = 0,
+    Directional = 1,
+    Point = 2,
+    Area = 3,
+    Disc = 4,
+}
+
 define_create!(GameObject, "UnityGameObject");
 define_create!(Transform, "UnityTransform");
 define_create!(Material, "UnityMaterial");
@@ -728,3 +1195,12 @@ define_create!(Texture2D, "UnityTexture2D");
 define_create!(MeshFilter, "UnityMeshFilter");
 define_create!(MeshRenderer, "UnityMeshRenderer");
 define_create!(ScriptMapper, "UnityScriptMapper");
+define_create!(SkinnedMeshRenderer, "UnitySkinnedMeshRenderer");
+define_create!(Animator, "UnityAnimator");
+define_create!(Avatar, "UnityAvatar");
+define_create!(AnimationClip, "UnityAnimationClip");
+define_create!(Terrain, "UnityTerrain");
+define_create!(TerrainLayer, "UnityTerrainLayer");
+define_create!(TerrainData, "UnityTerrainData");
+define_create!(LightmapSettings, "UnityLightmapSettings");
+define_create!(Light, "UnityLight");
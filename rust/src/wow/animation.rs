@@ -4,6 +4,7 @@ use wasm_bindgen::prelude::*;
 use js_sys::Float32Array;
 use crate::wow::m2::*;
 use crate::wow::common::*;
+use crate::spline::get_point_bezier;
 
 #[derive(Debug, Clone)]
 struct LcgRng {
@@ -59,12 +60,49 @@ pub struct M2Sequence {
 }
 
 impl M2Sequence {
+    // sequences without this flag keep their keyframes in a .anim file,
+    // unless they're just an alias for another sequence
+    pub fn is_external(&self) -> bool {
+        (self.flags & 0x20) == 0 && (self.flags & 0x40) == 0
+    }
+
     fn calculate_animation_repeats(&self, rng: &mut LcgRng) -> i32 {
         let times = (self.replay_max - self.replay_min) as f32;
         self.replay_min as i32 + (times * rng.next_f32()) as i32
     }
 }
 
+// The keyframes for a sequence that's stored outside of the M2. Legacy .anim
+// files are just the raw keyframe data, while chunked ones either have it all
+// in an AFM2 chunk, or split it into AFSB (bones) and AFSA (attachments)
+// chunks. Either way, pointers are relative to the start of the data.
+pub struct AnimFile<'a> {
+    pub bone_data: Option<&'a [u8]>,
+    pub attachment_data: Option<&'a [u8]>,
+}
+
+impl<'a> AnimFile<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        let is_chunked = data.len() >= 8 && matches!(&data[0..4], b"AFM2" | b"AFSA" | b"AFSB");
+        if !is_chunked {
+            return AnimFile { bone_data: Some(data), attachment_data: Some(data) };
+        }
+        let mut anim_file = AnimFile { bone_data: None, attachment_data: None };
+        for (chunk, chunk_data) in ChunkedData::new(data) {
+            match &chunk.magic {
+                b"AFM2" => {
+                    anim_file.bone_data = Some(chunk_data);
+                    anim_file.attachment_data = Some(chunk_data);
+                },
+                b"AFSB" => anim_file.bone_data = Some(chunk_data),
+                b"AFSA" => anim_file.attachment_data = Some(chunk_data),
+                _ => {},
+            }
+        }
+        anim_file
+    }
+}
+
 #[derive(DekuRead, Debug, Clone)]
 pub struct M2TrackPartial<T> {
     pub timestamps_unallocated: WowArray<u16>,
@@ -98,22 +136,54 @@ pub struct M2Track<T> {
     #[deku(skip)] pub timestamps: Option<Vec<Vec<u32>>>,
     pub values_unallocated: WowArray<WowArray<T>>,
     #[deku(skip)] pub values: Option<Vec<Vec<T>>>,
+    #[deku(skip)] pub timestamp_arrays: Option<Vec<WowArray<u32>>>,
+    #[deku(skip)] pub value_arrays: Option<Vec<WowArray<T>>>,
 }
 
 impl<T> M2Track<T> {
     pub fn allocate(&mut self, data: &[u8]) -> Result<(), String> where for<'a> T: DekuReader<'a> {
+        self.allocate_with_external(data, &[])
+    }
+
+    // Skips the keyframes of sequences stored in external .anim files, which
+    // can be filled in later with allocate_external()
+    pub fn allocate_with_external(&mut self, data: &[u8], external: &[bool]) -> Result<(), String> where for<'a> T: DekuReader<'a> {
+        let is_external = |i: usize| self.global_sequence < 0 && external.get(i).cloned().unwrap_or(false);
+
+        let timestamp_arrays = self.timestamps_unallocated.to_vec(data)?;
         let mut timestamps = Vec::new();
-        for arr in self.timestamps_unallocated.to_vec(data)? {
-            timestamps.push(arr.to_vec(data)?);
+        for (i, arr) in timestamp_arrays.iter().enumerate() {
+            timestamps.push(if is_external(i) { Vec::new() } else { arr.to_vec(data)? });
         }
-        self.timestamps = Some(timestamps);
 
+        let value_arrays = self.values_unallocated.to_vec(data)?;
         let mut values = Vec::new();
-        for arr in self.values_unallocated.to_vec(data)? {
-            values.push(arr.to_vec(data)?);
+        for (i, arr) in value_arrays.iter().enumerate() {
+            values.push(if is_external(i) { Vec::new() } else { arr.to_vec(data)? });
         }
+
+        self.timestamps = Some(timestamps);
         self.values = Some(values);
+        self.timestamp_arrays = Some(timestamp_arrays);
+        self.value_arrays = Some(value_arrays);
+        Ok(())
+    }
 
+    // Reads a sequence's keyframes out of its .anim file
+    pub fn allocate_external(&mut self, index: usize, data: &[u8]) -> Result<(), String> where for<'a> T: DekuReader<'a> {
+        let (Some(timestamp_arrays), Some(value_arrays)) = (&self.timestamp_arrays, &self.value_arrays) else {
+            return Err("must call M2Track::allocate() before M2Track::allocate_external()".to_string());
+        };
+        // global sequences always live in the M2 itself
+        if self.global_sequence >= 0 {
+            return Ok(());
+        }
+        if let (Some(timestamps), Some(values)) = (timestamp_arrays.get(index), value_arrays.get(index)) {
+            let timestamps = timestamps.to_vec(data)?;
+            let values = values.to_vec(data)?;
+            self.timestamps.as_mut().unwrap()[index] = timestamps;
+            self.values.as_mut().unwrap()[index] = values;
+        }
         Ok(())
     }
 
@@ -126,6 +196,52 @@ impl<T> M2Track<T> {
     }
 }
 
+// A track with timestamps but no values, used by events
+#[derive(DekuRead, Debug, Clone)]
+pub struct M2TrackBase {
+    pub interpolation_type: u16,
+    pub global_sequence: i16,
+    pub timestamps_unallocated: WowArray<WowArray<u32>>,
+    #[deku(skip)] pub timestamps: Option<Vec<Vec<u32>>>,
+    #[deku(skip)] pub timestamp_arrays: Option<Vec<WowArray<u32>>>,
+}
+
+impl M2TrackBase {
+    pub fn allocate_with_external(&mut self, data: &[u8], external: &[bool]) -> Result<(), String> {
+        let timestamp_arrays = self.timestamps_unallocated.to_vec(data)?;
+        let mut timestamps = Vec::new();
+        for (i, arr) in timestamp_arrays.iter().enumerate() {
+            if self.global_sequence < 0 && external.get(i).cloned().unwrap_or(false) {
+                timestamps.push(Vec::new());
+            } else {
+                timestamps.push(arr.to_vec(data)?);
+            }
+        }
+        self.timestamps = Some(timestamps);
+        self.timestamp_arrays = Some(timestamp_arrays);
+        Ok(())
+    }
+
+    // see M2Track::allocate_external()
+    pub fn allocate_external(&mut self, index: usize, data: &[u8]) -> Result<(), String> {
+        let Some(timestamp_arrays) = &self.timestamp_arrays else {
+            return Err("must call M2TrackBase::allocate() before M2TrackBase::allocate_external()".to_string());
+        };
+        if self.global_sequence >= 0 {
+            return Ok(());
+        }
+        if let Some(timestamps) = timestamp_arrays.get(index) {
+            let timestamps = timestamps.to_vec(data)?;
+            self.timestamps.as_mut().unwrap()[index] = timestamps;
+        }
+        Ok(())
+    }
+
+    pub fn timestamps(&self) -> &Vec<Vec<u32>> {
+        self.timestamps.as_ref().expect("must call M2TrackBase::allocate() before accessing timestamps")
+    }
+}
+
 impl<T> M2Track<T> where T: PartialOrd + Copy {
     pub fn max_value(&self, default: T) -> T {
         let values = self.values();
@@ -141,6 +257,99 @@ impl<T> M2Track<T> where T: PartialOrd + Copy {
     }
 }
 
+// A keyframe for tracks using bezier (2) or hermite (3) interpolation. For
+// bezier keys the tangents are control points, for hermite keys they're
+// derivatives
+#[derive(DekuRead, Debug, Clone, Copy)]
+pub struct M2SplineKey<T> where for<'a> T: DekuReader<'a> {
+    pub value: T,
+    pub in_tan: T,
+    pub out_tan: T,
+}
+
+pub trait SplineInterpolate: Copy + Lerp + for<'a> DekuReader<'a> {
+    fn bezier(p0: Self, p1: Self, p2: Self, p3: Self, t: f32) -> Self;
+    fn hermite(p0: Self, m0: Self, p1: Self, m1: Self, t: f32) -> Self;
+}
+
+fn hermite(p0: f32, m0: f32, p1: f32, m1: f32, t: f32) -> f32 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (2.0 * t3 - 3.0 * t2 + 1.0) * p0
+        + (t3 - 2.0 * t2 + t) * m0
+        + (-2.0 * t3 + 3.0 * t2) * p1
+        + (t3 - t2) * m1
+}
+
+impl SplineInterpolate for f32 {
+    fn bezier(p0: Self, p1: Self, p2: Self, p3: Self, t: f32) -> Self {
+        get_point_bezier(p0, p1, p2, p3, t)
+    }
+
+    fn hermite(p0: Self, m0: Self, p1: Self, m1: Self, t: f32) -> Self {
+        hermite(p0, m0, p1, m1, t)
+    }
+}
+
+impl SplineInterpolate for Vec3 {
+    fn bezier(p0: Self, p1: Self, p2: Self, p3: Self, t: f32) -> Self {
+        Vec3 {
+            x: get_point_bezier(p0.x, p1.x, p2.x, p3.x, t),
+            y: get_point_bezier(p0.y, p1.y, p2.y, p3.y, t),
+            z: get_point_bezier(p0.z, p1.z, p2.z, p3.z, t),
+        }
+    }
+
+    fn hermite(p0: Self, m0: Self, p1: Self, m1: Self, t: f32) -> Self {
+        Vec3 {
+            x: hermite(p0.x, m0.x, p1.x, m1.x, t),
+            y: hermite(p0.y, m0.y, p1.y, m1.y, t),
+            z: hermite(p0.z, m0.z, p1.z, m1.z, t),
+        }
+    }
+}
+
+impl<T> M2Track<M2SplineKey<T>> where T: SplineInterpolate {
+    pub fn get_spline_value(&self, mut animation_index: usize, time: f64, default: T) -> T {
+        if self.timestamps().len() <= animation_index {
+            animation_index = 0;
+        }
+        let Some(times) = self.timestamps().get(animation_index) else {
+            return default;
+        };
+        let keys = &self.values()[animation_index];
+        let Some(time_index) = find_timestamp_index(times, time) else {
+            return default;
+        };
+        if time_index == times.len() - 1 || time < times[time_index] as f64 {
+            return keys[time_index].value;
+        }
+
+        let key1 = &keys[time_index];
+        let key2 = &keys[time_index + 1];
+        let time1 = times[time_index] as f64;
+        let time2 = times[time_index + 1] as f64;
+        let t = if time2 > time1 { ((time - time1) / (time2 - time1)) as f32 } else { 0.0 };
+        match self.interpolation_type {
+            0 => key1.value,
+            1 => key1.value.lerp(key2.value, t),
+            2 => T::bezier(key1.value, key1.out_tan, key2.in_tan, key2.value, t),
+            3 => T::hermite(key1.value, key1.out_tan, key2.value, key2.in_tan, t),
+            _ => unreachable!("unknown interpolation type!"),
+        }
+    }
+
+    // falls back to the first sequence like get_spline_value()
+    pub fn get_duration(&self, mut animation_index: usize) -> u32 {
+        if self.timestamps().len() <= animation_index {
+            animation_index = 0;
+        }
+        self.timestamps().get(animation_index)
+            .and_then(|times| times.last().cloned())
+            .unwrap_or(0)
+    }
+}
+
 #[derive(DekuRead, Debug, Clone)]
 pub struct M2TextureTransform {
     pub translation: M2Track<Vec3>,
@@ -455,10 +664,61 @@ impl AnimationManager {
     pub fn get_num_texture_weights(&self) -> usize {
         self.texture_weights.len()
     }
+
+    pub fn get_sequence_index(&self, id: u16, sub_id: u16) -> Option<usize> {
+        self.sequences.iter().position(|seq| seq.id == id && seq.sub_id == sub_id)
+    }
+
+    pub fn get_current_sequence_duration(&self) -> u32 {
+        self.sequences[self.current_animation.animation_index.unwrap()].duration
+    }
 }
 
 // rust-only interface
 impl AnimationManager {
+    // Fills in the keyframes of a sequence stored in an external .anim file,
+    // see M2::add_external_animation()
+    pub fn add_external_animation(&mut self, index: usize, anim_file: &AnimFile) -> Result<(), String> {
+        let Some(data) = anim_file.bone_data else {
+            return Ok(());
+        };
+
+        for bone in self.bones.iter_mut() {
+            bone.translation.allocate_external(index, data)?;
+            bone.rotation_quat16.allocate_external(index, data)?;
+            bone.scaling.allocate_external(index, data)?;
+
+            // keep the converted rotations in sync
+            let rotation = bone.rotation.as_mut().unwrap();
+            if let (Some(timestamps), Some(quats)) = (bone.rotation_quat16.timestamps().get(index), bone.rotation_quat16.values().get(index)) {
+                rotation.timestamps.as_mut().unwrap()[index] = timestamps.clone();
+                rotation.values.as_mut().unwrap()[index] = quats.iter().map(|quat16| Quat::from(*quat16)).collect();
+            }
+        }
+        for color in self.colors.iter_mut() {
+            color.color.allocate_external(index, data)?;
+            color.alpha.allocate_external(index, data)?;
+        }
+        for weight in self.texture_weights.iter_mut() {
+            weight.allocate_external(index, data)?;
+        }
+        for transform in self.texture_transforms.iter_mut() {
+            transform.translation.allocate_external(index, data)?;
+            transform.rotation.allocate_external(index, data)?;
+            transform.scaling.allocate_external(index, data)?;
+        }
+        for light in self.lights.iter_mut() {
+            light.ambient_color.allocate_external(index, data)?;
+            light.ambient_intensity.allocate_external(index, data)?;
+            light.diffuse_color.allocate_external(index, data)?;
+            light.diffuse_intensity.allocate_external(index, data)?;
+            light.attenuation_start.allocate_external(index, data)?;
+            light.attenuation_end.allocate_external(index, data)?;
+            light.visibility.allocate_external(index, data)?;
+        }
+        Ok(())
+    }
+
     pub fn new(
         global_sequence_durations: Vec<u32>,
         sequences: Vec<M2Sequence>,
@@ -564,6 +824,36 @@ impl AnimationManager {
         result
     }
 
+    // Returns which of a track's animations is playing, and how far into it
+    // we are, without any blending between animations
+    pub fn get_track_time<T>(&self, track: &M2Track<T>) -> (usize, f64) {
+        self.get_animation_time(track.global_sequence, track.timestamps().len())
+    }
+
+    pub fn get_track_base_time(&self, track: &M2TrackBase) -> (usize, f64) {
+        self.get_animation_time(track.global_sequence, track.timestamps().len())
+    }
+
+    // how long the animation a track is currently playing lasts
+    pub fn get_track_base_duration(&self, track: &M2TrackBase) -> f64 {
+        if track.global_sequence >= 0 {
+            self.global_sequence_durations[track.global_sequence as usize] as f64
+        } else {
+            self.get_current_sequence_duration() as f64
+        }
+    }
+
+    fn get_animation_time(&self, global_sequence: i16, num_animations: usize) -> (usize, f64) {
+        if global_sequence >= 0 {
+            return (0, self.global_sequence_times[global_sequence as usize]);
+        }
+        let mut animation_index = self.current_animation.animation_index.unwrap();
+        if num_animations <= animation_index {
+            animation_index = 0;
+        }
+        (animation_index, self.current_animation.animation_time)
+    }
+
     pub fn get_particle_value<T>(&self, age: f64, animation: &M2TrackPartial<T>, default: T) -> T
         where T: Clone + Lerp {
             let num_timestamps = animation.timestamps().len();
@@ -588,7 +878,7 @@ impl AnimationManager {
         }
 }
 
-trait AsTimestamp {
+pub(crate) trait AsTimestamp {
     fn as_timestamp(&self) -> f64;
 }
 
@@ -602,7 +892,7 @@ impl AsTimestamp for u16 {
     }
 }
 
-fn find_timestamp_index<T: AsTimestamp>(timestamps: &Vec<T>, curr_time: f64) -> Option<usize> {
+pub(crate) fn find_timestamp_index<T: AsTimestamp>(timestamps: &Vec<T>, curr_time: f64) -> Option<usize> {
     if timestamps.len() > 1 {
         let last_index = timestamps.len() - 1;
         if curr_time > timestamps[last_index].as_timestamp() {
@@ -623,3 +913,89 @@ fn find_timestamp_index<T: AsTimestamp>(timestamps: &Vec<T>, curr_time: f64) ->
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(magic: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut result = magic.to_vec();
+        result.extend((data.len() as u32).to_le_bytes());
+        result.extend(data);
+        result
+    }
+
+    #[test]
+    fn test_anim_file() {
+        let legacy = [1, 2, 3, 4, 5, 6, 7, 8];
+        let anim_file = AnimFile::new(&legacy);
+        assert_eq!(anim_file.bone_data, Some(&legacy[..]));
+        assert_eq!(anim_file.attachment_data, Some(&legacy[..]));
+
+        let afm2 = chunk(b"AFM2", &[1, 2, 3]);
+        let anim_file = AnimFile::new(&afm2);
+        assert_eq!(anim_file.bone_data, Some(&[1, 2, 3][..]));
+        assert_eq!(anim_file.attachment_data, Some(&[1, 2, 3][..]));
+
+        let split = [chunk(b"AFSA", &[4, 5]), chunk(b"AFSB", &[6, 7, 8])].concat();
+        let anim_file = AnimFile::new(&split);
+        assert_eq!(anim_file.bone_data, Some(&[6, 7, 8][..]));
+        assert_eq!(anim_file.attachment_data, Some(&[4, 5][..]));
+
+        let bones_only = chunk(b"AFSB", &[6, 7, 8]);
+        assert_eq!(AnimFile::new(&bones_only).attachment_data, None);
+    }
+
+    fn array<T>(count: i32, offset: i32) -> WowArray<T> {
+        WowArray { count, offset, element_type: std::marker::PhantomData }
+    }
+
+    fn words(values: &[u32]) -> Vec<u8> {
+        values.iter().flat_map(|x| x.to_le_bytes()).collect()
+    }
+
+    // two sequences with one keyframe each, where the second sequence's
+    // keyframe lives at the start of the .anim file
+    fn make_track(global_sequence: i16) -> (M2Track<f32>, Vec<u8>, Vec<u8>) {
+        let track = M2Track {
+            interpolation_type: 1,
+            global_sequence,
+            timestamps_unallocated: array(2, 0),
+            timestamps: None,
+            values_unallocated: array(2, 16),
+            values: None,
+            timestamp_arrays: None,
+            value_arrays: None,
+        };
+        let m2_data = words(&[1, 32, 1, 0, 1, 36, 1, 4, 0, 1.0f32.to_bits()]);
+        let anim_data = words(&[500, 2.0f32.to_bits()]);
+        (track, m2_data, anim_data)
+    }
+
+    #[test]
+    fn test_allocate_external() {
+        let (mut track, m2_data, anim_data) = make_track(-1);
+        assert!(track.allocate_external(1, &anim_data).is_err());
+        track.allocate_with_external(&m2_data, &[false, true]).unwrap();
+        assert_eq!(track.timestamps(), &vec![vec![0], vec![]]);
+        assert_eq!(track.values(), &vec![vec![1.0], vec![]]);
+
+        track.allocate_external(1, &anim_data).unwrap();
+        assert_eq!(track.timestamps(), &vec![vec![0], vec![500]]);
+        assert_eq!(track.values(), &vec![vec![1.0], vec![2.0]]);
+        // out of range sequences are ignored
+        track.allocate_external(2, &anim_data).unwrap();
+    }
+
+    #[test]
+    fn test_allocate_external_global_sequence() {
+        // global sequences are never external, so their keyframes are read
+        // from the M2 and left alone afterwards
+        let (mut track, m2_data, anim_data) = make_track(0);
+        track.allocate_with_external(&m2_data, &[false, true]).unwrap();
+        assert_eq!(track.timestamps()[1], vec![1]);
+        track.allocate_external(1, &anim_data).unwrap();
+        assert_eq!(track.timestamps()[1], vec![1]);
+        assert_ne!(track.values()[1], vec![2.0]);
+    }
+}
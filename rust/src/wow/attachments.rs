@@ -0,0 +1,154 @@
+use nalgebra_glm::{self as glm, Mat4};
+use wasm_bindgen::prelude::*;
+
+use super::{
+    animation::{AnimFile, AnimationManager},
+    common::Vec3,
+    m2::{M2Attachment, M2Event},
+};
+
+// Places a point relative to a bone, given the bone's transforms as computed
+// on the JS side (see RibbonEmitter::update()). Like ribbons, this leaves out
+// the camera-facing rotation of billboarded bones.
+fn get_transform_at_bone(bone_transform_slice: &[f32], bone_post_billboard_transform_slice: &[f32], position: Vec3) -> Vec<f32> {
+    assert_eq!(bone_transform_slice.len(), 16);
+    assert_eq!(bone_post_billboard_transform_slice.len(), 16);
+    let bone_transform = Mat4::from_column_slice(bone_transform_slice);
+    let bone_post_billboard_transform = Mat4::from_column_slice(bone_post_billboard_transform_slice);
+    let transform = bone_post_billboard_transform * bone_transform * glm::translation(&position.into());
+    transform.as_slice().to_vec()
+}
+
+// A point that other models (weapons, helmets, lanterns) can be attached to
+#[wasm_bindgen(js_name = "WowM2Attachment")]
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    inner: M2Attachment,
+    pub id: u32,
+    pub bone: u16,
+    pub position: Vec3,
+}
+
+impl Attachment {
+    pub fn new(m2_attachment: M2Attachment) -> Self {
+        Attachment {
+            id: m2_attachment.id,
+            bone: m2_attachment.bone,
+            position: m2_attachment.position,
+            inner: m2_attachment,
+        }
+    }
+
+    // Fills in the keyframes of a sequence stored in an external .anim file,
+    // see M2::add_external_animation()
+    pub fn add_external_animation(&mut self, animation_index: usize, anim_file: &AnimFile) -> Result<(), String> {
+        match anim_file.attachment_data {
+            Some(data) => self.inner.animate_attached.allocate_external(animation_index, data),
+            None => Ok(()),
+        }
+    }
+}
+
+#[wasm_bindgen(js_class = "WowM2Attachment")]
+impl Attachment {
+    pub fn get_transform(&self, bone_transform_slice: &[f32], bone_post_billboard_transform_slice: &[f32]) -> Vec<f32> {
+        get_transform_at_bone(bone_transform_slice, bone_post_billboard_transform_slice, self.position)
+    }
+
+    // whether whatever's attached here should currently be shown
+    pub fn is_visible(&self, animation_manager: &AnimationManager) -> bool {
+        animation_manager.get_current_value_with_blend(&self.inner.animate_attached, 1u8) > 0
+    }
+}
+
+#[wasm_bindgen(js_name = "WowM2Event", getter_with_clone)]
+#[derive(Debug, Clone)]
+pub struct Event {
+    inner: M2Event,
+    pub identifier: String,
+    pub data: u32,
+    pub bone: u32,
+    pub position: Vec3,
+}
+
+impl Event {
+    pub fn new(m2_event: M2Event) -> Self {
+        Event {
+            identifier: String::from_utf8_lossy(&m2_event.identifier).into_owned(),
+            data: m2_event.data,
+            bone: m2_event.bone,
+            position: m2_event.position,
+            inner: m2_event,
+        }
+    }
+
+    // Fills in the keyframes of a sequence stored in an external .anim file,
+    // see M2::add_external_animation()
+    pub fn add_external_animation(&mut self, animation_index: usize, anim_file: &AnimFile) -> Result<(), String> {
+        match anim_file.bone_data {
+            Some(data) => self.inner.enabled.allocate_external(animation_index, data),
+            None => Ok(()),
+        }
+    }
+}
+
+#[wasm_bindgen(js_class = "WowM2Event")]
+impl Event {
+    pub fn get_transform(&self, bone_transform_slice: &[f32], bone_post_billboard_transform_slice: &[f32]) -> Vec<f32> {
+        get_transform_at_bone(bone_transform_slice, bone_post_billboard_transform_slice, self.position)
+    }
+
+    pub fn get_timestamps(&self, animation_index: usize) -> Vec<u32> {
+        self.inner.enabled.timestamps().get(animation_index).cloned().unwrap_or_default()
+    }
+
+    // Whether the event fired during the last dt_ms of the current
+    // animation, including just before it looped
+    pub fn did_fire(&self, animation_manager: &AnimationManager, dt_ms: f64) -> bool {
+        let (index, time) = animation_manager.get_track_base_time(&self.inner.enabled);
+        let Some(timestamps) = self.inner.enabled.timestamps().get(index) else {
+            return false;
+        };
+        let duration = animation_manager.get_track_base_duration(&self.inner.enabled);
+        fired_between(timestamps, time - dt_ms, time, duration)
+    }
+}
+
+// Whether any timestamp falls within (start, end], where a negative start
+// means the animation looped back to 0 in the meantime
+fn fired_between(timestamps: &[u32], start: f64, end: f64, duration: f64) -> bool {
+    let wrapped_start = start + duration;
+    timestamps.iter().any(|&timestamp| {
+        let timestamp = timestamp as f64;
+        (timestamp > start && timestamp <= end) || (start < 0.0 && timestamp > wrapped_start)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fired_between() {
+        let timestamps = [100, 900];
+        assert!(fired_between(&timestamps, 50.0, 100.0, 1000.0));
+        assert!(!fired_between(&timestamps, 100.0, 150.0, 1000.0));
+        assert!(!fired_between(&timestamps, 150.0, 850.0, 1000.0));
+
+        // the animation looped from 950 to 50
+        assert!(!fired_between(&timestamps, -50.0, 50.0, 1000.0));
+        // ... from 850 to 50, passing the event at 900
+        assert!(fired_between(&timestamps, -150.0, 50.0, 1000.0));
+        // ... from 950 to 150, passing the event at 100
+        assert!(fired_between(&timestamps, -50.0, 150.0, 1000.0));
+        assert!(!fired_between(&[], -50.0, 150.0, 1000.0));
+    }
+
+    #[test]
+    fn test_transform_at_bone() {
+        let bone_transform = glm::translation(&glm::vec3(1.0, 0.0, 0.0));
+        let post_billboard_transform = glm::scaling(&glm::vec3(2.0, 2.0, 2.0));
+        let transform = get_transform_at_bone(bone_transform.as_slice(), post_billboard_transform.as_slice(), Vec3 { x: 0.0, y: 1.0, z: 0.0 });
+        assert_eq!(&transform[12..15], &[2.0, 2.0, 0.0]);
+    }
+}
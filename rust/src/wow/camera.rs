@@ -0,0 +1,163 @@
+use wasm_bindgen::prelude::*;
+
+use super::{
+    animation::{AnimFile, AnimationManager},
+    common::Vec3,
+    m2::M2Camera,
+};
+
+// used when the FOV track has no keyframes for the current sequence
+const DEFAULT_FOV: f32 = 0.7;
+
+#[wasm_bindgen(js_name = "WowM2CameraState")]
+#[derive(Debug, Clone, Copy)]
+pub struct CameraState {
+    pub eye: Vec3,
+    pub target: Vec3,
+    pub roll: f32, // in radians
+    pub fov: f32, // diagonal, in radians
+}
+
+#[wasm_bindgen(js_name = "WowM2Camera")]
+#[derive(Debug, Clone)]
+pub struct Camera {
+    inner: M2Camera,
+    pub camera_type: i32,
+    pub near_clip: f32,
+    pub far_clip: f32,
+}
+
+impl Camera {
+    pub fn new(m2_camera: M2Camera) -> Self {
+        Camera {
+            camera_type: m2_camera.camera_type,
+            near_clip: m2_camera.near_clip,
+            far_clip: m2_camera.far_clip,
+            inner: m2_camera,
+        }
+    }
+
+    // Fills in the keyframes of a sequence stored in an external .anim file,
+    // see M2::add_external_animation()
+    pub fn add_external_animation(&mut self, animation_index: usize, anim_file: &AnimFile) -> Result<(), String> {
+        let Some(data) = anim_file.bone_data else {
+            return Ok(());
+        };
+        self.inner.positions.allocate_external(animation_index, data)?;
+        self.inner.target_positions.allocate_external(animation_index, data)?;
+        self.inner.roll.allocate_external(animation_index, data)?;
+        self.inner.fov.allocate_external(animation_index, data)?;
+        Ok(())
+    }
+
+    fn offset(base: Vec3, offset: Vec3) -> Vec3 {
+        Vec3 { x: base.x + offset.x, y: base.y + offset.y, z: base.z + offset.z }
+    }
+}
+
+#[wasm_bindgen(js_class = "WowM2Camera")]
+impl Camera {
+    // Samples the camera at a time (in milliseconds) into one of the model's
+    // sequences, e.g. to play back a flyby from start to finish
+    pub fn sample(&self, animation_index: usize, time_ms: f64) -> CameraState {
+        let eye = self.inner.positions.get_spline_value(animation_index, time_ms, Vec3::default());
+        let target = self.inner.target_positions.get_spline_value(animation_index, time_ms, Vec3::default());
+        CameraState {
+            eye: Camera::offset(self.inner.position_base, eye),
+            target: Camera::offset(self.inner.target_position_base, target),
+            roll: self.inner.roll.get_spline_value(animation_index, time_ms, 0.0),
+            fov: self.inner.fov.get_spline_value(animation_index, time_ms, DEFAULT_FOV),
+        }
+    }
+
+    // Samples the camera at the model's current animation time
+    pub fn sample_current(&self, animation_manager: &AnimationManager) -> CameraState {
+        let (index, time) = animation_manager.get_track_time(&self.inner.positions);
+        let eye = self.inner.positions.get_spline_value(index, time, Vec3::default());
+        let (index, time) = animation_manager.get_track_time(&self.inner.target_positions);
+        let target = self.inner.target_positions.get_spline_value(index, time, Vec3::default());
+        let (index, time) = animation_manager.get_track_time(&self.inner.roll);
+        let roll = self.inner.roll.get_spline_value(index, time, 0.0);
+        let (index, time) = animation_manager.get_track_time(&self.inner.fov);
+        let fov = self.inner.fov.get_spline_value(index, time, DEFAULT_FOV);
+        CameraState {
+            eye: Camera::offset(self.inner.position_base, eye),
+            target: Camera::offset(self.inner.target_position_base, target),
+            roll,
+            fov,
+        }
+    }
+
+    // the length of the camera's path in a sequence, in milliseconds
+    pub fn get_duration(&self, animation_index: usize) -> u32 {
+        self.inner.positions.get_duration(animation_index)
+            .max(self.inner.target_positions.get_duration(animation_index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wow::animation::{M2SplineKey, M2Track};
+    use crate::wow::common::WowArray;
+
+    fn make_track(interpolation_type: u16, keys: Vec<M2SplineKey<f32>>) -> M2Track<M2SplineKey<f32>> {
+        let timestamps = if keys.is_empty() { Vec::new() } else { vec![(0..keys.len() as u32).map(|i| i * 100).collect()] };
+        M2Track {
+            interpolation_type,
+            global_sequence: -1,
+            timestamps_unallocated: WowArray { count: 0, offset: 0, element_type: std::marker::PhantomData },
+            timestamps: Some(timestamps),
+            values_unallocated: WowArray { count: 0, offset: 0, element_type: std::marker::PhantomData },
+            values: Some(if keys.is_empty() { Vec::new() } else { vec![keys] }),
+            timestamp_arrays: None,
+            value_arrays: None,
+        }
+    }
+
+    fn key(value: f32, in_tan: f32, out_tan: f32) -> M2SplineKey<f32> {
+        M2SplineKey { value, in_tan, out_tan }
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-5, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_bezier_spline() {
+        // control points evenly spaced along a line give a linear curve
+        let track = make_track(2, vec![key(0.0, -1.0, 1.0), key(3.0, 2.0, 4.0)]);
+        assert_close(track.get_spline_value(0, 0.0, DEFAULT_FOV), 0.0);
+        assert_close(track.get_spline_value(0, 25.0, DEFAULT_FOV), 0.75);
+        assert_close(track.get_spline_value(0, 50.0, DEFAULT_FOV), 1.5);
+        assert_close(track.get_spline_value(0, 200.0, DEFAULT_FOV), 3.0);
+
+        // pulling both control points to the start bunches the curve up there
+        let track = make_track(2, vec![key(0.0, 0.0, 0.0), key(1.0, 0.0, 1.0)]);
+        assert_close(track.get_spline_value(0, 50.0, DEFAULT_FOV), 0.125);
+    }
+
+    #[test]
+    fn test_hermite_spline() {
+        // zero tangents give a smoothstep
+        let track = make_track(3, vec![key(0.0, 0.0, 0.0), key(1.0, 0.0, 0.0)]);
+        assert_close(track.get_spline_value(0, 25.0, DEFAULT_FOV), 0.15625);
+        assert_close(track.get_spline_value(0, 50.0, DEFAULT_FOV), 0.5);
+
+        // the first key's out tangent and the second key's in tangent are used
+        let track = make_track(3, vec![key(0.0, 5.0, 2.0), key(1.0, 0.0, 5.0)]);
+        assert_close(track.get_spline_value(0, 50.0, DEFAULT_FOV), 0.75);
+
+        assert_eq!(make_track(3, Vec::new()).get_spline_value(0, 50.0, DEFAULT_FOV), DEFAULT_FOV);
+    }
+
+    #[test]
+    fn test_duration_fallback() {
+        // sequences past the end of the track play the first one
+        let track = make_track(1, vec![key(0.0, 0.0, 0.0), key(1.0, 0.0, 0.0), key(2.0, 0.0, 0.0)]);
+        assert_eq!(track.get_duration(0), 200);
+        assert_eq!(track.get_duration(3), 200);
+        assert_close(track.get_spline_value(3, 150.0, DEFAULT_FOV), 1.5);
+        assert_eq!(make_track(1, Vec::new()).get_duration(3), 0);
+    }
+}
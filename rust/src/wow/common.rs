@@ -314,7 +314,10 @@ pub struct WowArray<T> {
 impl<T> WowArray<T> where for<'a> T: DekuReader<'a> {
     pub fn to_vec(&self, data: &[u8]) -> Result<Vec<T>, String> {
         let mut result = Vec::with_capacity(self.count as usize);
-        let mut cursor = Cursor::new(&data[self.offset as usize..]);
+        let Some(array_data) = data.get(self.offset as usize..) else {
+            return Err(format!("array offset {} is past the end of the data ({} bytes)", self.offset, data.len()));
+        };
+        let mut cursor = Cursor::new(array_data);
         let mut reader = Reader::new(&mut cursor);
         for _ in 0..self.count {
             let element = T::from_reader_with_ctx(&mut reader, ())
@@ -5,11 +5,19 @@ use nalgebra_glm::Vec2;
 use crate::geometry::{point_dist_to_polygon, point_inside_polygon};
 
 use super::common::*;
+use super::dbd::{DbdBuild, DbdColumnType, DbdFile};
 use wasm_bindgen::prelude::*;
 
+// WDC3, WDC4 and WDC5 share a layout, except that WDC5 adds a version and
+// schema string after the magic
 #[derive(DekuRead, Debug, Clone)]
-#[deku(magic = b"WDC4")]
-pub struct Wdc4Db2Header {
+pub struct Db2Header {
+    #[deku(assert = "matches!(magic, b\"WDC3\" | b\"WDC4\" | b\"WDC5\")")]
+    pub magic: [u8; 4],
+    #[deku(cond = "*magic == *b\"WDC5\"")]
+    pub version: Option<u32>,
+    #[deku(cond = "*magic == *b\"WDC5\"")]
+    pub schema_string: Option<[u8; 128]>,
     pub record_count: u32,
     pub field_count: u32,
     pub record_size: u32,
@@ -31,7 +39,7 @@ pub struct Wdc4Db2Header {
 }
 
 #[derive(DekuRead, Debug, Clone)]
-pub struct Wdc4Db2SectionHeader {
+pub struct Db2SectionHeader {
     pub tact_key_hash: u64,
     pub file_offset: u32,
     pub record_count: u32,
@@ -85,13 +93,13 @@ pub enum StorageType {
 }
 
 #[derive(DekuRead, Debug, Clone)]
-pub struct Wdc4Db2FieldStruct {
+pub struct Db2FieldStruct {
     pub size: i16,
     pub position: u16,
 }
 
 #[derive(DekuRead, Debug, Clone)]
-pub struct Wdc4Db2FieldInfo {
+pub struct Db2FieldInfo {
     pub field_offset_bits: u16,
     pub field_size_bits: u16,
     pub additional_data_size: u32,
@@ -99,21 +107,21 @@ pub struct Wdc4Db2FieldInfo {
 }
 
 #[derive(DekuRead, Debug, Clone)]
-pub struct Wdc4Db2File {
-    pub header: Wdc4Db2Header,
+pub struct Db2File {
+    pub header: Db2Header,
     #[deku(count = "header.section_count")]
-    pub section_headers: Vec<Wdc4Db2SectionHeader>,
+    pub section_headers: Vec<Db2SectionHeader>,
     #[deku(count = "header.total_field_count")]
-    pub field_structs: Vec<Wdc4Db2FieldStruct>,
+    pub field_structs: Vec<Db2FieldStruct>,
     #[deku(bytes_read = "header.field_storage_info_size")]
-    pub field_storage_info: Vec<Wdc4Db2FieldInfo>,
+    pub field_storage_info: Vec<Db2FieldInfo>,
     #[deku(count = "header.palette_data_size")]
     pub palette_data: Vec<u8>,
     #[deku(count = "header.common_data_size")]
     pub common_data: Vec<u8>,
 }
 
-impl Wdc4Db2File {
+impl Db2File {
     pub fn print_table_debug_info(&self) {
         assert_eq!(self.field_structs.len(), self.field_storage_info.len());
         println!("Number of fields: {}", self.field_structs.len());
@@ -171,7 +179,7 @@ fn read_field_to_u32<R: std::io::Read + std::io::Seek>(reader: &mut Reader<R>, f
     Ok(result)
 }
 
-impl Wdc4Db2File {
+impl Db2File {
     pub fn print_palettes(&self) {
         for field_index in 0..self.field_storage_info.len() {
             let info = &self.field_storage_info[field_index];
@@ -281,7 +289,7 @@ impl Wdc4Db2File {
         Ok(result)
     }
 
-    fn get_common_data(&self, field_number: usize, needle: u32) -> Option<u32> {
+    pub(crate) fn get_common_data(&self, field_number: usize, needle: u32) -> Option<u32> {
         let mut offset: usize = 0;
         for field_number_i in 0..field_number {
             match &self.field_storage_info[field_number_i].storage_type {
@@ -311,7 +319,7 @@ impl Wdc4Db2File {
         None
     }
 
-    fn get_palette_data(&self, field_number: usize, palette_index: usize) -> u32 {
+    pub(crate) fn get_palette_data(&self, field_number: usize, palette_index: usize) -> u32 {
         let mut offset = 0;
         for field_number_i in 0..field_number {
             match &self.field_storage_info[field_number_i].storage_type {
@@ -341,9 +349,9 @@ pub struct DatabaseTable<T> {
 
 impl<T> DatabaseTable<T> {
     pub fn new(data: &[u8]) -> Result<DatabaseTable<T>, String>
-        where for<'a> T: DekuReader<'a, Wdc4Db2File>
+        where for<'a> T: DekuReader<'a, Db2File>
     {
-        let (_, db2) = Wdc4Db2File::from_bytes((&data, 0))
+        let (_, db2) = Db2File::from_bytes((&data, 0))
             .map_err(|e| format!("{:?}", e))?;
         assert!(db2.section_headers.len() == 1);
         let mut records: Vec<T> = Vec::with_capacity(db2.header.record_count as usize);
@@ -423,8 +431,384 @@ impl<T> DatabaseTable<T> {
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum Db2Value {
+    Int(i64),
+    Float(f32),
+    String(String),
+    Array(Vec<Db2Value>),
+}
+
+impl Db2Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Db2Value::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Db2Value]> {
+        match self {
+            Db2Value::Array(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            Db2Value::Int(v) => Some(*v as f64),
+            Db2Value::Float(v) => Some(*v as f64),
+            _ => None,
+        }
+    }
+}
+
+// A row of a Db2Table, whose values are looked up by column name
+#[derive(Debug, Clone, Copy)]
+pub struct Db2Row<'a> {
+    table: &'a Db2Table,
+    index: usize,
+}
+
+impl<'a> Db2Row<'a> {
+    pub fn get(&self, column: &str) -> Option<&'a Db2Value> {
+        let column_index = *self.table.column_indices.get(column)?;
+        self.table.rows[self.index].get(column_index)
+    }
+
+    pub fn get_str(&self, column: &str) -> Option<&'a str> {
+        self.get(column)?.as_str()
+    }
+}
+
+// Where each section's string table ends up if they're all laid out back to
+// back after every section's records, which is what string offsets assume
+struct Db2StringTable<'a> {
+    data: &'a [u8],
+    sections: Vec<(usize, usize, usize)>, // (virtual start, file offset, size)
+}
+
+impl<'a> Db2StringTable<'a> {
+    fn get(&self, position: i64) -> Result<String, String> {
+        let (start, file_offset, size) = self.sections.iter()
+            .find(|(start, _, size)| position >= *start as i64 && position < (*start + *size) as i64)
+            .ok_or_else(|| format!("string offset {} is outside of the string tables", position))?;
+        let bytes = &self.data[file_offset + (position as usize - start)..file_offset + size];
+        let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+        Ok(String::from_utf8_lossy(&bytes[..end]).into_owned())
+    }
+}
+
+fn read_bits(record: &[u8], offset_bits: usize, size_bits: usize) -> u64 {
+    let shift = offset_bits & 7;
+    let size_bytes = (shift + size_bits + 7) >> 3;
+    let mut v: u128 = 0;
+    for (i, byte) in record.iter().skip(offset_bits >> 3).take(size_bytes).enumerate() {
+        v |= (*byte as u128) << (i * 8);
+    }
+    let v = (v >> shift) as u64;
+    if size_bits >= 64 { v } else { v & ((1 << size_bits) - 1) }
+}
+
+fn sign_extend(v: u64, size_bits: usize) -> i64 {
+    if size_bits == 0 || size_bits >= 64 {
+        return v as i64;
+    }
+    let shift = 64 - size_bits;
+    ((v << shift) as i64) >> shift
+}
+
+// base + index * stride, for offsets built from sizes in the file
+fn table_offset(base: usize, index: usize, stride: usize) -> Result<usize, String> {
+    index.checked_mul(stride)
+        .and_then(|offset| offset.checked_add(base))
+        .ok_or_else(|| "table offset overflows".to_string())
+}
+
+fn read_u32_at(data: &[u8], offset: usize) -> Result<u32, String> {
+    data.get(offset..offset.saturating_add(4))
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or_else(|| format!("offset {} is past the end of the file", offset))
+}
+
+impl Db2File {
+    // Reads one element of a field as raw bits, resolving palettes and common
+    // data. Signed bitpacked fields come back sign-extended to 64 bits.
+    fn read_raw_element(&self, record: &[u8], field_number: usize, element: usize, element_count: usize, id: u32) -> u64 {
+        let info = &self.field_storage_info[field_number];
+        let field_offset = info.field_offset_bits as usize;
+        let field_size = info.field_size_bits as usize;
+        let palette_len = info.additional_data_size as usize / 4;
+        match &info.storage_type {
+            StorageType::None { .. } => {
+                let element_size = field_size / element_count.max(1);
+                read_bits(record, field_offset + element * element_size, element_size)
+            },
+            StorageType::Bitpacked { size_bits, .. } => {
+                read_bits(record, field_offset, *size_bits as usize)
+            },
+            StorageType::BitpackedSigned { size_bits, .. } => {
+                sign_extend(read_bits(record, field_offset, *size_bits as usize), *size_bits as usize) as u64
+            },
+            StorageType::CommonData { default_value, .. } => {
+                self.get_common_data(field_number, id).unwrap_or(*default_value) as u64
+            },
+            StorageType::BitpackedIndexed { .. } => {
+                let index = read_bits(record, field_offset, field_size) as usize;
+                if index < palette_len { self.get_palette_data(field_number, index) as u64 } else { 0 }
+            },
+            StorageType::BitpackedIndexedArray { array_count, .. } => {
+                let index = read_bits(record, field_offset, field_size) as usize * *array_count as usize + element;
+                if index < palette_len { self.get_palette_data(field_number, index) as u64 } else { 0 }
+            },
+        }
+    }
+}
+
+// A DB2 table read using a .dbd definition rather than a hand-written record
+// type, for tables we only need a few columns from
+#[wasm_bindgen(js_name = "WowDb2Table")]
+#[derive(Debug)]
+pub struct Db2Table {
+    column_names: Vec<String>,
+    column_indices: HashMap<String, usize>,
+    rows: Vec<Vec<Db2Value>>,
+    ids: Vec<u32>,
+    id_indices: HashMap<u32, usize>,
+    copies: HashMap<u32, u32>,
+    encrypted_record_count: usize,
+}
+
+impl Db2Table {
+    pub fn new(data: &[u8], dbd: &DbdFile, build: Option<DbdBuild>) -> Result<Db2Table, String> {
+        let (_, db2) = Db2File::from_bytes((data, 0))
+            .map_err(|e| format!("{:?}", e))?;
+        let header = &db2.header;
+        if header.flags & 0x1 != 0 {
+            return Err("tables with an offset map aren't supported".into());
+        }
+        let definition = dbd.find_definition(header.layout_hash, build)?
+            .ok_or_else(|| format!("no definition matches layout {:08X}", header.layout_hash))?;
+        let inline_count = definition.inline_fields().count();
+        if inline_count != header.field_count as usize || inline_count > db2.field_storage_info.len() {
+            return Err(format!("definition has {} inline fields, but the table has {}", inline_count, header.field_count));
+        }
+
+        let column_names: Vec<String> = definition.fields.iter()
+            .map(|field| field.name.clone())
+            .collect();
+        let column_indices = column_names.iter().enumerate()
+            .map(|(i, name)| (name.clone(), i))
+            .collect();
+        let inline_id_field = definition.inline_fields().position(|field| field.is_id);
+
+        let record_size = header.record_size as usize;
+        if record_size == 0 && db2.section_headers.iter().any(|section| section.record_count > 0) {
+            return Err("table has records, but a record size of 0".into());
+        }
+        let mut string_table = Db2StringTable { data, sections: Vec::new() };
+        let mut virtual_start = 0;
+        for (section_index, section) in db2.section_headers.iter().enumerate() {
+            let file_offset = (section.record_count as usize).checked_mul(record_size)
+                .and_then(|records_size| records_size.checked_add(section.file_offset as usize))
+                .filter(|records_end| *records_end <= data.len())
+                .ok_or_else(|| format!("section {} is past the end of the file", section_index))?;
+            let size = section.string_table_size as usize;
+            if size > data.len() - file_offset {
+                return Err("string table is past the end of the file".into());
+            }
+            string_table.sections.push((virtual_start, file_offset, size));
+            virtual_start += size;
+        }
+        // every section's records are within the file at this point, so this
+        // is bounded by its size
+        let total_records: usize = db2.section_headers.iter()
+            .map(|section| section.record_count as usize)
+            .sum();
+
+        let mut table = Db2Table {
+            column_names,
+            column_indices,
+            rows: Vec::with_capacity(total_records),
+            ids: Vec::with_capacity(total_records),
+            id_indices: HashMap::new(),
+            copies: HashMap::new(),
+            encrypted_record_count: 0,
+        };
+        let mut record_start = 0;
+        for (section_index, section) in db2.section_headers.iter().enumerate() {
+            let section_record_start = record_start;
+            record_start += section.record_count as usize * record_size;
+
+            let records_offset = section.file_offset as usize;
+            let records_data = &data[records_offset..records_offset + section.record_count as usize * record_size];
+            // without the key, encrypted sections are zeroed out
+            if section.tact_key_hash != 0 && records_data.iter().all(|b| *b == 0) {
+                table.encrypted_record_count += section.record_count as usize;
+                continue;
+            }
+
+            // the sizes come from the file, so these can overflow on wasm32
+            let id_list_offset = table_offset(string_table.sections[section_index].1, section.string_table_size as usize, 1)?;
+            let copy_table_offset = table_offset(id_list_offset, section.id_list_size as usize, 1)?;
+            let offset_map_offset = table_offset(copy_table_offset, section.copy_table_count as usize, 8)?;
+            let relationship_offset = table_offset(offset_map_offset, section.offset_map_id_count as usize, 6)?;
+
+            let mut foreign_keys = HashMap::new();
+            if section.relationship_data_size > 0 {
+                let entry_count = read_u32_at(data, relationship_offset)? as usize;
+                let entries_offset = table_offset(relationship_offset, 12, 1)?;
+                for i in 0..entry_count {
+                    let entry_offset = table_offset(entries_offset, i, 8)?;
+                    let foreign_key = read_u32_at(data, entry_offset)?;
+                    let record_index = read_u32_at(data, table_offset(entry_offset, 4, 1)?)?;
+                    foreign_keys.insert(record_index as usize, foreign_key);
+                }
+            }
+
+            for i in 0..section.record_count as usize {
+                let record = &records_data[i * record_size..(i + 1) * record_size];
+                let id = if section.id_list_size > 0 {
+                    read_u32_at(data, table_offset(id_list_offset, i, 4)?)?
+                } else if let Some(field_number) = inline_id_field {
+                    db2.read_raw_element(record, field_number, 0, 1, 0) as u32
+                } else {
+                    header.min_id + table.rows.len() as u32
+                };
+
+                let mut row = Vec::with_capacity(definition.fields.len());
+                let mut field_number = 0;
+                for field in &definition.fields {
+                    if !field.is_inline {
+                        let value = if field.is_id {
+                            id as i64
+                        } else if field.is_relation {
+                            foreign_keys.get(&i).copied().unwrap_or(0) as i64
+                        } else {
+                            0
+                        };
+                        row.push(Db2Value::Int(value));
+                        continue;
+                    }
+
+                    let column_type = dbd.get_column(&field.name)
+                        .map(|column| column.column_type)
+                        .ok_or_else(|| format!("field {} has no matching column", field.name))?;
+                    let count = field.element_count();
+                    let mut values = Vec::with_capacity(count);
+                    for element in 0..count {
+                        let raw = db2.read_raw_element(record, field_number, element, count, id);
+                        let value = match column_type {
+                            DbdColumnType::Int => {
+                                let size = field.size.unwrap_or(32) as usize;
+                                if field.signed {
+                                    Db2Value::Int(sign_extend(raw, size))
+                                } else {
+                                    Db2Value::Int(read_bits(&raw.to_le_bytes(), 0, size) as i64)
+                                }
+                            },
+                            DbdColumnType::Float => Db2Value::Float(f32::from_bits(raw as u32)),
+                            DbdColumnType::String | DbdColumnType::LocString => {
+                                if raw == 0 {
+                                    Db2Value::String(String::new())
+                                } else {
+                                    // offsets are relative to the field itself
+                                    let element_offset = db2.field_storage_info[field_number].field_offset_bits as usize / 8 + element * 4;
+                                    let position = (section_record_start + i * record_size + element_offset) as i64
+                                        + raw as u32 as i64 - (total_records * record_size) as i64;
+                                    Db2Value::String(string_table.get(position)?)
+                                }
+                            },
+                        };
+                        values.push(value);
+                    }
+                    row.push(match field.array_len {
+                        Some(_) => Db2Value::Array(values),
+                        None => values.pop().unwrap(),
+                    });
+                    field_number += 1;
+                }
+
+                table.id_indices.insert(id, table.rows.len());
+                table.ids.push(id);
+                table.rows.push(row);
+            }
+
+            for i in 0..section.copy_table_count as usize {
+                let entry_offset = table_offset(copy_table_offset, i, 8)?;
+                let id_of_new_row = read_u32_at(data, entry_offset)?;
+                let id_of_old_row = read_u32_at(data, table_offset(entry_offset, 4, 1)?)?;
+                table.copies.insert(id_of_new_row, id_of_old_row);
+            }
+        }
+        Ok(table)
+    }
+
+    pub fn get_row(&self, id: u32) -> Option<Db2Row<'_>> {
+        let source_id = self.copies.get(&id).copied().unwrap_or(id);
+        let index = *self.id_indices.get(&source_id)?;
+        Some(Db2Row { table: self, index })
+    }
+
+    pub fn column_names(&self) -> &[String] {
+        &self.column_names
+    }
+}
+
+#[wasm_bindgen(js_class = "WowDb2Table")]
+impl Db2Table {
+    pub fn from_dbd(data: &[u8], dbd: &str, build: Option<String>) -> Result<Db2Table, String> {
+        let dbd = DbdFile::parse(dbd)?;
+        let build = build.map(|build| DbdBuild::parse(&build)).transpose()?;
+        Db2Table::new(data, &dbd, build)
+    }
+
+    // every ID with a row, including copies
+    pub fn get_ids(&self) -> Vec<u32> {
+        let mut copy_ids: Vec<u32> = self.copies.keys()
+            .filter(|id| self.get_row(**id).is_some())
+            .copied()
+            .collect();
+        copy_ids.sort_unstable();
+        let mut ids = self.ids.clone();
+        ids.extend(copy_ids);
+        ids
+    }
+
+    pub fn get_column_names(&self) -> Vec<String> {
+        self.column_names.clone()
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.rows.len()
+    }
+
+    // rows in sections we couldn't decrypt
+    pub fn get_encrypted_record_count(&self) -> usize {
+        self.encrypted_record_count
+    }
+
+    pub fn has_row(&self, id: u32) -> bool {
+        self.get_row(id).is_some()
+    }
+
+    pub fn get_number(&self, id: u32, column: &str) -> Option<f64> {
+        self.get_row(id)?.get(column)?.as_f64()
+    }
+
+    pub fn get_numbers(&self, id: u32, column: &str) -> Option<Vec<f64>> {
+        self.get_row(id)?.get(column)?.as_array()?.iter()
+            .map(|value| value.as_f64())
+            .collect()
+    }
+
+    pub fn get_string(&self, id: u32, column: &str) -> Option<String> {
+        self.get_row(id)?.get_str(column).map(String::from)
+    }
+}
+
 #[derive(DekuRead, Debug, Clone)]
-#[deku(ctx = "db2: Wdc4Db2File")]
+#[deku(ctx = "db2: Db2File")]
 struct ZoneLightRecord {
     #[deku(reader = "db2.read_field(deku::reader, 0)")]
     pub _unk_1: u32,
@@ -443,7 +827,7 @@ struct ZoneLightRecord {
 }
 
 #[derive(DekuRead, Debug, Clone)]
-#[deku(ctx = "db2: Wdc4Db2File")]
+#[deku(ctx = "db2: Db2File")]
 struct ZoneLightPointRecord {
     #[deku(reader = "db2.read_field(deku::reader, 0)")]
     pub coords: [f32; 2],
@@ -453,7 +837,7 @@ struct ZoneLightPointRecord {
 
 #[derive(DekuRead, Debug, Clone)]
 #[wasm_bindgen(js_name = "WowLightParamsRecord")]
-#[deku(ctx = "db2: Wdc4Db2File")]
+#[deku(ctx = "db2: Db2File")]
 pub struct LightParamsRecord {
     #[deku(reader = "db2.read_field(deku::reader, 0)")]
     _celestial_overrides: Vec3,
@@ -480,7 +864,7 @@ pub struct LightParamsRecord {
 }
 
 #[derive(DekuRead, Debug, Clone)]
-#[deku(ctx = "db2: Wdc4Db2File")]
+#[deku(ctx = "db2: Db2File")]
 struct LightDataRecord {
     #[deku(reader = "db2.read_field(deku::reader, 0)")]
     pub light_param_id: u32,
@@ -563,7 +947,7 @@ pub struct LightResult {
 
 #[derive(DekuRead, Debug, Clone)]
 #[wasm_bindgen(js_name = "WowLightRecord")]
-#[deku(ctx = "db2: Wdc4Db2File")]
+#[deku(ctx = "db2: Db2File")]
 pub struct LightRecord {
     #[deku(reader = "db2.read_field(deku::reader, 0)")]
     pub coords: Vec3,
@@ -788,7 +1172,7 @@ impl Lerp for LightResult {
 }
 
 #[derive(DekuRead, Clone, Debug)]
-#[deku(ctx = "db2: Wdc4Db2File")]
+#[deku(ctx = "db2: Db2File")]
 pub struct LiquidType {
     #[deku(reader = "db2.read_string(deku::reader, 0)")]
     pub name: String,
@@ -845,7 +1229,7 @@ pub struct LiquidType {
 }
 
 #[derive(DekuRead, Clone, Debug)]
-#[deku(ctx = "db2: Wdc4Db2File")]
+#[deku(ctx = "db2: Db2File")]
 pub struct LightSkyboxRecord {
     #[deku(reader = "db2.read_string(deku::reader, 0)")]
     pub name: String,
@@ -1123,6 +1507,98 @@ mod test {
     use super::*;
     use crate::wow::sheep::SheepfileManager;
 
+    // A WDC5 table with one section we can read, and one encrypted section
+    // that was zeroed out on extraction
+    fn build_test_db2() -> Vec<u8> {
+        let mut data = Vec::new();
+        let push = |data: &mut Vec<u8>, values: &[u32]| {
+            for v in values {
+                data.extend(v.to_le_bytes());
+            }
+        };
+        data.extend(b"WDC5");
+        push(&mut data, &[5]);
+        data.extend([0; 128]);
+        // record_count, field_count, record_size, string_table_size, table_hash, layout_hash, min_id, max_id, locale
+        push(&mut data, &[3, 3, 16, 11, 0, 0xDEADBEEF, 5, 9, 0]);
+        data.extend([0; 4]); // flags, id_index
+        // total_field_count, bitpacked_data_offset, lookup_column_count, field_storage_info_size, common_data_size, palette_data_size, section_count
+        push(&mut data, &[3, 12, 0, 72, 0, 0, 2]);
+        // sections
+        push(&mut data, &[0, 0, 368, 2, 11, 0, 8, 28, 0, 1]);
+        push(&mut data, &[0x1234, 0, 455, 1, 0, 0, 4, 0, 0, 0]);
+        // field structs
+        push(&mut data, &[0, 4 << 16, 12 << 16]);
+        // field storage info: Name, Coords[2], then a signed bitpacked Flags
+        push(&mut data, &[32 << 16, 0, 0, 0, 0, 0]);
+        push(&mut data, &[64 << 16 | 32, 0, 0, 0, 0, 0]);
+        push(&mut data, &[4 << 16 | 96, 0, 5, 0, 4, 0]);
+        assert_eq!(data.len(), 368);
+
+        // string offsets are relative to the field, as if every section's
+        // records came before the string tables
+        push(&mut data, &[48, 1.0f32.to_bits(), 2.0f32.to_bits(), 0b1101]);
+        push(&mut data, &[38, 3.5f32.to_bits(), (-1.0f32).to_bits(), 5]);
+        data.extend(b"Alpha\0Beta\0");
+        push(&mut data, &[5, 7]); // id list
+        push(&mut data, &[9, 5]); // copy table
+        push(&mut data, &[2, 0, 1, 100, 0, 200, 1]); // relationship map
+        assert_eq!(data.len(), 455);
+        data.extend([0; 20]);
+        data
+    }
+
+    #[test]
+    fn test_db2_table() {
+        let dbd = DbdFile::parse("COLUMNS
+int ID
+string Name
+float Coords
+int Flags
+int<Map::ID> MapID
+
+LAYOUT DEADBEEF
+$noninline,id$ID<32>
+Name
+Coords[2]
+Flags<8>
+$noninline,relation$MapID<32>
+").unwrap();
+        let table = Db2Table::new(&build_test_db2(), &dbd, None).unwrap();
+        assert_eq!(table.num_rows(), 2);
+        assert_eq!(table.get_encrypted_record_count(), 1);
+        assert_eq!(table.column_names(), ["ID", "Name", "Coords", "Flags", "MapID"]);
+
+        let row = table.get_row(5).unwrap();
+        assert_eq!(table.get_number(5, "ID"), Some(5.0));
+        assert_eq!(row.get_str("Name"), Some("Alpha"));
+        assert_eq!(row.get("Coords"), Some(&Db2Value::Array(vec![Db2Value::Float(1.0), Db2Value::Float(2.0)])));
+        assert_eq!(table.get_number(5, "Flags"), Some(-3.0));
+        assert_eq!(table.get_number(5, "MapID"), Some(100.0));
+
+        assert_eq!(table.get_string(7, "Name"), Some("Beta".to_string()));
+        assert_eq!(table.get_numbers(7, "Coords"), Some(vec![3.5, -1.0]));
+        assert_eq!(table.get_number(7, "Flags"), Some(5.0));
+        assert_eq!(table.get_number(7, "MapID"), Some(200.0));
+
+        assert_eq!(table.get_string(9, "Name"), Some("Alpha".to_string()));
+        assert!(table.get_row(6).is_none());
+        assert!(table.get_string(5, "Missing").is_none());
+
+        assert_eq!(table.get_ids(), [5, 7, 9]);
+        assert!(Db2Table::from_dbd(&build_test_db2(), "COLUMNS\nint ID\n\nLAYOUT 12345678\nID", None).is_err());
+
+        // a record count that runs past the end of the file
+        let mut data = build_test_db2();
+        data[216..220].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(Db2Table::new(&data, &dbd, None).is_err());
+        // and a copy table that does
+        let mut data = build_test_db2();
+        data[240..244].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(Db2Table::new(&data, &dbd, None).is_err());
+        assert_eq!(table_offset(usize::MAX - 4, 1, 8), Err("table offset overflows".to_string()));
+    }
+
     #[test]
     fn test_lighting_data() {
         let sheep_path = "../data/WorldOfWarcraft/sheep0";
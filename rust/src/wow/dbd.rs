@@ -0,0 +1,339 @@
+// Parser for WoWDBDefs-style .dbd table definitions. A .dbd file lists every
+// column a table has ever had, followed by one definition per set of builds
+// (or layout hashes) describing which of those columns are present in the
+// DB2, in what order, and how wide they are.
+//
+// See https://github.com/wowdev/WoWDBDefs for the format.
+
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DbdColumnType {
+    Int,
+    Float,
+    String,
+    LocString,
+}
+
+#[derive(Debug, Clone)]
+pub struct DbdColumn {
+    pub name: String,
+    pub column_type: DbdColumnType,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DbdField {
+    pub name: String,
+    // size in bits, for integer fields
+    pub size: Option<u32>,
+    pub signed: bool,
+    pub array_len: Option<usize>,
+    pub is_id: bool,
+    pub is_relation: bool,
+    // non-inline fields aren't stored in the record data itself, but in the
+    // section's ID list or relationship map
+    pub is_inline: bool,
+}
+
+impl DbdField {
+    pub fn element_count(&self) -> usize {
+        self.array_len.unwrap_or(1)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DbdBuild(pub u32, pub u32, pub u32, pub u32);
+
+impl DbdBuild {
+    pub fn parse(s: &str) -> Result<DbdBuild, String> {
+        let parts = s.trim().split('.')
+            .map(|part| part.parse::<u32>())
+            .collect::<Result<Vec<u32>, _>>()
+            .map_err(|_| format!("invalid build {}", s))?;
+        match parts[..] {
+            [major, minor, patch, build] => Ok(DbdBuild(major, minor, patch, build)),
+            _ => Err(format!("invalid build {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DbdDefinition {
+    pub layout_hashes: Vec<u32>,
+    // inclusive ranges, single builds are stored as a range of one
+    pub builds: Vec<(DbdBuild, DbdBuild)>,
+    pub fields: Vec<DbdField>,
+}
+
+impl DbdDefinition {
+    pub fn matches_build(&self, build: DbdBuild) -> bool {
+        self.builds.iter().any(|(min, max)| *min <= build && build <= *max)
+    }
+
+    // The fields stored in each record, in the order they appear there
+    pub fn inline_fields(&self) -> impl Iterator<Item = &DbdField> {
+        self.fields.iter().filter(|field| field.is_inline)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DbdFile {
+    pub columns: HashMap<String, DbdColumn>,
+    pub definitions: Vec<DbdDefinition>,
+}
+
+impl DbdFile {
+    pub fn parse(text: &str) -> Result<DbdFile, String> {
+        let mut dbd = DbdFile::default();
+        let mut lines = text.lines().map(str::trim).peekable();
+
+        match lines.next() {
+            Some("COLUMNS") => {},
+            _ => return Err("dbd file doesn't start with COLUMNS".into()),
+        }
+        while let Some(line) = lines.next_if(|line| !line.is_empty()) {
+            let column = parse_column(line)?;
+            dbd.columns.insert(column.name.clone(), column);
+        }
+
+        let mut definition = DbdDefinition::default();
+        for line in lines {
+            if line.is_empty() {
+                if !definition.fields.is_empty() {
+                    dbd.definitions.push(std::mem::take(&mut definition));
+                }
+            } else if let Some(hashes) = line.strip_prefix("LAYOUT ") {
+                for hash in hashes.split(',') {
+                    let hash = u32::from_str_radix(hash.trim(), 16)
+                        .map_err(|_| format!("invalid layout hash {}", hash))?;
+                    definition.layout_hashes.push(hash);
+                }
+            } else if let Some(builds) = line.strip_prefix("BUILD ") {
+                for build in builds.split(',') {
+                    let range = match build.split_once('-') {
+                        Some((min, max)) => (DbdBuild::parse(min)?, DbdBuild::parse(max)?),
+                        None => (DbdBuild::parse(build)?, DbdBuild::parse(build)?),
+                    };
+                    definition.builds.push(range);
+                }
+            } else if line.starts_with("COMMENT ") {
+                continue;
+            } else {
+                let field = parse_field(line)?;
+                if !dbd.columns.contains_key(&field.name) {
+                    return Err(format!("field {} has no matching column", field.name));
+                }
+                definition.fields.push(field);
+            }
+        }
+        if !definition.fields.is_empty() {
+            dbd.definitions.push(definition);
+        }
+        Ok(dbd)
+    }
+
+    pub fn find_definition_for_layout(&self, layout_hash: u32) -> Option<&DbdDefinition> {
+        self.definitions.iter()
+            .find(|definition| definition.layout_hashes.contains(&layout_hash))
+    }
+
+    pub fn find_definition_for_build(&self, build: DbdBuild) -> Option<&DbdDefinition> {
+        self.definitions.iter()
+            .find(|definition| definition.matches_build(build))
+    }
+
+    // Prefers a definition matching both the layout and the build, since
+    // several builds can share a layout hash while disagreeing on column
+    // names. Older definitions list only builds, so those match on the build
+    // alone, but a build match listing other layouts means the file and build
+    // disagree.
+    pub fn find_definition(&self, layout_hash: u32, build: Option<DbdBuild>) -> Result<Option<&DbdDefinition>, String> {
+        let Some(build) = build else {
+            return Ok(self.find_definition_for_layout(layout_hash));
+        };
+        let build_matches: Vec<&DbdDefinition> = self.definitions.iter()
+            .filter(|definition| definition.matches_build(build))
+            .collect();
+        if let Some(definition) = build_matches.iter().find(|definition| definition.layout_hashes.contains(&layout_hash)) {
+            return Ok(Some(definition));
+        }
+        if let Some(definition) = build_matches.iter().find(|definition| definition.layout_hashes.is_empty()) {
+            return Ok(Some(definition));
+        }
+        if !build_matches.is_empty() {
+            return Err(format!("build {}.{}.{}.{} doesn't use layout {:08X}", build.0, build.1, build.2, build.3, layout_hash));
+        }
+        Ok(self.find_definition_for_layout(layout_hash))
+    }
+
+    pub fn get_column(&self, name: &str) -> Option<&DbdColumn> {
+        self.columns.get(name)
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    match line.split_once("//") {
+        Some((line, _)) => line.trim(),
+        None => line,
+    }
+}
+
+// e.g. "int<Map::ID> ContinentID" or "locstring ZoneName_lang?"
+fn parse_column(line: &str) -> Result<DbdColumn, String> {
+    let line = strip_comment(line);
+    let (type_str, name) = line.split_once(' ')
+        .ok_or_else(|| format!("invalid column {}", line))?;
+    // foreign keys (e.g. Map::ID) aren't needed to read the table
+    let type_name = match type_str.split_once('<') {
+        Some((type_name, rest)) => {
+            rest.strip_suffix('>')
+                .and_then(|key| key.split_once("::"))
+                .ok_or_else(|| format!("invalid foreign key in column {}", line))?;
+            type_name
+        },
+        None => type_str,
+    };
+    let column_type = match type_name {
+        "int" => DbdColumnType::Int,
+        "float" => DbdColumnType::Float,
+        "string" => DbdColumnType::String,
+        "locstring" => DbdColumnType::LocString,
+        _ => return Err(format!("unknown column type {}", type_name)),
+    };
+    // a trailing ? marks names that haven't been verified
+    let name = name.trim();
+    let name = name.strip_suffix('?').unwrap_or(name);
+    Ok(DbdColumn {
+        name: name.to_string(),
+        column_type,
+    })
+}
+
+// e.g. "$noninline,id$ID<32>", "Flags<u8>[2]" or "Name_lang"
+fn parse_field(line: &str) -> Result<DbdField, String> {
+    let mut rest = strip_comment(line);
+    let mut field = DbdField { is_inline: true, signed: true, ..Default::default() };
+
+    if let Some(annotated) = rest.strip_prefix('$') {
+        let (annotations, remainder) = annotated.split_once('$')
+            .ok_or_else(|| format!("unterminated annotation in field {}", line))?;
+        for annotation in annotations.split(',') {
+            match annotation {
+                "id" => field.is_id = true,
+                "relation" => field.is_relation = true,
+                "noninline" => field.is_inline = false,
+                _ => {}, // e.g. "lang", which we don't need
+            }
+        }
+        rest = remainder;
+    }
+
+    if let Some((remainder, array_len)) = rest.strip_suffix(']').and_then(|rest| rest.rsplit_once('[')) {
+        field.array_len = Some(array_len.parse()
+            .map_err(|_| format!("invalid array length in field {}", line))?);
+        rest = remainder;
+    }
+
+    if let Some((name, size)) = rest.strip_suffix('>').and_then(|rest| rest.split_once('<')) {
+        let size = match size.strip_prefix('u') {
+            Some(size) => {
+                field.signed = false;
+                size
+            },
+            None => size,
+        };
+        field.size = Some(size.parse()
+            .map_err(|_| format!("invalid size in field {}", line))?);
+        rest = name;
+    }
+
+    if rest.is_empty() {
+        return Err(format!("field {} has no name", line));
+    }
+    field.name = rest.to_string();
+    Ok(field)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const GROUND_EFFECT_DOODAD: &str = "COLUMNS
+int ID
+string Model // path to the doodad
+float Animscale?
+int Flags
+float PushScale
+int<GroundEffectTexture::ID> TextureID
+
+LAYOUT 3FE64CEC, 8D4E7CA3
+BUILD 8.0.1.26231-8.3.7.35435
+BUILD 9.0.1.36216
+$id$ID<32>
+Model
+Flags<u8>
+Animscale
+PushScale[2]
+$noninline,relation$TextureID<32>
+
+LAYOUT 0E3B9D9D
+BUILD 7.3.5.25600
+$noninline,id$ID<32>
+Model
+Flags<u16>
+";
+
+    #[test]
+    fn test_parse_dbd() {
+        let dbd = DbdFile::parse(GROUND_EFFECT_DOODAD).unwrap();
+        assert_eq!(dbd.columns.len(), 6);
+        assert_eq!(dbd.definitions.len(), 2);
+
+        assert_eq!(dbd.get_column("TextureID").unwrap().column_type, DbdColumnType::Int);
+        assert_eq!(dbd.get_column("Animscale").unwrap().column_type, DbdColumnType::Float);
+        assert_eq!(dbd.get_column("Model").unwrap().column_type, DbdColumnType::String);
+
+        let definition = dbd.find_definition_for_layout(0x8D4E7CA3).unwrap();
+        assert_eq!(definition.builds.len(), 2);
+        assert_eq!(definition.inline_fields().count(), 5);
+        let flags = &definition.fields[2];
+        assert_eq!((flags.size, flags.signed), (Some(8), false));
+        assert_eq!(definition.fields[4].array_len, Some(2));
+        let relation = &definition.fields[5];
+        assert!(relation.is_relation && !relation.is_inline && !relation.is_id);
+
+        let build = DbdBuild::parse("8.2.5.31337").unwrap();
+        assert_eq!(dbd.find_definition_for_build(build).unwrap().layout_hashes[0], 0x3FE64CEC);
+        assert!(dbd.find_definition_for_build(DbdBuild::parse("1.12.1.5875").unwrap()).is_none());
+    }
+
+    #[test]
+    fn test_find_definition() {
+        let dbd = DbdFile::parse(GROUND_EFFECT_DOODAD).unwrap();
+        let legion = DbdBuild::parse("7.3.5.25600").unwrap();
+        let legacy = dbd.find_definition(0x0E3B9D9D, Some(legion)).unwrap().unwrap();
+        assert!(legacy.fields[0].is_id && !legacy.fields[0].is_inline);
+        let bfa = dbd.find_definition(0x3FE64CEC, Some(DbdBuild::parse("8.2.5.31337").unwrap())).unwrap().unwrap();
+        assert!(bfa.fields[0].is_inline);
+
+        // the build and the layout name different definitions
+        assert!(dbd.find_definition(0x3FE64CEC, Some(legion)).is_err());
+        // falls back to the layout for unknown builds, or without one
+        let vanilla = DbdBuild::parse("1.12.1.5875").unwrap();
+        assert_eq!(dbd.find_definition(0x8D4E7CA3, Some(vanilla)).unwrap().unwrap().layout_hashes[0], 0x3FE64CEC);
+        assert_eq!(dbd.find_definition(0x0E3B9D9D, None).unwrap().unwrap().layout_hashes[0], 0x0E3B9D9D);
+        assert!(dbd.find_definition(0x12345678, None).unwrap().is_none());
+
+        // definitions without a LAYOUT match any layout for their builds
+        let dbd = DbdFile::parse("COLUMNS\nint ID\n\nBUILD 1.12.1.5875\n$id$ID<32>\n").unwrap();
+        assert!(dbd.find_definition(0x12345678, Some(vanilla)).unwrap().is_some());
+        assert!(dbd.find_definition(0x12345678, Some(legion)).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_parse_dbd_errors() {
+        assert!(DbdFile::parse("LAYOUT 1234").is_err());
+        assert!(DbdFile::parse("COLUMNS\nint ID\n\n$id$Unknown<32>").is_err());
+        assert!(DbdFile::parse("COLUMNS\nbool ID").is_err());
+    }
+}
@@ -4,7 +4,7 @@ use std::marker::PhantomData;
 use deku::prelude::*;
 
 use wasm_bindgen::prelude::*;
-use crate::wow::{animation::*, common::parse, particles::Emitter};
+use crate::wow::{animation::*, attachments::{Attachment, Event}, camera::Camera, common::parse, particles::Emitter, ribbons::RibbonEmitter};
 
 use super::common::{
     fixed_precision_6_9_to_f32, parse_array, AABBox, ChunkedData, Fixedi16, Quat, Vec2, Vec3, WowArray, WowCharArray
@@ -41,13 +41,13 @@ pub struct M2Header {
     _collision_triangles: WowArray<u16>,
     _collision_vertices: WowArray<Vec3>,
     _collision_normals: WowArray<Vec3>,
-    _attachments: WowArray<()>,
-    _attachment_lookup_table: WowArray<u16>,
-    _events: WowArray<()>,
+    attachments: WowArray<M2Attachment>,
+    attachment_lookup_table: WowArray<u16>,
+    events: WowArray<M2Event>,
     lights: WowArray<M2Light>,
-    _cameras: WowArray<()>,
-    _camera_lookup_table: WowArray<u16>,
-    _ribbon_emitters: WowArray<()>,
+    cameras: WowArray<M2Camera>,
+    camera_lookup_table: WowArray<u16>,
+    ribbon_emitters: WowArray<M2RibbonEmitter>,
     particle_emitters: WowArray<ParticleEmitter>,
     _blend_map_overrides: WowArray<u16>,
 }
@@ -61,11 +61,11 @@ impl M2Header {
         self.materials.to_vec(m2_data)
     }
 
-    fn get_vertex_colors(&self, m2_data: &[u8]) -> Result<Vec<M2Color>, String> {
+    fn get_vertex_colors(&self, m2_data: &[u8], external: &[bool]) -> Result<Vec<M2Color>, String> {
         let mut colors: Vec<M2Color> = self.colors.to_vec(m2_data)?;
         for color in colors.iter_mut() {
-            color.color.allocate(m2_data)?;
-            color.alpha.allocate(m2_data)?;
+            color.color.allocate_with_external(m2_data, external)?;
+            color.alpha.allocate_with_external(m2_data, external)?;
         }
         Ok(colors)
     }
@@ -74,22 +74,22 @@ impl M2Header {
         self.textures.to_vec(m2_data)
     }
 
-    fn get_texture_transforms(&self, m2_data: &[u8]) -> Result<Vec<M2TextureTransform>, String> {
+    fn get_texture_transforms(&self, m2_data: &[u8], external: &[bool]) -> Result<Vec<M2TextureTransform>, String> {
         let mut texture_transforms: Vec<M2TextureTransform> = self.texture_transforms.to_vec(m2_data)?;
         for tex in texture_transforms.iter_mut() {
-            tex.translation.allocate(m2_data)?;
-            tex.rotation.allocate(m2_data)?;
-            tex.scaling.allocate(m2_data)?;
+            tex.translation.allocate_with_external(m2_data, external)?;
+            tex.rotation.allocate_with_external(m2_data, external)?;
+            tex.scaling.allocate_with_external(m2_data, external)?;
         }
         Ok(texture_transforms)
     }
 
-    fn get_bones(&self, m2_data: &[u8]) -> Result<Vec<M2CompBone>, String> {
+    fn get_bones(&self, m2_data: &[u8], external: &[bool]) -> Result<Vec<M2CompBone>, String> {
         let mut bones: Vec<M2CompBone> = self.bones.to_vec(m2_data)?;
         for bone in bones.iter_mut() {
-            bone.rotation_quat16.allocate(m2_data)?;
-            bone.translation.allocate(m2_data)?;
-            bone.scaling.allocate(m2_data)?;
+            bone.rotation_quat16.allocate_with_external(m2_data, external)?;
+            bone.translation.allocate_with_external(m2_data, external)?;
+            bone.scaling.allocate_with_external(m2_data, external)?;
 
             // convert the quat16s into quats so we don't have to do the
             // math countless times per frame
@@ -111,16 +111,18 @@ impl M2Header {
                 // hack: put in some fake pointers
                 timestamps_unallocated: WowArray { count: 0, offset: 0, element_type: PhantomData },
                 values_unallocated: WowArray { count: 0, offset: 0, element_type: PhantomData },
+                timestamp_arrays: None,
+                value_arrays: None,
             });
         }
         Ok(bones)
     }
 
-    fn get_texture_weights(&self, m2_data: &[u8]) -> Result<Vec<M2Track<Fixedi16>>, String> {
+    fn get_texture_weights(&self, m2_data: &[u8], external: &[bool]) -> Result<Vec<M2Track<Fixedi16>>, String> {
         let mut weights: Vec<M2Track<Fixedi16>> = self.texture_weights.to_vec(m2_data)?;
 
         for weight in weights.iter_mut() {
-            weight.allocate(m2_data)?;
+            weight.allocate_with_external(m2_data, external)?;
         }
 
         Ok(weights)
@@ -149,38 +151,38 @@ impl M2Header {
         self.transparency_lookup_table.to_vec(m2_data)
     }
 
-    fn get_lights(&self, m2_data: &[u8]) -> Result<Vec<M2Light>, String> {
+    fn get_lights(&self, m2_data: &[u8], external: &[bool]) -> Result<Vec<M2Light>, String> {
         let mut lights: Vec<M2Light> = self.lights.to_vec(m2_data)?;
         for light in lights.iter_mut() {
-            light.ambient_color.allocate(m2_data)?;
-            light.ambient_intensity.allocate(m2_data)?;
-            light.diffuse_color.allocate(m2_data)?;
-            light.diffuse_intensity.allocate(m2_data)?;
-            light.attenuation_start.allocate(m2_data)?;
-            light.attenuation_end.allocate(m2_data)?;
-            light.visibility.allocate(m2_data)?;
+            light.ambient_color.allocate_with_external(m2_data, external)?;
+            light.ambient_intensity.allocate_with_external(m2_data, external)?;
+            light.diffuse_color.allocate_with_external(m2_data, external)?;
+            light.diffuse_intensity.allocate_with_external(m2_data, external)?;
+            light.attenuation_start.allocate_with_external(m2_data, external)?;
+            light.attenuation_end.allocate_with_external(m2_data, external)?;
+            light.visibility.allocate_with_external(m2_data, external)?;
         }
         Ok(lights)
     }
 
-    fn get_particle_emitters(&self, m2_data: &[u8]) -> Result<Vec<ParticleEmitter>, String> {
+    fn get_particle_emitters(&self, m2_data: &[u8], external: &[bool]) -> Result<Vec<ParticleEmitter>, String> {
         let mut particle_emitters: Vec<ParticleEmitter> = self.particle_emitters.to_vec(m2_data)?;
         for emitter in particle_emitters.iter_mut() {
-            emitter.emission_speed.allocate(m2_data)?;
-            emitter.speed_variation.allocate(m2_data)?;
-            emitter.vertical_range.allocate(m2_data)?;
-            emitter.horizontal_range.allocate(m2_data)?;
-            emitter.gravity.allocate(m2_data)?;
-            emitter.lifespan.allocate(m2_data)?;
-            emitter.emission_rate.allocate(m2_data)?;
-            emitter.emission_area_length.allocate(m2_data)?;
-            emitter.emission_area_width.allocate(m2_data)?;
-            emitter.z_source.allocate(m2_data)?;
+            emitter.emission_speed.allocate_with_external(m2_data, external)?;
+            emitter.speed_variation.allocate_with_external(m2_data, external)?;
+            emitter.vertical_range.allocate_with_external(m2_data, external)?;
+            emitter.horizontal_range.allocate_with_external(m2_data, external)?;
+            emitter.gravity.allocate_with_external(m2_data, external)?;
+            emitter.lifespan.allocate_with_external(m2_data, external)?;
+            emitter.emission_rate.allocate_with_external(m2_data, external)?;
+            emitter.emission_area_length.allocate_with_external(m2_data, external)?;
+            emitter.emission_area_width.allocate_with_external(m2_data, external)?;
+            emitter.z_source.allocate_with_external(m2_data, external)?;
             emitter.color.allocate(m2_data)?;
             emitter.alpha.allocate(m2_data)?;
             emitter.scale.allocate(m2_data)?;
             emitter.head_cell.allocate(m2_data)?;
-            emitter.enabled.allocate(m2_data)?;
+            emitter.enabled.allocate_with_external(m2_data, external)?;
             emitter.tail_cell.allocate(m2_data)?;
             emitter.geometry_model_filename = Some(emitter.geometry_model_filename_unallocated.to_string(m2_data)?);
             emitter.recursion_model_filename = Some(emitter.recursion_model_filename_unallocated.to_string(m2_data)?);
@@ -188,6 +190,56 @@ impl M2Header {
         }
         Ok(particle_emitters)
     }
+
+    fn get_attachments(&self, m2_data: &[u8], external: &[bool]) -> Result<Vec<M2Attachment>, String> {
+        let mut attachments: Vec<M2Attachment> = self.attachments.to_vec(m2_data)?;
+        for attachment in attachments.iter_mut() {
+            attachment.animate_attached.allocate_with_external(m2_data, external)?;
+        }
+        Ok(attachments)
+    }
+
+    fn get_attachment_lookup_table(&self, m2_data: &[u8]) -> Result<Vec<u16>, String> {
+        self.attachment_lookup_table.to_vec(m2_data)
+    }
+
+    fn get_events(&self, m2_data: &[u8], external: &[bool]) -> Result<Vec<M2Event>, String> {
+        let mut events: Vec<M2Event> = self.events.to_vec(m2_data)?;
+        for event in events.iter_mut() {
+            event.enabled.allocate_with_external(m2_data, external)?;
+        }
+        Ok(events)
+    }
+
+    fn get_cameras(&self, m2_data: &[u8], external: &[bool]) -> Result<Vec<M2Camera>, String> {
+        let mut cameras: Vec<M2Camera> = self.cameras.to_vec(m2_data)?;
+        for camera in cameras.iter_mut() {
+            camera.positions.allocate_with_external(m2_data, external)?;
+            camera.target_positions.allocate_with_external(m2_data, external)?;
+            camera.roll.allocate_with_external(m2_data, external)?;
+            camera.fov.allocate_with_external(m2_data, external)?;
+        }
+        Ok(cameras)
+    }
+
+    fn get_camera_lookup_table(&self, m2_data: &[u8]) -> Result<Vec<u16>, String> {
+        self.camera_lookup_table.to_vec(m2_data)
+    }
+
+    fn get_ribbon_emitters(&self, m2_data: &[u8], external: &[bool]) -> Result<Vec<M2RibbonEmitter>, String> {
+        let mut ribbon_emitters: Vec<M2RibbonEmitter> = self.ribbon_emitters.to_vec(m2_data)?;
+        for emitter in ribbon_emitters.iter_mut() {
+            emitter.color.allocate_with_external(m2_data, external)?;
+            emitter.alpha.allocate_with_external(m2_data, external)?;
+            emitter.height_above.allocate_with_external(m2_data, external)?;
+            emitter.height_below.allocate_with_external(m2_data, external)?;
+            emitter.tex_slot.allocate_with_external(m2_data, external)?;
+            emitter.visibility.allocate_with_external(m2_data, external)?;
+            emitter.texture_indices = Some(emitter.texture_indices_unallocated.to_vec(m2_data)?);
+            emitter.material_indices = Some(emitter.material_indices_unallocated.to_vec(m2_data)?);
+        }
+        Ok(ribbon_emitters)
+    }
 }
 
 #[wasm_bindgen(js_name = "WowM2", getter_with_clone)]
@@ -208,6 +260,13 @@ pub struct M2 {
     transparency_lookup_table: Option<Vec<u16>>,
     animation_manager: Option<AnimationManager>,
     particle_emitters: Option<Vec<Emitter>>,
+    ribbon_emitters: Option<Vec<RibbonEmitter>>,
+    cameras: Option<Vec<Camera>>,
+    camera_lookup_table: Option<Vec<u16>>,
+    attachments: Option<Vec<Attachment>>,
+    attachment_lookup_table: Option<Vec<u16>>,
+    events: Option<Vec<Event>>,
+    anim_file_ids: Vec<AnimFileId>,
 }
 
 #[wasm_bindgen(js_class = "WowM2")]
@@ -224,12 +283,14 @@ impl M2 {
         let mut sfid: Option<Vec<u32>> = None;
         let mut txac: Option<Vec<u16>> = None;
         let mut exp2_unallocated: Option<WowArray<Exp2Record>> = None;
+        let mut afid: Option<Vec<AnimFileId>> = None;
         for (chunk, chunk_data) in &mut chunked_data {
             match &chunk.magic {
                 b"TXID" => txid = Some(parse_array(chunk_data, 4)?),
                 b"SFID" => sfid = Some(parse_array(chunk_data, 4)?),
                 b"TXAC" => txac = Some(parse_array(chunk_data, 2)?),
                 b"EXP2" => exp2_unallocated = Some(parse(chunk_data)?),
+                b"AFID" => afid = Some(parse_array(chunk_data, 8)?),
                 _ => {},
             }
         }
@@ -238,12 +299,15 @@ impl M2 {
         // always be 16 bytes in
         let m2_data = &data[8..];
 
+        let sequences: Vec<M2Sequence> = header.sequences.to_vec(m2_data)?;
+        let external: Vec<bool> = sequences.iter().map(|seq| seq.is_external()).collect();
+
         let mut exp2_allocated = None;
         if let Some(exp2_unallocated) = exp2_unallocated {
             exp2_allocated = Some(exp2_unallocated.to_vec(m2_data)?);
         }
         let mut particle_emitters = Vec::new();
-        for (i, emitter) in header.get_particle_emitters(m2_data)?.drain(..).enumerate() {
+        for (i, emitter) in header.get_particle_emitters(m2_data, &external)?.drain(..).enumerate() {
             let mut emitter_txac = 0;
             if let Some(txac_values) = txac.as_ref() {
                 emitter_txac = txac_values[i];
@@ -255,14 +319,29 @@ impl M2 {
             particle_emitters.push(Emitter::new(emitter, emitter_txac, emitter_z_source));
         }
 
+        let ribbon_emitters = header.get_ribbon_emitters(m2_data, &external)?.drain(..)
+            .map(RibbonEmitter::new)
+            .collect();
+
+        let cameras = header.get_cameras(m2_data, &external)?.drain(..)
+            .map(Camera::new)
+            .collect();
+
+        let attachments = header.get_attachments(m2_data, &external)?.drain(..)
+            .map(Attachment::new)
+            .collect();
+        let events = header.get_events(m2_data, &external)?.drain(..)
+            .map(Event::new)
+            .collect();
+
         let animation_manager = Some(AnimationManager::new(
             header.global_sequence_durations.to_vec(m2_data)?,
-            header.sequences.to_vec(m2_data)?,
-            header.get_texture_weights(m2_data)?,
-            header.get_texture_transforms(m2_data)?,
-            header.get_vertex_colors(m2_data)?,
-            header.get_bones(m2_data)?,
-            header.get_lights(m2_data)?,
+            sequences,
+            header.get_texture_weights(m2_data, &external)?,
+            header.get_texture_transforms(m2_data, &external)?,
+            header.get_vertex_colors(m2_data, &external)?,
+            header.get_bones(m2_data, &external)?,
+            header.get_lights(m2_data, &external)?,
         ));
 
         let mut legacy_textures = Vec::new();
@@ -286,6 +365,13 @@ impl M2 {
             texture_lookup_table: Some(header.get_texture_lookup_table(m2_data)?),
             bone_lookup_table: Some(header.get_bone_lookup_table(m2_data)?),
             particle_emitters: Some(particle_emitters),
+            ribbon_emitters: Some(ribbon_emitters),
+            cameras: Some(cameras),
+            camera_lookup_table: Some(header.get_camera_lookup_table(m2_data)?),
+            attachments: Some(attachments),
+            attachment_lookup_table: Some(header.get_attachment_lookup_table(m2_data)?),
+            events: Some(events),
+            anim_file_ids: afid.unwrap_or_default(),
             legacy_textures: Some(legacy_textures),
             texture_transforms_lookup_table: Some(header.get_texture_transforms_lookup_table(m2_data)?),
             transparency_lookup_table: Some(header.get_transparency_lookup_table(m2_data)?),
@@ -333,6 +419,65 @@ impl M2 {
         self.particle_emitters.take().expect("particle emitters have already been taken")
     }
 
+    pub fn take_ribbon_emitters(&mut self) -> Vec<RibbonEmitter> {
+        self.ribbon_emitters.take().expect("ribbon emitters have already been taken")
+    }
+
+    pub fn take_cameras(&mut self) -> Vec<Camera> {
+        self.cameras.take().expect("cameras have already been taken")
+    }
+
+    // maps camera ids (0 = portrait, 1 = character info, ...) to cameras
+    pub fn take_camera_lookup(&mut self) -> Vec<u16> {
+        self.camera_lookup_table.take().expect("camera lookup table has already been taken")
+    }
+
+    pub fn take_attachments(&mut self) -> Vec<Attachment> {
+        self.attachments.take().expect("attachments have already been taken")
+    }
+
+    // maps attachment ids (0 = shield, 1 = right hand, ...) to attachments
+    pub fn take_attachment_lookup(&mut self) -> Vec<u16> {
+        self.attachment_lookup_table.take().expect("attachment lookup table has already been taken")
+    }
+
+    pub fn take_events(&mut self) -> Vec<Event> {
+        self.events.take().expect("events have already been taken")
+    }
+
+    // the .anim files holding keyframes for sequences that aren't in the M2
+    pub fn get_anim_file_ids(&self) -> Vec<AnimFileId> {
+        self.anim_file_ids.clone()
+    }
+
+    // Fills in the keyframes of a sequence stored in an external .anim file
+    // (see get_anim_file_ids()) for the AnimationManager and every emitter,
+    // ribbon, camera, attachment and event, so call this before taking them
+    pub fn add_external_animation(&mut self, anim_id: u16, sub_anim_id: u16, data: &[u8]) -> Result<(), String> {
+        let animation_manager = self.animation_manager.as_mut()
+            .ok_or("M2 AnimationManager already taken".to_string())?;
+        let index = animation_manager.get_sequence_index(anim_id, sub_anim_id)
+            .ok_or(format!("no sequence with id {}.{}", anim_id, sub_anim_id))?;
+        let anim_file = AnimFile::new(data);
+        animation_manager.add_external_animation(index, &anim_file)?;
+        for emitter in self.particle_emitters.iter_mut().flatten() {
+            emitter.add_external_animation(index, &anim_file)?;
+        }
+        for ribbon in self.ribbon_emitters.iter_mut().flatten() {
+            ribbon.add_external_animation(index, &anim_file)?;
+        }
+        for camera in self.cameras.iter_mut().flatten() {
+            camera.add_external_animation(index, &anim_file)?;
+        }
+        for attachment in self.attachments.iter_mut().flatten() {
+            attachment.add_external_animation(index, &anim_file)?;
+        }
+        for event in self.events.iter_mut().flatten() {
+            event.add_external_animation(index, &anim_file)?;
+        }
+        Ok(())
+    }
+
     pub fn get_vertex_stride() -> usize {
         // position + bone weights + bone indices + normal + texture coords
         12 + 4 + 4 + 12 + 2 * 8
@@ -343,6 +488,14 @@ impl M2 {
     }
 }
 
+#[wasm_bindgen(js_name = "WowM2AnimFileId")]
+#[derive(DekuRead, Debug, Clone, Copy)]
+pub struct AnimFileId {
+    pub anim_id: u16,
+    pub sub_anim_id: u16,
+    pub file_id: u32,
+}
+
 #[derive(DekuRead)]
 pub struct Exp2Record {
     pub z_source: f32,
@@ -546,6 +699,64 @@ pub struct ParticleEmitter {
     texture_velocity_variance1: [u16; 2],
 }
 
+#[derive(Debug, DekuRead, Clone)]
+pub struct M2RibbonEmitter {
+    pub ribbon_id: i32,
+    pub bone: u32,
+    pub position: Vec3,
+    texture_indices_unallocated: WowArray<u16>,
+    #[deku(skip)] pub texture_indices: Option<Vec<u16>>,
+    material_indices_unallocated: WowArray<u16>,
+    #[deku(skip)] pub material_indices: Option<Vec<u16>>,
+    pub(crate) color: M2Track<Vec3>,
+    pub(crate) alpha: M2Track<Fixedi16>,
+    pub(crate) height_above: M2Track<f32>,
+    pub(crate) height_below: M2Track<f32>,
+    pub edges_per_second: f32,
+    pub edge_lifetime: f32, // in seconds
+    pub gravity: f32,
+    pub texture_rows: u16,
+    pub texture_cols: u16,
+    pub(crate) tex_slot: M2Track<u16>,
+    pub(crate) visibility: M2Track<u8>,
+    pub priority_plane: i16,
+    pub ribbon_color_index: i8,
+    pub texture_transform_lookup_index: i8,
+}
+
+#[derive(Debug, DekuRead, Clone)]
+pub struct M2Attachment {
+    pub id: u32,
+    #[deku(pad_bytes_after = "2")]
+    pub bone: u16,
+    pub position: Vec3,
+    pub(crate) animate_attached: M2Track<u8>,
+}
+
+// Events fire at each of their timestamps, e.g. footstep sounds ("$DSL")
+#[derive(Debug, DekuRead, Clone)]
+pub struct M2Event {
+    pub identifier: [u8; 4],
+    pub data: u32,
+    pub bone: u32,
+    pub position: Vec3,
+    pub(crate) enabled: M2TrackBase,
+}
+
+// Camera positions are relative to their base positions
+#[derive(Debug, DekuRead, Clone)]
+pub struct M2Camera {
+    pub camera_type: i32, // 0 - portrait, 1 - character info, -1 - flyby
+    pub far_clip: f32,
+    pub near_clip: f32,
+    pub(crate) positions: M2Track<M2SplineKey<Vec3>>,
+    pub position_base: Vec3,
+    pub(crate) target_positions: M2Track<M2SplineKey<Vec3>>,
+    pub target_position_base: Vec3,
+    pub(crate) roll: M2Track<M2SplineKey<f32>>,
+    pub(crate) fov: M2Track<M2SplineKey<f32>>,
+}
+
 #[wasm_bindgen(js_name = "WowM2ParticleShaderType")]
 #[derive(Debug, Copy, Clone)]
 pub enum ParticleShaderType {
@@ -7,5 +7,9 @@ mod blp;
 mod adt;
 mod wmo;
 mod db;
+mod dbd;
 mod sheep;
 mod particles;
+mod ribbons;
+mod camera;
+mod attachments;
@@ -6,7 +6,7 @@ use wasm_bindgen::prelude::*;
 use crate::spline::BezierSpline;
 
 use super::{
-    animation::AnimationManager,
+    animation::{AnimFile, AnimationManager},
     common::{Vec3 as WowVec3, Vec2 as WowVec2, Fixedi16},
     m2::{M2BlendingMode, ParticleEmitter as M2ParticleEmitter, ParticleShaderType},
 };
@@ -245,6 +245,27 @@ impl Emitter {
         }
     }
 
+    // Fills in the keyframes of a sequence stored in an external .anim file,
+    // see M2::add_external_animation()
+    pub fn add_external_animation(&mut self, animation_index: usize, anim_file: &AnimFile) -> Result<(), String> {
+        let Some(data) = anim_file.bone_data else {
+            return Ok(());
+        };
+        let emitter = &mut self.inner;
+        emitter.emission_speed.allocate_external(animation_index, data)?;
+        emitter.speed_variation.allocate_external(animation_index, data)?;
+        emitter.vertical_range.allocate_external(animation_index, data)?;
+        emitter.horizontal_range.allocate_external(animation_index, data)?;
+        emitter.gravity.allocate_external(animation_index, data)?;
+        emitter.lifespan.allocate_external(animation_index, data)?;
+        emitter.emission_rate.allocate_external(animation_index, data)?;
+        emitter.emission_area_length.allocate_external(animation_index, data)?;
+        emitter.emission_area_width.allocate_external(animation_index, data)?;
+        emitter.z_source.allocate_external(animation_index, data)?;
+        emitter.enabled.allocate_external(animation_index, data)?;
+        Ok(())
+    }
+
     // returns value in (-a, a)
     fn random_range(&mut self, a: f32) -> f32 {
         if a == 0.0 {
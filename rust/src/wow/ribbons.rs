@@ -0,0 +1,305 @@
+use std::collections::VecDeque;
+
+use js_sys::Float32Array;
+use nalgebra_glm::{mat4_to_mat3, vec3, vec3_to_vec4, Mat4, Vec3};
+use wasm_bindgen::prelude::*;
+
+use super::{
+    animation::{AnimFile, AnimationManager},
+    common::{Vec3 as WowVec3, Fixedi16},
+    m2::M2RibbonEmitter,
+};
+
+// position + texture coords
+pub const FLOATS_PER_RIBBON_VERTEX: usize = 3 + 2;
+// bounds the vertex buffer for ribbons with bogus emission rates or lifetimes
+const MAX_RIBBON_EDGES: usize = 1024;
+
+#[derive(Debug, Clone)]
+struct RibbonEdge {
+    position: Vec3,
+    up: Vec3,
+    height_above: f32,
+    height_below: f32,
+    age: f32,
+    fall_speed: f32,
+}
+
+#[wasm_bindgen(js_name = "WowM2RibbonParams")]
+#[derive(Default, Debug, Clone, Copy)]
+pub struct RibbonParams {
+    pub enabled: bool,
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub alpha: f32,
+    pub height_above: f32,
+    pub height_below: f32,
+    pub tex_slot: u16,
+}
+
+// A ribbon is a strip of edges left behind by a moving bone, e.g. a sword
+// trail. The newest edge follows the bone every frame, and is left in place
+// once it's time to emit another one. The WoW renderer doesn't take these
+// from the M2 yet, so ribbons aren't drawn anywhere for now.
+#[wasm_bindgen(js_name = "WowM2RibbonEmitter", getter_with_clone)]
+#[derive(Debug, Clone)]
+pub struct RibbonEmitter {
+    inner: M2RibbonEmitter,
+    edges: VecDeque<RibbonEdge>,
+    time_since_last_edge: f32,
+    position: Vec3,
+    pub max_edges: usize,
+    pub params: RibbonParams,
+    pub bone: u32,
+    pub texture_ids: Vec<u16>,
+    pub material_ids: Vec<u16>,
+    pub priority_plane: i16,
+}
+
+impl RibbonEmitter {
+    pub fn new(mut m2_ribbon: M2RibbonEmitter) -> Self {
+        let position = m2_ribbon.position.into();
+        // one live edge at the head, plus every edge still alive at the tail
+        let max_edges = ((m2_ribbon.edges_per_second * m2_ribbon.edge_lifetime).ceil().max(0.0) as usize)
+            .saturating_add(2)
+            .min(MAX_RIBBON_EDGES);
+
+        RibbonEmitter {
+            edges: VecDeque::with_capacity(max_edges),
+            time_since_last_edge: 0.0,
+            position,
+            max_edges,
+            params: RibbonParams::default(),
+            bone: m2_ribbon.bone,
+            texture_ids: m2_ribbon.texture_indices.take().unwrap_or_default(),
+            material_ids: m2_ribbon.material_indices.take().unwrap_or_default(),
+            priority_plane: m2_ribbon.priority_plane,
+            inner: m2_ribbon,
+        }
+    }
+
+    // Fills in the keyframes of a sequence stored in an external .anim file,
+    // see M2::add_external_animation()
+    pub fn add_external_animation(&mut self, animation_index: usize, anim_file: &AnimFile) -> Result<(), String> {
+        let Some(data) = anim_file.bone_data else {
+            return Ok(());
+        };
+        self.inner.color.allocate_external(animation_index, data)?;
+        self.inner.alpha.allocate_external(animation_index, data)?;
+        self.inner.height_above.allocate_external(animation_index, data)?;
+        self.inner.height_below.allocate_external(animation_index, data)?;
+        self.inner.tex_slot.allocate_external(animation_index, data)?;
+        self.inner.visibility.allocate_external(animation_index, data)?;
+        Ok(())
+    }
+
+    fn update_params(&mut self, animation_manager: &AnimationManager) {
+        let visibility = animation_manager.get_current_value_with_blend(&self.inner.visibility, 1u8);
+        self.params.enabled = visibility > 0;
+
+        let color = animation_manager.get_current_value_with_blend(&self.inner.color, WowVec3::new(1.0));
+        self.params.r = color.x;
+        self.params.g = color.y;
+        self.params.b = color.z;
+        self.params.alpha = animation_manager.get_current_value_with_blend(&self.inner.alpha, Fixedi16::from(1.0)).into();
+        self.params.height_above = animation_manager.get_current_value_with_blend(&self.inner.height_above, 0.0);
+        self.params.height_below = animation_manager.get_current_value_with_blend(&self.inner.height_below, 0.0);
+        self.params.tex_slot = animation_manager.get_current_value_with_blend(&self.inner.tex_slot, 0);
+    }
+
+    fn get_tex_coord_bounds(&self) -> (f32, f32, f32, f32) {
+        let rows = self.inner.texture_rows.max(1) as u32;
+        let cols = self.inner.texture_cols.max(1) as u32;
+        let cell = self.params.tex_slot as u32 % (rows * cols);
+        let (col, row) = ((cell % cols) as f32, (cell / cols) as f32);
+        let (cols, rows) = (cols as f32, rows as f32);
+        (col / cols, (col + 1.0) / cols, row / rows, (row + 1.0) / rows)
+    }
+
+    fn simulate(&mut self, dt_secs: f32, model_mat: &Mat4) {
+        let lifetime = self.inner.edge_lifetime;
+        let gravity = self.inner.gravity;
+        for edge in self.edges.iter_mut() {
+            edge.age += dt_secs;
+            edge.fall_speed += gravity * dt_secs;
+            edge.position.z -= edge.fall_speed * dt_secs;
+        }
+        while self.edges.back().is_some_and(|edge| edge.age > lifetime) {
+            self.edges.pop_back();
+        }
+
+        if !self.params.enabled {
+            return;
+        }
+
+        let mut up = mat4_to_mat3(model_mat) * vec3(0.0, 0.0, 1.0);
+        if up.magnitude() > 0.0001 {
+            up.normalize_mut();
+        } else {
+            up = vec3(0.0, 0.0, 1.0);
+        }
+        let head = RibbonEdge {
+            position: transform(&self.position, model_mat),
+            up,
+            height_above: self.params.height_above,
+            height_below: self.params.height_below,
+            age: 0.0,
+            fall_speed: 0.0,
+        };
+
+        self.time_since_last_edge += dt_secs;
+        let edge_interval = if self.inner.edges_per_second > 0.0 { 1.0 / self.inner.edges_per_second } else { f32::INFINITY };
+        if self.edges.is_empty() || self.time_since_last_edge >= edge_interval {
+            self.edges.push_front(head);
+            self.time_since_last_edge %= edge_interval;
+            self.edges.truncate(self.max_edges);
+        } else {
+            self.edges[0] = head;
+        }
+    }
+
+    pub fn get_vertex_data(&self) -> Vec<f32> {
+        let mut data = Vec::with_capacity(self.num_vertices() * FLOATS_PER_RIBBON_VERTEX);
+        if self.edges.len() < 2 {
+            return data;
+        }
+        let (u_min, u_max, v_min, v_max) = self.get_tex_coord_bounds();
+        let lifetime = self.inner.edge_lifetime;
+        for edge in &self.edges {
+            // the texture runs from the head of the ribbon to its tail
+            let t = if lifetime > 0.0 { (edge.age / lifetime).min(1.0) } else { 0.0 };
+            let u = u_min + (u_max - u_min) * t;
+            let top = edge.position + edge.up * edge.height_above;
+            let bottom = edge.position - edge.up * edge.height_below;
+            data.extend_from_slice(&[top.x, top.y, top.z, u, v_min]);
+            data.extend_from_slice(&[bottom.x, bottom.y, bottom.z, u, v_max]);
+        }
+        data
+    }
+}
+
+#[wasm_bindgen(js_class = "WowM2RibbonEmitter")]
+impl RibbonEmitter {
+    pub fn update(
+        &mut self,
+        dt_ms: f32,
+        animation_manager: &AnimationManager,
+        bone_transform_slice: &[f32],
+        bone_post_billboard_transform_slice: &[f32]
+    ) {
+        assert_eq!(bone_transform_slice.len(), 16);
+        assert_eq!(bone_post_billboard_transform_slice.len(), 16);
+
+        self.update_params(animation_manager);
+
+        let bone_transform = Mat4::from_column_slice(bone_transform_slice);
+        let bone_post_billboard_transform = Mat4::from_column_slice(bone_post_billboard_transform_slice);
+        self.simulate(dt_ms / 1000.0, &(bone_post_billboard_transform * bone_transform));
+    }
+
+    // Fills the buffer with a triangle strip, two vertices per edge
+    pub fn fill_vertex_buffer(&self, buffer: &Float32Array) {
+        let mut data = self.get_vertex_data();
+        data.resize(self.get_max_vertices() * FLOATS_PER_RIBBON_VERTEX, 0.0);
+        buffer.copy_from(&data);
+    }
+
+    pub fn get_floats_per_vertex() -> usize {
+        FLOATS_PER_RIBBON_VERTEX
+    }
+
+    pub fn get_max_vertices(&self) -> usize {
+        self.max_edges * 2
+    }
+
+    pub fn num_vertices(&self) -> usize {
+        if self.edges.len() < 2 {
+            0
+        } else {
+            self.edges.len() * 2
+        }
+    }
+
+    pub fn num_edges(&self) -> usize {
+        self.edges.len()
+    }
+}
+
+fn transform(p: &Vec3, m: &Mat4) -> Vec3 {
+    let mut p_hom = vec3_to_vec4(p);
+    p_hom[3] = 1.0;
+    (m * p_hom).xyz()
+}
+
+#[cfg(test)]
+mod tests {
+    use deku::DekuContainerRead;
+    use nalgebra_glm::{identity, translation};
+
+    use super::*;
+
+    fn make_ribbon(edges_per_second: f32, edge_lifetime: f32, texture_rows: u16, texture_cols: u16) -> RibbonEmitter {
+        let mut data = vec![0; 176];
+        data[116..120].copy_from_slice(&edges_per_second.to_le_bytes());
+        data[120..124].copy_from_slice(&edge_lifetime.to_le_bytes());
+        data[128..130].copy_from_slice(&texture_rows.to_le_bytes());
+        data[130..132].copy_from_slice(&texture_cols.to_le_bytes());
+        let (_, m2_ribbon) = M2RibbonEmitter::from_bytes((&data, 0)).unwrap();
+        let mut ribbon = RibbonEmitter::new(m2_ribbon);
+        ribbon.params.enabled = true;
+        ribbon.params.height_above = 1.0;
+        ribbon
+    }
+
+    #[test]
+    fn test_edge_emission() {
+        let mut ribbon = make_ribbon(10.0, 0.25, 1, 1);
+        assert_eq!(ribbon.max_edges, 5);
+        ribbon.simulate(0.0, &identity());
+        assert_eq!(ribbon.num_edges(), 1);
+        assert_eq!(ribbon.num_vertices(), 0);
+
+        // the head follows the bone until it's time to emit the next edge
+        ribbon.simulate(0.05, &translation(&vec3(1.0, 0.0, 0.0)));
+        assert_eq!(ribbon.num_edges(), 1);
+        assert_eq!(ribbon.edges[0].position, vec3(1.0, 0.0, 0.0));
+        ribbon.simulate(0.05, &translation(&vec3(2.0, 0.0, 0.0)));
+        assert_eq!(ribbon.num_edges(), 2);
+        assert_eq!(ribbon.edges[1].position, vec3(1.0, 0.0, 0.0));
+
+        let data = ribbon.get_vertex_data();
+        assert_eq!(data.len(), ribbon.num_vertices() * FLOATS_PER_RIBBON_VERTEX);
+        assert_eq!(&data[..5], &[2.0, 0.0, 1.0, 0.0, 0.0]);
+        assert_eq!(&data[10..15], &[1.0, 0.0, 1.0, 0.05 / 0.25, 0.0]);
+    }
+
+    #[test]
+    fn test_edge_expiry() {
+        let mut ribbon = make_ribbon(10.0, 0.25, 1, 1);
+        for _ in 0..10 {
+            ribbon.simulate(0.1, &identity());
+            assert!(ribbon.num_edges() <= 3);
+        }
+        assert!(ribbon.edges.iter().all(|edge| edge.age <= 0.25));
+
+        // disabled ribbons stop emitting, and their edges die off
+        ribbon.params.enabled = false;
+        ribbon.simulate(0.2, &identity());
+        assert_eq!(ribbon.num_edges(), 1);
+        ribbon.simulate(0.2, &identity());
+        assert_eq!(ribbon.num_edges(), 0);
+    }
+
+    #[test]
+    fn test_limits() {
+        assert_eq!(make_ribbon(f32::INFINITY, 1.0, 1, 1).max_edges, MAX_RIBBON_EDGES);
+        assert_eq!(make_ribbon(f32::NAN, 1.0, 1, 1).max_edges, 2);
+
+        // 300 * 300 cells overflows a u16
+        let mut ribbon = make_ribbon(10.0, 1.0, 300, 300);
+        ribbon.params.tex_slot = u16::MAX;
+        let (u_min, _, v_min, _) = ribbon.get_tex_coord_bounds();
+        assert_eq!((u_min, v_min), (135.0 / 300.0, 218.0 / 300.0));
+    }
+}
@@ -0,0 +1,113 @@
+// Dumps every tag we know how to parse in a Halo map to JSON, along with each
+// bitmap's top mip level as a TGA.
+//
+// usage: halo-dump <map> <output dir> [bitmaps.map]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use anyhow::Result;
+use noclip_rust_support::halo::bitmap::*;
+use noclip_rust_support::halo::json::ToJson;
+use noclip_rust_support::halo::map::*;
+use noclip_rust_support::halo::tag::*;
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 3 {
+        eprintln!("usage: {} <map> <output dir> [bitmaps.map]", args[0]);
+        process::exit(1);
+    }
+    if let Err(err) = dump_map(Path::new(&args[1]), Path::new(&args[2]), args.get(3).map(Path::new)) {
+        eprintln!("failed to dump {}: {}", args[1], err);
+        process::exit(1);
+    }
+}
+
+fn dump_map(map_path: &Path, output_dir: &Path, bitmaps_path: Option<&Path>) -> Result<()> {
+    let mut mgr = MapManager::new(fs::read(map_path)?)?;
+    if let Some(bitmaps_path) = bitmaps_path {
+        mgr.add_resource_map(fs::read(bitmaps_path)?)?;
+    }
+
+    let mut index = String::from("[");
+    let (mut dumped, mut skipped) = (0, 0);
+    for header in mgr.get_tag_headers().to_vec() {
+        if index.len() > 1 {
+            index.push(',');
+        }
+        index.push_str(&header.to_json());
+
+        let tag = match mgr.read_any_tag(&header) {
+            Ok(tag) => tag,
+            Err(err) => {
+                log_skip(&header, &err);
+                skipped += 1;
+                continue;
+            }
+        };
+        let tag_path = get_output_path(output_dir, &header);
+        fs::create_dir_all(tag_path.parent().unwrap())?;
+        let file_name = format!("{}.{:?}.json", tag_path.file_name().unwrap().to_string_lossy(), header.primary_class);
+        fs::write(tag_path.with_file_name(file_name), tag.to_json())?;
+        dumped += 1;
+
+        if let TagData::Bitmap(bitmap) = &tag.data {
+            if let Err(err) = dump_bitmap(&mut mgr, bitmap, &tag_path) {
+                log_skip(&header, &err);
+            }
+        }
+    }
+    index.push(']');
+    fs::create_dir_all(output_dir)?;
+    fs::write(output_dir.join("tags.json"), index)?;
+
+    println!("dumped {} tags ({} unsupported or unreadable) to {}", dumped, skipped, output_dir.display());
+    Ok(())
+}
+
+fn log_skip(header: &TagHeader, err: &anyhow::Error) {
+    eprintln!("skipping {:?} {}: {}", header.primary_class, header.path, err);
+}
+
+// tag paths use backslashes regardless of platform
+fn get_output_path(output_dir: &Path, header: &TagHeader) -> PathBuf {
+    let mut path = output_dir.to_path_buf();
+    path.extend(header.path.split('\\').filter(|part| !part.is_empty() && *part != ".."));
+    path
+}
+
+// Cube map faces and 3D texture slices are stacked vertically in the output
+fn dump_bitmap(mgr: &mut MapManager, bitmap: &Bitmap, tag_path: &Path) -> Result<()> {
+    for i in 0..bitmap.get_bitmap_count() {
+        let metadata = bitmap.get_metadata_for_index(i);
+        let data = mgr.get_and_convert_bitmap_data(&metadata, true)?;
+        let surfaces = match metadata.bitmap_type {
+            BitmapDataType::CubeMap => 6,
+            BitmapDataType::Tex3D => metadata.depth as usize,
+            _ => 1,
+        };
+        let (width, height) = (metadata.width as usize, metadata.height as usize * surfaces);
+        let size = width * height * 4;
+        if data.len() < size {
+            anyhow::bail!("bitmap {} has unsupported format {:?}", i, metadata.format);
+        }
+        let file_name = format!("{}_{}.tga", tag_path.file_name().unwrap().to_string_lossy(), i);
+        fs::write(tag_path.with_file_name(file_name), encode_tga(&data[..size], width, height))?;
+    }
+    Ok(())
+}
+
+// uncompressed 32-bit TGA, stored top to bottom
+fn encode_tga(rgba: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(18 + rgba.len());
+    out.extend([0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    out.extend((width as u16).to_le_bytes());
+    out.extend((height as u16).to_le_bytes());
+    out.extend([32, 0x28]);
+    for pixel in rgba.chunks_exact(4) {
+        out.extend([pixel[2], pixel[1], pixel[0], pixel[3]]);
+    }
+    out
+}
@@ -250,7 +250,7 @@ export class AssetFile {
     }
 
     private createMeshData = async (assetSystem: UnityAssetSystem, objData: AssetObjectData): Promise<UnityMeshData> => {
-        const mesh = rust.UnityMesh.create(assetSystem.version, objData.data);
+        const mesh = rust.UnityMesh.create_with_endian(assetSystem.version, objData.assetFile.is_big_endian(), objData.data);
 
         const streamingInfo: UnityStreamingInfo = mesh.streaming_info;
         if (streamingInfo.path.length !== 0) {
@@ -269,7 +269,7 @@ export class AssetFile {
         if (objData.classID !== rust.UnityClassID.Texture2D)
             return null;
 
-        const header = rust.UnityTexture2D.create(assetSystem.version, objData.data);
+        const header = rust.UnityTexture2D.create_with_endian(assetSystem.version, objData.assetFile.is_big_endian(), objData.data);
         let data = header.data;
         if (data.length === 0) {
             const streaming_info = header.streaming_info;
@@ -281,13 +281,13 @@ export class AssetFile {
     };
 
     private createShaderData = async (assetSystem: UnityAssetSystem, objData: AssetObjectData): Promise<UnityShaderData> => {
-        const header = rust.UnityShader.create(assetSystem.version, objData.data);
+        const header = rust.UnityShader.create_with_endian(assetSystem.version, objData.assetFile.is_big_endian(), objData.data);
         const shaderData = new UnityShaderData(objData.location, header);
         return shaderData;
     };
 
     private createMaterialData = async (assetSystem: UnityAssetSystem, objData: AssetObjectData): Promise<UnityMaterialData> => {
-        const header = rust.UnityMaterial.create(assetSystem.version, objData.data);
+        const header = rust.UnityMaterial.create_with_endian(assetSystem.version, objData.assetFile.is_big_endian(), objData.data);
         const materialData = new UnityMaterialData(objData.location, header);
         await materialData.load(assetSystem);
         return materialData;
@@ -295,7 +295,7 @@ export abstract class UnityMaterialFactory {
 }
 
 interface WasmFromBytes<T> {
-    create(version: UnityVersion, data: Uint8Array): T;
+    create_with_endian(version: UnityVersion, big_endian: boolean, data: Uint8Array): T;
 }
 
 class UnityLevel {
@@ -321,7 +321,7 @@ class UnityLevel {
     }
 
     private loadOneComponent<CompT extends UnityComponent, WasmT>(obj: AssetObjectData, gameObject: GameObject, fromBytes: WasmFromBytes<WasmT>, constructor: ComponentConstructor<CompT, WasmT>): Promise<void> {
-        const wasmObj = fromBytes.create(this.runtime.version, obj.data);
+        const wasmObj = fromBytes.create_with_endian(this.runtime.version, obj.assetFile.is_big_endian(), obj.data);
         const comp = new constructor(this, gameObject, wasmObj);
         gameObject.components.push(comp);
         this.components.set(Number(obj.location.pathID), comp);
@@ -351,7 +351,7 @@ class UnityLevel {
             const pathID = unityObject.file_id;
             const objData = await assetFile.fetchObject(pathID);
             try {
-                const wasmGameObject = rust.UnityGameObject.create(this.runtime.version, objData.data);
+                const wasmGameObject = rust.UnityGameObject.create_with_endian(this.runtime.version, objData.assetFile.is_big_endian(), objData.data);
                 const gameObject = new GameObject(objData.location, wasmGameObject);
                 gameObject.isActive = wasmGameObject.is_active > 0;
                 gameObject.layer = wasmGameObject.layer;
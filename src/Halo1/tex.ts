@@ -72,9 +72,9 @@ function makeTexture(device: GfxDevice, bitmap: HaloBitmap, mgr: HaloSceneManage
     const bitmapMetadata = bitmap.get_metadata_for_index(submap);
     let bitmapData;
     if (bitmapMetadata.is_external()) {
-        bitmapData = bitmapReader.get_and_convert_bitmap_data(bitmap, submap);
+        bitmapData = bitmapReader.get_and_convert_bitmap_data(bitmap, submap, false);
     } else {
-        bitmapData = mgr.get_and_convert_bitmap_data(bitmap, submap);
+        bitmapData = mgr.get_and_convert_bitmap_data(bitmap, submap, false);
     }
     const format = getBitmapTextureFormat(bitmapMetadata.format);
     const mipmapCount = Math.max(bitmapMetadata.mipmap_count, 1);
@@ -102,20 +102,8 @@ function makeTexture(device: GfxDevice, bitmap: HaloBitmap, mgr: HaloSceneManage
     for (let i = 0; i < mipmapCount; i++) {
         const sliceByteLength = getImageFormatByteLength(format, w, h);
 
-        let buffer = new ArrayBufferSlice(bitmapData.buffer, byteOffset, sliceByteLength * depth);
-        if (dimension === GfxTextureDimension.Cube) {
-            // Rearrange cubemaps. Need to swap 1st and 2nd face.
-            // TODO: Maybe it makes more sense to do this in Rust?
-
-            const newData = new Uint8Array(buffer.copyToBuffer());
-
-            const face1Offs = 1 * sliceByteLength;
-            const face2Offs = 2 * sliceByteLength;
-            newData.set(buffer.subarray(face2Offs, sliceByteLength).createTypedArray(Uint8Array), face1Offs);
-            newData.set(buffer.subarray(face1Offs, sliceByteLength).createTypedArray(Uint8Array), face2Offs);
-
-            buffer = new ArrayBufferSlice(newData.buffer);
-        }
+        // cube map faces are already in +X, -X, +Y, -Y, +Z, -Z order
+        const buffer = new ArrayBufferSlice(bitmapData.buffer, byteOffset, sliceByteLength * depth);
 
         let levelData: ArrayBufferView;
         if (format === GfxFormat.U16_RGB_565) {
@@ -584,9 +584,36 @@ export class ModelData {
         );
     }
 
+    // sequences stored in .anim files have no keyframes until their data is
+    // added, which has to happen before anything animated is taken from the M2
+    private async loadExternalAnimations(cache: WowCache, m2: WowM2): Promise<void> {
+        const animFileIds = m2.get_anim_file_ids();
+        const animFiles = await Promise.all(
+            animFileIds.map(async (animFileId) => {
+                if (animFileId.file_id === 0) {
+                    return null;
+                }
+                try {
+                    return await cache.fetchDataByFileID(animFileId.file_id);
+                } catch (e) {
+                    console.error(`failed to fetch anim file: ${e}`);
+                    return null;
+                }
+            }),
+        );
+        animFileIds.forEach((animFileId, i) => {
+            const data = animFiles[i];
+            if (data !== null) {
+                m2.add_external_animation(animFileId.anim_id, animFileId.sub_anim_id, data);
+            }
+            animFileId.free();
+        });
+    }
+
     public async load(cache: WowCache): Promise<undefined> {
         const m2 = await cache.fetchFileByID(this.fileId, rust.WowM2.new);
         this.flags = m2.flags;
+        await this.loadExternalAnimations(cache, m2);
 
         this.vertexBuffer = m2.take_vertex_data();
         this.modelAABB = convertWowAABB(m2.get_bounding_box());